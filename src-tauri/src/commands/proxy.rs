@@ -8,6 +8,7 @@ use crate::proxy::monitor::{ProxyRequestLog, ProxyStats};
 use crate::proxy::token_manager::TokenManager;
 use crate::proxy::server::AxumServer;
 use crate::proxy::security::ProxySecurityConfig;
+use crate::proxy::tls::loopback_url;
 use serde::{Deserialize, Serialize};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
@@ -117,6 +118,7 @@ pub async fn internal_start_proxy_service(
 
     // Sync config to TokenManager
     token_manager.start_auto_cleanup().await;
+    token_manager.start_token_refresh().await;
 
     // Load circuit breaker config
     let app_config = crate::modules::config::load_app_config()
@@ -125,6 +127,11 @@ pub async fn internal_start_proxy_service(
         .update_circuit_breaker_config(app_config.circuit_breaker)
         .await;
 
+    // Load scheduling config (sticky mode, concurrency limit, fair queue weights)
+    token_manager
+        .update_sticky_config(config.scheduling.clone())
+        .await;
+
     // Restore preferred account mode
     if let Some(ref account_id) = config.preferred_account_id {
         token_manager
@@ -141,7 +148,7 @@ pub async fn internal_start_proxy_service(
         return Ok(ProxyStatus {
             running: false,
             port: config.port,
-            base_url: format!("http://127.0.0.1:{}", config.port),
+            base_url: loopback_url(&config.tls, config.port),
             active_accounts: 0,
         });
     }
@@ -163,7 +170,7 @@ pub async fn internal_start_proxy_service(
     Ok(ProxyStatus {
         running: true,
         port: config.port,
-        base_url: format!("http://127.0.0.1:{}", config.port),
+        base_url: loopback_url(&config.tls, config.port),
         active_accounts,
     })
 }
@@ -186,6 +193,8 @@ pub async fn ensure_admin_server(
         }
         if let Some(monitor) = monitor_lock.as_ref() {
             monitor.set_enabled(config.enable_logging);
+            // monitor_middleware 通过全局实例记录请求
+            crate::proxy::monitor::set_global_monitor(monitor.clone());
         }
     }
 
@@ -207,15 +216,19 @@ pub async fn ensure_admin_server(
     .await
     .map_err(|e| format!("启动管理服务器失败: {}", e))?;
 
+    // Initialize runtime configs (shared with admin save / security hot updates)
+    let mut app_config = crate::modules::config::load_app_config()
+        .unwrap_or_else(|_| crate::models::config::AppConfig::new());
+    app_config.proxy = config.clone();
+    axum_server.apply_runtime_config(&app_config).await;
+
     *admin_lock = Some(AdminServerInstance {
         axum_server,
         server_handle,
     });
 
-    // Initialize global configs
-    crate::proxy::update_thinking_budget_config(config.thinking_budget.clone());
-    crate::proxy::update_global_system_prompt_config(config.global_system_prompt.clone());
-    crate::proxy::update_image_thinking_mode(config.image_thinking_mode.clone());
+    // 代理池：账号出口代理 + 健康检查
+    crate::proxy::proxy_pool::init_global_proxy_pool(config.proxy_pool.clone()).await;
 
     Ok(())
 }
@@ -269,7 +282,7 @@ pub async fn get_proxy_status(state: State<'_, ProxyServiceState>) -> Result<Pro
             Some(instance) => Ok(ProxyStatus {
                 running: true,
                 port: instance.config.port,
-                base_url: format!("http://127.0.0.1:{}", instance.config.port),
+                base_url: loopback_url(&instance.config.tls, instance.config.port),
                 active_accounts: instance.token_manager.len(),
             }),
             None => Ok(ProxyStatus {
@@ -447,14 +460,9 @@ pub async fn check_proxy_health(
 ) -> Result<ProxyPoolConfig, String> {
     let instance_lock = state.instance.read().await;
     if instance_lock.is_some() {
-        let app_config = crate::modules::config::load_app_config()
-            .map_err(|e| format!("Failed to load config: {}", e))?;
-        let pool_config = app_config.proxy.proxy_pool.clone();
-        let pool_config_arc = Arc::new(RwLock::new(pool_config));
-        let manager = crate::proxy::proxy_pool::ProxyPoolManager::new(pool_config_arc.clone());
+        let manager = crate::commands::proxy_pool::pool_manager().await?;
         manager.health_check().await?;
-        let updated = pool_config_arc.read().await.clone();
-        Ok(updated)
+        Ok(manager.get_config().await)
     } else {
         Err("服务未运行".to_string())
     }
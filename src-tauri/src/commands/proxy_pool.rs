@@ -4,9 +4,25 @@
 // in the proxy pool system.
 
 use crate::commands::proxy::ProxyServiceState;
+use crate::proxy::proxy_pool::{get_global_proxy_pool, ProxyPoolManager};
 use std::collections::HashMap;
+use std::sync::Arc;
 use tauri::State;
 
+/// Use the live pool when the proxy service has initialized it, so binding
+/// changes take effect on upstream requests immediately; otherwise operate on
+/// a manager backed by the saved config.
+pub(crate) async fn pool_manager() -> Result<Arc<ProxyPoolManager>, String> {
+    if let Some(pool) = get_global_proxy_pool() {
+        return Ok(pool);
+    }
+    let app_config = crate::modules::config::load_app_config()
+        .map_err(|e| format!("Failed to load config: {}", e))?;
+    Ok(Arc::new(ProxyPoolManager::new(Arc::new(
+        tokio::sync::RwLock::new(app_config.proxy.proxy_pool),
+    ))))
+}
+
 // ============================================================================
 // Tauri Commands
 // ============================================================================
@@ -20,11 +36,7 @@ pub async fn bind_account_proxy(
 ) -> Result<(), String> {
     let instance_lock = state.instance.read().await;
     if instance_lock.is_some() {
-        let app_config = crate::modules::config::load_app_config()
-            .map_err(|e| format!("Failed to load config: {}", e))?;
-        let pool_config_arc =
-            std::sync::Arc::new(tokio::sync::RwLock::new(app_config.proxy.proxy_pool));
-        let manager = crate::proxy::proxy_pool::ProxyPoolManager::new(pool_config_arc);
+        let manager = pool_manager().await?;
         manager.bind_account_to_proxy(account_id, proxy_id).await
     } else {
         Err("Service not running".to_string())
@@ -39,11 +51,7 @@ pub async fn unbind_account_proxy(
 ) -> Result<(), String> {
     let instance_lock = state.instance.read().await;
     if instance_lock.is_some() {
-        let app_config = crate::modules::config::load_app_config()
-            .map_err(|e| format!("Failed to load config: {}", e))?;
-        let pool_config_arc =
-            std::sync::Arc::new(tokio::sync::RwLock::new(app_config.proxy.proxy_pool));
-        let manager = crate::proxy::proxy_pool::ProxyPoolManager::new(pool_config_arc);
+        let manager = pool_manager().await?;
         manager.unbind_account_proxy(&account_id).await;
         Ok(())
     } else {
@@ -59,11 +67,7 @@ pub async fn get_account_proxy_binding(
 ) -> Result<Option<String>, String> {
     let instance_lock = state.instance.read().await;
     if instance_lock.is_some() {
-        let app_config = crate::modules::config::load_app_config()
-            .map_err(|e| format!("Failed to load config: {}", e))?;
-        let pool_config_arc =
-            std::sync::Arc::new(tokio::sync::RwLock::new(app_config.proxy.proxy_pool));
-        let manager = crate::proxy::proxy_pool::ProxyPoolManager::new(pool_config_arc);
+        let manager = pool_manager().await?;
         Ok(manager.get_account_binding(&account_id))
     } else {
         Err("Service not running".to_string())
@@ -77,17 +81,33 @@ pub async fn get_all_account_bindings(
 ) -> Result<HashMap<String, String>, String> {
     let instance_lock = state.instance.read().await;
     if instance_lock.is_some() {
-        let app_config = crate::modules::config::load_app_config()
-            .map_err(|e| format!("Failed to load config: {}", e))?;
-        let pool_config_arc =
-            std::sync::Arc::new(tokio::sync::RwLock::new(app_config.proxy.proxy_pool));
-        let manager = crate::proxy::proxy_pool::ProxyPoolManager::new(pool_config_arc);
+        let manager = pool_manager().await?;
         Ok(manager.get_all_bindings_snapshot())
     } else {
         Err("Service not running".to_string())
     }
 }
 
+/// Bulk import proxies into the pool
+#[tauri::command]
+pub async fn import_proxies(
+    state: State<'_, ProxyServiceState>,
+    content: String,
+    tags: Option<Vec<String>>,
+) -> Result<crate::proxy::proxy_import::ProxyImportReport, String> {
+    let instance_lock = state.instance.read().await;
+    if instance_lock.is_some() {
+        let manager = pool_manager().await?;
+        let request = crate::proxy::proxy_import::ProxyImportRequest {
+            content,
+            tags: tags.unwrap_or_default(),
+        };
+        crate::proxy::proxy_import::import_proxies(&manager, request).await
+    } else {
+        Err("Service not running".to_string())
+    }
+}
+
 // ============================================================================
 // Tests
 // ============================================================================
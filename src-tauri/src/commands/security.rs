@@ -231,7 +231,7 @@ pub async fn update_security_config(
         let mut instance_lock = app_state.instance.write().await;
         if let Some(instance) = instance_lock.as_mut() {
             instance.config.security_monitor = config;
-            instance.axum_server.update_security(&instance.config).await;
+            instance.axum_server.update_security(&app_config).await;
         }
     }
 
@@ -138,6 +138,7 @@ pub fn run() {
             commands::proxy_pool::unbind_account_proxy,
             commands::proxy_pool::get_account_proxy_binding,
             commands::proxy_pool::get_all_account_bindings,
+            commands::proxy_pool::import_proxies,
             // Autostart commands
             commands::autostart::toggle_auto_launch,
             commands::autostart::is_auto_launch_enabled,
@@ -48,6 +48,16 @@ pub struct StickySessionConfig {
     pub mode: SchedulingMode,
     /// 缓存优先模式下的最大等待时间 (秒)
     pub max_wait_seconds: u64,
+    /// 单账号最大并发请求数 (0 = 不限制)
+    pub max_concurrent_per_account: u32,
+    /// 所有账号都已满载时，排队等待空闲账号的最长时间 (秒)
+    pub queue_timeout_seconds: u64,
+    /// User Token ID -> 公平排队权重 (未配置时为 1)
+    pub fair_queue_weights: HashMap<String, u32>,
+    /// 会话绑定空闲多久后过期 (秒)，持久化绑定同样适用
+    pub session_binding_ttl_seconds: u64,
+    /// 最多保留的会话绑定数量，超出时淘汰最久未使用的
+    pub max_session_bindings: usize,
 }
 
 impl Default for StickySessionConfig {
@@ -55,6 +65,11 @@ impl Default for StickySessionConfig {
         Self {
             mode: SchedulingMode::Balance,
             max_wait_seconds: 60,
+            max_concurrent_per_account: 0,
+            queue_timeout_seconds: 30,
+            fair_queue_weights: HashMap::new(),
+            session_binding_ttl_seconds: 86400,
+            max_session_bindings: 10000,
         }
     }
 }
@@ -258,12 +273,29 @@ fn default_thinking_budget_custom_value() -> u32 {
 // Global System Prompt
 // ============================================================================
 
+/// 全局系统提示词与客户端 system prompt 的合并方式
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SystemPromptMode {
+    Prepend,
+    Append,
+    Replace,
+}
+
+impl Default for SystemPromptMode {
+    fn default() -> Self {
+        Self::Prepend
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct GlobalSystemPromptConfig {
     #[serde(default)]
     pub enabled: bool,
     #[serde(default)]
     pub content: String,
+    #[serde(default)]
+    pub mode: SystemPromptMode,
 }
 
 impl Default for GlobalSystemPromptConfig {
@@ -271,6 +303,7 @@ impl Default for GlobalSystemPromptConfig {
         Self {
             enabled: false,
             content: String::new(),
+            mode: SystemPromptMode::Prepend,
         }
     }
 }
@@ -384,8 +417,12 @@ pub enum ProxySelectionStrategy {
     RoundRobin,
     Random,
     Priority,
+    /// 在途请求数最少
     LeastConnections,
+    /// 平滑加权轮询 (按 ProxyEntry.weight)
     WeightedRoundRobin,
+    /// EWMA 延迟最低 (健康检查 + 实际流量)
+    LatencyAware,
 }
 
 impl Default for ProxySelectionStrategy {
@@ -416,6 +453,13 @@ pub struct ProxyEntry {
     pub last_check_time: Option<i64>,
     pub is_healthy: bool,
     pub latency: Option<u64>,
+    /// 加权轮询权重 (0 视为 1)
+    #[serde(default = "default_proxy_weight")]
+    pub weight: u32,
+}
+
+fn default_proxy_weight() -> u32 {
+    1
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -593,22 +637,66 @@ impl Default for PinnedQuotaModelsConfig {
 // Circuit Breaker
 // ============================================================================
 
+/// 熔断配置
+///
+/// `backoff_steps` 用于限流锁定时长；其余字段控制按 账号+模型 的熔断状态机
+/// (closed → open → half-open)。
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct CircuitBreakerConfig {
     pub enabled: bool,
     #[serde(default = "default_backoff_steps")]
     pub backoff_steps: Vec<u64>,
+    /// 连续失败多少次后熔断 (0 = 不按连续失败熔断)
+    #[serde(default = "default_breaker_failure_threshold")]
+    pub failure_threshold: u32,
+    /// 统计窗口内错误率达到该百分比时熔断
+    #[serde(default = "default_breaker_error_rate_percent")]
+    pub error_rate_percent: u8,
+    /// 错误率统计窗口 (秒)
+    #[serde(default = "default_breaker_window_seconds")]
+    pub window_seconds: u64,
+    /// 窗口内请求数不足时不按错误率熔断
+    #[serde(default = "default_breaker_min_requests")]
+    pub min_requests: u32,
+    /// 熔断打开后多久进入半开状态并放行一个探测请求 (秒)
+    #[serde(default = "default_breaker_open_seconds")]
+    pub open_seconds: u64,
 }
 
 fn default_backoff_steps() -> Vec<u64> {
     vec![60, 300, 1800, 7200]
 }
 
+fn default_breaker_failure_threshold() -> u32 {
+    5
+}
+
+fn default_breaker_error_rate_percent() -> u8 {
+    50
+}
+
+fn default_breaker_window_seconds() -> u64 {
+    60
+}
+
+fn default_breaker_min_requests() -> u32 {
+    10
+}
+
+fn default_breaker_open_seconds() -> u64 {
+    30
+}
+
 impl CircuitBreakerConfig {
     pub fn new() -> Self {
         Self {
             enabled: true,
             backoff_steps: default_backoff_steps(),
+            failure_threshold: default_breaker_failure_threshold(),
+            error_rate_percent: default_breaker_error_rate_percent(),
+            window_seconds: default_breaker_window_seconds(),
+            min_requests: default_breaker_min_requests(),
+            open_seconds: default_breaker_open_seconds(),
         }
     }
 }
@@ -619,6 +707,555 @@ impl Default for CircuitBreakerConfig {
     }
 }
 
+// ============================================================================
+// TLS / mTLS
+// ============================================================================
+
+/// 客户端证书认证模式 (mTLS)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ClientAuthMode {
+    /// 不请求客户端证书
+    #[default]
+    Off,
+    /// 请求客户端证书，但允许无证书连接 (仍需其他方式认证)
+    Optional,
+    /// 必须提供受信任 CA 签发的客户端证书 (本机回环连接除外)
+    Required,
+}
+
+fn default_tls_reload_interval() -> u64 {
+    30
+}
+
+/// HTTPS 监听配置
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TlsConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// PEM 证书路径 (为空时使用 data_dir/tls/cert.pem)
+    #[serde(default)]
+    pub cert_path: Option<String>,
+    /// PEM 私钥路径 (为空时使用 data_dir/tls/key.pem)
+    #[serde(default)]
+    pub key_path: Option<String>,
+    /// 证书不存在时自动生成自签名证书
+    #[serde(default = "default_true")]
+    pub auto_generate_self_signed: bool,
+    /// 证书文件变更检查间隔 (秒)
+    #[serde(default = "default_tls_reload_interval")]
+    pub reload_interval_secs: u64,
+    #[serde(default)]
+    pub client_auth: ClientAuthMode,
+    /// 用于校验客户端证书的 CA (PEM)
+    #[serde(default)]
+    pub client_ca_path: Option<String>,
+    /// 客户端证书 Subject (完整 DN 或 CN) -> User Token ID
+    #[serde(default)]
+    pub client_cert_identities: HashMap<String, String>,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cert_path: None,
+            key_path: None,
+            auto_generate_self_signed: true,
+            reload_interval_secs: default_tls_reload_interval(),
+            client_auth: ClientAuthMode::Off,
+            client_ca_path: None,
+            client_cert_identities: HashMap::new(),
+        }
+    }
+}
+
+// ============================================================================
+// JWT / OIDC client authentication
+// ============================================================================
+
+fn default_jwks_refresh_secs() -> u64 {
+    3600
+}
+
+fn default_jwt_algorithms() -> Vec<String> {
+    vec!["RS256".to_string(), "ES256".to_string()]
+}
+
+fn default_jwt_leeway_secs() -> u64 {
+    60
+}
+
+fn default_jwt_username_claim() -> String {
+    "sub".to_string()
+}
+
+fn default_jwt_groups_claim() -> String {
+    "groups".to_string()
+}
+
+/// 客户端 JWT 鉴权配置 (由内部 IdP 签发的短期令牌)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JwtAuthConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// 期望的 iss
+    #[serde(default)]
+    pub issuer: String,
+    /// 可接受的 aud (任一匹配即可)
+    #[serde(default)]
+    pub audiences: Vec<String>,
+    /// JWKS 地址 (优先于 jwks_path)
+    #[serde(default)]
+    pub jwks_url: Option<String>,
+    /// 本地 JWKS 文件
+    #[serde(default)]
+    pub jwks_path: Option<String>,
+    /// JWKS 缓存刷新间隔 (秒)
+    #[serde(default = "default_jwks_refresh_secs")]
+    pub jwks_refresh_secs: u64,
+    /// 允许的签名算法 (仅非对称算法)
+    #[serde(default = "default_jwt_algorithms")]
+    pub algorithms: Vec<String>,
+    /// exp / nbf 容差 (秒)
+    #[serde(default = "default_jwt_leeway_secs")]
+    pub leeway_secs: u64,
+    /// 作为用户名的 claim
+    #[serde(default = "default_jwt_username_claim")]
+    pub username_claim: String,
+    /// 用户组 claim (字符串数组或以空格/逗号分隔的字符串)
+    #[serde(default = "default_jwt_groups_claim")]
+    pub groups_claim: String,
+    /// 非空时，令牌必须至少属于其中一个组
+    #[serde(default)]
+    pub allowed_groups: Vec<String>,
+    /// 自定义配额 claim (数值，每日 token 上限)
+    #[serde(default)]
+    pub quota_claim: Option<String>,
+}
+
+impl Default for JwtAuthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            issuer: String::new(),
+            audiences: Vec::new(),
+            jwks_url: None,
+            jwks_path: None,
+            jwks_refresh_secs: default_jwks_refresh_secs(),
+            algorithms: default_jwt_algorithms(),
+            leeway_secs: default_jwt_leeway_secs(),
+            username_claim: default_jwt_username_claim(),
+            groups_claim: default_jwt_groups_claim(),
+            allowed_groups: Vec::new(),
+            quota_claim: None,
+        }
+    }
+}
+
+// ============================================================================
+// Redaction (PII / secrets)
+// ============================================================================
+
+/// 命中规则后的处理方式
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RedactionAction {
+    /// 发送到上游前脱敏 (同时在日志中脱敏)
+    MaskUpstream,
+    /// 仅在持久化的日志中脱敏
+    #[default]
+    MaskLogs,
+    /// 拒绝请求 (400)
+    Block,
+}
+
+/// 内置检测器
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum RedactionDetector {
+    Email,
+    Phone,
+    CreditCard,
+    AwsKey,
+    GcpKey,
+    OpenaiKey,
+    Jwt,
+    PrivateKey,
+}
+
+impl RedactionDetector {
+    /// 规则按顺序执行：先匹配特征明确的密钥/卡号，再匹配宽松的邮箱/电话
+    pub const ALL: [RedactionDetector; 8] = [
+        RedactionDetector::PrivateKey,
+        RedactionDetector::Jwt,
+        RedactionDetector::AwsKey,
+        RedactionDetector::GcpKey,
+        RedactionDetector::OpenaiKey,
+        RedactionDetector::CreditCard,
+        RedactionDetector::Email,
+        RedactionDetector::Phone,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RedactionDetector::Email => "email",
+            RedactionDetector::Phone => "phone",
+            RedactionDetector::CreditCard => "credit_card",
+            RedactionDetector::AwsKey => "aws_key",
+            RedactionDetector::GcpKey => "gcp_key",
+            RedactionDetector::OpenaiKey => "openai_key",
+            RedactionDetector::Jwt => "jwt",
+            RedactionDetector::PrivateKey => "private_key",
+        }
+    }
+}
+
+/// 脱敏规则：内置检测器 (detector) 或自定义正则 (pattern)，二选一
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RedactionRule {
+    pub name: String,
+    #[serde(default)]
+    pub detector: Option<RedactionDetector>,
+    #[serde(default)]
+    pub pattern: Option<String>,
+    #[serde(default)]
+    pub action: RedactionAction,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+fn default_redaction_rules() -> Vec<RedactionRule> {
+    RedactionDetector::ALL
+        .iter()
+        .map(|detector| RedactionRule {
+            name: detector.as_str().to_string(),
+            detector: Some(*detector),
+            pattern: None,
+            action: RedactionAction::MaskLogs,
+            enabled: true,
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RedactionConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_redaction_rules")]
+    pub rules: Vec<RedactionRule>,
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            rules: default_redaction_rules(),
+        }
+    }
+}
+
+// ============================================================================
+// Safety Settings
+// ============================================================================
+
+/// Gemini 安全过滤阈值 (序列化值与上游 API 一致)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SafetyThreshold {
+    #[default]
+    Off,
+    BlockNone,
+    BlockOnlyHigh,
+    BlockMediumAndAbove,
+    BlockLowAndAbove,
+}
+
+impl SafetyThreshold {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SafetyThreshold::Off => "OFF",
+            SafetyThreshold::BlockNone => "BLOCK_NONE",
+            SafetyThreshold::BlockOnlyHigh => "BLOCK_ONLY_HIGH",
+            SafetyThreshold::BlockMediumAndAbove => "BLOCK_MEDIUM_AND_ABOVE",
+            SafetyThreshold::BlockLowAndAbove => "BLOCK_LOW_AND_ABOVE",
+        }
+    }
+}
+
+/// 安全过滤策略
+///
+/// 优先级: User Token > 模型 (精确 / 通配符) > 默认值
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct SafetyConfig {
+    /// 默认阈值 (OFF 保持原有行为)
+    #[serde(default)]
+    pub default_threshold: SafetyThreshold,
+    /// 模型名或通配符 -> 阈值
+    #[serde(default)]
+    pub model_thresholds: HashMap<String, SafetyThreshold>,
+    /// User Token ID -> 阈值
+    #[serde(default)]
+    pub token_thresholds: HashMap<String, SafetyThreshold>,
+    /// 允许 Gemini 原生请求自带的 safetySettings 透传
+    #[serde(default)]
+    pub allow_client_settings: bool,
+}
+
+// ============================================================================
+// Tool Result Compression (工具结果压缩)
+// ============================================================================
+
+fn default_tool_result_max_chars() -> usize {
+    200_000
+}
+
+/// 超大工具结果压缩配置 (浏览器快照 / 落盘提示 / HTML / base64 图片)
+///
+/// 阈值优先级: 模型 (精确 / 通配符) > 默认值
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ToolResultCompressionConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 单个工具结果的默认字符上限
+    #[serde(default = "default_tool_result_max_chars")]
+    pub default_max_chars: usize,
+    /// 模型名或通配符 -> 字符上限
+    #[serde(default)]
+    pub model_max_chars: HashMap<String, usize>,
+}
+
+impl Default for ToolResultCompressionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            default_max_chars: default_tool_result_max_chars(),
+            model_max_chars: HashMap::new(),
+        }
+    }
+}
+
+// ============================================================================
+// Upstream Timeouts (上游超时)
+// ============================================================================
+
+fn default_first_byte_timeout() -> u64 {
+    90
+}
+
+fn default_stream_idle_timeout() -> u64 {
+    60
+}
+
+fn default_min_request_timeout() -> u64 {
+    30
+}
+
+fn default_max_request_timeout() -> u64 {
+    1800
+}
+
+fn default_model_timeout_overrides() -> HashMap<String, ModelTimeoutOverride> {
+    HashMap::from([
+        // 图像生成首字节前需要完整出图，放宽总时长与首字节时限
+        (
+            "*-image*".to_string(),
+            ModelTimeoutOverride {
+                request_timeout: Some(300),
+                first_byte_timeout: Some(240),
+                stream_idle_timeout: None,
+            },
+        ),
+        // Flash 模型响应快，尽早判定卡死以便换号重试
+        (
+            "*flash*".to_string(),
+            ModelTimeoutOverride {
+                request_timeout: None,
+                first_byte_timeout: Some(45),
+                stream_idle_timeout: Some(30),
+            },
+        ),
+    ])
+}
+
+/// 单模型超时覆盖 (秒)，未设置的项沿用全局值
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct ModelTimeoutOverride {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_timeout: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_byte_timeout: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream_idle_timeout: Option<u64>,
+}
+
+/// 上游请求时限配置 (秒)；总时长默认取 `ProxyConfig.request_timeout`
+///
+/// 优先级: 请求头 `X-Request-Timeout` (限制在 min / max_request_timeout 之间) > 模型 (精确 / 通配符) > 全局
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UpstreamTimeoutConfig {
+    /// 发出请求到收到首个有效内容的时限
+    #[serde(default = "default_first_byte_timeout")]
+    pub first_byte_timeout: u64,
+    /// 流式响应相邻两个数据块之间的最长间隔
+    #[serde(default = "default_stream_idle_timeout")]
+    pub stream_idle_timeout: u64,
+    /// 请求头可指定的总时长下限
+    #[serde(default = "default_min_request_timeout")]
+    pub min_request_timeout: u64,
+    /// 请求头可指定的总时长上限
+    #[serde(default = "default_max_request_timeout")]
+    pub max_request_timeout: u64,
+    /// 模型名或通配符 -> 超时覆盖
+    #[serde(default = "default_model_timeout_overrides")]
+    pub model_overrides: HashMap<String, ModelTimeoutOverride>,
+}
+
+impl Default for UpstreamTimeoutConfig {
+    fn default() -> Self {
+        Self {
+            first_byte_timeout: default_first_byte_timeout(),
+            stream_idle_timeout: default_stream_idle_timeout(),
+            min_request_timeout: default_min_request_timeout(),
+            max_request_timeout: default_max_request_timeout(),
+            model_overrides: default_model_timeout_overrides(),
+        }
+    }
+}
+
+// ============================================================================
+// Model Fallback (跨模型降级)
+// ============================================================================
+
+/// 跨模型降级链配置
+///
+/// 主模型在整个账号池中均不可用 (限流 / 配额保护) 时，按链顺序尝试替代模型。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct ModelFallbackConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// 模型名或通配符 -> 降级链 (按顺序尝试)
+    #[serde(default)]
+    pub chains: HashMap<String, Vec<String>>,
+    /// 不参与降级的 User Token ID
+    #[serde(default)]
+    pub opt_out_tokens: Vec<String>,
+}
+
+// ============================================================================
+// Stream Recovery (流中断续写)
+// ============================================================================
+
+fn default_max_recoveries() -> u32 {
+    2
+}
+
+/// 流中断续写配置
+///
+/// 上游流在已向客户端输出内容后中断时，换账号并以已输出内容作为 prefill 续写。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StreamRecoveryConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// 单个请求最多续写次数
+    #[serde(default = "default_max_recoveries")]
+    pub max_recoveries: u32,
+}
+
+impl Default for StreamRecoveryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_recoveries: default_max_recoveries(),
+        }
+    }
+}
+
+// ============================================================================
+// Session Affinity (会话亲和)
+// ============================================================================
+
+fn default_session_header() -> String {
+    "x-session-id".to_string()
+}
+
+/// 会话亲和配置
+///
+/// 显式会话标识优先于内容指纹：请求头 > 请求体字段 (OpenAI `prompt_cache_key` / `user`，
+/// Claude `metadata.user_id`) > 首条用户消息哈希。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SessionAffinityConfig {
+    /// 客户端携带显式会话 ID 的请求头
+    #[serde(default = "default_session_header")]
+    pub header_name: String,
+    /// 内容指纹同时包含 system prompt (共享首句但模板不同的对话不再冲突)
+    #[serde(default)]
+    pub fingerprint_include_system: bool,
+    /// 会话 ID 按 User Token 隔离，不同租户永不共享绑定
+    #[serde(default = "default_true")]
+    pub scope_by_user_token: bool,
+}
+
+impl Default for SessionAffinityConfig {
+    fn default() -> Self {
+        Self {
+            header_name: default_session_header(),
+            fingerprint_include_system: false,
+            scope_by_user_token: true,
+        }
+    }
+}
+
+// ============================================================================
+// Hedged Requests (对冲请求)
+// ============================================================================
+
+fn default_hedge_max_extra_percent() -> u32 {
+    5
+}
+
+fn default_hedge_initial_delay_ms() -> u64 {
+    3000
+}
+
+fn default_hedge_min_delay_ms() -> u64 {
+    500
+}
+
+/// 对冲请求配置
+///
+/// 选中账号在首 token 延迟百分位内未返回内容时，在另一账号上发出相同请求，
+/// 取先返回者并取消另一路。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HedgingConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// 模型名或通配符 -> 触发对冲的首 token 延迟百分位 (1-100)
+    #[serde(default)]
+    pub model_percentiles: HashMap<String, u8>,
+    /// 对冲请求占启用对冲请求总数的最大百分比
+    #[serde(default = "default_hedge_max_extra_percent")]
+    pub max_extra_percent: u32,
+    /// 延迟样本不足时使用的对冲延迟 (毫秒)
+    #[serde(default = "default_hedge_initial_delay_ms")]
+    pub initial_delay_ms: u64,
+    /// 对冲延迟下限 (毫秒)
+    #[serde(default = "default_hedge_min_delay_ms")]
+    pub min_delay_ms: u64,
+}
+
+impl Default for HedgingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            model_percentiles: HashMap::new(),
+            max_extra_percent: default_hedge_max_extra_percent(),
+            initial_delay_ms: default_hedge_initial_delay_ms(),
+            min_delay_ms: default_hedge_min_delay_ms(),
+        }
+    }
+}
+
 // ============================================================================
 // ProxyConfig (main proxy configuration)
 // ============================================================================
@@ -627,6 +1264,10 @@ fn default_request_timeout() -> u64 {
     120
 }
 
+fn default_admin_session_ttl_hours() -> u64 {
+    12
+}
+
 /// 反代服务配置
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ProxyConfig {
@@ -638,6 +1279,9 @@ pub struct ProxyConfig {
     pub port: u16,
     pub api_key: String,
     pub admin_password: Option<String>,
+    /// 管理员登录会话有效期 (小时)
+    #[serde(default = "default_admin_session_ttl_hours")]
+    pub admin_session_ttl_hours: u64,
     pub auto_start: bool,
     #[serde(default)]
     pub custom_mapping: HashMap<String, String>,
@@ -671,6 +1315,26 @@ pub struct ProxyConfig {
     pub image_thinking_mode: Option<String>,
     #[serde(default)]
     pub proxy_pool: ProxyPoolConfig,
+    #[serde(default)]
+    pub tls: TlsConfig,
+    #[serde(default)]
+    pub jwt_auth: JwtAuthConfig,
+    #[serde(default)]
+    pub redaction: RedactionConfig,
+    #[serde(default)]
+    pub safety: SafetyConfig,
+    #[serde(default)]
+    pub model_fallback: ModelFallbackConfig,
+    #[serde(default)]
+    pub stream_recovery: StreamRecoveryConfig,
+    #[serde(default)]
+    pub hedging: HedgingConfig,
+    #[serde(default)]
+    pub session_affinity: SessionAffinityConfig,
+    #[serde(default)]
+    pub tool_result_compression: ToolResultCompressionConfig,
+    #[serde(default)]
+    pub upstream_timeouts: UpstreamTimeoutConfig,
 }
 
 impl Default for ProxyConfig {
@@ -682,6 +1346,7 @@ impl Default for ProxyConfig {
             port: 8045,
             api_key: format!("sk-{}", uuid::Uuid::new_v4().simple()),
             admin_password: None,
+            admin_session_ttl_hours: default_admin_session_ttl_hours(),
             auto_start: false,
             custom_mapping: HashMap::new(),
             request_timeout: default_request_timeout(),
@@ -699,6 +1364,16 @@ impl Default for ProxyConfig {
             global_system_prompt: GlobalSystemPromptConfig::default(),
             proxy_pool: ProxyPoolConfig::default(),
             image_thinking_mode: None,
+            tls: TlsConfig::default(),
+            jwt_auth: JwtAuthConfig::default(),
+            redaction: RedactionConfig::default(),
+            safety: SafetyConfig::default(),
+            model_fallback: ModelFallbackConfig::default(),
+            stream_recovery: StreamRecoveryConfig::default(),
+            hedging: HedgingConfig::default(),
+            session_affinity: SessionAffinityConfig::default(),
+            tool_result_compression: ToolResultCompressionConfig::default(),
+            upstream_timeouts: UpstreamTimeoutConfig::default(),
         }
     }
 }
@@ -794,9 +1469,36 @@ mod tests {
     }
 
     fn arb_sticky_session_config() -> impl Strategy<Value = StickySessionConfig> {
-        (arb_scheduling_mode(), 0u64..=600u64).prop_map(|(mode, max_wait_seconds)| {
-            StickySessionConfig { mode, max_wait_seconds }
-        })
+        (
+            arb_scheduling_mode(),
+            0u64..=600u64,
+            0u32..=16u32,
+            0u64..=120u64,
+            hash_map("[a-f0-9-]{36}", 1u32..=10u32, 0..3),
+            60u64..=604800u64,
+            0usize..=100000usize,
+        )
+            .prop_map(
+                |(
+                    mode,
+                    max_wait_seconds,
+                    max_concurrent_per_account,
+                    queue_timeout_seconds,
+                    fair_queue_weights,
+                    session_binding_ttl_seconds,
+                    max_session_bindings,
+                )| {
+                    StickySessionConfig {
+                        mode,
+                        max_wait_seconds,
+                        max_concurrent_per_account,
+                        queue_timeout_seconds,
+                        fair_queue_weights,
+                        session_binding_ttl_seconds,
+                        max_session_bindings,
+                    }
+                },
+            )
     }
 
     fn arb_zai_dispatch_mode() -> impl Strategy<Value = ZaiDispatchMode> {
@@ -892,10 +1594,22 @@ mod tests {
             })
     }
 
+    fn arb_system_prompt_mode() -> impl Strategy<Value = SystemPromptMode> {
+        prop_oneof![
+            Just(SystemPromptMode::Prepend),
+            Just(SystemPromptMode::Append),
+            Just(SystemPromptMode::Replace),
+        ]
+    }
+
     fn arb_global_system_prompt_config() -> impl Strategy<Value = GlobalSystemPromptConfig> {
-        (any::<bool>(), "[a-zA-Z0-9 ]{0,50}").prop_map(|(enabled, content)| {
-            GlobalSystemPromptConfig { enabled, content }
-        })
+        (any::<bool>(), "[a-zA-Z0-9 ]{0,50}", arb_system_prompt_mode()).prop_map(
+            |(enabled, content, mode)| GlobalSystemPromptConfig {
+                enabled,
+                content,
+                mode,
+            },
+        )
     }
 
     fn arb_debug_logging_config() -> impl Strategy<Value = DebugLoggingConfig> {
@@ -935,6 +1649,7 @@ mod tests {
             Just(ProxySelectionStrategy::Priority),
             Just(ProxySelectionStrategy::LeastConnections),
             Just(ProxySelectionStrategy::WeightedRoundRobin),
+            Just(ProxySelectionStrategy::LatencyAware),
         ]
     }
 
@@ -946,24 +1661,27 @@ mod tests {
 
     fn arb_proxy_entry() -> impl Strategy<Value = ProxyEntry> {
         (
-            "[a-f0-9-]{36}",
-            "[a-zA-Z0-9 ]{1,20}",
-            "[a-zA-Z0-9:/._-]{5,40}",
-            proptest::option::of(arb_proxy_auth()),
-            any::<bool>(),
-            -10i32..=10i32,
-            vec("[a-zA-Z0-9]{2,10}", 0..3),
-            proptest::option::of(1usize..=100usize),
-            proptest::option::of("[a-zA-Z0-9:/._-]{5,40}"),
-            proptest::option::of(0i64..=2_000_000_000i64),
-            any::<bool>(),
-            proptest::option::of(0u64..=10000u64),
+            (
+                "[a-f0-9-]{36}",
+                "[a-zA-Z0-9 ]{1,20}",
+                "[a-zA-Z0-9:/._-]{5,40}",
+                proptest::option::of(arb_proxy_auth()),
+                any::<bool>(),
+                -10i32..=10i32,
+                vec("[a-zA-Z0-9]{2,10}", 0..3),
+                proptest::option::of(1usize..=100usize),
+                proptest::option::of("[a-zA-Z0-9:/._-]{5,40}"),
+                proptest::option::of(0i64..=2_000_000_000i64),
+                any::<bool>(),
+                proptest::option::of(0u64..=10000u64),
+            ),
+            0u32..=10u32,
         )
             .prop_map(
-                |(id, name, url, auth, enabled, priority, tags, max_accounts, health_check_url, last_check_time, is_healthy, latency)| {
+                |((id, name, url, auth, enabled, priority, tags, max_accounts, health_check_url, last_check_time, is_healthy, latency), weight)| {
                     ProxyEntry {
                         id, name, url, auth, enabled, priority, tags, max_accounts,
-                        health_check_url, last_check_time, is_healthy, latency,
+                        health_check_url, last_check_time, is_healthy, latency, weight,
                     }
                 },
             )
@@ -1024,11 +1742,237 @@ mod tests {
     }
 
     fn arb_circuit_breaker_config() -> impl Strategy<Value = CircuitBreakerConfig> {
-        (any::<bool>(), vec(1u64..=10000u64, 1..6)).prop_map(|(enabled, backoff_steps)| {
-            CircuitBreakerConfig { enabled, backoff_steps }
+        (
+            any::<bool>(),
+            vec(1u64..=10000u64, 1..6),
+            0u32..=20u32,
+            1u8..=100u8,
+            1u64..=600u64,
+            0u32..=100u32,
+            1u64..=3600u64,
+        )
+            .prop_map(
+                |(
+                    enabled,
+                    backoff_steps,
+                    failure_threshold,
+                    error_rate_percent,
+                    window_seconds,
+                    min_requests,
+                    open_seconds,
+                )| CircuitBreakerConfig {
+                    enabled,
+                    backoff_steps,
+                    failure_threshold,
+                    error_rate_percent,
+                    window_seconds,
+                    min_requests,
+                    open_seconds,
+                },
+            )
+    }
+
+    fn arb_tls_config() -> impl Strategy<Value = TlsConfig> {
+        (
+            any::<bool>(),
+            proptest::option::of("/[a-z]{3,10}/cert\\.pem"),
+            proptest::option::of("/[a-z]{3,10}/key\\.pem"),
+            any::<bool>(),
+            1u64..=3600u64,
+            prop_oneof![
+                Just(ClientAuthMode::Off),
+                Just(ClientAuthMode::Optional),
+                Just(ClientAuthMode::Required),
+            ],
+            proptest::option::of("/[a-z]{3,10}/ca\\.pem"),
+            hash_map("CN=[a-z-]{3,15}", "[a-f0-9-]{36}", 0..3),
+        )
+            .prop_map(
+                |(enabled, cert_path, key_path, auto_generate_self_signed, reload_interval_secs, client_auth, client_ca_path, client_cert_identities)| {
+                    TlsConfig {
+                        enabled, cert_path, key_path, auto_generate_self_signed,
+                        reload_interval_secs, client_auth, client_ca_path, client_cert_identities,
+                    }
+                },
+            )
+    }
+
+    fn arb_jwt_auth_config() -> impl Strategy<Value = JwtAuthConfig> {
+        let group1 = (
+            any::<bool>(),
+            "https://[a-z]{3,10}\\.example",
+            vec("[a-z-]{3,15}", 0..3),
+            proptest::option::of("https://[a-z]{3,10}\\.example/jwks"),
+            proptest::option::of("/[a-z]{3,10}/jwks\\.json"),
+            1u64..=86400u64,
+        );
+        let group2 = (
+            vec(prop_oneof!["RS256", "ES256", "PS256", "EdDSA"].boxed(), 1..3),
+            0u64..=600u64,
+            "[a-z_]{3,20}",
+            "[a-z_]{3,20}",
+            vec("[a-z-]{3,15}", 0..3),
+            proptest::option::of("[a-z_]{3,20}"),
+        );
+        (group1, group2).prop_map(|(g1, g2)| JwtAuthConfig {
+            enabled: g1.0,
+            issuer: g1.1,
+            audiences: g1.2,
+            jwks_url: g1.3,
+            jwks_path: g1.4,
+            jwks_refresh_secs: g1.5,
+            algorithms: g2.0,
+            leeway_secs: g2.1,
+            username_claim: g2.2,
+            groups_claim: g2.3,
+            allowed_groups: g2.4,
+            quota_claim: g2.5,
         })
     }
 
+    fn arb_redaction_rule() -> impl Strategy<Value = RedactionRule> {
+        (
+            "[a-z_]{3,15}",
+            proptest::option::of(proptest::sample::select(RedactionDetector::ALL.to_vec())),
+            proptest::option::of("[a-z0-9]{3,10}"),
+            prop_oneof![
+                Just(RedactionAction::MaskUpstream),
+                Just(RedactionAction::MaskLogs),
+                Just(RedactionAction::Block),
+            ],
+            any::<bool>(),
+        )
+            .prop_map(|(name, detector, pattern, action, enabled)| RedactionRule {
+                name, detector, pattern, action, enabled,
+            })
+    }
+
+    fn arb_redaction_config() -> impl Strategy<Value = RedactionConfig> {
+        (any::<bool>(), vec(arb_redaction_rule(), 0..5))
+            .prop_map(|(enabled, rules)| RedactionConfig { enabled, rules })
+    }
+
+    fn arb_safety_threshold() -> impl Strategy<Value = SafetyThreshold> {
+        prop_oneof![
+            Just(SafetyThreshold::Off),
+            Just(SafetyThreshold::BlockNone),
+            Just(SafetyThreshold::BlockOnlyHigh),
+            Just(SafetyThreshold::BlockMediumAndAbove),
+            Just(SafetyThreshold::BlockLowAndAbove),
+        ]
+    }
+
+    fn arb_safety_config() -> impl Strategy<Value = SafetyConfig> {
+        (
+            arb_safety_threshold(),
+            hash_map("[a-z0-9*-]{3,20}", arb_safety_threshold(), 0..3),
+            hash_map("[a-f0-9-]{36}", arb_safety_threshold(), 0..3),
+            any::<bool>(),
+        )
+            .prop_map(|(default_threshold, model_thresholds, token_thresholds, allow_client_settings)| {
+                SafetyConfig { default_threshold, model_thresholds, token_thresholds, allow_client_settings }
+            })
+    }
+
+    fn arb_model_fallback_config() -> impl Strategy<Value = ModelFallbackConfig> {
+        (
+            any::<bool>(),
+            hash_map("[a-z0-9*-]{3,20}", vec("[a-z0-9-]{3,20}", 0..3), 0..3),
+            vec("[a-f0-9-]{36}", 0..3),
+        )
+            .prop_map(|(enabled, chains, opt_out_tokens)| ModelFallbackConfig {
+                enabled,
+                chains,
+                opt_out_tokens,
+            })
+    }
+
+    fn arb_stream_recovery_config() -> impl Strategy<Value = StreamRecoveryConfig> {
+        (any::<bool>(), 0u32..=5u32).prop_map(|(enabled, max_recoveries)| StreamRecoveryConfig {
+            enabled,
+            max_recoveries,
+        })
+    }
+
+    fn arb_session_affinity_config() -> impl Strategy<Value = SessionAffinityConfig> {
+        ("x-[a-z-]{3,20}", any::<bool>(), any::<bool>()).prop_map(
+            |(header_name, fingerprint_include_system, scope_by_user_token)| {
+                SessionAffinityConfig {
+                    header_name,
+                    fingerprint_include_system,
+                    scope_by_user_token,
+                }
+            },
+        )
+    }
+
+    fn arb_hedging_config() -> impl Strategy<Value = HedgingConfig> {
+        (
+            any::<bool>(),
+            hash_map("[a-z0-9*-]{3,20}", 1u8..=100u8, 0..3),
+            0u32..=20u32,
+            100u64..=10000u64,
+            0u64..=2000u64,
+        )
+            .prop_map(
+                |(enabled, model_percentiles, max_extra_percent, initial_delay_ms, min_delay_ms)| {
+                    HedgingConfig {
+                        enabled,
+                        model_percentiles,
+                        max_extra_percent,
+                        initial_delay_ms,
+                        min_delay_ms,
+                    }
+                },
+            )
+    }
+
+    fn arb_tool_result_compression_config() -> impl Strategy<Value = ToolResultCompressionConfig> {
+        (
+            any::<bool>(),
+            1000usize..=500_000usize,
+            hash_map("[a-z0-9*-]{3,20}", 1000usize..=500_000usize, 0..3),
+        )
+            .prop_map(|(enabled, default_max_chars, model_max_chars)| {
+                ToolResultCompressionConfig { enabled, default_max_chars, model_max_chars }
+            })
+    }
+
+    fn arb_upstream_timeout_config() -> impl Strategy<Value = UpstreamTimeoutConfig> {
+        let arb_override = (
+            proptest::option::of(30u64..=7200u64),
+            proptest::option::of(5u64..=600u64),
+            proptest::option::of(5u64..=600u64),
+        )
+            .prop_map(|(request_timeout, first_byte_timeout, stream_idle_timeout)| {
+                ModelTimeoutOverride { request_timeout, first_byte_timeout, stream_idle_timeout }
+            });
+        (
+            5u64..=600u64,
+            5u64..=600u64,
+            1u64..=120u64,
+            30u64..=7200u64,
+            hash_map("[a-z0-9*-]{3,20}", arb_override, 0..3),
+        )
+            .prop_map(
+                |(
+                    first_byte_timeout,
+                    stream_idle_timeout,
+                    min_request_timeout,
+                    max_request_timeout,
+                    model_overrides,
+                )| {
+                    UpstreamTimeoutConfig {
+                        first_byte_timeout,
+                        stream_idle_timeout,
+                        min_request_timeout,
+                        max_request_timeout,
+                        model_overrides,
+                    }
+                },
+            )
+    }
+
     /// Build a ProxyConfig strategy by composing smaller groups.
     fn arb_proxy_config() -> impl Strategy<Value = ProxyConfig> {
         let group1 = (
@@ -1054,6 +1998,9 @@ mod tests {
             arb_security_monitor_config(),
             proptest::option::of("[a-f0-9-]{36}"),
             proptest::option::of("[a-zA-Z0-9 /_-]{3,30}"),
+            arb_session_affinity_config(),
+            arb_tool_result_compression_config(),
+            arb_upstream_timeout_config(),
         );
 
         let group3 = (
@@ -1061,6 +2008,14 @@ mod tests {
             arb_global_system_prompt_config(),
             proptest::option::of(prop_oneof!["enabled", "disabled"].boxed()),
             arb_proxy_pool_config(),
+            1u64..=720u64,
+            arb_tls_config(),
+            arb_jwt_auth_config(),
+            arb_redaction_config(),
+            arb_safety_config(),
+            arb_model_fallback_config(),
+            arb_stream_recovery_config(),
+            arb_hedging_config(),
         );
 
         (group1, group2, group3).prop_map(|(g1, g2, g3)| ProxyConfig {
@@ -1070,6 +2025,7 @@ mod tests {
             port: g1.3,
             api_key: g1.4,
             admin_password: g1.5,
+            admin_session_ttl_hours: g3.4,
             auto_start: g1.6,
             custom_mapping: g1.7,
             request_timeout: g1.8,
@@ -1087,6 +2043,16 @@ mod tests {
             global_system_prompt: g3.1,
             image_thinking_mode: g3.2,
             proxy_pool: g3.3,
+            tls: g3.5,
+            jwt_auth: g3.6,
+            redaction: g3.7,
+            safety: g3.8,
+            model_fallback: g3.9,
+            stream_recovery: g3.10,
+            hedging: g3.11,
+            session_affinity: g2.9,
+            tool_result_compression: g2.10,
+            upstream_timeouts: g2.11,
         })
     }
 
@@ -12,8 +12,8 @@ pub use config::{
     GlobalSystemPromptConfig, IpBlacklistConfig, IpWhitelistConfig, PinnedQuotaModelsConfig,
     ProxyAuth, ProxyAuthMode, ProxyConfig, ProxyEntry, ProxyPoolConfig, ProxySelectionStrategy,
     QuotaProtectionConfig, ScheduledWarmupConfig, SchedulingMode, SecurityMonitorConfig,
-    StickySessionConfig, ThinkingBudgetConfig, ThinkingBudgetMode, TunnelMode, UpstreamProxyConfig,
-    ZaiConfig, ZaiDispatchMode, ZaiMcpConfig, ZaiModelDefaults,
+    StickySessionConfig, SystemPromptMode, ThinkingBudgetConfig, ThinkingBudgetMode, TunnelMode,
+    UpstreamProxyConfig, ZaiConfig, ZaiDispatchMode, ZaiMcpConfig, ZaiModelDefaults,
 };
 pub use quota::{ModelQuota, QuotaData};
 pub use token::TokenData;
@@ -0,0 +1,803 @@
+//! Admin User Database Module
+//! 管理员账号与登录会话数据库操作模块
+//!
+//! 替代单一共享的 admin_password：每个管理员拥有独立账号和角色
+//! (viewer / operator / owner)，密码使用 Argon2 哈希存储，
+//! 登录后签发带过期时间的会话令牌（数据库仅保存令牌的 SHA-256）。
+
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// 最短密码长度
+pub const MIN_PASSWORD_LEN: usize = 8;
+
+/// 连续失败该次数以内不限制登录
+const LOGIN_FREE_ATTEMPTS: u32 = 5;
+/// 超出免限制次数后的首次锁定时长，此后每次失败翻倍
+const LOGIN_BASE_BACKOFF: Duration = Duration::from_secs(2);
+/// 单次锁定时长上限
+const LOGIN_MAX_BACKOFF: Duration = Duration::from_secs(15 * 60);
+/// 距上次失败超过该时长后清零失败计数
+const LOGIN_FAILURE_WINDOW: Duration = Duration::from_secs(30 * 60);
+/// 失败记录条数上限，超出时清理过期记录
+const LOGIN_THROTTLE_LIMIT: usize = 10_000;
+
+// ============================================================================
+// Data Structures
+// ============================================================================
+
+/// 管理员角色（按权限从低到高排序）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AdminRole {
+    /// 只读：统计与日志
+    Viewer,
+    /// 运维：账号、限流、预热
+    Operator,
+    /// 所有者：配置、安全、令牌、管理员账号
+    Owner,
+}
+
+impl AdminRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AdminRole::Viewer => "viewer",
+            AdminRole::Operator => "operator",
+            AdminRole::Owner => "owner",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "viewer" => Some(AdminRole::Viewer),
+            "operator" => Some(AdminRole::Operator),
+            "owner" => Some(AdminRole::Owner),
+            _ => None,
+        }
+    }
+
+    /// 当前角色是否满足所需的最低角色
+    pub fn satisfies(&self, required: AdminRole) -> bool {
+        *self >= required
+    }
+}
+
+/// 管理员账号
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdminUser {
+    pub id: String,
+    pub username: String,
+    #[serde(skip_serializing, default)]
+    pub password_hash: String,
+    pub role: AdminRole,
+    pub enabled: bool,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub last_login_at: Option<i64>,
+}
+
+/// 登录会话（仅在签发时返回明文令牌）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdminSession {
+    pub token: String,
+    pub user_id: String,
+    pub username: String,
+    pub role: AdminRole,
+    pub expires_at: i64,
+}
+
+// ============================================================================
+// Password & token hashing
+// ============================================================================
+
+/// 使用 Argon2id 哈希密码，返回 PHC 格式字符串
+pub fn hash_password(password: &str) -> Result<String, String> {
+    let salt_bytes: [u8; 16] = rand::random();
+    let salt = SaltString::encode_b64(&salt_bytes).map_err(|e| e.to_string())?;
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|h| h.to_string())
+        .map_err(|e| format!("Failed to hash password: {}", e))
+}
+
+/// 校验密码与 PHC 哈希是否匹配
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    match PasswordHash::new(password_hash) {
+        Ok(parsed) => Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// 未知用户名登录时用于校验的固定哈希（与真实哈希参数相同，开销一致）
+fn dummy_password_hash() -> &'static str {
+    static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+    DUMMY_HASH.get_or_init(|| hash_password("kiro-dummy-password").unwrap_or_default())
+}
+
+fn hash_session_token(token: &str) -> String {
+    let digest = Sha256::digest(token.as_bytes());
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn generate_session_token() -> String {
+    format!(
+        "kas-{}{}",
+        Uuid::new_v4().simple(),
+        Uuid::new_v4().simple()
+    )
+}
+
+fn validate_password_strength(password: &str) -> Result<(), String> {
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(format!(
+            "Password must be at least {} characters",
+            MIN_PASSWORD_LEN
+        ));
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Database Connection
+// ============================================================================
+
+fn get_db_path() -> Result<PathBuf, String> {
+    Ok(crate::modules::account::get_data_dir()?.join("admin_users.db"))
+}
+
+/// 本进程内表结构是否已初始化（避免每次管理请求重复执行 CREATE TABLE）
+static SCHEMA_READY: AtomicBool = AtomicBool::new(false);
+
+fn connect_db() -> Result<Connection, String> {
+    let path = get_db_path()?;
+    let conn = Connection::open(&path)
+        .map_err(|e| format!("Failed to open admin_users.db: {}", e))?;
+    conn.pragma_update(None, "busy_timeout", 5000)
+        .map_err(|e| e.to_string())?;
+    if !SCHEMA_READY.load(Ordering::Acquire) {
+        init_db_with_conn(&conn)?;
+        SCHEMA_READY.store(true, Ordering::Release);
+    }
+    Ok(conn)
+}
+
+/// 初始化 admin_users.db（可重复调用）
+pub fn init_db() -> Result<(), String> {
+    let conn = connect_db()?;
+    init_db_with_conn(&conn)
+}
+
+fn init_db_with_conn(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS admin_users (
+            id TEXT PRIMARY KEY,
+            username TEXT UNIQUE NOT NULL COLLATE NOCASE,
+            password_hash TEXT NOT NULL,
+            role TEXT NOT NULL,
+            enabled BOOLEAN DEFAULT 1,
+            created_at INTEGER,
+            updated_at INTEGER,
+            last_login_at INTEGER
+        );
+        CREATE TABLE IF NOT EXISTS admin_sessions (
+            token_hash TEXT PRIMARY KEY,
+            user_id TEXT NOT NULL,
+            created_at INTEGER,
+            expires_at INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_admin_sessions_user ON admin_sessions (user_id);",
+    )
+    .map_err(|e| format!("Failed to init admin_users.db: {}", e))
+}
+
+// ============================================================================
+// Public API
+// ============================================================================
+
+pub fn count_users() -> Result<i64, String> {
+    count_users_with_conn(&connect_db()?)
+}
+
+/// 是否存在启用中的 owner（不存在时仍接受旧的共享密钥，避免无人可管理）
+pub fn has_enabled_owner() -> Result<bool, String> {
+    Ok(count_enabled_owners_with_conn(&connect_db()?)? > 0)
+}
+
+pub fn list_users() -> Result<Vec<AdminUser>, String> {
+    list_users_with_conn(&connect_db()?)
+}
+
+pub fn create_user(username: &str, password: &str, role: AdminRole) -> Result<AdminUser, String> {
+    create_user_with_conn(&connect_db()?, username, password, role)
+}
+
+pub fn update_user(
+    id: &str,
+    role: Option<AdminRole>,
+    enabled: Option<bool>,
+    password: Option<&str>,
+) -> Result<(), String> {
+    update_user_with_conn(&connect_db()?, id, role, enabled, password)
+}
+
+pub fn delete_user(id: &str) -> Result<(), String> {
+    delete_user_with_conn(&connect_db()?, id)
+}
+
+/// 用户名密码登录，成功后签发会话令牌
+pub fn login(username: &str, password: &str, ttl_secs: i64) -> Result<Option<AdminSession>, String> {
+    login_with_conn(&connect_db()?, username, password, ttl_secs)
+}
+
+/// 校验会话令牌，返回对应的（启用中的）管理员
+pub fn validate_session(token: &str) -> Result<Option<AdminUser>, String> {
+    validate_session_with_conn(&connect_db()?, token)
+}
+
+pub fn revoke_session(token: &str) -> Result<(), String> {
+    revoke_session_with_conn(&connect_db()?, token)
+}
+
+/// 清理已过期的会话
+pub fn cleanup_expired_sessions() -> Result<usize, String> {
+    let conn = connect_db()?;
+    conn.execute(
+        "DELETE FROM admin_sessions WHERE expires_at <= ?1",
+        params![Utc::now().timestamp()],
+    )
+    .map_err(|e| e.to_string())
+}
+
+// ============================================================================
+// Login throttling
+// ============================================================================
+
+#[derive(Debug, Clone, Copy)]
+struct LoginFailures {
+    count: u32,
+    last_failure: Instant,
+    locked_until: Option<Instant>,
+}
+
+/// 登录失败退避（按用户名与客户端 IP 分别计数）
+///
+/// 连续失败超过 LOGIN_FREE_ATTEMPTS 次后按指数退避锁定，成功登录后清零。
+#[derive(Debug, Default)]
+pub struct LoginThrottle {
+    entries: Mutex<HashMap<String, LoginFailures>>,
+}
+
+impl LoginThrottle {
+    pub fn global() -> &'static LoginThrottle {
+        static INSTANCE: OnceLock<LoginThrottle> = OnceLock::new();
+        INSTANCE.get_or_init(LoginThrottle::default)
+    }
+
+    /// 限流键：用户名（忽略大小写）与客户端 IP
+    pub fn keys(username: &str, client_ip: Option<&str>) -> Vec<String> {
+        let mut keys = vec![format!("user:{}", username.trim().to_lowercase())];
+        if let Some(ip) = client_ip {
+            keys.push(format!("ip:{}", ip));
+        }
+        keys
+    }
+
+    /// 仍处于锁定期时返回剩余时长
+    pub fn retry_after(&self, keys: &[String]) -> Option<Duration> {
+        self.retry_after_at(keys, Instant::now())
+    }
+
+    pub fn record_failure(&self, keys: &[String]) {
+        self.record_failure_at(keys, Instant::now());
+    }
+
+    pub fn record_success(&self, keys: &[String]) {
+        if let Ok(mut entries) = self.entries.lock() {
+            for key in keys {
+                entries.remove(key);
+            }
+        }
+    }
+
+    fn retry_after_at(&self, keys: &[String], now: Instant) -> Option<Duration> {
+        let entries = self.entries.lock().ok()?;
+        keys.iter()
+            .filter_map(|key| entries.get(key)?.locked_until)
+            .filter(|until| *until > now)
+            .map(|until| until - now)
+            .max()
+    }
+
+    fn record_failure_at(&self, keys: &[String], now: Instant) {
+        let Ok(mut entries) = self.entries.lock() else {
+            return;
+        };
+        if entries.len() >= LOGIN_THROTTLE_LIMIT {
+            entries.retain(|_, f| now.duration_since(f.last_failure) < LOGIN_FAILURE_WINDOW);
+        }
+        for key in keys {
+            let entry = entries.entry(key.clone()).or_insert(LoginFailures {
+                count: 0,
+                last_failure: now,
+                locked_until: None,
+            });
+            if now.duration_since(entry.last_failure) >= LOGIN_FAILURE_WINDOW {
+                entry.count = 0;
+            }
+            entry.count += 1;
+            entry.last_failure = now;
+            if entry.count > LOGIN_FREE_ATTEMPTS {
+                let exponent = (entry.count - LOGIN_FREE_ATTEMPTS - 1).min(16);
+                let backoff = LOGIN_BASE_BACKOFF
+                    .saturating_mul(1 << exponent)
+                    .min(LOGIN_MAX_BACKOFF);
+                entry.locked_until = Some(now + backoff);
+            }
+        }
+    }
+}
+
+// ============================================================================
+// Internal DB helpers (testable with injected connection)
+// ============================================================================
+
+const USER_COLUMNS: &str =
+    "id, username, password_hash, role, enabled, created_at, updated_at, last_login_at";
+
+fn row_to_user(row: &rusqlite::Row) -> rusqlite::Result<AdminUser> {
+    let role: String = row.get(3)?;
+    Ok(AdminUser {
+        id: row.get(0)?,
+        username: row.get(1)?,
+        password_hash: row.get(2)?,
+        role: AdminRole::parse(&role).unwrap_or(AdminRole::Viewer),
+        enabled: row.get(4)?,
+        created_at: row.get(5)?,
+        updated_at: row.get(6)?,
+        last_login_at: row.get(7)?,
+    })
+}
+
+fn count_users_with_conn(conn: &Connection) -> Result<i64, String> {
+    conn.query_row("SELECT COUNT(*) FROM admin_users", [], |row| row.get(0))
+        .map_err(|e| e.to_string())
+}
+
+fn list_users_with_conn(conn: &Connection) -> Result<Vec<AdminUser>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM admin_users ORDER BY created_at ASC",
+            USER_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], row_to_user)
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+}
+
+fn get_user_with_conn(conn: &Connection, id: &str) -> Result<Option<AdminUser>, String> {
+    conn.query_row(
+        &format!("SELECT {} FROM admin_users WHERE id = ?1", USER_COLUMNS),
+        params![id],
+        row_to_user,
+    )
+    .optional()
+    .map_err(|e| e.to_string())
+}
+
+fn count_enabled_owners_with_conn(conn: &Connection) -> Result<i64, String> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM admin_users WHERE role = 'owner' AND enabled = 1",
+        [],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+fn create_user_with_conn(
+    conn: &Connection,
+    username: &str,
+    password: &str,
+    role: AdminRole,
+) -> Result<AdminUser, String> {
+    let username = username.trim();
+    if username.is_empty() {
+        return Err("Username must not be empty".to_string());
+    }
+    validate_password_strength(password)?;
+
+    let exists: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM admin_users WHERE username = ?1 COLLATE NOCASE",
+            params![username],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if exists > 0 {
+        return Err(format!("Admin user '{}' already exists", username));
+    }
+    // 首个可用账号必须是 owner，否则无人能访问 owner 路由
+    if role != AdminRole::Owner && count_enabled_owners_with_conn(conn)? == 0 {
+        return Err("Create an enabled owner before adding other admin users".to_string());
+    }
+
+    let id = Uuid::new_v4().to_string();
+    let password_hash = hash_password(password)?;
+    let now = Utc::now().timestamp();
+
+    conn.execute(
+        "INSERT INTO admin_users (id, username, password_hash, role, enabled, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, 1, ?5, ?5)",
+        params![id, username, password_hash, role.as_str(), now],
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(AdminUser {
+        id,
+        username: username.to_string(),
+        password_hash,
+        role,
+        enabled: true,
+        created_at: now,
+        updated_at: now,
+        last_login_at: None,
+    })
+}
+
+fn update_user_with_conn(
+    conn: &Connection,
+    id: &str,
+    role: Option<AdminRole>,
+    enabled: Option<bool>,
+    password: Option<&str>,
+) -> Result<(), String> {
+    let user = get_user_with_conn(conn, id)?
+        .ok_or_else(|| format!("Admin user not found: {}", id))?;
+
+    // 不允许移除最后一个可用的 owner
+    let loses_owner = user.role == AdminRole::Owner
+        && user.enabled
+        && (role.map(|r| r != AdminRole::Owner).unwrap_or(false) || enabled == Some(false));
+    if loses_owner && count_enabled_owners_with_conn(conn)? <= 1 {
+        return Err("Cannot demote or disable the last enabled owner".to_string());
+    }
+
+    let now = Utc::now().timestamp();
+    if let Some(role) = role {
+        conn.execute(
+            "UPDATE admin_users SET role = ?1, updated_at = ?2 WHERE id = ?3",
+            params![role.as_str(), now, id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    if let Some(enabled) = enabled {
+        conn.execute(
+            "UPDATE admin_users SET enabled = ?1, updated_at = ?2 WHERE id = ?3",
+            params![enabled, now, id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    if let Some(password) = password {
+        validate_password_strength(password)?;
+        let password_hash = hash_password(password)?;
+        conn.execute(
+            "UPDATE admin_users SET password_hash = ?1, updated_at = ?2 WHERE id = ?3",
+            params![password_hash, now, id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    // 角色、状态或密码变化后，强制该用户重新登录
+    if role.is_some() || enabled == Some(false) || password.is_some() {
+        conn.execute(
+            "DELETE FROM admin_sessions WHERE user_id = ?1",
+            params![id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn delete_user_with_conn(conn: &Connection, id: &str) -> Result<(), String> {
+    let user = get_user_with_conn(conn, id)?
+        .ok_or_else(|| format!("Admin user not found: {}", id))?;
+    if user.role == AdminRole::Owner && user.enabled && count_enabled_owners_with_conn(conn)? <= 1 {
+        return Err("Cannot delete the last enabled owner".to_string());
+    }
+    conn.execute("DELETE FROM admin_sessions WHERE user_id = ?1", params![id])
+        .map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM admin_users WHERE id = ?1", params![id])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn login_with_conn(
+    conn: &Connection,
+    username: &str,
+    password: &str,
+    ttl_secs: i64,
+) -> Result<Option<AdminSession>, String> {
+    let user = conn
+        .query_row(
+            &format!(
+                "SELECT {} FROM admin_users WHERE username = ?1 COLLATE NOCASE",
+                USER_COLUMNS
+            ),
+            params![username.trim()],
+            row_to_user,
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+
+    // 用户名不存在时也校验一次固定哈希，避免通过响应时间探测用户名是否存在
+    let password_hash = match &user {
+        Some(u) => u.password_hash.as_str(),
+        None => dummy_password_hash(),
+    };
+    let password_ok = verify_password(password, password_hash);
+    let user = match user {
+        Some(u) if u.enabled && password_ok => u,
+        _ => return Ok(None),
+    };
+
+    let token = generate_session_token();
+    let now = Utc::now().timestamp();
+    let expires_at = now + ttl_secs.max(60);
+
+    conn.execute(
+        "INSERT INTO admin_sessions (token_hash, user_id, created_at, expires_at) VALUES (?1, ?2, ?3, ?4)",
+        params![hash_session_token(&token), user.id, now, expires_at],
+    )
+    .map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE admin_users SET last_login_at = ?1 WHERE id = ?2",
+        params![now, user.id],
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(Some(AdminSession {
+        token,
+        user_id: user.id,
+        username: user.username,
+        role: user.role,
+        expires_at,
+    }))
+}
+
+fn validate_session_with_conn(conn: &Connection, token: &str) -> Result<Option<AdminUser>, String> {
+    let session: Option<(String, i64)> = conn
+        .query_row(
+            "SELECT user_id, expires_at FROM admin_sessions WHERE token_hash = ?1",
+            params![hash_session_token(token)],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+
+    let (user_id, expires_at) = match session {
+        Some(s) => s,
+        None => return Ok(None),
+    };
+
+    if Utc::now().timestamp() >= expires_at {
+        revoke_session_with_conn(conn, token)?;
+        return Ok(None);
+    }
+
+    Ok(get_user_with_conn(conn, &user_id)?.filter(|u| u.enabled))
+}
+
+fn revoke_session_with_conn(conn: &Connection, token: &str) -> Result<(), String> {
+    conn.execute(
+        "DELETE FROM admin_sessions WHERE token_hash = ?1",
+        params![hash_session_token(token)],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup_test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db_with_conn(&conn).unwrap();
+        conn
+    }
+
+    /// 已有 owner 的数据库（非 owner 账号需在 owner 之后创建）
+    fn setup_test_db_with_owner() -> Connection {
+        let conn = setup_test_db();
+        create_user_with_conn(&conn, "owner", "password123", AdminRole::Owner).unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_role_ordering() {
+        assert!(AdminRole::Owner.satisfies(AdminRole::Operator));
+        assert!(AdminRole::Operator.satisfies(AdminRole::Viewer));
+        assert!(AdminRole::Viewer.satisfies(AdminRole::Viewer));
+        assert!(!AdminRole::Viewer.satisfies(AdminRole::Operator));
+        assert!(!AdminRole::Operator.satisfies(AdminRole::Owner));
+    }
+
+    #[test]
+    fn test_role_parse_roundtrip() {
+        for role in [AdminRole::Viewer, AdminRole::Operator, AdminRole::Owner] {
+            assert_eq!(AdminRole::parse(role.as_str()), Some(role));
+        }
+        assert_eq!(AdminRole::parse(" Owner "), Some(AdminRole::Owner));
+        assert_eq!(AdminRole::parse("root"), None);
+    }
+
+    #[test]
+    fn test_password_hash_and_verify() {
+        let hash = hash_password("correct horse").unwrap();
+        assert!(hash.starts_with("$argon2"));
+        assert!(verify_password("correct horse", &hash));
+        assert!(!verify_password("wrong horse", &hash));
+        assert!(!verify_password("correct horse", "not-a-hash"));
+    }
+
+    #[test]
+    fn test_create_user_rejects_weak_password_and_duplicates() {
+        let conn = setup_test_db();
+        assert!(create_user_with_conn(&conn, "alice", "short", AdminRole::Owner).is_err());
+        create_user_with_conn(&conn, "alice", "long-enough", AdminRole::Owner).unwrap();
+        assert!(create_user_with_conn(&conn, "alice", "long-enough", AdminRole::Viewer).is_err());
+        assert!(create_user_with_conn(&conn, " ALICE ", "long-enough", AdminRole::Viewer).is_err());
+        assert_eq!(count_users_with_conn(&conn).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_login_username_is_case_insensitive() {
+        let conn = setup_test_db();
+        create_user_with_conn(&conn, "Dave", "password123", AdminRole::Owner).unwrap();
+
+        let session = login_with_conn(&conn, "dave", "password123", 3600).unwrap().unwrap();
+        assert_eq!(session.username, "Dave");
+        assert!(login_with_conn(&conn, "DAVE", "password123", 3600).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_dummy_hash_is_a_valid_argon2_hash() {
+        // 未知用户名的校验必须真正执行 Argon2，而不是因解析失败立即返回
+        assert!(PasswordHash::new(dummy_password_hash()).is_ok());
+        assert!(!verify_password("password123", dummy_password_hash()));
+    }
+
+    #[test]
+    fn test_user_serialization_hides_password_hash() {
+        let conn = setup_test_db_with_owner();
+        let user = create_user_with_conn(&conn, "bob", "password123", AdminRole::Viewer).unwrap();
+        let json = serde_json::to_string(&user).unwrap();
+        assert!(!json.contains("password_hash"));
+        assert!(json.contains("\"role\":\"viewer\""));
+    }
+
+    #[test]
+    fn test_login_and_validate_session() {
+        let conn = setup_test_db_with_owner();
+        create_user_with_conn(&conn, "carol", "password123", AdminRole::Operator).unwrap();
+
+        assert!(login_with_conn(&conn, "carol", "bad-password", 3600).unwrap().is_none());
+        assert!(login_with_conn(&conn, "nobody", "password123", 3600).unwrap().is_none());
+
+        let session = login_with_conn(&conn, "carol", "password123", 3600).unwrap().unwrap();
+        assert_eq!(session.role, AdminRole::Operator);
+
+        let user = validate_session_with_conn(&conn, &session.token).unwrap().unwrap();
+        assert_eq!(user.username, "carol");
+        assert!(validate_session_with_conn(&conn, "kas-bogus").unwrap().is_none());
+
+        revoke_session_with_conn(&conn, &session.token).unwrap();
+        assert!(validate_session_with_conn(&conn, &session.token).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_expired_session_is_rejected() {
+        let conn = setup_test_db_with_owner();
+        create_user_with_conn(&conn, "dave", "password123", AdminRole::Viewer).unwrap();
+        let session = login_with_conn(&conn, "dave", "password123", 3600).unwrap().unwrap();
+        conn.execute("UPDATE admin_sessions SET expires_at = 0", []).unwrap();
+        assert!(validate_session_with_conn(&conn, &session.token).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_session_token_not_stored_in_plaintext() {
+        let conn = setup_test_db_with_owner();
+        create_user_with_conn(&conn, "erin", "password123", AdminRole::Viewer).unwrap();
+        let session = login_with_conn(&conn, "erin", "password123", 3600).unwrap().unwrap();
+        let stored: String = conn
+            .query_row("SELECT token_hash FROM admin_sessions", [], |row| row.get(0))
+            .unwrap();
+        assert_ne!(stored, session.token);
+        assert_eq!(stored, hash_session_token(&session.token));
+    }
+
+    #[test]
+    fn test_disable_or_password_change_revokes_sessions() {
+        let conn = setup_test_db();
+        create_user_with_conn(&conn, "owner", "password123", AdminRole::Owner).unwrap();
+        let user = create_user_with_conn(&conn, "frank", "password123", AdminRole::Operator).unwrap();
+
+        let session = login_with_conn(&conn, "frank", "password123", 3600).unwrap().unwrap();
+        update_user_with_conn(&conn, &user.id, None, None, Some("new-password")).unwrap();
+        assert!(validate_session_with_conn(&conn, &session.token).unwrap().is_none());
+        assert!(login_with_conn(&conn, "frank", "password123", 3600).unwrap().is_none());
+
+        let session = login_with_conn(&conn, "frank", "new-password", 3600).unwrap().unwrap();
+        update_user_with_conn(&conn, &user.id, None, Some(false), None).unwrap();
+        assert!(validate_session_with_conn(&conn, &session.token).unwrap().is_none());
+        assert!(login_with_conn(&conn, "frank", "new-password", 3600).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_last_owner_is_protected() {
+        let conn = setup_test_db();
+        let owner = create_user_with_conn(&conn, "root", "password123", AdminRole::Owner).unwrap();
+
+        assert!(update_user_with_conn(&conn, &owner.id, Some(AdminRole::Viewer), None, None).is_err());
+        assert!(update_user_with_conn(&conn, &owner.id, None, Some(false), None).is_err());
+        assert!(delete_user_with_conn(&conn, &owner.id).is_err());
+
+        create_user_with_conn(&conn, "root2", "password123", AdminRole::Owner).unwrap();
+        update_user_with_conn(&conn, &owner.id, Some(AdminRole::Viewer), None, None).unwrap();
+        delete_user_with_conn(&conn, &owner.id).unwrap();
+        assert_eq!(count_users_with_conn(&conn).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_first_user_must_be_owner() {
+        let conn = setup_test_db();
+        assert!(create_user_with_conn(&conn, "viewer", "password123", AdminRole::Viewer).is_err());
+        assert!(create_user_with_conn(&conn, "ops", "password123", AdminRole::Operator).is_err());
+        assert_eq!(count_users_with_conn(&conn).unwrap(), 0);
+
+        create_user_with_conn(&conn, "root", "password123", AdminRole::Owner).unwrap();
+        create_user_with_conn(&conn, "viewer", "password123", AdminRole::Viewer).unwrap();
+        assert_eq!(count_users_with_conn(&conn).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_login_throttle_backoff() {
+        let throttle = LoginThrottle::default();
+        let keys = LoginThrottle::keys(" Alice ", Some("10.0.0.1"));
+        assert_eq!(keys, vec!["user:alice".to_string(), "ip:10.0.0.1".to_string()]);
+
+        let start = Instant::now();
+        for _ in 0..LOGIN_FREE_ATTEMPTS {
+            throttle.record_failure_at(&keys, start);
+        }
+        assert!(throttle.retry_after_at(&keys, start).is_none());
+
+        throttle.record_failure_at(&keys, start);
+        assert_eq!(throttle.retry_after_at(&keys, start), Some(LOGIN_BASE_BACKOFF));
+        throttle.record_failure_at(&keys, start);
+        assert_eq!(throttle.retry_after_at(&keys, start), Some(LOGIN_BASE_BACKOFF * 2));
+        // 同一 IP 换用户名仍受限
+        let other_user = LoginThrottle::keys("bob", Some("10.0.0.1"));
+        assert!(throttle.retry_after_at(&other_user, start).is_some());
+        assert!(throttle.retry_after_at(&keys, start + LOGIN_BASE_BACKOFF * 2).is_none());
+
+        throttle.record_success(&keys);
+        assert!(throttle.retry_after_at(&other_user, start).is_none());
+    }
+}
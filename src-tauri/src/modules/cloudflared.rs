@@ -1,4 +1,5 @@
 use crate::models::config::{CloudflaredConfig, TunnelMode};
+use crate::proxy::tls;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::process::Stdio;
@@ -210,7 +211,11 @@ impl CloudflaredManager {
             return Err("Cloudflared not installed".to_string());
         }
 
-        let local_url = format!("http://localhost:{}", config.port);
+        // 代理启用 TLS 时隧道需以 https 回源；证书通常为自签名，跳过回源证书校验
+        let tls = crate::modules::config::load_app_config()
+            .map(|c| c.proxy.tls)
+            .unwrap_or_default();
+        let local_url = format!("{}://localhost:{}", tls::scheme(&tls), config.port);
         info!("[cloudflared] Starting tunnel to: {}", local_url);
 
         let mut cmd = Command::new(&self.bin_path);
@@ -223,6 +228,9 @@ impl CloudflaredManager {
         match config.mode {
             TunnelMode::Quick => {
                 cmd.arg("tunnel").arg("--url").arg(&local_url);
+                if tls.enabled {
+                    cmd.arg("--no-tls-verify");
+                }
                 if config.use_http2 {
                     cmd.arg("--protocol").arg("http2");
                 }
@@ -1,4 +1,5 @@
 pub mod account;
+pub mod admin_user_db;
 pub mod cloudflared;
 pub mod config;
 pub mod device;
@@ -9,6 +10,7 @@ pub mod proxy_db;
 pub mod quota;
 pub mod scheduler;
 pub mod security_db;
+pub mod session_binding_db;
 pub mod token_stats;
 pub mod user_token_db;
 
@@ -12,6 +12,7 @@ use tracing::{info, warn};
 
 use crate::models::quota::QuotaData;
 use crate::modules::config;
+use crate::proxy::tls::{loopback_client_builder, loopback_url};
 
 const QUOTA_API_URL: &str =
     "https://daily-cloudcode-pa.sandbox.googleapis.com/v1internal:fetchAvailableModels";
@@ -287,11 +288,11 @@ pub async fn warmup_model_directly(
     percentage: i32,
     _account_id: Option<&str>,
 ) -> bool {
-    let port = config::load_app_config()
-        .map(|c| c.proxy.port)
-        .unwrap_or(8045);
+    let (port, tls) = config::load_app_config()
+        .map(|c| (c.proxy.port, c.proxy.tls))
+        .unwrap_or_else(|_| (8045, Default::default()));
 
-    let warmup_url = format!("http://127.0.0.1:{}/internal/warmup", port);
+    let warmup_url = format!("{}/internal/warmup", loopback_url(&tls, port));
     let body = json!({
         "email": email,
         "model": model_name,
@@ -299,10 +300,10 @@ pub async fn warmup_model_directly(
         "project_id": project_id
     });
 
-    // No-proxy client for localhost (prevents Docker routing through external proxies)
-    let client = reqwest::Client::builder()
+    // No-proxy client for localhost (prevents Docker routing through external proxies);
+    // trusts the proxy's own certificate when TLS is enabled
+    let client = loopback_client_builder(&tls)
         .timeout(std::time::Duration::from_secs(60))
-        .no_proxy()
         .build()
         .unwrap_or_else(|_| reqwest::Client::new());
 
@@ -0,0 +1,425 @@
+//! Session Binding Database Module
+//! 会话 -> 账号绑定的持久化，保证重启后粘性会话不丢失
+
+use rusqlite::{params, Connection};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::OnceLock;
+
+/// 持久化的会话绑定
+#[derive(Debug, Clone, PartialEq)]
+pub struct PersistedBinding {
+    pub session_id: String,
+    pub account_id: String,
+    /// 绑定创建时间 (unix 秒)
+    pub bound_at: i64,
+    /// 最近一次使用时间 (unix 秒)
+    pub last_used: i64,
+}
+
+// ============================================================================
+// Database Connection
+// ============================================================================
+
+/// 会话绑定数据库路径 (跟随 TokenManager 的数据目录)
+pub fn get_session_binding_db_path(data_dir: &Path) -> PathBuf {
+    data_dir.join("session_bindings.db")
+}
+
+fn connect_db(data_dir: &Path) -> Result<Connection, String> {
+    let conn =
+        Connection::open(get_session_binding_db_path(data_dir)).map_err(|e| e.to_string())?;
+    conn.pragma_update(None, "journal_mode", "WAL")
+        .map_err(|e| e.to_string())?;
+    conn.pragma_update(None, "busy_timeout", 5000)
+        .map_err(|e| e.to_string())?;
+    conn.pragma_update(None, "synchronous", "NORMAL")
+        .map_err(|e| e.to_string())?;
+    init_db_with_conn(&conn)?;
+    Ok(conn)
+}
+
+fn init_db_with_conn(conn: &Connection) -> Result<(), String> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS session_bindings (
+            session_id TEXT PRIMARY KEY,
+            account_id TEXT NOT NULL,
+            bound_at INTEGER NOT NULL,
+            last_used INTEGER NOT NULL
+        )",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_session_bindings_last_used ON session_bindings (last_used)",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+// ============================================================================
+// Background Writer
+// ============================================================================
+
+/// 排队执行的绑定写操作
+enum BindingWrite {
+    Upsert {
+        session_id: String,
+        account_id: String,
+        now: i64,
+    },
+    Touch {
+        session_id: String,
+        now: i64,
+    },
+    Delete {
+        session_id: String,
+    },
+    DeleteForAccount {
+        account_id: String,
+    },
+    Clear,
+    Prune {
+        cutoff: i64,
+        max_entries: usize,
+    },
+    Flush(mpsc::Sender<()>),
+}
+
+/// 会话绑定后台写入器
+///
+/// 请求路径只把写操作投递到队列，由独立线程按顺序写入 SQLite；
+/// 连接在写线程内复用，不会在异步请求路径上打开数据库或建表。
+pub struct BindingWriter {
+    data_dir: PathBuf,
+    tx: OnceLock<mpsc::Sender<BindingWrite>>,
+}
+
+impl BindingWriter {
+    /// 写线程在第一次写入时才启动
+    pub fn new(data_dir: PathBuf) -> Self {
+        Self {
+            data_dir,
+            tx: OnceLock::new(),
+        }
+    }
+
+    fn send(&self, op: BindingWrite) {
+        let tx = self.tx.get_or_init(|| spawn_writer(self.data_dir.clone()));
+        if tx.send(op).is_err() {
+            tracing::debug!("[SessionBinding] Writer thread is gone, dropping write");
+        }
+    }
+
+    /// 写入或替换绑定。账号变化时重置 bound_at
+    pub fn upsert(&self, session_id: &str, account_id: &str, now: i64) {
+        self.send(BindingWrite::Upsert {
+            session_id: session_id.to_string(),
+            account_id: account_id.to_string(),
+            now,
+        });
+    }
+
+    /// 更新最近使用时间
+    pub fn touch(&self, session_id: &str, now: i64) {
+        self.send(BindingWrite::Touch {
+            session_id: session_id.to_string(),
+            now,
+        });
+    }
+
+    /// 删除单个会话绑定
+    pub fn delete(&self, session_id: &str) {
+        self.send(BindingWrite::Delete {
+            session_id: session_id.to_string(),
+        });
+    }
+
+    /// 删除指向某个账号的全部绑定
+    pub fn delete_for_account(&self, account_id: &str) {
+        self.send(BindingWrite::DeleteForAccount {
+            account_id: account_id.to_string(),
+        });
+    }
+
+    /// 清空全部绑定
+    pub fn clear(&self) {
+        self.send(BindingWrite::Clear);
+    }
+
+    /// 删除过期 (last_used < cutoff) 以及超出容量上限的绑定
+    pub fn prune(&self, cutoff: i64, max_entries: usize) {
+        self.send(BindingWrite::Prune {
+            cutoff,
+            max_entries,
+        });
+    }
+
+    /// 阻塞等待已排队的写操作全部落盘
+    pub fn flush(&self) {
+        let Some(tx) = self.tx.get() else {
+            return;
+        };
+        let (done_tx, done_rx) = mpsc::channel();
+        if tx.send(BindingWrite::Flush(done_tx)).is_ok() {
+            let _ = done_rx.recv();
+        }
+    }
+}
+
+/// 启动写线程；所有 Sender 释放后线程处理完剩余队列再退出
+fn spawn_writer(data_dir: PathBuf) -> mpsc::Sender<BindingWrite> {
+    let (tx, rx) = mpsc::channel::<BindingWrite>();
+    let spawned = std::thread::Builder::new()
+        .name("session-binding-writer".to_string())
+        .spawn(move || {
+            let mut conn: Option<Connection> = None;
+            for op in rx {
+                let op = match op {
+                    BindingWrite::Flush(done) => {
+                        let _ = done.send(());
+                        continue;
+                    }
+                    op => op,
+                };
+                if conn.is_none() {
+                    match connect_db(&data_dir) {
+                        Ok(c) => conn = Some(c),
+                        Err(e) => {
+                            tracing::warn!("[SessionBinding] Failed to open database: {}", e);
+                            continue;
+                        }
+                    }
+                }
+                if let Some(conn) = conn.as_ref() {
+                    if let Err(e) = apply_write(conn, op) {
+                        tracing::warn!("[SessionBinding] Failed to persist binding change: {}", e);
+                    }
+                }
+            }
+        });
+    if let Err(e) = spawned {
+        tracing::warn!("[SessionBinding] Failed to start writer thread: {}", e);
+    }
+    tx
+}
+
+fn apply_write(conn: &Connection, op: BindingWrite) -> Result<(), String> {
+    match op {
+        BindingWrite::Upsert {
+            session_id,
+            account_id,
+            now,
+        } => upsert_binding_with_conn(conn, &session_id, &account_id, now),
+        BindingWrite::Touch { session_id, now } => touch_binding_with_conn(conn, &session_id, now),
+        BindingWrite::Delete { session_id } => delete_binding_with_conn(conn, &session_id),
+        BindingWrite::DeleteForAccount { account_id } => {
+            delete_bindings_for_account_with_conn(conn, &account_id).map(|_| ())
+        }
+        BindingWrite::Clear => clear_bindings_with_conn(conn),
+        BindingWrite::Prune {
+            cutoff,
+            max_entries,
+        } => prune_bindings_with_conn(conn, cutoff, max_entries).map(|_| ()),
+        BindingWrite::Flush(done) => {
+            let _ = done.send(());
+            Ok(())
+        }
+    }
+}
+
+// ============================================================================
+// CRUD
+// ============================================================================
+
+fn upsert_binding_with_conn(
+    conn: &Connection,
+    session_id: &str,
+    account_id: &str,
+    now: i64,
+) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO session_bindings (session_id, account_id, bound_at, last_used)
+         VALUES (?1, ?2, ?3, ?3)
+         ON CONFLICT(session_id) DO UPDATE SET
+            bound_at = CASE WHEN account_id = excluded.account_id THEN bound_at ELSE excluded.bound_at END,
+            account_id = excluded.account_id,
+            last_used = excluded.last_used",
+        params![session_id, account_id, now],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn touch_binding_with_conn(conn: &Connection, session_id: &str, now: i64) -> Result<(), String> {
+    conn.execute(
+        "UPDATE session_bindings SET last_used = ?2 WHERE session_id = ?1",
+        params![session_id, now],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn delete_binding_with_conn(conn: &Connection, session_id: &str) -> Result<(), String> {
+    conn.execute(
+        "DELETE FROM session_bindings WHERE session_id = ?1",
+        params![session_id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn delete_bindings_for_account_with_conn(
+    conn: &Connection,
+    account_id: &str,
+) -> Result<usize, String> {
+    conn.execute(
+        "DELETE FROM session_bindings WHERE account_id = ?1",
+        params![account_id],
+    )
+    .map_err(|e| e.to_string())
+}
+
+fn clear_bindings_with_conn(conn: &Connection) -> Result<(), String> {
+    conn.execute("DELETE FROM session_bindings", [])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// 删除过期 (last_used < cutoff) 以及超出容量上限的绑定，返回删除数量
+fn prune_bindings_with_conn(
+    conn: &Connection,
+    cutoff: i64,
+    max_entries: usize,
+) -> Result<usize, String> {
+    let expired = conn
+        .execute(
+            "DELETE FROM session_bindings WHERE last_used < ?1",
+            params![cutoff],
+        )
+        .map_err(|e| e.to_string())?;
+
+    let overflow = conn
+        .execute(
+            "DELETE FROM session_bindings WHERE session_id IN (
+                SELECT session_id FROM session_bindings
+                ORDER BY last_used DESC
+                LIMIT -1 OFFSET ?1
+            )",
+            params![max_entries as i64],
+        )
+        .map_err(|e| e.to_string())?;
+
+    Ok(expired + overflow)
+}
+
+/// 先清理过期/超量数据，再按最近使用排序加载全部绑定
+pub fn load_bindings(
+    data_dir: &Path,
+    cutoff: i64,
+    max_entries: usize,
+) -> Result<Vec<PersistedBinding>, String> {
+    let conn = connect_db(data_dir)?;
+    load_bindings_with_conn(&conn, cutoff, max_entries)
+}
+
+fn load_bindings_with_conn(
+    conn: &Connection,
+    cutoff: i64,
+    max_entries: usize,
+) -> Result<Vec<PersistedBinding>, String> {
+    prune_bindings_with_conn(conn, cutoff, max_entries)?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT session_id, account_id, bound_at, last_used
+             FROM session_bindings ORDER BY last_used DESC",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(PersistedBinding {
+                session_id: row.get(0)?,
+                account_id: row.get(1)?,
+                bound_at: row.get(2)?,
+                last_used: row.get(3)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup_test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        init_db_with_conn(&conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_upsert_keeps_bound_at_for_same_account() {
+        let conn = setup_test_db();
+        upsert_binding_with_conn(&conn, "s1", "acc1", 100).unwrap();
+        upsert_binding_with_conn(&conn, "s1", "acc1", 200).unwrap();
+        let rows = load_bindings_with_conn(&conn, 0, 10).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!((rows[0].bound_at, rows[0].last_used), (100, 200));
+
+        // Rebinding to another account resets bound_at
+        upsert_binding_with_conn(&conn, "s1", "acc2", 300).unwrap();
+        let rows = load_bindings_with_conn(&conn, 0, 10).unwrap();
+        assert_eq!(rows[0].account_id, "acc2");
+        assert_eq!((rows[0].bound_at, rows[0].last_used), (300, 300));
+    }
+
+    #[test]
+    fn test_writer_applies_writes_in_order() {
+        let dir = std::env::temp_dir().join(format!("session-binding-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let writer = BindingWriter::new(dir.clone());
+        writer.upsert("s1", "acc1", 100);
+        writer.upsert("s2", "acc2", 150);
+        writer.touch("s1", 200);
+        writer.delete("s2");
+        writer.upsert("s2", "acc1", 300);
+        writer.delete_for_account("acc2");
+        writer.flush();
+
+        let rows = load_bindings(&dir, 0, 10).unwrap();
+        let summary: Vec<(&str, &str, i64)> = rows
+            .iter()
+            .map(|r| (r.session_id.as_str(), r.account_id.as_str(), r.last_used))
+            .collect();
+        assert_eq!(summary, vec![("s2", "acc1", 300), ("s1", "acc1", 200)]);
+
+        writer.clear();
+        writer.flush();
+        assert!(load_bindings(&dir, 0, 10).unwrap().is_empty());
+
+        drop(writer);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_load_prunes_expired_and_overflow() {
+        let conn = setup_test_db();
+        upsert_binding_with_conn(&conn, "old", "acc1", 10).unwrap();
+        upsert_binding_with_conn(&conn, "a", "acc1", 100).unwrap();
+        upsert_binding_with_conn(&conn, "b", "acc2", 200).unwrap();
+        upsert_binding_with_conn(&conn, "c", "acc2", 300).unwrap();
+
+        let rows = load_bindings_with_conn(&conn, 50, 2).unwrap();
+        let ids: Vec<&str> = rows.iter().map(|r| r.session_id.as_str()).collect();
+        assert_eq!(ids, vec!["c", "b"]);
+    }
+}
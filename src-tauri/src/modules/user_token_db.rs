@@ -441,6 +441,19 @@ pub fn get_token_by_value(token_str: &str) -> Result<Option<UserToken>, String>
     .map_err(|e| e.to_string())
 }
 
+/// 根据 ID 获取 Token（mTLS 客户端证书映射使用）
+pub fn get_token_by_id(id: &str) -> Result<Option<UserToken>, String> {
+    let conn = get_connection()?;
+    conn.query_row(
+        "SELECT token FROM user_tokens WHERE id = ?1",
+        params![id],
+        |row| row.get::<_, String>(0),
+    )
+    .optional()
+    .map_err(|e| e.to_string())?
+    .map_or(Ok(None), |token| get_token_by_value(&token))
+}
+
 
 // ============================================================================
 // Property-Based Tests
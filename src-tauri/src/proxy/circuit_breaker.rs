@@ -0,0 +1,366 @@
+// 账号 + 模型 维度的熔断状态机
+//
+// - Closed: 正常放行，记录滑动窗口内的成功/失败
+// - 连续失败达到 failure_threshold，或窗口内错误率达到 error_rate_percent (且请求数 ≥ min_requests) → Open
+// - Open 持续 open_seconds 后进入 HalfOpen，只放行一个探测请求
+// - 探测成功 → Closed；探测失败 → 重新 Open
+//
+// 探测请求被取消而未上报结果时，超过 open_seconds 后允许再次探测，避免永久卡在半开状态。
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use serde::Serialize;
+
+use crate::models::config::CircuitBreakerConfig;
+
+/// 熔断状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+/// 单个 账号+模型 熔断器的快照 (管理 API)
+#[derive(Debug, Clone, Serialize)]
+pub struct BreakerSnapshot {
+    pub model: String,
+    pub state: BreakerState,
+    pub consecutive_failures: u32,
+    pub window_requests: usize,
+    pub window_failures: usize,
+    /// Open 状态下距离进入半开的剩余秒数
+    pub open_remaining_seconds: u64,
+    /// 累计熔断次数
+    pub trips: u32,
+}
+
+struct Breaker {
+    state: BreakerState,
+    /// (时间, 是否成功)
+    window: VecDeque<(Instant, bool)>,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    probe_started_at: Option<Instant>,
+    trips: u32,
+}
+
+impl Breaker {
+    fn new() -> Self {
+        Self {
+            state: BreakerState::Closed,
+            window: VecDeque::new(),
+            consecutive_failures: 0,
+            opened_at: None,
+            probe_started_at: None,
+            trips: 0,
+        }
+    }
+
+    fn prune(&mut self, now: Instant, window: Duration) {
+        while let Some((at, _)) = self.window.front() {
+            if now.duration_since(*at) > window {
+                self.window.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn open_elapsed(&self, now: Instant, config: &CircuitBreakerConfig) -> bool {
+        self.opened_at
+            .map(|at| now.duration_since(at) >= Duration::from_secs(config.open_seconds))
+            .unwrap_or(true)
+    }
+
+    fn probe_stale(&self, now: Instant, config: &CircuitBreakerConfig) -> bool {
+        self.probe_started_at
+            .map(|at| now.duration_since(at) >= Duration::from_secs(config.open_seconds))
+            .unwrap_or(true)
+    }
+
+    fn should_trip(&self, config: &CircuitBreakerConfig) -> bool {
+        if config.failure_threshold > 0 && self.consecutive_failures >= config.failure_threshold {
+            return true;
+        }
+        let total = self.window.len();
+        if total == 0 || (total as u32) < config.min_requests.max(1) {
+            return false;
+        }
+        let failures = self.window.iter().filter(|(_, ok)| !ok).count();
+        failures * 100 >= total * config.error_rate_percent as usize
+    }
+
+    fn trip(&mut self, now: Instant) {
+        self.state = BreakerState::Open;
+        self.opened_at = Some(now);
+        self.probe_started_at = None;
+        self.trips += 1;
+    }
+}
+
+/// 全部 账号+模型 熔断器
+#[derive(Default)]
+pub struct CircuitBreakerRegistry {
+    breakers: DashMap<(String, String), Breaker>,
+}
+
+impl CircuitBreakerRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 是否可被调度 (只读，不占用探测名额)
+    pub fn is_available(
+        &self,
+        account_id: &str,
+        model: &str,
+        config: &CircuitBreakerConfig,
+    ) -> bool {
+        let key = (account_id.to_string(), model.to_string());
+        let Some(breaker) = self.breakers.get(&key) else {
+            return true;
+        };
+        let now = Instant::now();
+        match breaker.state {
+            BreakerState::Closed => true,
+            BreakerState::Open => breaker.open_elapsed(now, config),
+            BreakerState::HalfOpen => breaker.probe_stale(now, config),
+        }
+    }
+
+    /// 选中账号时调用：Closed 直接放行；Open 到期或半开时占用唯一的探测名额
+    pub fn try_acquire(
+        &self,
+        account_id: &str,
+        model: &str,
+        config: &CircuitBreakerConfig,
+    ) -> bool {
+        let key = (account_id.to_string(), model.to_string());
+        let Some(mut breaker) = self.breakers.get_mut(&key) else {
+            return true;
+        };
+        let now = Instant::now();
+        match breaker.state {
+            BreakerState::Closed => true,
+            BreakerState::Open if breaker.open_elapsed(now, config) => {
+                breaker.state = BreakerState::HalfOpen;
+                breaker.probe_started_at = Some(now);
+                tracing::info!(
+                    "[CircuitBreaker] {} / {}: open -> half_open, sending probe",
+                    account_id,
+                    model
+                );
+                true
+            }
+            BreakerState::Open => false,
+            BreakerState::HalfOpen if breaker.probe_stale(now, config) => {
+                breaker.probe_started_at = Some(now);
+                tracing::info!(
+                    "[CircuitBreaker] {} / {}: previous probe never reported, sending another",
+                    account_id,
+                    model
+                );
+                true
+            }
+            BreakerState::HalfOpen => false,
+        }
+    }
+
+    /// 记录一次上游结果，返回发生的状态转换
+    pub fn record(
+        &self,
+        account_id: &str,
+        model: &str,
+        success: bool,
+        config: &CircuitBreakerConfig,
+    ) -> Option<(BreakerState, BreakerState)> {
+        let key = (account_id.to_string(), model.to_string());
+        if success && !self.breakers.contains_key(&key) {
+            // 从未失败过的组合无需建档
+            return None;
+        }
+        let mut breaker = self.breakers.entry(key).or_insert_with(Breaker::new);
+        let now = Instant::now();
+        let from = breaker.state;
+
+        breaker.window.push_back((now, success));
+        breaker.prune(now, Duration::from_secs(config.window_seconds));
+        if success {
+            breaker.consecutive_failures = 0;
+        } else {
+            breaker.consecutive_failures += 1;
+        }
+
+        match (from, success) {
+            (BreakerState::HalfOpen, true) => {
+                breaker.state = BreakerState::Closed;
+                breaker.opened_at = None;
+                breaker.probe_started_at = None;
+                breaker.window.clear();
+            }
+            (BreakerState::HalfOpen, false) => breaker.trip(now),
+            (BreakerState::Closed, false) if breaker.should_trip(config) => breaker.trip(now),
+            // Open 期间仍在途的请求结果只计入统计
+            _ => {}
+        }
+
+        let to = breaker.state;
+        if from == to {
+            return None;
+        }
+        match to {
+            BreakerState::Open => tracing::warn!(
+                "[CircuitBreaker] {} / {}: {:?} -> open (consecutive_failures={}, window={} req, trips={})",
+                account_id,
+                model,
+                from,
+                breaker.consecutive_failures,
+                breaker.window.len(),
+                breaker.trips
+            ),
+            _ => tracing::info!(
+                "[CircuitBreaker] {} / {}: {:?} -> {:?}",
+                account_id,
+                model,
+                from,
+                to
+            ),
+        }
+        Some((from, to))
+    }
+
+    /// 当前状态 (未建档视为 Closed)
+    pub fn state(&self, account_id: &str, model: &str) -> BreakerState {
+        self.breakers
+            .get(&(account_id.to_string(), model.to_string()))
+            .map(|b| b.state)
+            .unwrap_or(BreakerState::Closed)
+    }
+
+    /// 某账号下所有模型的熔断器快照
+    pub fn snapshot_for_account(
+        &self,
+        account_id: &str,
+        config: &CircuitBreakerConfig,
+    ) -> Vec<BreakerSnapshot> {
+        let now = Instant::now();
+        let mut snapshots: Vec<BreakerSnapshot> = self
+            .breakers
+            .iter()
+            .filter(|e| e.key().0 == account_id)
+            .map(|e| {
+                let breaker = e.value();
+                let open_remaining_seconds = match (breaker.state, breaker.opened_at) {
+                    (BreakerState::Open, Some(at)) => config
+                        .open_seconds
+                        .saturating_sub(now.duration_since(at).as_secs()),
+                    _ => 0,
+                };
+                BreakerSnapshot {
+                    model: e.key().1.clone(),
+                    state: breaker.state,
+                    consecutive_failures: breaker.consecutive_failures,
+                    window_requests: breaker.window.len(),
+                    window_failures: breaker.window.iter().filter(|(_, ok)| !ok).count(),
+                    open_remaining_seconds,
+                    trips: breaker.trips,
+                }
+            })
+            .collect();
+        snapshots.sort_by(|a, b| a.model.cmp(&b.model));
+        snapshots
+    }
+
+    /// 移除某账号的全部熔断器
+    pub fn reset_account(&self, account_id: &str) {
+        self.breakers.retain(|key, _| key.0 != account_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> CircuitBreakerConfig {
+        CircuitBreakerConfig {
+            failure_threshold: 3,
+            error_rate_percent: 50,
+            window_seconds: 60,
+            min_requests: 4,
+            open_seconds: 0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_consecutive_failures_trip_and_probe_closes() {
+        let registry = CircuitBreakerRegistry::new();
+        let cfg = CircuitBreakerConfig {
+            open_seconds: 3600,
+            ..config()
+        };
+
+        for _ in 0..2 {
+            assert!(registry.record("acc1", "m", false, &cfg).is_none());
+        }
+        assert_eq!(
+            registry.record("acc1", "m", false, &cfg),
+            Some((BreakerState::Closed, BreakerState::Open))
+        );
+        assert!(!registry.is_available("acc1", "m", &cfg));
+        assert!(!registry.try_acquire("acc1", "m", &cfg));
+        // Other models on the same account are unaffected
+        assert!(registry.is_available("acc1", "other", &cfg));
+
+        // open_seconds elapsed -> exactly one probe
+        let cfg = config();
+        assert!(registry.try_acquire("acc1", "m", &cfg));
+        assert_eq!(registry.state("acc1", "m"), BreakerState::HalfOpen);
+        let strict = CircuitBreakerConfig {
+            open_seconds: 3600,
+            ..config()
+        };
+        assert!(!registry.try_acquire("acc1", "m", &strict));
+
+        assert_eq!(
+            registry.record("acc1", "m", true, &cfg),
+            Some((BreakerState::HalfOpen, BreakerState::Closed))
+        );
+        assert!(registry.is_available("acc1", "m", &strict));
+    }
+
+    #[test]
+    fn test_error_rate_trip_and_failed_probe_reopens() {
+        let registry = CircuitBreakerRegistry::new();
+        let cfg = CircuitBreakerConfig {
+            failure_threshold: 0,
+            ..config()
+        };
+
+        registry.record("acc1", "m", false, &cfg);
+        registry.record("acc1", "m", true, &cfg);
+        registry.record("acc1", "m", false, &cfg);
+        // 2/3 failed but below min_requests
+        assert_eq!(registry.state("acc1", "m"), BreakerState::Closed);
+        assert_eq!(
+            registry.record("acc1", "m", false, &cfg),
+            Some((BreakerState::Closed, BreakerState::Open))
+        );
+
+        assert!(registry.try_acquire("acc1", "m", &cfg));
+        assert_eq!(
+            registry.record("acc1", "m", false, &cfg),
+            Some((BreakerState::HalfOpen, BreakerState::Open))
+        );
+        let snapshot = registry.snapshot_for_account("acc1", &cfg);
+        assert_eq!(snapshot.len(), 1);
+        assert_eq!(snapshot[0].trips, 2);
+
+        registry.reset_account("acc1");
+        assert!(registry.snapshot_for_account("acc1", &cfg).is_empty());
+    }
+}
@@ -157,7 +157,11 @@ impl ContextManager {
     /// - An assistant message with tool_use
     /// - One or more user messages with tool_result
     ///
-    /// Returns true if any messages were removed.
+    /// Only the tool_use / tool_result blocks of old rounds are removed; other
+    /// content in the same message (e.g. user text) is kept. A message is
+    /// dropped once nothing but thinking blocks remains in it.
+    ///
+    /// Returns true if any messages were modified.
     pub fn trim_tool_messages(messages: &mut Vec<Message>, keep_last_n_rounds: usize) -> bool {
         let tool_rounds = identify_tool_rounds(messages);
 
@@ -166,30 +170,48 @@ impl ContextManager {
         }
 
         let rounds_to_remove = tool_rounds.len() - keep_last_n_rounds;
-        let mut indices_to_remove = std::collections::HashSet::new();
+        let mut indices_to_trim = std::collections::HashSet::new();
 
         for round in tool_rounds.iter().take(rounds_to_remove) {
             for idx in &round.indices {
-                indices_to_remove.insert(*idx);
+                indices_to_trim.insert(*idx);
             }
         }
 
-        let mut removed_count = 0;
+        let mut removed_blocks = 0;
+        let mut removed_messages = 0;
         for idx in (0..messages.len()).rev() {
-            if indices_to_remove.contains(&idx) {
-                messages.remove(idx);
-                removed_count += 1;
+            if !indices_to_trim.contains(&idx) {
+                continue;
+            }
+            if let MessageContent::Array(blocks) = &mut messages[idx].content {
+                let original_len = blocks.len();
+                blocks.retain(|b| {
+                    !matches!(
+                        b,
+                        ContentBlock::ToolUse { .. } | ContentBlock::ToolResult { .. }
+                    )
+                });
+                removed_blocks += original_len - blocks.len();
+
+                if blocks
+                    .iter()
+                    .all(|b| matches!(b, ContentBlock::Thinking { .. }))
+                {
+                    messages.remove(idx);
+                    removed_messages += 1;
+                }
             }
         }
 
-        if removed_count > 0 {
+        if removed_blocks > 0 {
             info!(
-                "[ContextManager] [Layer-1] Trimmed {} tool messages, kept last {} rounds",
-                removed_count, keep_last_n_rounds
+                "[ContextManager] [Layer-1] Trimmed {} tool blocks ({} messages dropped), kept last {} rounds",
+                removed_blocks, removed_messages, keep_last_n_rounds
             );
         }
 
-        removed_count > 0
+        removed_blocks > 0
     }
 
     // ===== [Layer 2] Thinking Content Compression + Signature Preservation =====
@@ -289,7 +311,9 @@ impl ContextManager {
     /// Purify message history by removing thinking blocks.
     ///
     /// Unlike compression (Layer 2), this completely removes thinking blocks.
-    /// Used when context is critical or signatures are invalid.
+    /// Used when context is critical or signatures are invalid. The latest
+    /// assistant turn is always kept intact: it may be the in-progress tool-use
+    /// turn whose signed thinking block the upstream still validates.
     pub fn purify_history(messages: &mut Vec<Message>, strategy: PurificationStrategy) -> bool {
         let protected_last_n = match strategy {
             PurificationStrategy::Soft => 4,
@@ -307,10 +331,11 @@ impl ContextManager {
         }
 
         let start_protection_idx = total_msgs.saturating_sub(protected_last_n);
+        let last_assistant_idx = messages.iter().rposition(|m| m.role == "assistant");
         let mut modified = false;
 
         for (i, msg) in messages.iter_mut().enumerate() {
-            if i >= start_protection_idx {
+            if i >= start_protection_idx || Some(i) == last_assistant_idx {
                 continue;
             }
 
@@ -335,6 +360,161 @@ impl ContextManager {
     }
 }
 
+// ===== Context Window Fitting =====
+// Escalates through the layers above until the request fits the model's
+// context window: soft purification → aggressive purification → trimming old
+// tool rounds.
+
+/// Compression kicks in once the estimate exceeds this share of the window
+/// (estimation is approximate, leave headroom for the upstream tokenizer).
+const CONTEXT_TRIGGER_RATIO: f32 = 0.9;
+
+/// Tool rounds kept by successive trimming passes.
+const TOOL_ROUND_KEEP_STEPS: [usize; 4] = [8, 4, 2, 1];
+
+/// Approximate context window (tokens) of an upstream model.
+pub fn context_window_for_model(model: &str) -> u32 {
+    let m = model.to_lowercase();
+    if m.contains("claude") {
+        200_000
+    } else if m.starts_with("gemini") {
+        1_048_576
+    } else if m.contains("gpt-oss") {
+        131_072
+    } else {
+        128_000
+    }
+}
+
+/// What `fit_to_context_window` removed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ContextCompressionReport {
+    pub context_window: u32,
+    pub tokens_before: u32,
+    pub tokens_after: u32,
+    /// Applied steps, in order: "soft_purify", "aggressive_purify", "trim_tool_rounds"
+    pub steps: Vec<&'static str>,
+    pub thinking_blocks_removed: usize,
+    /// Messages dropped entirely (mixed messages only lose their tool blocks)
+    pub tool_messages_removed: usize,
+    /// Tool rounds kept by the last trimming pass
+    pub kept_tool_rounds: Option<usize>,
+}
+
+impl ContextCompressionReport {
+    /// Whether the request fits the budget after compression.
+    pub fn fits(&self) -> bool {
+        self.tokens_after <= context_budget(self.context_window)
+    }
+
+    /// Compact summary for the `X-Context-Compressed` header and logs.
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "{}; tokens={}->{}; window={}; thinking_removed={}; tool_messages_removed={}",
+            self.steps.join(","),
+            self.tokens_before,
+            self.tokens_after,
+            self.context_window,
+            self.thinking_blocks_removed,
+            self.tool_messages_removed
+        );
+        if let Some(kept) = self.kept_tool_rounds {
+            summary.push_str(&format!("; kept_tool_rounds={}", kept));
+        }
+        summary
+    }
+}
+
+fn context_budget(context_window: u32) -> u32 {
+    (context_window as f32 * CONTEXT_TRIGGER_RATIO) as u32
+}
+
+fn count_thinking_blocks(messages: &[Message]) -> usize {
+    messages
+        .iter()
+        .filter_map(|m| match &m.content {
+            MessageContent::Array(blocks) => Some(
+                blocks
+                    .iter()
+                    .filter(|b| matches!(b, ContentBlock::Thinking { .. }))
+                    .count(),
+            ),
+            MessageContent::String(_) => None,
+        })
+        .sum()
+}
+
+impl ContextManager {
+    /// Shrink `request` until its estimated size fits `model`'s context window.
+    ///
+    /// Returns `None` when nothing had to be done. The report may still
+    /// say `fits() == false` when every layer was exhausted; the request is
+    /// then sent as-is and the upstream decides.
+    pub fn fit_to_context_window(
+        request: &mut ClaudeRequest,
+        model: &str,
+    ) -> Option<ContextCompressionReport> {
+        let context_window = context_window_for_model(model);
+        let budget = context_budget(context_window);
+        let tokens_before = Self::estimate_token_usage(request);
+        if tokens_before <= budget {
+            return None;
+        }
+
+        let mut report = ContextCompressionReport {
+            context_window,
+            tokens_before,
+            tokens_after: tokens_before,
+            ..Default::default()
+        };
+        let thinking_before = count_thinking_blocks(&request.messages);
+
+        for (step, strategy) in [
+            ("soft_purify", PurificationStrategy::Soft),
+            ("aggressive_purify", PurificationStrategy::Aggressive),
+        ] {
+            if Self::purify_history(&mut request.messages, strategy) {
+                report.steps.push(step);
+                report.tokens_after = Self::estimate_token_usage(request);
+                if report.tokens_after <= budget {
+                    break;
+                }
+            }
+        }
+        report.thinking_blocks_removed = thinking_before - count_thinking_blocks(&request.messages);
+
+        if report.tokens_after > budget {
+            let messages_before = request.messages.len();
+            for keep in TOOL_ROUND_KEEP_STEPS {
+                if Self::trim_tool_messages(&mut request.messages, keep) {
+                    report.kept_tool_rounds = Some(keep);
+                    report.tokens_after = Self::estimate_token_usage(request);
+                    if report.tokens_after <= budget {
+                        break;
+                    }
+                }
+            }
+            report.tool_messages_removed = messages_before - request.messages.len();
+            if report.kept_tool_rounds.is_some() {
+                report.steps.push("trim_tool_rounds");
+            }
+        }
+
+        info!(
+            "[ContextManager] Fitted request to {} (window {}): {}{}",
+            model,
+            context_window,
+            report.summary(),
+            if report.fits() {
+                ""
+            } else {
+                " (still over budget)"
+            }
+        );
+        Some(report)
+    }
+}
+
 /// Represents a tool call round (assistant tool_use + user tool_result(s))
 #[derive(Debug)]
 struct ToolRound {
@@ -536,19 +716,29 @@ mod tests {
 
     #[test]
     fn test_purify_history_aggressive() {
-        let mut messages = vec![Message {
+        let assistant = |thought: &str, text: &str| Message {
             role: "assistant".into(),
             content: MessageContent::Array(vec![
                 ContentBlock::Thinking {
-                    thinking: "thought".into(),
-                    signature: None,
+                    thinking: thought.into(),
+                    signature: Some("sig".into()),
                     cache_control: None,
                 },
-                ContentBlock::Text {
-                    text: "text".into(),
-                },
+                ContentBlock::Text { text: text.into() },
             ]),
-        }];
+        };
+        let mut messages = vec![
+            assistant("old thought", "A1"),
+            Message {
+                role: "user".into(),
+                content: MessageContent::String("Q2".into()),
+            },
+            assistant("current thought", "A2"),
+            Message {
+                role: "user".into(),
+                content: MessageContent::String("current".into()),
+            },
+        ];
 
         ContextManager::purify_history(&mut messages, PurificationStrategy::Aggressive);
 
@@ -556,6 +746,11 @@ mod tests {
             assert_eq!(blocks.len(), 1);
             assert!(matches!(blocks[0], ContentBlock::Text { .. }));
         }
+        // The latest assistant turn keeps its signed thinking block
+        if let MessageContent::Array(blocks) = &messages[2].content {
+            assert_eq!(blocks.len(), 2);
+            assert!(matches!(blocks[0], ContentBlock::Thinking { .. }));
+        }
     }
 
     #[test]
@@ -751,8 +946,110 @@ mod tests {
         assert_eq!(messages.len(), 2);
     }
 
+    #[test]
+    fn test_trim_tool_messages_keeps_user_text() {
+        let mut messages = tool_round("1", "ok".into());
+        if let MessageContent::Array(blocks) = &mut messages[1].content {
+            blocks.push(ContentBlock::Text {
+                text: "also check b.txt".into(),
+            });
+        }
+        messages.extend(tool_round("2", "ok".into()));
+
+        assert!(ContextManager::trim_tool_messages(&mut messages, 1));
+        // Round 1's assistant turn is gone; the user's text survives without its tool_result
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0].role, "user");
+        match &messages[0].content {
+            MessageContent::Array(blocks) => {
+                assert_eq!(blocks.len(), 1);
+                assert!(
+                    matches!(&blocks[0], ContentBlock::Text { text } if text == "also check b.txt")
+                );
+            }
+            MessageContent::String(_) => panic!("expected block content"),
+        }
+    }
+
     #[test]
     fn test_estimate_tokens_empty() {
         assert_eq!(estimate_tokens_from_str(""), 0);
     }
+
+    fn tool_round(id: &str, result: String) -> Vec<Message> {
+        vec![
+            Message {
+                role: "assistant".into(),
+                content: MessageContent::Array(vec![
+                    ContentBlock::Thinking {
+                        thinking: format!("thinking about {}", id),
+                        signature: None,
+                        cache_control: None,
+                    },
+                    ContentBlock::ToolUse {
+                        id: id.into(),
+                        name: "read_file".into(),
+                        input: serde_json::json!({"path": id}),
+                        signature: None,
+                        cache_control: None,
+                    },
+                ]),
+            },
+            Message {
+                role: "user".into(),
+                content: MessageContent::Array(vec![ContentBlock::ToolResult {
+                    tool_use_id: id.into(),
+                    content: serde_json::json!(result),
+                    is_error: None,
+                }]),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_context_window_table() {
+        assert_eq!(
+            context_window_for_model("claude-sonnet-4-5-thinking"),
+            200_000
+        );
+        assert_eq!(context_window_for_model("gemini-3-pro-high"), 1_048_576);
+        assert_eq!(context_window_for_model("unknown-model"), 128_000);
+    }
+
+    #[test]
+    fn test_fit_to_context_window_noop_when_small() {
+        let mut req = create_test_request();
+        req.messages = tool_round("1", "ok".into());
+        assert!(ContextManager::fit_to_context_window(&mut req, "claude-sonnet-4-5").is_none());
+        assert_eq!(req.messages.len(), 2);
+    }
+
+    #[test]
+    fn test_fit_to_context_window_escalates_to_tool_trimming() {
+        let mut req = create_test_request();
+        req.messages.push(Message {
+            role: "user".into(),
+            content: MessageContent::String("start".into()),
+        });
+        // 12 rounds × ~30k tokens each ≫ 200k window
+        for i in 0..12 {
+            req.messages
+                .extend(tool_round(&i.to_string(), "x".repeat(100_000)));
+        }
+
+        let report = ContextManager::fit_to_context_window(&mut req, "claude-sonnet-4-5").unwrap();
+        assert!(report.fits());
+        assert_eq!(
+            report.steps,
+            vec!["soft_purify", "aggressive_purify", "trim_tool_rounds"]
+        );
+        // The latest assistant turn keeps its thinking block
+        assert_eq!(report.thinking_blocks_removed, 11);
+        assert_eq!(report.kept_tool_rounds, Some(4));
+        // 8 rounds dropped, first user message and last 4 rounds kept
+        assert_eq!(report.tool_messages_removed, 16);
+        assert_eq!(req.messages.len(), 9);
+        assert!(report.tokens_after < report.tokens_before);
+        assert!(report.summary().contains("kept_tool_rounds=4"));
+    }
 }
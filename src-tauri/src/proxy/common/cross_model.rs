@@ -0,0 +1,305 @@
+//! Cross-Model Checks
+//!
+//! When a conversation switches model family mid-session (e.g. Claude → Gemini
+//! after a mapping change or fallback), history artifacts produced by the
+//! previous family are rejected upstream: thinking signatures fail validation
+//! and server tool blocks have no equivalent. With `enable_cross_model_checks`
+//! on, such artifacts are stripped or converted to plain text before transform.
+//!
+//! A switch is detected when either:
+//! - the session's last served family differs from the current one, or
+//! - a signature in history is recorded in `SignatureCache` for another family.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+use crate::proxy::mappers::claude::models::{ContentBlock, Message, MessageContent};
+use crate::proxy::signature_cache::SignatureCache;
+
+/// Session family entries expire after this long without traffic
+const SESSION_FAMILY_TTL: Duration = Duration::from_secs(2 * 60 * 60);
+
+/// Upper bound on tracked sessions
+const SESSION_FAMILY_LIMIT: usize = 1000;
+
+static SESSION_FAMILIES: OnceLock<Mutex<HashMap<String, (String, Instant)>>> = OnceLock::new();
+
+fn session_families() -> &'static Mutex<HashMap<String, (String, Instant)>> {
+    SESSION_FAMILIES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Canonical family of a model name ("claude" / "gemini" / "openai" / raw name)
+pub fn model_family(model: &str) -> String {
+    SignatureCache::normalize_family(model).to_string()
+}
+
+/// Record the family that served the latest request of a session.
+pub fn record_session_family(session_id: &str, model: &str) {
+    let Ok(mut map) = session_families().lock() else {
+        return;
+    };
+    if map.len() >= SESSION_FAMILY_LIMIT && !map.contains_key(session_id) {
+        map.retain(|_, (_, seen)| seen.elapsed() < SESSION_FAMILY_TTL);
+        if map.len() >= SESSION_FAMILY_LIMIT {
+            if let Some(oldest) = map
+                .iter()
+                .min_by_key(|(_, (_, seen))| *seen)
+                .map(|(k, _)| k.clone())
+            {
+                map.remove(&oldest);
+            }
+        }
+    }
+    map.insert(
+        session_id.to_string(),
+        (model_family(model), Instant::now()),
+    );
+}
+
+/// Family that served the previous request of a session, if still fresh.
+pub fn previous_session_family(session_id: &str) -> Option<String> {
+    let map = session_families().lock().ok()?;
+    map.get(session_id)
+        .filter(|(_, seen)| seen.elapsed() < SESSION_FAMILY_TTL)
+        .map(|(family, _)| family.clone())
+}
+
+/// Summary of artifacts removed from history
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CrossModelReport {
+    pub thinking_removed: usize,
+    pub signatures_cleared: usize,
+    pub server_tools_converted: usize,
+}
+
+impl CrossModelReport {
+    pub fn is_empty(&self) -> bool {
+        self.thinking_removed == 0
+            && self.signatures_cleared == 0
+            && self.server_tools_converted == 0
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "thinking_removed={}, signatures_cleared={}, server_tools_converted={}",
+            self.thinking_removed, self.signatures_cleared, self.server_tools_converted
+        )
+    }
+}
+
+fn history_signatures(messages: &[Message]) -> impl Iterator<Item = &String> {
+    messages
+        .iter()
+        .filter_map(|m| match &m.content {
+            MessageContent::Array(blocks) => Some(blocks),
+            MessageContent::String(_) => None,
+        })
+        .flatten()
+        .filter_map(|block| match block {
+            ContentBlock::Thinking { signature, .. } | ContentBlock::ToolUse { signature, .. } => {
+                signature.as_ref()
+            }
+            _ => None,
+        })
+}
+
+/// Whether the conversation is switching away from the family that produced its history.
+pub fn detect_family_switch(
+    session_id: Option<&str>,
+    messages: &[Message],
+    target_model: &str,
+) -> bool {
+    let target = model_family(target_model);
+    if let Some(previous) = session_id.and_then(previous_session_family) {
+        if previous != target {
+            return true;
+        }
+    }
+    let cache = SignatureCache::global();
+    history_signatures(messages).any(|sig| {
+        cache
+            .get_signature_family(sig)
+            .is_some_and(|family| model_family(&family) != target)
+    })
+}
+
+fn server_tool_text(block: &ContentBlock) -> Option<String> {
+    match block {
+        ContentBlock::ServerToolUse { name, input, .. } => {
+            Some(format!("[Server tool call: {} {}]", name, input))
+        }
+        ContentBlock::WebSearchToolResult { content, .. } => {
+            let results: Vec<String> = content
+                .as_array()
+                .map(|items| {
+                    items
+                        .iter()
+                        .filter_map(|item| {
+                            let url = item.get("url").and_then(Value::as_str)?;
+                            let title = item.get("title").and_then(Value::as_str).unwrap_or(url);
+                            Some(format!("- {} ({})", title, url))
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            if results.is_empty() {
+                Some(format!("[Web search result: {}]", content))
+            } else {
+                Some(format!("[Web search results]\n{}", results.join("\n")))
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Strip thinking blocks and signatures, and convert server tool blocks to text.
+pub fn strip_cross_model_artifacts(messages: &mut [Message]) -> CrossModelReport {
+    let mut report = CrossModelReport::default();
+
+    for message in messages.iter_mut() {
+        let MessageContent::Array(blocks) = &mut message.content else {
+            continue;
+        };
+        let original = std::mem::take(blocks);
+        for mut block in original {
+            if let Some(text) = server_tool_text(&block) {
+                report.server_tools_converted += 1;
+                blocks.push(ContentBlock::Text { text });
+                continue;
+            }
+            match &mut block {
+                ContentBlock::Thinking { .. } | ContentBlock::RedactedThinking { .. } => {
+                    report.thinking_removed += 1;
+                    continue;
+                }
+                ContentBlock::ToolUse { signature, .. } if signature.is_some() => {
+                    *signature = None;
+                    report.signatures_cleared += 1;
+                }
+                _ => {}
+            }
+            blocks.push(block);
+        }
+    }
+
+    report
+}
+
+/// Detect a family switch and clean history if needed.
+///
+/// The family is not recorded here: callers record it with
+/// [`record_session_family`] once the upstream has answered successfully, so
+/// failed attempts and retries on another model do not move the session.
+pub fn apply_cross_model_checks(
+    session_id: &str,
+    messages: &mut [Message],
+    target_model: &str,
+) -> Option<CrossModelReport> {
+    let switched = detect_family_switch(Some(session_id), messages, target_model);
+    if !switched {
+        return None;
+    }
+    let report = strip_cross_model_artifacts(messages);
+    (!report.is_empty()).then_some(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn history() -> Vec<Message> {
+        serde_json::from_value(json!([
+            {"role": "user", "content": "search the docs"},
+            {"role": "assistant", "content": [
+                {"type": "thinking", "thinking": "let me search", "signature": "sig-a"},
+                {"type": "server_tool_use", "id": "srv_1", "name": "web_search", "input": {"query": "docs"}},
+                {"type": "web_search_tool_result", "tool_use_id": "srv_1", "content": [
+                    {"type": "web_search_result", "title": "Docs", "url": "https://example.com/docs"}
+                ]},
+                {"type": "tool_use", "id": "toolu_1", "name": "read", "input": {}, "signature": "sig-b"}
+            ]},
+            {"role": "user", "content": [
+                {"type": "tool_result", "tool_use_id": "toolu_1", "content": "ok"}
+            ]}
+        ]))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_strip_cross_model_artifacts() {
+        let mut messages = history();
+        let report = strip_cross_model_artifacts(&mut messages);
+        assert_eq!(
+            report,
+            CrossModelReport {
+                thinking_removed: 1,
+                signatures_cleared: 1,
+                server_tools_converted: 2,
+            }
+        );
+
+        let MessageContent::Array(blocks) = &messages[1].content else {
+            panic!("expected blocks");
+        };
+        assert_eq!(blocks.len(), 3);
+        assert!(
+            matches!(&blocks[1], ContentBlock::Text { text } if text.contains("https://example.com/docs"))
+        );
+        assert!(matches!(
+            &blocks[2],
+            ContentBlock::ToolUse {
+                signature: None,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_session_family_switch() {
+        let session = "cross-model-test-session";
+        let mut messages = history();
+
+        // 首次请求：无历史记录，不视为切换
+        assert!(apply_cross_model_checks(session, &mut messages, "claude-sonnet-4-5").is_none());
+        record_session_family(session, "claude-sonnet-4-5");
+        // 同族模型继续对话
+        assert!(apply_cross_model_checks(session, &mut messages, "claude-opus-4-5").is_none());
+        record_session_family(session, "claude-opus-4-5");
+        // 切换到 Gemini
+        let report = apply_cross_model_checks(session, &mut messages, "gemini-3-pro").unwrap();
+        assert_eq!(report.thinking_removed, 1);
+        record_session_family(session, "gemini-3-pro");
+        assert_eq!(previous_session_family(session).as_deref(), Some("gemini"));
+    }
+
+    #[test]
+    fn test_checks_do_not_record_family() {
+        let session = "cross-model-unrecorded-session";
+        record_session_family(session, "claude-sonnet-4-5");
+
+        // 上游失败的 Gemini 尝试不改变会话记录的模型族
+        let mut messages = history();
+        assert!(apply_cross_model_checks(session, &mut messages, "gemini-3-pro").is_some());
+        assert_eq!(previous_session_family(session).as_deref(), Some("claude"));
+    }
+
+    #[test]
+    fn test_signature_family_switch() {
+        let sig = format!("{}-cross-model", "s".repeat(60));
+        SignatureCache::global()
+            .cache_thinking_family(sig.clone(), "claude-sonnet-4-5".to_string());
+        let messages: Vec<Message> = serde_json::from_value(json!([
+            {"role": "assistant", "content": [
+                {"type": "thinking", "thinking": "hmm", "signature": sig}
+            ]}
+        ]))
+        .unwrap();
+
+        assert!(detect_family_switch(None, &messages, "gemini-3-flash"));
+        assert!(!detect_family_switch(None, &messages, "claude-opus-4-5"));
+    }
+}
@@ -2,6 +2,10 @@
 
 pub mod common_utils;
 pub mod context_manager;
+pub mod cross_model;
 pub mod error_classifier;
 pub mod model_mapping;
+pub mod request_policy;
+pub mod safety;
+pub mod tool_loop;
 pub mod tool_result_compressor;
@@ -155,6 +155,22 @@ pub fn get_wildcard_mapping(
     best_match.map(|(_, target, _)| target.to_string())
 }
 
+/// Look up a per-model setting keyed by exact model name or wildcard pattern.
+///
+/// Exact keys win; otherwise the most specific matching wildcard pattern is used
+/// (same specificity rule as `get_wildcard_mapping`).
+pub fn lookup_model_pattern<'a, V>(model: &str, table: &'a HashMap<String, V>) -> Option<&'a V> {
+    if let Some(value) = table.get(model) {
+        return Some(value);
+    }
+
+    table
+        .iter()
+        .filter(|(pattern, _)| pattern.contains('*') && wildcard_match(pattern, model))
+        .max_by_key(|(pattern, _)| pattern.chars().count() - pattern.matches('*').count())
+        .map(|(_, value)| value)
+}
+
 /// Core model routing function.
 ///
 /// Priority: exact custom_mapping > wildcard custom_mapping > built-in mapping
@@ -488,6 +504,19 @@ mod tests {
         assert!(!wildcard_match("*-thinking", "claude-opus-4"));
     }
 
+    #[test]
+    fn test_lookup_model_pattern() {
+        let mut table = HashMap::new();
+        table.insert("gemini-*".to_string(), 1);
+        table.insert("gemini-3-*".to_string(), 2);
+        table.insert("gemini-3-pro".to_string(), 3);
+
+        assert_eq!(lookup_model_pattern("gemini-3-pro", &table), Some(&3));
+        assert_eq!(lookup_model_pattern("gemini-3-flash", &table), Some(&2));
+        assert_eq!(lookup_model_pattern("gemini-2.5-flash", &table), Some(&1));
+        assert_eq!(lookup_model_pattern("claude-sonnet-4-5", &table), None);
+    }
+
     #[test]
     fn test_wildcard_match_middle() {
         assert!(wildcard_match("claude-*-sonnet-*", "claude-3-5-sonnet-20241022"));
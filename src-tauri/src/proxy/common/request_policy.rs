@@ -0,0 +1,479 @@
+// 请求策略层 (Request Policy)
+//
+// 协议转换之后、发往上游之前，对 OpenAI / Claude / Gemini 原生三条路径统一应用：
+// - 全局系统提示词注入 (prepend / append / replace)
+// - 思考预算：各协议的思考参数先归一为 ThinkingIntent，再按映射后的模型裁剪
+//   · OpenAI: reasoning_effort / thinking.budget_tokens / thinking.effort
+//   · Claude: thinking.budget_tokens / thinking.effort
+//   · Gemini: generationConfig.thinkingConfig.thinkingBudget / thinkingLevel
+// 等价请求无论从哪个协议进入，发往上游的 systemInstruction / generationConfig 一致。
+
+use serde_json::{json, Map, Value};
+
+use crate::models::config::{
+    GlobalSystemPromptConfig, SystemPromptMode, ThinkingBudgetConfig, ThinkingBudgetMode,
+};
+use crate::proxy::config::{
+    effort_to_thinking_budget, get_global_system_prompt, get_thinking_budget_config,
+    resolve_thinking_budget_with,
+};
+use crate::proxy::mappers::claude::ClaudeRequest;
+use crate::proxy::mappers::openai::OpenAIRequest;
+
+/// 客户端未指定预算时的默认思考预算
+pub const DEFAULT_THINKING_BUDGET: i64 = 24576;
+
+/// maxOutputTokens 不大于思考预算时补足的输出余量
+const MIN_OUTPUT_OVERHEAD: i64 = 8192;
+
+/// 客户端未指定 max tokens 时在思考预算之上预留的输出余量
+const DEFAULT_OUTPUT_OVERHEAD: i64 = 32768;
+
+/// 归一化后的客户端思考意图
+#[derive(Debug, Clone, PartialEq)]
+pub enum ThinkingIntent {
+    Budget(i64),
+    Effort(String),
+}
+
+/// 从客户端请求中提取、与协议无关的策略输入
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PolicyInput {
+    pub thinking: Option<ThinkingIntent>,
+    pub max_output_tokens: Option<i64>,
+}
+
+impl PolicyInput {
+    pub fn from_openai(req: &OpenAIRequest) -> Self {
+        let thinking = req
+            .thinking
+            .as_ref()
+            .filter(|t| t.thinking_type.as_deref() != Some("disabled"));
+        let intent = thinking
+            .and_then(|t| t.budget_tokens)
+            .map(|b| ThinkingIntent::Budget(b as i64))
+            .or_else(|| {
+                thinking
+                    .and_then(|t| t.effort.clone())
+                    .map(ThinkingIntent::Effort)
+            })
+            .or_else(|| {
+                req.reasoning_effort
+                    .clone()
+                    .filter(|e| e != "none")
+                    .map(ThinkingIntent::Effort)
+            });
+        Self {
+            thinking: intent,
+            max_output_tokens: req.max_tokens.map(i64::from),
+        }
+    }
+
+    pub fn from_claude(req: &ClaudeRequest) -> Self {
+        let thinking = req
+            .thinking
+            .as_ref()
+            .filter(|t| t.type_ == "enabled" || t.type_ == "adaptive");
+        let intent = thinking
+            .and_then(|t| t.budget_tokens)
+            .map(|b| ThinkingIntent::Budget(b as i64))
+            .or_else(|| {
+                thinking
+                    .and_then(|t| t.effort.clone())
+                    .map(ThinkingIntent::Effort)
+            });
+        Self {
+            thinking: intent,
+            max_output_tokens: req.max_tokens.map(i64::from),
+        }
+    }
+
+    /// 从 Gemini 原生请求体 (未包装) 中提取
+    pub fn from_gemini(body: &Value) -> Self {
+        let generation_config = body.get("generationConfig");
+        let thinking_config = generation_config.and_then(|g| g.get("thinkingConfig"));
+        let intent = thinking_config
+            .and_then(|t| t.get("thinkingBudget"))
+            .and_then(Value::as_i64)
+            .filter(|b| *b > 0)
+            .map(ThinkingIntent::Budget)
+            .or_else(|| {
+                thinking_config
+                    .and_then(|t| t.get("thinkingLevel"))
+                    .and_then(Value::as_str)
+                    .map(|level| ThinkingIntent::Effort(level.to_string()))
+            });
+        Self {
+            thinking: intent,
+            max_output_tokens: generation_config
+                .and_then(|g| g.get("maxOutputTokens"))
+                .and_then(Value::as_i64),
+        }
+    }
+}
+
+/// 一次请求生效的全局策略快照
+#[derive(Debug, Clone, Default)]
+pub struct RequestPolicy {
+    pub system_prompt: GlobalSystemPromptConfig,
+    pub thinking_budget: ThinkingBudgetConfig,
+}
+
+impl RequestPolicy {
+    /// 读取当前热更新的全局配置
+    pub fn current() -> Self {
+        Self {
+            system_prompt: get_global_system_prompt(),
+            thinking_budget: get_thinking_budget_config(),
+        }
+    }
+}
+
+/// 目标模型是否支持思考 (thinkingConfig)
+pub fn model_supports_thinking(mapped_model: &str) -> bool {
+    let lower = mapped_model.to_lowercase();
+    lower.contains("-thinking")
+        || lower.contains("gemini-2.0-pro")
+        || lower.contains("gemini-3-pro")
+}
+
+/// 在 v1internal 请求体上应用全局策略；图像生成请求保持原样
+pub fn apply_request_policy(
+    body: &mut Value,
+    mapped_model: &str,
+    input: &PolicyInput,
+    policy: &RequestPolicy,
+) {
+    if body.get("requestType").and_then(Value::as_str) == Some("image_gen") {
+        return;
+    }
+    let Some(request) = body.get_mut("request").and_then(Value::as_object_mut) else {
+        return;
+    };
+    apply_system_prompt(request, &policy.system_prompt);
+    apply_thinking_budget(request, mapped_model, input, &policy.thinking_budget);
+}
+
+fn apply_system_prompt(request: &mut Map<String, Value>, config: &GlobalSystemPromptConfig) {
+    let content = config.content.trim();
+    if !config.enabled || content.is_empty() {
+        return;
+    }
+
+    let instruction = request
+        .entry("systemInstruction")
+        .or_insert_with(|| json!({ "role": "user", "parts": [] }));
+    if !instruction.is_object() {
+        *instruction = json!({ "role": "user", "parts": [] });
+    }
+    if !instruction.get("parts").is_some_and(Value::is_array) {
+        instruction["parts"] = json!([]);
+    }
+    let Some(parts) = instruction["parts"].as_array_mut() else {
+        return;
+    };
+
+    let part = json!({ "text": content });
+    match config.mode {
+        SystemPromptMode::Prepend => parts.insert(0, part),
+        SystemPromptMode::Append => parts.push(part),
+        SystemPromptMode::Replace => {
+            parts.clear();
+            parts.push(part);
+        }
+    }
+}
+
+fn apply_thinking_budget(
+    request: &mut Map<String, Value>,
+    mapped_model: &str,
+    input: &PolicyInput,
+    config: &ThinkingBudgetConfig,
+) {
+    let existing = request
+        .get("generationConfig")
+        .and_then(|g| g.get("thinkingConfig"))
+        .cloned();
+    let include_thoughts = existing
+        .as_ref()
+        .and_then(|t| t.get("includeThoughts"))
+        .and_then(Value::as_bool);
+    // 思考被显式关闭 (如图像思考模式 disabled)
+    if include_thoughts == Some(false) {
+        return;
+    }
+    if existing.is_none() && !(input.thinking.is_some() && model_supports_thinking(mapped_model)) {
+        return;
+    }
+
+    let requested_effort = match &input.thinking {
+        Some(ThinkingIntent::Effort(effort)) => Some(effort.as_str()),
+        _ => None,
+    };
+    let effort = if config.mode == ThinkingBudgetMode::Adaptive {
+        requested_effort.or(config.effort.as_deref())
+    } else {
+        requested_effort
+    };
+    let requested_budget = match &input.thinking {
+        Some(ThinkingIntent::Budget(budget)) => Some(*budget),
+        _ => None,
+    };
+    let existing_budget = existing
+        .as_ref()
+        .and_then(|t| t.get("thinkingBudget"))
+        .and_then(Value::as_i64);
+
+    let base = effort
+        .and_then(effort_to_thinking_budget)
+        .or(requested_budget)
+        .or(existing_budget)
+        .unwrap_or(DEFAULT_THINKING_BUDGET);
+    // 客户端自带的 0 (关闭) / -1 (动态) 预算原样保留
+    if base <= 0 {
+        return;
+    }
+    let budget = resolve_thinking_budget_with(config, base, mapped_model);
+
+    let generation_config = request
+        .entry("generationConfig")
+        .or_insert_with(|| json!({}));
+    if !generation_config.is_object() {
+        *generation_config = json!({});
+    }
+    generation_config["thinkingConfig"] = json!({
+        "includeThoughts": include_thoughts.unwrap_or(true),
+        "thinkingBudget": budget
+    });
+
+    // maxOutputTokens 必须大于 thinkingBudget
+    let max_output_tokens = match input.max_output_tokens {
+        Some(max) if max > budget => max,
+        Some(_) => budget + MIN_OUTPUT_OVERHEAD,
+        None => budget + DEFAULT_OUTPUT_OVERHEAD,
+    };
+    generation_config["maxOutputTokens"] = json!(max_output_tokens);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::mappers::claude::transform_claude_request;
+    use crate::proxy::mappers::gemini::wrap_request;
+    use crate::proxy::mappers::openai::transform_openai_request;
+
+    const MODEL: &str = "gemini-3-pro-high";
+
+    fn policy(mode: ThinkingBudgetMode, prompt_mode: SystemPromptMode) -> RequestPolicy {
+        RequestPolicy {
+            system_prompt: GlobalSystemPromptConfig {
+                enabled: true,
+                content: "Always answer in English.".to_string(),
+                mode: prompt_mode,
+            },
+            thinking_budget: ThinkingBudgetConfig {
+                mode,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// 三种协议表达同一个请求：system + 一条用户消息 + 思考参数
+    fn upstream_bodies(
+        claude_thinking: Value,
+        openai_extra: Value,
+        gemini_thinking: Value,
+        policy: &RequestPolicy,
+    ) -> Vec<Value> {
+        let claude: ClaudeRequest = serde_json::from_value(json!({
+            "model": "claude-sonnet-4-5",
+            "system": "You are helpful.",
+            "messages": [{"role": "user", "content": "hi"}],
+            "max_tokens": 4096,
+            "temperature": 0.5,
+            "top_p": 0.75,
+            "thinking": claude_thinking
+        }))
+        .unwrap();
+        let (mut claude_body, _, _) = transform_claude_request(&claude, "p", MODEL).unwrap();
+        apply_request_policy(
+            &mut claude_body,
+            MODEL,
+            &PolicyInput::from_claude(&claude),
+            policy,
+        );
+
+        let mut openai_json = json!({
+            "model": "gpt-4o",
+            "messages": [
+                {"role": "system", "content": "You are helpful."},
+                {"role": "user", "content": "hi"}
+            ],
+            "max_tokens": 4096,
+            "temperature": 0.5,
+            "top_p": 0.75
+        });
+        openai_json
+            .as_object_mut()
+            .unwrap()
+            .extend(openai_extra.as_object().unwrap().clone());
+        let openai: OpenAIRequest = serde_json::from_value(openai_json).unwrap();
+        let (mut openai_body, _, _) = transform_openai_request(&openai, "p", MODEL);
+        apply_request_policy(
+            &mut openai_body,
+            MODEL,
+            &PolicyInput::from_openai(&openai),
+            policy,
+        );
+
+        let gemini = json!({
+            "systemInstruction": {"parts": [{"text": "You are helpful."}]},
+            "contents": [{"role": "user", "parts": [{"text": "hi"}]}],
+            "generationConfig": {
+                "temperature": 0.5,
+                "topP": 0.75,
+                "maxOutputTokens": 4096,
+                "thinkingConfig": gemini_thinking
+            }
+        });
+        let mut gemini_body = wrap_request(&gemini, "p", MODEL, None);
+        apply_request_policy(
+            &mut gemini_body,
+            MODEL,
+            &PolicyInput::from_gemini(&gemini),
+            policy,
+        );
+
+        vec![claude_body, openai_body, gemini_body]
+    }
+
+    fn assert_identical(bodies: &[Value]) -> Value {
+        let views: Vec<Value> = bodies
+            .iter()
+            .map(|b| {
+                json!({
+                    "systemInstruction": b["request"]["systemInstruction"],
+                    "contents": b["request"]["contents"],
+                    "generationConfig": b["request"]["generationConfig"],
+                })
+            })
+            .collect();
+        for view in &views[1..] {
+            assert_eq!(&views[0], view);
+        }
+        views[0].clone()
+    }
+
+    #[test]
+    fn test_matrix_explicit_budget_clamped() {
+        let bodies = upstream_bodies(
+            json!({"type": "enabled", "budget_tokens": 32768}),
+            json!({"thinking": {"type": "enabled", "budget_tokens": 32768}}),
+            json!({"includeThoughts": true, "thinkingBudget": 32768}),
+            &policy(ThinkingBudgetMode::Auto, SystemPromptMode::Prepend),
+        );
+        let view = assert_identical(&bodies);
+        assert_eq!(
+            view["generationConfig"]["thinkingConfig"]["thinkingBudget"],
+            24576
+        );
+        assert_eq!(view["generationConfig"]["maxOutputTokens"], 24576 + 8192);
+        assert_eq!(
+            view["systemInstruction"]["parts"][0]["text"],
+            "Always answer in English."
+        );
+        assert_eq!(
+            view["systemInstruction"]["parts"][1]["text"],
+            "You are helpful."
+        );
+    }
+
+    #[test]
+    fn test_matrix_passthrough_budget() {
+        let bodies = upstream_bodies(
+            json!({"type": "enabled", "budget_tokens": 32768}),
+            json!({"thinking": {"type": "enabled", "budget_tokens": 32768}}),
+            json!({"includeThoughts": true, "thinkingBudget": 32768}),
+            &policy(ThinkingBudgetMode::Passthrough, SystemPromptMode::Append),
+        );
+        let view = assert_identical(&bodies);
+        assert_eq!(
+            view["generationConfig"]["thinkingConfig"]["thinkingBudget"],
+            32768
+        );
+        assert_eq!(
+            view["systemInstruction"]["parts"][1]["text"],
+            "Always answer in English."
+        );
+    }
+
+    #[test]
+    fn test_matrix_effort() {
+        let bodies = upstream_bodies(
+            json!({"type": "adaptive", "effort": "low"}),
+            json!({"reasoning_effort": "low"}),
+            json!({"includeThoughts": true, "thinkingLevel": "low"}),
+            &policy(ThinkingBudgetMode::Auto, SystemPromptMode::Replace),
+        );
+        let view = assert_identical(&bodies);
+        assert_eq!(
+            view["generationConfig"]["thinkingConfig"],
+            json!({"includeThoughts": true, "thinkingBudget": 4096})
+        );
+        assert_eq!(view["generationConfig"]["maxOutputTokens"], 4096 + 8192);
+        assert_eq!(
+            view["systemInstruction"]["parts"],
+            json!([{"text": "Always answer in English."}])
+        );
+    }
+
+    #[test]
+    fn test_custom_mode_overrides_request() {
+        let mut policy = policy(ThinkingBudgetMode::Custom, SystemPromptMode::Prepend);
+        policy.thinking_budget.custom_value = 8192;
+        let bodies = upstream_bodies(
+            json!({"type": "enabled", "budget_tokens": 2048}),
+            json!({"reasoning_effort": "high"}),
+            json!({"includeThoughts": true, "thinkingBudget": 2048}),
+            &policy,
+        );
+        let view = assert_identical(&bodies);
+        assert_eq!(
+            view["generationConfig"]["thinkingConfig"]["thinkingBudget"],
+            8192
+        );
+    }
+
+    #[test]
+    fn test_thinking_not_added_for_unsupported_model() {
+        let mut body =
+            json!({"request": {"contents": [], "generationConfig": {"maxOutputTokens": 100}}});
+        let input = PolicyInput {
+            thinking: Some(ThinkingIntent::Budget(4096)),
+            max_output_tokens: Some(100),
+        };
+        apply_request_policy(
+            &mut body,
+            "gemini-2.5-flash-lite",
+            &input,
+            &RequestPolicy::default(),
+        );
+        assert!(body["request"]["generationConfig"]
+            .get("thinkingConfig")
+            .is_none());
+        assert!(body["request"].get("systemInstruction").is_none());
+    }
+
+    #[test]
+    fn test_image_gen_untouched() {
+        let mut body = json!({"requestType": "image_gen", "request": {"contents": []}});
+        let original = body.clone();
+        apply_request_policy(
+            &mut body,
+            "gemini-3-pro-image",
+            &PolicyInput::default(),
+            &policy(ThinkingBudgetMode::Auto, SystemPromptMode::Prepend),
+        );
+        assert_eq!(body, original);
+    }
+}
@@ -0,0 +1,233 @@
+// Safety settings policy
+//
+// Builds Gemini `safetySettings` from the configured policy instead of the
+// previous hard-coded OFF thresholds, and maps blocked candidates to
+// protocol-appropriate refusals.
+//
+// Threshold priority: user token > model (exact / wildcard) > default.
+
+use serde_json::{json, Value};
+
+use crate::models::config::{SafetyConfig, SafetyThreshold};
+use crate::proxy::common::model_mapping::lookup_model_pattern;
+
+/// Harm categories sent upstream
+pub const HARM_CATEGORIES: [&str; 5] = [
+    "HARM_CATEGORY_HARASSMENT",
+    "HARM_CATEGORY_HATE_SPEECH",
+    "HARM_CATEGORY_SEXUALLY_EXPLICIT",
+    "HARM_CATEGORY_DANGEROUS_CONTENT",
+    "HARM_CATEGORY_CIVIC_INTEGRITY",
+];
+
+/// Gemini finish / block reasons that mean the output was withheld by a safety filter
+const SAFETY_BLOCK_REASONS: [&str; 6] = [
+    "SAFETY",
+    "RECITATION",
+    "BLOCKLIST",
+    "PROHIBITED_CONTENT",
+    "SPII",
+    "IMAGE_SAFETY",
+];
+
+/// Resolve the threshold for a request.
+pub fn resolve_threshold(
+    config: &SafetyConfig,
+    mapped_model: &str,
+    token_id: Option<&str>,
+) -> SafetyThreshold {
+    token_id
+        .and_then(|id| config.token_thresholds.get(id))
+        .or_else(|| lookup_model_pattern(mapped_model, &config.model_thresholds))
+        .copied()
+        .unwrap_or(config.default_threshold)
+}
+
+/// Build the `safetySettings` array for a threshold.
+pub fn safety_settings_for(threshold: SafetyThreshold) -> Value {
+    Value::Array(
+        HARM_CATEGORIES
+            .iter()
+            .map(|category| json!({ "category": category, "threshold": threshold.as_str() }))
+            .collect(),
+    )
+}
+
+/// Build `safetySettings` from the global policy.
+pub fn build_safety_settings(mapped_model: &str, token_id: Option<&str>) -> Value {
+    let config = crate::proxy::config::get_safety_config();
+    safety_settings_for(resolve_threshold(&config, mapped_model, token_id))
+}
+
+/// Apply the policy to an inner Gemini request (the object holding `contents`).
+///
+/// Client-supplied `safetySettings` are kept only when `allow_client` is set by the
+/// caller (native Gemini requests) and the policy permits pass-through.
+pub fn apply_safety_policy(
+    config: &SafetyConfig,
+    inner_request: &mut Value,
+    mapped_model: &str,
+    token_id: Option<&str>,
+    allow_client: bool,
+) {
+    let Some(obj) = inner_request.as_object_mut() else {
+        return;
+    };
+    let client_supplied = obj
+        .get("safetySettings")
+        .and_then(|v| v.as_array())
+        .is_some_and(|arr| !arr.is_empty());
+    if allow_client && config.allow_client_settings && client_supplied {
+        return;
+    }
+    obj.insert(
+        "safetySettings".to_string(),
+        safety_settings_for(resolve_threshold(config, mapped_model, token_id)),
+    );
+}
+
+/// Apply the global policy to an upstream v1internal body (`{ "request": { ... } }`).
+pub fn apply_to_upstream_body(
+    body: &mut Value,
+    mapped_model: &str,
+    token_id: Option<&str>,
+    allow_client: bool,
+) {
+    let config = crate::proxy::config::get_safety_config();
+    if let Some(inner) = body.get_mut("request") {
+        apply_safety_policy(&config, inner, mapped_model, token_id, allow_client);
+    }
+}
+
+/// Whether a Gemini finish reason / block reason means a safety refusal.
+pub fn is_safety_block(reason: &str) -> bool {
+    SAFETY_BLOCK_REASONS.contains(&reason)
+}
+
+/// Extract the safety block reason from a Gemini response, if any.
+///
+/// Checks the first candidate's `finishReason` and, when the prompt itself was
+/// blocked (no candidates), `promptFeedback.blockReason`.
+pub fn safety_block_reason(response: &Value) -> Option<String> {
+    let candidate_reason = response
+        .get("candidates")
+        .and_then(|c| c.get(0))
+        .and_then(|c| c.get("finishReason"))
+        .and_then(|r| r.as_str());
+    let prompt_reason = response
+        .get("promptFeedback")
+        .and_then(|f| f.get("blockReason"))
+        .and_then(|r| r.as_str());
+
+    candidate_reason
+        .filter(|r| is_safety_block(r))
+        .or(prompt_reason.filter(|r| !r.is_empty() && *r != "BLOCK_REASON_UNSPECIFIED"))
+        .map(|r| r.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn config() -> SafetyConfig {
+        SafetyConfig {
+            default_threshold: SafetyThreshold::BlockOnlyHigh,
+            model_thresholds: HashMap::from([
+                ("gemini-*".to_string(), SafetyThreshold::BlockMediumAndAbove),
+                (
+                    "gemini-3-pro-image".to_string(),
+                    SafetyThreshold::BlockLowAndAbove,
+                ),
+            ]),
+            token_thresholds: HashMap::from([("tok-1".to_string(), SafetyThreshold::Off)]),
+            allow_client_settings: true,
+        }
+    }
+
+    #[test]
+    fn test_default_config_keeps_off() {
+        let settings = safety_settings_for(resolve_threshold(
+            &SafetyConfig::default(),
+            "claude-sonnet-4-5",
+            None,
+        ));
+        let arr = settings.as_array().unwrap();
+        assert_eq!(arr.len(), HARM_CATEGORIES.len());
+        assert!(arr.iter().all(|s| s["threshold"] == "OFF"));
+    }
+
+    #[test]
+    fn test_threshold_priority() {
+        let config = config();
+        assert_eq!(
+            resolve_threshold(&config, "claude-sonnet-4-5", None),
+            SafetyThreshold::BlockOnlyHigh
+        );
+        assert_eq!(
+            resolve_threshold(&config, "gemini-3-flash", None),
+            SafetyThreshold::BlockMediumAndAbove
+        );
+        assert_eq!(
+            resolve_threshold(&config, "gemini-3-pro-image", None),
+            SafetyThreshold::BlockLowAndAbove
+        );
+        assert_eq!(
+            resolve_threshold(&config, "gemini-3-pro-image", Some("tok-1")),
+            SafetyThreshold::Off
+        );
+        assert_eq!(
+            resolve_threshold(&config, "gemini-3-flash", Some("tok-unknown")),
+            SafetyThreshold::BlockMediumAndAbove
+        );
+    }
+
+    #[test]
+    fn test_client_settings_passthrough_requires_policy_and_caller() {
+        let client = json!([{ "category": "HARM_CATEGORY_HARASSMENT", "threshold": "BLOCK_NONE" }]);
+        let mut config = config();
+
+        let mut inner = json!({ "contents": [], "safetySettings": client.clone() });
+        apply_safety_policy(&config, &mut inner, "gemini-3-flash", None, true);
+        assert_eq!(inner["safetySettings"], client);
+
+        // Non-native protocol: always overwritten
+        let mut inner = json!({ "contents": [], "safetySettings": client.clone() });
+        apply_safety_policy(&config, &mut inner, "gemini-3-flash", None, false);
+        assert_eq!(
+            inner["safetySettings"][0]["threshold"],
+            "BLOCK_MEDIUM_AND_ABOVE"
+        );
+
+        // Policy forbids pass-through
+        config.allow_client_settings = false;
+        let mut inner = json!({ "contents": [], "safetySettings": client });
+        apply_safety_policy(&config, &mut inner, "gemini-3-flash", None, true);
+        assert_eq!(
+            inner["safetySettings"].as_array().unwrap().len(),
+            HARM_CATEGORIES.len()
+        );
+    }
+
+    #[test]
+    fn test_safety_block_reason() {
+        assert_eq!(
+            safety_block_reason(&json!({ "candidates": [{ "finishReason": "SAFETY" }] })),
+            Some("SAFETY".to_string())
+        );
+        assert_eq!(
+            safety_block_reason(
+                &json!({ "promptFeedback": { "blockReason": "PROHIBITED_CONTENT" } })
+            ),
+            Some("PROHIBITED_CONTENT".to_string())
+        );
+        assert_eq!(
+            safety_block_reason(&json!({ "candidates": [{ "finishReason": "STOP" }] })),
+            None
+        );
+        assert_eq!(
+            safety_block_reason(&json!({ "candidates": [{ "finishReason": "MAX_TOKENS" }] })),
+            None
+        );
+    }
+}
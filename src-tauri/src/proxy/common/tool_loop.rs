@@ -0,0 +1,225 @@
+// 工具循环恢复 (Tool Loop Recovery)
+//
+// Agent 有时会陷入死循环：反复发起参数完全相同的工具调用，拿到完全相同的结果。
+// 开启 `enable_tool_loop_recovery` 后，在发往上游的 v1internal 请求体上检测末尾
+// 连续重复的 [model functionCall -> user functionResponse] 轮次：
+// - 达到 LOOP_WARN_THRESHOLD 次：在最后一条 user 消息追加纠正提示
+// - 达到 LOOP_BREAK_THRESHOLD 次：同时禁用本轮工具调用 (mode = NONE)，强制模型作答
+
+use serde_json::{json, Value};
+
+/// 连续重复达到该次数时注入纠正提示
+pub const LOOP_WARN_THRESHOLD: usize = 3;
+
+/// 连续重复达到该次数时禁用工具调用以打断循环
+pub const LOOP_BREAK_THRESHOLD: usize = 5;
+
+/// 检测到工具循环时的处理结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolLoopReport {
+    pub tool_names: Vec<String>,
+    pub repeats: usize,
+    pub broken: bool,
+}
+
+/// 递归排序对象 key，保证参数比较与字段顺序无关
+fn canonical(value: &Value) -> String {
+    fn normalize(value: &Value) -> Value {
+        match value {
+            Value::Object(map) => {
+                let mut keys: Vec<&String> = map.keys().collect();
+                keys.sort();
+                let mut sorted = serde_json::Map::new();
+                for key in keys {
+                    sorted.insert(key.clone(), normalize(&map[key]));
+                }
+                Value::Object(sorted)
+            }
+            Value::Array(items) => Value::Array(items.iter().map(normalize).collect()),
+            other => other.clone(),
+        }
+    }
+    normalize(value).to_string()
+}
+
+fn parts_of(content: &Value) -> &[Value] {
+    content
+        .get("parts")
+        .and_then(|p| p.as_array())
+        .map(|p| p.as_slice())
+        .unwrap_or(&[])
+}
+
+fn role_of(content: &Value) -> &str {
+    content.get("role").and_then(|r| r.as_str()).unwrap_or("")
+}
+
+/// 提取某条消息中指定类型 part 的规范化签名 (name + 内容)，按字典序排列
+fn signatures(content: &Value, kind: &str, payload: &str) -> Vec<(String, String)> {
+    let mut sigs: Vec<(String, String)> = parts_of(content)
+        .iter()
+        .filter_map(|p| p.get(kind))
+        .map(|f| {
+            let name = f.get("name").and_then(|n| n.as_str()).unwrap_or("");
+            let body = f.get(payload).map(canonical).unwrap_or_default();
+            (name.to_string(), body)
+        })
+        .collect();
+    sigs.sort();
+    sigs
+}
+
+type Round = (Vec<(String, String)>, Vec<(String, String)>);
+
+/// 从末尾开始统计与最后一轮完全相同的连续工具轮次数
+fn trailing_identical_rounds(contents: &[Value]) -> (usize, Option<Round>) {
+    let mut repeats = 0;
+    let mut last: Option<Round> = None;
+    let mut idx = contents.len();
+
+    while idx >= 2 {
+        let (call_msg, result_msg) = (&contents[idx - 2], &contents[idx - 1]);
+        if role_of(call_msg) != "model" || role_of(result_msg) != "user" {
+            break;
+        }
+        let calls = signatures(call_msg, "functionCall", "args");
+        let results = signatures(result_msg, "functionResponse", "response");
+        if calls.is_empty() || results.is_empty() {
+            break;
+        }
+        let round = (calls, results);
+        match &last {
+            None => last = Some(round),
+            Some(prev) if *prev == round => {}
+            Some(_) => break,
+        }
+        repeats += 1;
+        idx -= 2;
+    }
+
+    (repeats, last)
+}
+
+/// 在 v1internal 请求体上检测并处理工具循环；未检测到循环时返回 None
+pub fn apply_tool_loop_recovery(body: &mut Value) -> Option<ToolLoopReport> {
+    let request = body.get_mut("request")?;
+    let contents = request.get_mut("contents")?.as_array_mut()?;
+
+    let (repeats, round) = trailing_identical_rounds(contents);
+    if repeats < LOOP_WARN_THRESHOLD {
+        return None;
+    }
+    let (calls, _) = round?;
+    let mut tool_names: Vec<String> = calls.into_iter().map(|(name, _)| name).collect();
+    tool_names.dedup();
+    let broken = repeats >= LOOP_BREAK_THRESHOLD;
+
+    let note = if broken {
+        format!(
+            "[System note] The tool call(s) `{}` have been repeated {} times with identical arguments and identical results. Tool use is disabled for this turn: do not call any tool, answer using the results you already have.",
+            tool_names.join("`, `"),
+            repeats
+        )
+    } else {
+        format!(
+            "[System note] The tool call(s) `{}` have been repeated {} times with identical arguments and identical results. Do not repeat the same call again; use the result you already have or try a different approach.",
+            tool_names.join("`, `"),
+            repeats
+        )
+    };
+    if let Some(parts) = contents
+        .last_mut()
+        .and_then(|c| c.get_mut("parts"))
+        .and_then(|p| p.as_array_mut())
+    {
+        parts.push(json!({ "text": note }));
+    }
+
+    if broken {
+        request["toolConfig"] = json!({
+            "functionCallingConfig": { "mode": "NONE" }
+        });
+    }
+
+    Some(ToolLoopReport {
+        tool_names,
+        repeats,
+        broken,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round(args: Value, result: &str) -> Vec<Value> {
+        vec![
+            json!({"role": "model", "parts": [{"functionCall": {"name": "read_file", "args": args}}]}),
+            json!({"role": "user", "parts": [{"functionResponse": {"name": "read_file", "response": {"result": result}}}]}),
+        ]
+    }
+
+    fn body_with_rounds(rounds: Vec<Vec<Value>>) -> Value {
+        let mut contents = vec![json!({"role": "user", "parts": [{"text": "fix the bug"}]})];
+        contents.extend(rounds.into_iter().flatten());
+        json!({"project": "p", "request": {"contents": contents}})
+    }
+
+    #[test]
+    fn test_no_loop_below_threshold() {
+        let mut body = body_with_rounds(vec![
+            round(json!({"path": "a.rs"}), "same"),
+            round(json!({"path": "a.rs"}), "same"),
+        ]);
+        assert!(apply_tool_loop_recovery(&mut body).is_none());
+    }
+
+    #[test]
+    fn test_different_results_are_not_a_loop() {
+        let mut body = body_with_rounds(vec![
+            round(json!({"path": "a.rs"}), "v1"),
+            round(json!({"path": "a.rs"}), "v2"),
+            round(json!({"path": "a.rs"}), "v3"),
+        ]);
+        assert!(apply_tool_loop_recovery(&mut body).is_none());
+    }
+
+    #[test]
+    fn test_loop_injects_note() {
+        let mut body = body_with_rounds(vec![
+            round(json!({"path": "b.rs"}), "other"),
+            round(json!({"path": "a.rs", "lines": 10}), "same"),
+            round(json!({"lines": 10, "path": "a.rs"}), "same"),
+            round(json!({"path": "a.rs", "lines": 10}), "same"),
+        ]);
+        let report = apply_tool_loop_recovery(&mut body).unwrap();
+        assert_eq!(report.repeats, 3);
+        assert_eq!(report.tool_names, vec!["read_file".to_string()]);
+        assert!(!report.broken);
+
+        let last = body["request"]["contents"]
+            .as_array()
+            .unwrap()
+            .last()
+            .unwrap();
+        let note = last["parts"][1]["text"].as_str().unwrap();
+        assert!(note.contains("`read_file`"));
+        assert!(body["request"].get("toolConfig").is_none());
+    }
+
+    #[test]
+    fn test_loop_is_broken_at_threshold() {
+        let rounds = (0..LOOP_BREAK_THRESHOLD)
+            .map(|_| round(json!({"path": "a.rs"}), "same"))
+            .collect();
+        let mut body = body_with_rounds(rounds);
+        body["request"]["toolConfig"] = json!({"functionCallingConfig": {"mode": "VALIDATED"}});
+
+        let report = apply_tool_loop_recovery(&mut body).unwrap();
+        assert!(report.broken);
+        assert_eq!(
+            body["request"]["toolConfig"]["functionCallingConfig"]["mode"],
+            "NONE"
+        );
+    }
+}
@@ -5,11 +5,19 @@
 //! - Large file notice compression (extract key info)
 //! - Generic truncation (200,000 character limit)
 //! - HTML deep cleaning (remove style, script, base64)
+//!
+//! Limits are configurable per model (`tool_result_compression`) and can be
+//! disabled per request with the `X-Tool-Result-Compression: off` header.
 
+use axum::http::HeaderMap;
 use regex::Regex;
 use serde_json::Value;
+use std::sync::{OnceLock, RwLock};
 use tracing::{debug, info};
 
+use crate::models::config::ToolResultCompressionConfig;
+use crate::proxy::common::model_mapping::lookup_model_pattern;
+
 /// Maximum tool result characters (~200K, prevents prompt overflow)
 const MAX_TOOL_RESULT_CHARS: usize = 200_000;
 
@@ -175,9 +183,9 @@ fn compact_browser_snapshot(text: &str, max_chars: usize) -> Option<String> {
     let head_len = head_len.min(10_000).max(500);
     let tail_len = budget.saturating_sub(head_len).min(3_000);
 
-    let head = &text[..head_len.min(text.len())];
+    let head = &text[..floor_char_boundary(text, head_len)];
     let tail = if tail_len > 0 && text.len() > head_len {
-        let start = text.len().saturating_sub(tail_len);
+        let start = ceil_char_boundary(text, text.len().saturating_sub(tail_len));
         &text[start..]
     } else {
         ""
@@ -209,8 +217,8 @@ pub fn truncate_text_safe(text: &str, max_chars: usize) -> String {
         return text.to_string();
     }
 
-    let mut split_pos = max_chars;
-    let sub = &text[..max_chars];
+    let mut split_pos = floor_char_boundary(text, max_chars);
+    let sub = &text[..split_pos];
 
     // Avoid cutting inside HTML tags
     if let Some(last_open) = sub.rfind('<') {
@@ -226,7 +234,7 @@ pub fn truncate_text_safe(text: &str, max_chars: usize) -> String {
     // Avoid cutting inside JSON braces
     if let Some(last_open_brace) = sub.rfind('{') {
         if let Some(last_close_brace) = sub.rfind('}') {
-            if last_open_brace > last_close_brace && max_chars - last_open_brace < 100 {
+            if last_open_brace > last_close_brace && sub.len() - last_open_brace < 100 {
                 split_pos = split_pos.min(last_open_brace);
             }
         }
@@ -237,6 +245,24 @@ pub fn truncate_text_safe(text: &str, max_chars: usize) -> String {
     format!("{}\n...[truncated {} chars]", truncated, omitted)
 }
 
+/// Largest char boundary <= `index` (tool output is often multi-byte text)
+fn floor_char_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+/// Smallest char boundary >= `index`
+fn ceil_char_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index += 1;
+    }
+    index
+}
+
 /// Deep clean HTML content by removing style, script, base64 data, and excess whitespace.
 fn deep_clean_html(html: &str) -> String {
     let mut result = html.to_string();
@@ -267,6 +293,11 @@ fn deep_clean_html(html: &str) -> String {
 /// 2. Compress text content (using intelligent compression)
 /// 3. Limit total characters (default 200,000)
 pub fn sanitize_tool_result_blocks(blocks: &mut Vec<Value>) {
+    sanitize_tool_result_blocks_with_limit(blocks, MAX_TOOL_RESULT_CHARS);
+}
+
+/// Same as [`sanitize_tool_result_blocks`] with an explicit total character limit.
+pub fn sanitize_tool_result_blocks_with_limit(blocks: &mut Vec<Value>, max_chars: usize) {
     let mut used_chars = 0;
     let mut cleaned_blocks = Vec::new();
     let mut removed_image = false;
@@ -275,7 +306,7 @@ pub fn sanitize_tool_result_blocks(blocks: &mut Vec<Value>) {
         info!(
             "[ToolCompressor] Processing {} blocks for truncation (MAX: {} chars)",
             blocks.len(),
-            MAX_TOOL_RESULT_CHARS
+            max_chars
         );
     }
 
@@ -287,7 +318,7 @@ pub fn sanitize_tool_result_blocks(blocks: &mut Vec<Value>) {
         }
 
         if let Some(text) = block.get("text").and_then(|v| v.as_str()) {
-            let remaining = MAX_TOOL_RESULT_CHARS.saturating_sub(used_chars);
+            let remaining = max_chars.saturating_sub(used_chars);
             if remaining == 0 {
                 debug!("[ToolCompressor] Reached character limit, stopping");
                 break;
@@ -303,7 +334,7 @@ pub fn sanitize_tool_result_blocks(blocks: &mut Vec<Value>) {
             used_chars += 100; // Estimate non-text block size
         }
 
-        if used_chars >= MAX_TOOL_RESULT_CHARS {
+        if used_chars >= max_chars {
             break;
         }
     }
@@ -335,6 +366,151 @@ fn is_base64_image(block: &Value) -> bool {
             == Some("base64")
 }
 
+// ============================================================================
+// Compression Policy (per-model limit + per-request opt-out)
+// ============================================================================
+
+/// Request header that disables compression for a single request
+/// (`off` / `false` / `0` / `disabled`).
+pub const TOOL_RESULT_COMPRESSION_HEADER: &str = "x-tool-result-compression";
+
+static GLOBAL_TOOL_RESULT_COMPRESSION_CONFIG: OnceLock<RwLock<ToolResultCompressionConfig>> =
+    OnceLock::new();
+
+/// 当前生效的工具结果压缩配置
+pub fn get_tool_result_compression_config() -> ToolResultCompressionConfig {
+    GLOBAL_TOOL_RESULT_COMPRESSION_CONFIG
+        .get()
+        .and_then(|lock| lock.read().ok())
+        .map(|cfg| cfg.clone())
+        .unwrap_or_default()
+}
+
+/// 更新工具结果压缩配置 (启动及配置热更新时调用)
+pub fn update_tool_result_compression_config(config: &ToolResultCompressionConfig) {
+    let lock = GLOBAL_TOOL_RESULT_COMPRESSION_CONFIG
+        .get_or_init(|| RwLock::new(ToolResultCompressionConfig::default()));
+    if let Ok(mut cfg) = lock.write() {
+        *cfg = config.clone();
+    }
+    info!(
+        "[ToolCompressor] Config updated: enabled={}, default_max_chars={}, model_rules={}",
+        config.enabled,
+        config.default_max_chars,
+        config.model_max_chars.len()
+    );
+}
+
+/// Whether the client opted out of compression for this request.
+pub fn compression_opted_out(headers: &HeaderMap) -> bool {
+    headers
+        .get(TOOL_RESULT_COMPRESSION_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().to_ascii_lowercase())
+        .is_some_and(|v| matches!(v.as_str(), "off" | "false" | "0" | "disabled"))
+}
+
+/// Resolve the per-tool-result character limit for a model.
+///
+/// Priority: model (exact / wildcard) > default. `None` means compression is
+/// disabled and tool results are forwarded unchanged.
+pub fn resolve_tool_result_limit(
+    config: &ToolResultCompressionConfig,
+    mapped_model: &str,
+    opted_out: bool,
+) -> Option<usize> {
+    if !config.enabled || opted_out {
+        return None;
+    }
+    Some(
+        lookup_model_pattern(mapped_model, &config.model_max_chars)
+            .copied()
+            .unwrap_or(config.default_max_chars),
+    )
+}
+
+/// Resolve the limit for a request from the global config and request headers.
+pub fn tool_result_limit_for_request(mapped_model: &str, headers: &HeaderMap) -> Option<usize> {
+    resolve_tool_result_limit(
+        &get_tool_result_compression_config(),
+        mapped_model,
+        compression_opted_out(headers),
+    )
+}
+
+/// Resolve the limit from the global config alone (no request headers).
+pub fn default_tool_result_limit(mapped_model: &str) -> Option<usize> {
+    resolve_tool_result_limit(&get_tool_result_compression_config(), mapped_model, false)
+}
+
+/// Hard cap applied to every tool result, even when compression is disabled.
+///
+/// 关闭压缩只跳过智能压缩，超过 200K 字符的结果仍会被截断，避免撑爆上下文。
+pub fn cap_tool_result(text: String) -> String {
+    if text.len() <= MAX_TOOL_RESULT_CHARS {
+        return text;
+    }
+    let mut truncated: String = text.chars().take(MAX_TOOL_RESULT_CHARS).collect();
+    if truncated.len() == text.len() {
+        return text;
+    }
+    truncated.push_str("\n...[truncated output]");
+    truncated
+}
+
+/// Compress a single tool result text with a resolved limit.
+pub fn compress_tool_result(text: String, limit: Option<usize>) -> String {
+    match limit {
+        Some(max_chars) if text.len() > max_chars => {
+            let compacted = compact_tool_result_text(&text, max_chars);
+            info!(
+                "[ToolCompressor] Compressed tool result {} -> {} chars (limit {})",
+                text.len(),
+                compacted.len(),
+                max_chars
+            );
+            compacted
+        }
+        _ => text,
+    }
+}
+
+/// Compress `functionResponse` results in an upstream v1internal body
+/// (`{ "request": { "contents": [...] } }`), used for native Gemini requests.
+pub fn apply_to_upstream_body(body: &mut Value, limit: Option<usize>) {
+    let Some(max_chars) = limit else {
+        return;
+    };
+    let Some(contents) = body
+        .get_mut("request")
+        .and_then(|r| r.get_mut("contents"))
+        .and_then(|c| c.as_array_mut())
+    else {
+        return;
+    };
+
+    for part in contents
+        .iter_mut()
+        .filter_map(|c| c.get_mut("parts").and_then(|p| p.as_array_mut()))
+        .flatten()
+    {
+        let Some(response) = part
+            .get_mut("functionResponse")
+            .and_then(|f| f.get_mut("response"))
+            .and_then(|r| r.as_object_mut())
+        else {
+            continue;
+        };
+        for value in response.values_mut() {
+            if let Value::String(text) = value {
+                if text.len() > max_chars {
+                    *text = compress_tool_result(std::mem::take(text), Some(max_chars));
+                }
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -458,4 +634,78 @@ Please read the file locally."#;
         let result = compact_tool_result_text("", 1000);
         assert_eq!(result, "");
     }
+
+    #[test]
+    fn test_truncate_multibyte_text() {
+        let text = "工具输出".repeat(10_000);
+        let result = truncate_text_safe(&text, 1001);
+        assert!(result.contains("[truncated"));
+        assert!(result.len() < 1100);
+    }
+
+    #[test]
+    fn test_resolve_tool_result_limit() {
+        let mut config = ToolResultCompressionConfig {
+            default_max_chars: 50_000,
+            ..Default::default()
+        };
+        let models = &mut config.model_max_chars;
+        models.insert("gemini-*".to_string(), 80_000);
+        models.insert("gemini-3-pro".to_string(), 120_000);
+        let limit = |config: &ToolResultCompressionConfig, model: &str| {
+            resolve_tool_result_limit(config, model, false)
+        };
+
+        assert_eq!(limit(&config, "gemini-3-pro"), Some(120_000));
+        assert_eq!(limit(&config, "gemini-2.5-flash"), Some(80_000));
+        assert_eq!(limit(&config, "claude-sonnet-4-5"), Some(50_000));
+        // 请求级关闭
+        assert!(resolve_tool_result_limit(&config, "gemini-3-pro", true).is_none());
+
+        config.enabled = false;
+        assert_eq!(limit(&config, "gemini-3-pro"), None);
+    }
+
+    #[test]
+    fn test_compression_opt_out_header() {
+        let mut headers = HeaderMap::new();
+        assert!(!compression_opted_out(&headers));
+        headers.insert(TOOL_RESULT_COMPRESSION_HEADER, "Off".parse().unwrap());
+        assert!(compression_opted_out(&headers));
+        headers.insert(TOOL_RESULT_COMPRESSION_HEADER, "on".parse().unwrap());
+        assert!(!compression_opted_out(&headers));
+    }
+
+    #[test]
+    fn test_apply_to_upstream_body() {
+        let large = format!("<html><body>{}</body></html>", "x".repeat(20_000));
+        let mut body = serde_json::json!({
+            "request": {
+                "contents": [{
+                    "role": "user",
+                    "parts": [
+                        {"functionResponse": {"name": "fetch", "response": {"result": large}}},
+                        {"text": "y".repeat(20_000)}
+                    ]
+                }]
+            }
+        });
+
+        let result_of = |body: &Value| {
+            body["request"]["contents"][0]["parts"][0]["functionResponse"]["response"]["result"]
+                .as_str()
+                .unwrap()
+                .to_string()
+        };
+
+        apply_to_upstream_body(&mut body, None);
+        assert!(result_of(&body).len() > 20_000);
+
+        apply_to_upstream_body(&mut body, Some(5_000));
+        let result = result_of(&body);
+        assert!(result.len() < 5_100);
+        assert!(result.contains("[truncated"));
+        let text = &body["request"]["contents"][0]["parts"][1]["text"];
+        assert_eq!(text.as_str().unwrap().len(), 20_000);
+    }
 }
@@ -0,0 +1,402 @@
+// 账号并发限制与公平排队
+//
+// - 单账号在途请求数不超过 max_concurrent_per_account；get_token 返回的 ProxyToken 携带 RAII 租约，
+//   租约随 Token 及其响应流一起释放
+// - 所有可选账号都已满载时进入全局准入队列，在超时前等待空闲租约
+// - 队列按 User Token 做加权公平排队 (WFQ)：等待者按虚拟结束时间排序，
+//   权重越大的用户获得的份额越多，单个重度用户无法独占所有租约
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use dashmap::DashMap;
+use futures::StreamExt;
+use serde::Serialize;
+use tokio::sync::Notify;
+
+use crate::proxy::upstream::stream_peek::UpstreamByteStream;
+
+/// 未携带 User Token 的请求共用的排队键
+pub const ANONYMOUS_QUEUE_KEY: &str = "anonymous";
+
+/// 虚拟时间刻度：权重为 1 的请求每次推进的虚拟时间
+const WFQ_SCALE: u64 = 1_000_000;
+
+// ============================================================================
+// Lease
+// ============================================================================
+
+/// 账号并发租约，Drop 时释放并唤醒排队者
+pub struct AccountLease {
+    account_id: String,
+    limiter: Arc<ConcurrencyLimiter>,
+}
+
+impl AccountLease {
+    pub fn account_id(&self) -> &str {
+        &self.account_id
+    }
+}
+
+impl std::fmt::Debug for AccountLease {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AccountLease")
+            .field("account_id", &self.account_id)
+            .finish()
+    }
+}
+
+impl Drop for AccountLease {
+    fn drop(&mut self) {
+        self.limiter.release(&self.account_id);
+    }
+}
+
+/// 让上游字节流持有租约，直到流结束或被丢弃 (客户端断开)
+pub fn hold_lease(
+    stream: UpstreamByteStream,
+    lease: Option<Arc<AccountLease>>,
+) -> UpstreamByteStream {
+    match lease {
+        Some(lease) => Box::pin(stream.map(move |item| {
+            let _ = &lease;
+            item
+        })),
+        None => stream,
+    }
+}
+
+// ============================================================================
+// Fair Queue
+// ============================================================================
+
+struct Waiter {
+    user: String,
+    notify: Arc<Notify>,
+}
+
+#[derive(Default)]
+struct FairQueue {
+    /// (虚拟结束时间, 序号) -> 等待者
+    waiters: BTreeMap<(u64, u64), Waiter>,
+    /// 用户 -> 最近一个等待者的虚拟结束时间
+    user_finish: HashMap<String, u64>,
+    virtual_time: u64,
+    next_seq: u64,
+}
+
+impl FairQueue {
+    fn push(&mut self, user: &str, weight: u32) -> ((u64, u64), Arc<Notify>) {
+        let start = self
+            .user_finish
+            .get(user)
+            .copied()
+            .unwrap_or(0)
+            .max(self.virtual_time);
+        let finish = start + WFQ_SCALE / u64::from(weight.max(1));
+        self.user_finish.insert(user.to_string(), finish);
+
+        let key = (finish, self.next_seq);
+        self.next_seq += 1;
+        let notify = Arc::new(Notify::new());
+        self.waiters.insert(
+            key,
+            Waiter {
+                user: user.to_string(),
+                notify: notify.clone(),
+            },
+        );
+        (key, notify)
+    }
+
+    fn remove(&mut self, key: (u64, u64)) {
+        if let Some(waiter) = self.waiters.remove(&key) {
+            let idle = !self.waiters.values().any(|w| w.user == waiter.user);
+            let behind = self
+                .user_finish
+                .get(&waiter.user)
+                .is_some_and(|finish| *finish <= self.virtual_time);
+            if idle && behind {
+                self.user_finish.remove(&waiter.user);
+            }
+        }
+    }
+
+    /// 唤醒 `after` 之后的第一个等待者 (None 表示队首)
+    fn wake_after(&self, after: Option<(u64, u64)>) {
+        let next = match after {
+            Some(key) => self
+                .waiters
+                .range((std::ops::Bound::Excluded(key), std::ops::Bound::Unbounded))
+                .next(),
+            None => self.waiters.iter().next(),
+        };
+        if let Some((_, waiter)) = next {
+            waiter.notify.notify_one();
+        }
+    }
+}
+
+// ============================================================================
+// Limiter
+// ============================================================================
+
+#[derive(Default)]
+struct QueueCounters {
+    admitted: u64,
+    timeouts: u64,
+    total_wait_ms: u64,
+    max_wait_ms: u64,
+}
+
+/// 并发与排队统计
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ConcurrencyStats {
+    pub max_concurrent_per_account: u32,
+    /// 当前排队请求数
+    pub queue_depth: usize,
+    /// User Token ID -> 排队请求数
+    pub queue_depth_by_user: HashMap<String, usize>,
+    /// 账号 ID -> 在途请求数
+    pub in_flight: HashMap<String, u32>,
+    /// 经排队后获得租约的请求数
+    pub admitted_after_wait: u64,
+    /// 排队超时的请求数
+    pub queue_timeouts: u64,
+    pub avg_wait_ms: u64,
+    pub max_wait_ms: u64,
+}
+
+/// 账号并发限制器 (每个 TokenManager 一个)
+#[derive(Default)]
+pub struct ConcurrencyLimiter {
+    in_flight: DashMap<String, u32>,
+    queue: Mutex<FairQueue>,
+    counters: Mutex<QueueCounters>,
+}
+
+impl ConcurrencyLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 账号未满载时占用一个租约
+    pub fn try_acquire(self: &Arc<Self>, account_id: &str, max: u32) -> Option<AccountLease> {
+        let mut count = self.in_flight.entry(account_id.to_string()).or_insert(0);
+        if *count >= max {
+            return None;
+        }
+        *count += 1;
+        Some(AccountLease {
+            account_id: account_id.to_string(),
+            limiter: self.clone(),
+        })
+    }
+
+    fn release(&self, account_id: &str) {
+        self.in_flight.remove_if_mut(account_id, |_, count| {
+            *count = count.saturating_sub(1);
+            *count == 0
+        });
+        self.wake_after(None);
+    }
+
+    /// 已达并发上限的账号
+    pub fn saturated_accounts(&self, max: u32) -> HashSet<String> {
+        self.in_flight
+            .iter()
+            .filter(|entry| *entry.value() >= max)
+            .map(|entry| entry.key().clone())
+            .collect()
+    }
+
+    pub fn in_flight(&self, account_id: &str) -> u32 {
+        self.in_flight.get(account_id).map(|c| *c).unwrap_or(0)
+    }
+
+    pub fn has_waiters(&self) -> bool {
+        self.queue
+            .lock()
+            .map(|q| !q.waiters.is_empty())
+            .unwrap_or(false)
+    }
+
+    /// 加入准入队列
+    pub fn enqueue(self: &Arc<Self>, user: &str, weight: u32) -> QueueTicket {
+        let (key, notify) = match self.queue.lock() {
+            Ok(mut queue) => queue.push(user, weight),
+            Err(_) => ((u64::MAX, u64::MAX), Arc::new(Notify::new())),
+        };
+        QueueTicket {
+            limiter: self.clone(),
+            key,
+            notify,
+            enqueued_at: Instant::now(),
+        }
+    }
+
+    fn wake_after(&self, after: Option<(u64, u64)>) {
+        if let Ok(queue) = self.queue.lock() {
+            queue.wake_after(after);
+        }
+    }
+
+    /// 唤醒队首，按公平顺序依次尝试获取租约
+    pub fn wake_head(&self) {
+        self.wake_after(None);
+    }
+
+    pub fn stats(&self, max_concurrent_per_account: u32) -> ConcurrencyStats {
+        let mut stats = ConcurrencyStats {
+            max_concurrent_per_account,
+            in_flight: self
+                .in_flight
+                .iter()
+                .map(|entry| (entry.key().clone(), *entry.value()))
+                .collect(),
+            ..Default::default()
+        };
+        if let Ok(queue) = self.queue.lock() {
+            stats.queue_depth = queue.waiters.len();
+            for waiter in queue.waiters.values() {
+                *stats
+                    .queue_depth_by_user
+                    .entry(waiter.user.clone())
+                    .or_insert(0) += 1;
+            }
+        }
+        if let Ok(counters) = self.counters.lock() {
+            stats.admitted_after_wait = counters.admitted;
+            stats.queue_timeouts = counters.timeouts;
+            stats.avg_wait_ms = counters
+                .total_wait_ms
+                .checked_div(counters.admitted)
+                .unwrap_or(0);
+            stats.max_wait_ms = counters.max_wait_ms;
+        }
+        stats
+    }
+}
+
+/// 排队凭证；Drop 时离开队列
+pub struct QueueTicket {
+    limiter: Arc<ConcurrencyLimiter>,
+    key: (u64, u64),
+    notify: Arc<Notify>,
+    enqueued_at: Instant,
+}
+
+impl QueueTicket {
+    /// 等待被唤醒；超过 deadline 返回 false
+    pub async fn wait(&self, deadline: tokio::time::Instant) -> bool {
+        tokio::time::timeout_at(deadline, self.notify.notified())
+            .await
+            .is_ok()
+    }
+
+    /// 本次唤醒未能获得租约，交给下一个等待者尝试
+    pub fn pass_on(&self) {
+        self.limiter.wake_after(Some(self.key));
+    }
+
+    /// 已获得租约
+    pub fn admit(self) {
+        let waited = self.enqueued_at.elapsed().as_millis() as u64;
+        if let Ok(mut queue) = self.limiter.queue.lock() {
+            queue.virtual_time = queue.virtual_time.max(self.key.0);
+        }
+        if let Ok(mut counters) = self.limiter.counters.lock() {
+            counters.admitted += 1;
+            counters.total_wait_ms += waited;
+            counters.max_wait_ms = counters.max_wait_ms.max(waited);
+        }
+    }
+
+    /// 排队超时
+    pub fn timed_out(self) -> u64 {
+        if let Ok(mut counters) = self.limiter.counters.lock() {
+            counters.timeouts += 1;
+        }
+        self.enqueued_at.elapsed().as_millis() as u64
+    }
+}
+
+impl Drop for QueueTicket {
+    fn drop(&mut self) {
+        if let Ok(mut queue) = self.limiter.queue.lock() {
+            queue.remove(self.key);
+            // 获得租约后可能仍有空闲容量；取消 / 超时的等待者可能吞掉了一次唤醒
+            queue.wake_after(Some(self.key));
+        }
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lease_limits_and_releases() {
+        let limiter = Arc::new(ConcurrencyLimiter::new());
+        let a = limiter.try_acquire("acc1", 2).unwrap();
+        let b = limiter.try_acquire("acc1", 2).unwrap();
+        assert!(limiter.try_acquire("acc1", 2).is_none());
+        assert_eq!(limiter.in_flight("acc1"), 2);
+        assert!(limiter.saturated_accounts(2).contains("acc1"));
+
+        drop(a);
+        assert_eq!(limiter.in_flight("acc1"), 1);
+        assert!(limiter.try_acquire("acc1", 2).is_some());
+
+        drop(b);
+        assert_eq!(limiter.in_flight("acc1"), 0);
+        assert!(limiter.stats(2).in_flight.is_empty());
+    }
+
+    #[test]
+    fn test_weighted_fair_ordering() {
+        let mut queue = FairQueue::default();
+        // 重度用户先排入 3 个请求，轻度用户随后排入 1 个
+        let (h1, _) = queue.push("heavy", 1);
+        let (h2, _) = queue.push("heavy", 1);
+        let (h3, _) = queue.push("heavy", 1);
+        let (l1, _) = queue.push("light", 1);
+        let order: Vec<_> = queue.waiters.keys().copied().collect();
+        assert_eq!(order, vec![h1, l1, h2, h3]);
+
+        // 权重 4 的用户在同一时间段内获得更多份额
+        let mut queue = FairQueue::default();
+        let (n1, _) = queue.push("normal", 1);
+        let (v1, _) = queue.push("vip", 4);
+        let (v2, _) = queue.push("vip", 4);
+        let (v3, _) = queue.push("vip", 4);
+        let order: Vec<_> = queue.waiters.keys().copied().collect();
+        assert_eq!(order, vec![v1, v2, v3, n1]);
+    }
+
+    #[tokio::test]
+    async fn test_release_wakes_queue_head() {
+        let limiter = Arc::new(ConcurrencyLimiter::new());
+        let lease = limiter.try_acquire("acc1", 1).unwrap();
+        let ticket = limiter.enqueue("user", 1);
+        assert_eq!(limiter.stats(1).queue_depth, 1);
+
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_millis(50);
+        assert!(!ticket.wait(deadline).await);
+
+        drop(lease);
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(1);
+        assert!(ticket.wait(deadline).await);
+        assert!(limiter.try_acquire("acc1", 1).is_some());
+        ticket.admit();
+
+        let stats = limiter.stats(1);
+        assert_eq!(stats.queue_depth, 0);
+        assert_eq!(stats.admitted_after_wait, 1);
+    }
+}
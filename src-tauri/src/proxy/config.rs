@@ -1,7 +1,7 @@
 // Proxy configuration module
 //
 // Global runtime configuration storage for thinking budget, system prompt,
-// and image thinking mode. Uses OnceLock<RwLock<T>> for thread-safe
+// image thinking mode, safety settings and experimental flags. Uses OnceLock<RwLock<T>> for thread-safe
 // hot-update support without requiring function signature changes in
 // request transform paths.
 //
@@ -9,7 +9,10 @@
 
 use std::sync::{OnceLock, RwLock};
 
-use crate::models::config::{GlobalSystemPromptConfig, ThinkingBudgetConfig, ThinkingBudgetMode};
+use crate::models::config::{
+    ExperimentalConfig, GlobalSystemPromptConfig, SafetyConfig, ThinkingBudgetConfig,
+    ThinkingBudgetMode,
+};
 
 // ============================================================================
 // Utility functions
@@ -75,7 +78,15 @@ pub fn update_thinking_budget_config(config: ThinkingBudgetConfig) {
 /// # Returns
 /// The final budget value to inject into `thinkingConfig.thinkingBudget`.
 pub fn resolve_thinking_budget(user_budget: i64, mapped_model: &str) -> i64 {
-    let config = get_thinking_budget_config();
+    resolve_thinking_budget_with(&get_thinking_budget_config(), user_budget, mapped_model)
+}
+
+/// Same as [`resolve_thinking_budget`] against an explicit configuration.
+pub fn resolve_thinking_budget_with(
+    config: &ThinkingBudgetConfig,
+    user_budget: i64,
+    mapped_model: &str,
+) -> i64 {
     let model_lower = mapped_model.to_lowercase();
 
     match config.mode {
@@ -122,6 +133,19 @@ pub fn resolve_thinking_budget(user_budget: i64, mapped_model: &str) -> i64 {
     }
 }
 
+/// Map a reasoning effort level (OpenAI `reasoning_effort`, Claude `thinking.effort`,
+/// Gemini `thinkingLevel`) to a thinking budget. Unknown levels return `None`.
+pub fn effort_to_thinking_budget(effort: &str) -> Option<i64> {
+    match effort.trim().to_lowercase().as_str() {
+        "minimal" => Some(1024),
+        "low" => Some(4096),
+        "medium" => Some(16384),
+        "high" => Some(24576),
+        "max" | "xhigh" => Some(32768),
+        _ => None,
+    }
+}
+
 // ============================================================================
 // Global System Prompt Config
 // ============================================================================
@@ -187,6 +211,73 @@ pub fn update_image_thinking_mode(mode: Option<String>) {
     }
 }
 
+// ============================================================================
+// Global Safety Config
+// ============================================================================
+static GLOBAL_SAFETY_CONFIG: OnceLock<RwLock<SafetyConfig>> = OnceLock::new();
+
+/// Get the current safety settings policy.
+pub fn get_safety_config() -> SafetyConfig {
+    GLOBAL_SAFETY_CONFIG
+        .get()
+        .and_then(|lock| lock.read().ok())
+        .map(|cfg| cfg.clone())
+        .unwrap_or_default()
+}
+
+/// Update the safety settings policy.
+pub fn update_safety_config(config: SafetyConfig) {
+    if let Some(lock) = GLOBAL_SAFETY_CONFIG.get() {
+        if let Ok(mut cfg) = lock.write() {
+            *cfg = config.clone();
+            tracing::info!(
+                "[Safety] Config updated: default={}, model_rules={}, token_rules={}",
+                config.default_threshold.as_str(),
+                config.model_thresholds.len(),
+                config.token_thresholds.len()
+            );
+        }
+    } else {
+        let _ = GLOBAL_SAFETY_CONFIG.set(RwLock::new(config.clone()));
+        tracing::info!(
+            "[Safety] Config initialized: default={}, model_rules={}, token_rules={}",
+            config.default_threshold.as_str(),
+            config.model_thresholds.len(),
+            config.token_thresholds.len()
+        );
+    }
+}
+
+// ============================================================================
+// Global Experimental Config
+// ============================================================================
+static GLOBAL_EXPERIMENTAL_CONFIG: OnceLock<RwLock<ExperimentalConfig>> = OnceLock::new();
+
+/// Get the current experimental feature flags.
+pub fn get_experimental_config() -> ExperimentalConfig {
+    GLOBAL_EXPERIMENTAL_CONFIG
+        .get()
+        .and_then(|lock| lock.read().ok())
+        .map(|cfg| cfg.clone())
+        .unwrap_or_default()
+}
+
+/// Update the experimental feature flags.
+pub fn update_experimental_config(config: ExperimentalConfig) {
+    let lock =
+        GLOBAL_EXPERIMENTAL_CONFIG.get_or_init(|| RwLock::new(ExperimentalConfig::default()));
+    if let Ok(mut cfg) = lock.write() {
+        *cfg = config.clone();
+    }
+    tracing::info!(
+        "[Experimental] Config updated: signature_cache={}, tool_loop_recovery={}, cross_model_checks={}, usage_scaling={}",
+        config.enable_signature_cache,
+        config.enable_tool_loop_recovery,
+        config.enable_cross_model_checks,
+        config.enable_usage_scaling
+    );
+}
+
 // ============================================================================
 // Tests
 // ============================================================================
@@ -318,6 +409,7 @@ mod tests {
         update_global_system_prompt_config(GlobalSystemPromptConfig {
             enabled: true,
             content: "You are a helpful assistant.".to_string(),
+            ..Default::default()
         });
         let cfg = get_global_system_prompt();
         assert!(cfg.enabled);
@@ -370,6 +462,7 @@ mod tests {
         let config = GlobalSystemPromptConfig {
             enabled: true,
             content: "Test prompt".to_string(),
+            ..Default::default()
         };
         let json = serde_json::to_string(&config).unwrap();
         let deserialized: GlobalSystemPromptConfig = serde_json::from_str(&json).unwrap();
@@ -4,23 +4,30 @@
 // - 1.1-1.16: Account management (list, add, delete, export, sort, switch, refresh quota)
 // - 5.1-5.8: Quota monitoring and warmup
 // - 6.1-6.18: Security management (IP logs, blacklist, whitelist, user tokens)
+// - Admin users: named admin accounts with roles, login sessions
 // - 8.1-8.6: CLI sync (status, sync, restore, config view)
 // - 13.1-13.5: Request monitoring and statistics
 // - 14.1-14.8: Hot update and runtime configuration
 
+use std::net::SocketAddr;
+
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
+    extract::{ConnectInfo, Path, Query, State},
+    http::{HeaderMap, StatusCode},
     response::{IntoResponse, Json},
+    Extension,
 };
 use serde::{Deserialize, Serialize};
 use tracing::{error, warn};
 
-use crate::modules::{account, config as app_config, device, oauth, quota, security_db, token_stats, user_token_db, proxy_db};
+use crate::modules::{account, admin_user_db, config as app_config, device, oauth, quota, security_db, token_stats, user_token_db, proxy_db};
+use crate::modules::admin_user_db::{AdminRole, LoginThrottle};
 use crate::proxy::cli_sync::{self, CliApp};
 use crate::proxy::opencode_sync;
 use crate::proxy::droid_sync;
+use crate::proxy::middleware::auth::AdminIdentity;
 use crate::proxy::monitor;
+use crate::proxy::tls;
 
 use super::AppState;
 
@@ -49,37 +56,37 @@ fn err_400(msg: String) -> (StatusCode, Json<ErrorResponse>) {
 
 /// List all accounts
 pub async fn admin_list_accounts(
-    State(_state): State<AppState>,
+    State(state): State<AppState>,
 ) -> AdminResult<impl IntoResponse> {
     let accounts = account::list_accounts().map_err(err_500)?;
     let current_id = account::get_current_account_id().unwrap_or(None);
 
-    let responses: Vec<serde_json::Value> = accounts
-        .into_iter()
-        .map(|acc| {
-            let is_current = current_id.as_ref().map(|id| id == &acc.id).unwrap_or(false);
-            serde_json::json!({
-                "id": acc.id,
-                "email": acc.email,
-                "name": acc.name,
-                "is_current": is_current,
-                "disabled": acc.disabled,
-                "disabled_reason": acc.disabled_reason,
-                "disabled_at": acc.disabled_at,
-                "proxy_disabled": acc.proxy_disabled,
-                "proxy_disabled_reason": acc.proxy_disabled_reason,
-                "proxy_disabled_at": acc.proxy_disabled_at,
-                "protected_models": acc.protected_models.iter().collect::<Vec<_>>(),
-                "validation_blocked": acc.validation_blocked,
-                "validation_blocked_until": acc.validation_blocked_until,
-                "validation_blocked_reason": acc.validation_blocked_reason,
-                "quota": acc.quota,
-                "device_bound": acc.device_profile.is_some(),
-                "last_used": acc.last_used,
-                "custom_label": acc.custom_label,
-            })
-        })
-        .collect();
+    let mut responses: Vec<serde_json::Value> = Vec::with_capacity(accounts.len());
+    for acc in accounts {
+        let is_current = current_id.as_ref().map(|id| id == &acc.id).unwrap_or(false);
+        let circuit_breakers = state.token_manager.circuit_breaker_states(&acc.id).await;
+        responses.push(serde_json::json!({
+            "id": acc.id,
+            "email": acc.email,
+            "name": acc.name,
+            "is_current": is_current,
+            "disabled": acc.disabled,
+            "disabled_reason": acc.disabled_reason,
+            "disabled_at": acc.disabled_at,
+            "proxy_disabled": acc.proxy_disabled,
+            "proxy_disabled_reason": acc.proxy_disabled_reason,
+            "proxy_disabled_at": acc.proxy_disabled_at,
+            "protected_models": acc.protected_models.iter().collect::<Vec<_>>(),
+            "validation_blocked": acc.validation_blocked,
+            "validation_blocked_until": acc.validation_blocked_until,
+            "validation_blocked_reason": acc.validation_blocked_reason,
+            "quota": acc.quota,
+            "device_bound": acc.device_profile.is_some(),
+            "last_used": acc.last_used,
+            "custom_label": acc.custom_label,
+            "circuit_breakers": circuit_breakers,
+        }));
+    }
 
     Ok(Json(serde_json::json!({
         "current_account_id": current_id,
@@ -444,9 +451,11 @@ pub struct SaveConfigWrapper {
 
 /// Save application config
 pub async fn admin_save_config(
+    State(state): State<AppState>,
     Json(payload): Json<SaveConfigWrapper>,
 ) -> AdminResult<impl IntoResponse> {
     app_config::save_app_config(&payload.config).map_err(err_500)?;
+    state.apply_runtime_config(&payload.config).await;
     Ok(Json(serde_json::json!({ "success": true })))
 }
 
@@ -538,6 +547,27 @@ pub async fn admin_clear_rate_limit(
     Json(serde_json::json!({ "success": cleared }))
 }
 
+#[derive(Deserialize)]
+pub struct ExplainSchedulingQuery {
+    pub model: String,
+    pub session_id: Option<String>,
+}
+
+/// Dry-run account selection for a model (does not touch session bindings)
+pub async fn admin_explain_scheduling(
+    State(state): State<AppState>,
+    Query(params): Query<ExplainSchedulingQuery>,
+) -> AdminResult<impl IntoResponse> {
+    if params.model.trim().is_empty() {
+        return Err(err_400("model is required".to_string()));
+    }
+    let explanation = state
+        .token_manager
+        .explain_selection(params.model.trim(), params.session_id.as_deref())
+        .await;
+    Ok(Json(explanation))
+}
+
 /// Get preferred account
 pub async fn admin_get_preferred_account(
     State(state): State<AppState>,
@@ -564,8 +594,10 @@ pub async fn admin_set_preferred_account(
 pub async fn admin_set_proxy_monitor_enabled(
     Json(payload): Json<serde_json::Value>,
 ) -> impl IntoResponse {
-    let _enabled = payload["enabled"].as_bool().unwrap_or(true);
-    // Monitor enable/disable is handled via ProxyMonitor instance
+    let enabled = payload["enabled"].as_bool().unwrap_or(true);
+    if let Some(monitor) = monitor::get_global_monitor() {
+        monitor.set_enabled(enabled);
+    }
     Json(serde_json::json!({ "success": true }))
 }
 
@@ -583,8 +615,7 @@ pub async fn admin_get_cli_sync_status(
     Json(payload): Json<CliSyncStatusRequest>,
 ) -> AdminResult<impl IntoResponse> {
     let config = app_config::load_app_config().map_err(err_500)?;
-    let port = config.proxy.port;
-    let proxy_url = format!("http://127.0.0.1:{}", port);
+    let proxy_url = tls::loopback_url(&config.proxy.tls, config.proxy.port);
 
     let app = parse_cli_app(&payload.app)?;
     let status = cli_sync::get_cli_status(&app, &proxy_url);
@@ -603,8 +634,7 @@ pub async fn admin_execute_cli_sync(
     Json(payload): Json<CliSyncRequest>,
 ) -> AdminResult<impl IntoResponse> {
     let config = app_config::load_app_config().map_err(err_500)?;
-    let port = config.proxy.port;
-    let proxy_url = format!("http://127.0.0.1:{}", port);
+    let proxy_url = tls::loopback_url(&config.proxy.tls, config.proxy.port);
     let api_key = payload.api_key.unwrap_or(config.proxy.api_key);
 
     let app = parse_cli_app(&payload.app)?;
@@ -666,7 +696,9 @@ pub async fn admin_get_opencode_sync_status(
     Json(payload): Json<OpencodeSyncStatusRequest>,
 ) -> AdminResult<impl IntoResponse> {
     let config = app_config::load_app_config().map_err(err_500)?;
-    let proxy_url = payload.proxy_url.unwrap_or_else(|| format!("http://127.0.0.1:{}", config.proxy.port));
+    let proxy_url = payload
+        .proxy_url
+        .unwrap_or_else(|| tls::loopback_url(&config.proxy.tls, config.proxy.port));
     let status = opencode_sync::get_opencode_status(&proxy_url);
     Ok(Json(status))
 }
@@ -682,7 +714,7 @@ pub async fn admin_execute_opencode_sync(
     Json(payload): Json<OpencodeSyncRequest>,
 ) -> AdminResult<impl IntoResponse> {
     let config = app_config::load_app_config().map_err(err_500)?;
-    let proxy_url = format!("http://127.0.0.1:{}", config.proxy.port);
+    let proxy_url = tls::loopback_url(&config.proxy.tls, config.proxy.port);
     let api_key = payload.api_key.unwrap_or(config.proxy.api_key);
 
     let model_refs: Option<Vec<String>> = payload.model_ids;
@@ -720,7 +752,9 @@ pub async fn admin_execute_opencode_clear(
     Json(payload): Json<OpencodeClearRequest>,
 ) -> AdminResult<impl IntoResponse> {
     let config = app_config::load_app_config().map_err(err_500)?;
-    let proxy_url = payload.proxy_url.unwrap_or_else(|| format!("http://127.0.0.1:{}", config.proxy.port));
+    let proxy_url = payload
+        .proxy_url
+        .unwrap_or_else(|| tls::loopback_url(&config.proxy.tls, config.proxy.port));
     let api_key = &config.proxy.api_key;
 
     // Clear by syncing with empty config
@@ -742,7 +776,9 @@ pub async fn admin_get_droid_sync_status(
     Json(payload): Json<DroidSyncStatusRequest>,
 ) -> AdminResult<impl IntoResponse> {
     let config = app_config::load_app_config().map_err(err_500)?;
-    let proxy_url = payload.proxy_url.unwrap_or_else(|| format!("http://127.0.0.1:{}", config.proxy.port));
+    let proxy_url = payload
+        .proxy_url
+        .unwrap_or_else(|| tls::loopback_url(&config.proxy.tls, config.proxy.port));
     let status = droid_sync::get_droid_status(&proxy_url);
     Ok(Json(status))
 }
@@ -989,6 +1025,8 @@ pub async fn admin_get_user_token_summary() -> AdminResult<impl IntoResponse> {
         "expired": expired,
         "total_requests": tokens.iter().map(|t| t.total_requests).sum::<i64>(),
         "total_tokens_used": tokens.iter().map(|t| t.total_tokens_used).sum::<i64>(),
+        // JWT 调用方的每日配额与当日用量
+        "jwt_quotas": crate::proxy::jwt_auth::quota_usage_stats(),
     })))
 }
 
@@ -1083,6 +1121,172 @@ pub async fn admin_update_user_token(
     Ok(Json(serde_json::json!({ "success": true })))
 }
 
+// ============================================================================
+// Admin Users & Sessions
+// ============================================================================
+
+#[derive(Deserialize)]
+pub struct AdminLoginRequest {
+    pub username: String,
+    pub password: String,
+}
+
+/// Log in with an admin account and issue an expiring session token
+///
+/// Failed attempts are throttled per username and per client IP.
+pub async fn admin_login(
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    Json(payload): Json<AdminLoginRequest>,
+) -> AdminResult<impl IntoResponse> {
+    let client_ip = connect_info.map(|ConnectInfo(addr)| addr.ip().to_string());
+    let throttle = LoginThrottle::global();
+    let keys = LoginThrottle::keys(&payload.username, client_ip.as_deref());
+    if let Some(wait) = throttle.retry_after(&keys) {
+        warn!(
+            "Admin login throttled for user '{}' from {}",
+            payload.username,
+            client_ip.as_deref().unwrap_or("unknown")
+        );
+        return Err((
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(ErrorResponse {
+                error: format!(
+                    "Too many failed login attempts, retry in {}s",
+                    wait.as_secs().max(1)
+                ),
+            }),
+        ));
+    }
+
+    // Argon2 校验与 SQLite 读写都是阻塞操作
+    let username = payload.username.clone();
+    let session = tokio::task::spawn_blocking(move || {
+        let ttl_hours = app_config::load_app_config()
+            .map(|c| c.proxy.admin_session_ttl_hours)
+            .unwrap_or(12);
+        let ttl_secs = (ttl_hours as i64).saturating_mul(3600);
+        admin_user_db::login(&payload.username, &payload.password, ttl_secs)
+    })
+    .await
+    .map_err(|e| err_500(format!("Login task failed: {}", e)))?
+    .map_err(err_500)?;
+
+    match session {
+        Some(session) => {
+            throttle.record_success(&keys);
+            Ok(Json(session))
+        }
+        None => {
+            throttle.record_failure(&keys);
+            warn!(
+                "Admin login failed for user '{}' from {}",
+                username,
+                client_ip.as_deref().unwrap_or("unknown")
+            );
+            Err((
+                StatusCode::UNAUTHORIZED,
+                Json(ErrorResponse {
+                    error: "Invalid username or password".to_string(),
+                }),
+            ))
+        }
+    }
+}
+
+/// Revoke the session token used for this request
+pub async fn admin_logout(headers: HeaderMap) -> AdminResult<impl IntoResponse> {
+    let token = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .map(|s| s.strip_prefix("Bearer ").unwrap_or(s))
+        .or_else(|| headers.get("x-api-key").and_then(|h| h.to_str().ok()));
+    if let Some(token) = token.map(str::to_string) {
+        tokio::task::spawn_blocking(move || admin_user_db::revoke_session(&token))
+            .await
+            .map_err(|e| err_500(format!("Admin user task failed: {}", e)))?
+            .map_err(err_500)?;
+    }
+    Ok(Json(serde_json::json!({ "success": true })))
+}
+
+/// Get the identity of the current admin
+pub async fn admin_get_current_admin(
+    Extension(identity): Extension<AdminIdentity>,
+) -> impl IntoResponse {
+    Json(serde_json::json!({
+        "user_id": identity.user_id,
+        "username": identity.username,
+        "role": identity.role,
+    }))
+}
+
+/// List admin users
+pub async fn admin_list_admin_users() -> AdminResult<impl IntoResponse> {
+    let users = tokio::task::spawn_blocking(admin_user_db::list_users)
+        .await
+        .map_err(|e| err_500(format!("Admin user task failed: {}", e)))?
+        .map_err(err_500)?;
+    Ok(Json(users))
+}
+
+#[derive(Deserialize)]
+pub struct CreateAdminUserRequest {
+    pub username: String,
+    pub password: String,
+    pub role: AdminRole,
+}
+
+/// Create admin user
+pub async fn admin_create_admin_user(
+    Json(payload): Json<CreateAdminUserRequest>,
+) -> AdminResult<impl IntoResponse> {
+    // 密码哈希 (Argon2) 与 SQLite 写入在阻塞线程池执行
+    let user = tokio::task::spawn_blocking(move || {
+        admin_user_db::create_user(&payload.username, &payload.password, payload.role)
+    })
+    .await
+    .map_err(|e| err_500(format!("Admin user task failed: {}", e)))?
+    .map_err(err_400)?;
+    Ok(Json(user))
+}
+
+#[derive(Deserialize)]
+pub struct UpdateAdminUserRequest {
+    pub role: Option<AdminRole>,
+    pub enabled: Option<bool>,
+    pub password: Option<String>,
+}
+
+/// Update admin user role, status or password
+pub async fn admin_update_admin_user(
+    Path(id): Path<String>,
+    Json(payload): Json<UpdateAdminUserRequest>,
+) -> AdminResult<impl IntoResponse> {
+    tokio::task::spawn_blocking(move || {
+        admin_user_db::update_user(
+            &id,
+            payload.role,
+            payload.enabled,
+            payload.password.as_deref(),
+        )
+    })
+    .await
+    .map_err(|e| err_500(format!("Admin user task failed: {}", e)))?
+    .map_err(err_400)?;
+    Ok(Json(serde_json::json!({ "success": true })))
+}
+
+/// Delete admin user
+pub async fn admin_delete_admin_user(
+    Path(id): Path<String>,
+) -> AdminResult<impl IntoResponse> {
+    tokio::task::spawn_blocking(move || admin_user_db::delete_user(&id))
+        .await
+        .map_err(|e| err_500(format!("Admin user task failed: {}", e)))?
+        .map_err(err_400)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
 // ============================================================================
 // System Management
 // ============================================================================
@@ -1307,6 +1511,53 @@ pub async fn admin_clear_token_stats() -> impl IntoResponse {
     Json(serde_json::json!({ "success": true }))
 }
 
+/// Get redaction match counters
+pub async fn admin_get_redaction_stats() -> impl IntoResponse {
+    Json(crate::proxy::redaction::get_redaction_stats())
+}
+
+/// Reset redaction match counters
+pub async fn admin_clear_redaction_stats() -> impl IntoResponse {
+    crate::proxy::redaction::reset_redaction_stats();
+    Json(serde_json::json!({ "success": true }))
+}
+
+/// Get cross-model fallback counters
+pub async fn admin_get_fallback_stats() -> impl IntoResponse {
+    Json(crate::proxy::model_fallback::get_fallback_stats())
+}
+
+/// Reset cross-model fallback counters
+pub async fn admin_clear_fallback_stats() -> impl IntoResponse {
+    crate::proxy::model_fallback::reset_fallback_stats();
+    Json(serde_json::json!({ "success": true }))
+}
+
+/// Get per-account in-flight leases, admission queue depth and wait times
+pub async fn admin_get_concurrency_stats(
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    Json(state.token_manager.concurrency_stats().await)
+}
+
+/// Get session binding counts and ages
+pub async fn admin_get_session_binding_stats(
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    Json(state.token_manager.session_binding_stats().await)
+}
+
+/// Get hedged request counters
+pub async fn admin_get_hedge_stats() -> impl IntoResponse {
+    Json(crate::proxy::upstream::hedging::get_hedge_stats())
+}
+
+/// Reset hedged request counters (also resets the hedge budget)
+pub async fn admin_clear_hedge_stats() -> impl IntoResponse {
+    crate::proxy::upstream::hedging::reset_hedge_stats();
+    Json(serde_json::json!({ "success": true }))
+}
+
 // ============================================================================
 // OAuth
 // ============================================================================
@@ -1314,8 +1565,10 @@ pub async fn admin_clear_token_stats() -> impl IntoResponse {
 /// Prepare OAuth URL
 pub async fn admin_prepare_oauth_url() -> AdminResult<impl IntoResponse> {
     let config = app_config::load_app_config().map_err(err_500)?;
-    let port = config.proxy.port;
-    let redirect_uri = format!("http://127.0.0.1:{}/auth/callback", port);
+    let redirect_uri = format!(
+        "{}/auth/callback",
+        tls::loopback_url(&config.proxy.tls, config.proxy.port)
+    );
     let state = uuid::Uuid::new_v4().to_string();
     let url = oauth::get_auth_url(&redirect_uri, &state);
 
@@ -1356,8 +1609,10 @@ pub async fn admin_submit_oauth_code(
     Json(payload): Json<SubmitCodeRequest>,
 ) -> AdminResult<impl IntoResponse> {
     let config = app_config::load_app_config().map_err(err_500)?;
-    let port = config.proxy.port;
-    let redirect_uri = format!("http://127.0.0.1:{}/auth/callback", port);
+    let redirect_uri = format!(
+        "{}/auth/callback",
+        tls::loopback_url(&config.proxy.tls, config.proxy.port)
+    );
 
     let token_response = oauth::exchange_code(&payload.code, &redirect_uri)
         .await
@@ -1388,7 +1643,6 @@ pub async fn admin_prepare_oauth_url_web(
     Query(params): Query<std::collections::HashMap<String, String>>,
 ) -> AdminResult<impl IntoResponse> {
     let config = app_config::load_app_config().map_err(err_500)?;
-    let port = config.proxy.port;
 
     // Use host/proto from query params if available (for reverse proxy scenarios)
     let host = params.get("host");
@@ -1397,7 +1651,10 @@ pub async fn admin_prepare_oauth_url_web(
     let redirect_uri = if let (Some(h), Some(p)) = (host, proto) {
         format!("{}://{}/auth/callback", p, h)
     } else {
-        format!("http://127.0.0.1:{}/auth/callback", port)
+        format!(
+            "{}/auth/callback",
+            tls::loopback_url(&config.proxy.tls, config.proxy.port)
+        )
     };
 
     let state_val = uuid::Uuid::new_v4().to_string();
@@ -1538,6 +1795,38 @@ pub async fn admin_get_account_proxy_binding(
     }
 }
 
+/// Get per-proxy traffic statistics (requests served / connection failures)
+pub async fn admin_get_proxy_pool_stats() -> AdminResult<impl IntoResponse> {
+    if let Some(pool) = crate::proxy::proxy_pool::get_global_proxy_pool() {
+        Ok(Json(serde_json::json!({ "stats": pool.get_usage_stats_snapshot() })))
+    } else {
+        Ok(Json(serde_json::json!({ "stats": {} })))
+    }
+}
+
+/// Thinking signature cache hit / miss counters
+pub async fn admin_get_signature_cache_stats() -> AdminResult<impl IntoResponse> {
+    let stats = crate::proxy::signature_cache::SignatureCache::global().stats();
+    Ok(Json(serde_json::json!({
+        "enabled": crate::proxy::get_experimental_config().enable_signature_cache,
+        "stats": stats,
+    })))
+}
+
+/// Bulk import proxies (URI / host:port:user:pass lists, Clash proxies)
+pub async fn admin_import_proxies(
+    Json(payload): Json<crate::proxy::proxy_import::ProxyImportRequest>,
+) -> AdminResult<impl IntoResponse> {
+    if let Some(pool) = crate::proxy::proxy_pool::get_global_proxy_pool() {
+        let report = crate::proxy::proxy_import::import_proxies(&pool, payload)
+            .await
+            .map_err(err_500)?;
+        Ok(Json(report))
+    } else {
+        Err(err_500("Proxy pool not initialized".to_string()))
+    }
+}
+
 /// Trigger proxy health check
 pub async fn admin_trigger_proxy_health_check() -> AdminResult<impl IntoResponse> {
     if let Some(pool) = crate::proxy::proxy_pool::get_global_proxy_pool() {
@@ -1566,13 +1855,19 @@ pub struct OAuthCallbackParams {
 /// Build the OAuth redirect URI from the incoming request headers.
 ///
 /// Supports reverse-proxy scenarios via X-Forwarded-Proto / Host headers.
-pub fn build_oauth_redirect_uri(port: u16, host: Option<&str>, proto: Option<&str>) -> String {
+/// `scheme` is the listener's own scheme (`https` when TLS is enabled).
+pub fn build_oauth_redirect_uri(
+    scheme: &str,
+    port: u16,
+    host: Option<&str>,
+    proto: Option<&str>,
+) -> String {
     if let (Some(h), Some(p)) = (host, proto) {
         format!("{}://{}/auth/callback", p, h)
     } else if let Some(h) = host {
-        format!("http://{}/auth/callback", h)
+        format!("{}://{}/auth/callback", scheme, h)
     } else {
-        format!("http://127.0.0.1:{}/auth/callback", port)
+        format!("{}://127.0.0.1:{}/auth/callback", scheme, port)
     }
 }
 
@@ -1603,7 +1898,7 @@ pub async fn handle_oauth_callback(
     let proto = headers
         .get("x-forwarded-proto")
         .and_then(|h| h.to_str().ok());
-    let redirect_uri = build_oauth_redirect_uri(port, host, proto);
+    let redirect_uri = build_oauth_redirect_uri(tls::scheme(&config.proxy.tls), port, host, proto);
 
     // Exchange the authorization code for tokens
     let token_response = match oauth::exchange_code(&params.code, &redirect_uri).await {
@@ -1876,6 +2171,26 @@ mod tests {
         assert_eq!(req.description, Some("office".to_string()));
     }
 
+    #[test]
+    fn test_create_admin_user_request_deserialize() {
+        let json = r#"{"username": "ops", "password": "password123", "role": "operator"}"#;
+        let req: CreateAdminUserRequest = serde_json::from_str(json).unwrap();
+        assert_eq!(req.username, "ops");
+        assert_eq!(req.role, AdminRole::Operator);
+        assert!(serde_json::from_str::<CreateAdminUserRequest>(
+            r#"{"username": "x", "password": "password123", "role": "root"}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_update_admin_user_request_partial() {
+        let req: UpdateAdminUserRequest = serde_json::from_str(r#"{"enabled": false}"#).unwrap();
+        assert_eq!(req.enabled, Some(false));
+        assert!(req.role.is_none());
+        assert!(req.password.is_none());
+    }
+
     #[test]
     fn test_renew_token_request_deserialize() {
         let json = r#"{"expires_type": "month"}"#;
@@ -1938,26 +2253,34 @@ mod tests {
 
     #[test]
     fn test_build_oauth_redirect_uri_with_host_and_proto() {
-        let uri = build_oauth_redirect_uri(3000, Some("example.com"), Some("https"));
+        let uri = build_oauth_redirect_uri("http", 3000, Some("example.com"), Some("https"));
         assert_eq!(uri, "https://example.com/auth/callback");
     }
 
     #[test]
     fn test_build_oauth_redirect_uri_with_host_only() {
-        let uri = build_oauth_redirect_uri(3000, Some("myhost:8080"), None);
+        let uri = build_oauth_redirect_uri("http", 3000, Some("myhost:8080"), None);
         assert_eq!(uri, "http://myhost:8080/auth/callback");
     }
 
+    #[test]
+    fn test_build_oauth_redirect_uri_uses_listener_scheme() {
+        let uri = build_oauth_redirect_uri("https", 3000, Some("myhost:8080"), None);
+        assert_eq!(uri, "https://myhost:8080/auth/callback");
+        let uri = build_oauth_redirect_uri("https", 4567, None, None);
+        assert_eq!(uri, "https://127.0.0.1:4567/auth/callback");
+    }
+
     #[test]
     fn test_build_oauth_redirect_uri_fallback_to_localhost() {
-        let uri = build_oauth_redirect_uri(4567, None, None);
+        let uri = build_oauth_redirect_uri("http", 4567, None, None);
         assert_eq!(uri, "http://127.0.0.1:4567/auth/callback");
     }
 
     #[test]
     fn test_build_oauth_redirect_uri_proto_without_host_falls_back() {
         // proto alone without host should still fall back to localhost
-        let uri = build_oauth_redirect_uri(9000, None, Some("https"));
+        let uri = build_oauth_redirect_uri("http", 9000, None, Some("https"));
         assert_eq!(uri, "http://127.0.0.1:9000/auth/callback");
     }
 
@@ -14,6 +14,7 @@ use serde_json::{json, Value};
 use tracing::info;
 
 use crate::proxy::audio::AudioProcessor;
+use crate::proxy::token_manager::UpstreamOutcome;
 
 use super::AppState;
 
@@ -95,9 +96,9 @@ pub async fn handle_audio_transcription(
     // Get token
     let token = state
         .token_manager
-        .get_token("text", None)
+        .get_token(&model, None)
         .await
-        .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, e))?;
+        .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, e.to_string()))?;
 
     let project_id = token.project_id.clone().unwrap_or_default();
     info!("Using account: {}", token.email);
@@ -122,18 +123,49 @@ pub async fn handle_audio_transcription(
             None,
             Some(token.account_id.as_str()),
         )
-        .await
-        .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Upstream error: {}", e)))?
-        .response;
+        .await;
+    let response = match response {
+        Ok(call_result) => call_result.response,
+        Err(e) => {
+            state
+                .token_manager
+                .report_outcome(&token.account_id, Some(model.as_str()), UpstreamOutcome::Transport)
+                .await;
+            return Err((StatusCode::BAD_GATEWAY, format!("Upstream error: {}", e)));
+        }
+    };
 
     if !response.status().is_success() {
+        let status_code = response.status().as_u16();
+        let retry_after = response
+            .headers()
+            .get("Retry-After")
+            .and_then(|h| h.to_str().ok())
+            .map(str::to_string);
         let error_text = response.text().await.unwrap_or_default();
+        state
+            .token_manager
+            .report_outcome(
+                &token.account_id,
+                Some(model.as_str()),
+                UpstreamOutcome::HttpError {
+                    status: status_code,
+                    retry_after: retry_after.as_deref(),
+                    body: &error_text,
+                },
+            )
+            .await;
         return Err((
             StatusCode::BAD_GATEWAY,
             format!("Gemini API error: {}", error_text),
         ));
     }
 
+    state
+        .token_manager
+        .report_outcome(&token.account_id, Some(model.as_str()), UpstreamOutcome::Success)
+        .await;
+
     let result: Value = response
         .json()
         .await
@@ -4,33 +4,66 @@
 // - 2.2: POST /v1/messages → Gemini
 // - 2.15: POST /v1/messages/count_tokens
 
+use std::time::Instant;
+
 use axum::{
     body::Body,
-    extract::{Json, State},
-    http::StatusCode,
+    extract::{Extension, Json, State},
+    http::{HeaderMap, StatusCode},
     response::{IntoResponse, Response},
 };
 use futures::StreamExt;
 use serde_json::{json, Value};
 use tracing::{debug, info};
 
-use super::common::{apply_retry_strategy, determine_retry_strategy, should_rotate_account};
+use super::common::{
+    apply_retry_strategy, determine_retry_strategy, should_rotate_account, with_context_header,
+    with_fallback_header,
+};
 use super::AppState;
+use crate::proxy::common::context_manager::{ContextCompressionReport, ContextManager};
+use crate::proxy::common::cross_model::{apply_cross_model_checks, record_session_family};
+use crate::proxy::common::request_policy::{apply_request_policy, PolicyInput, RequestPolicy};
+use crate::proxy::common::safety::apply_to_upstream_body;
+use crate::proxy::common::tool_loop::apply_tool_loop_recovery;
+use crate::proxy::common::tool_result_compressor::tool_result_limit_for_request;
+use crate::proxy::get_experimental_config;
 use crate::proxy::mappers::claude::{
     clean_cache_control_from_messages, create_claude_sse_stream, estimate_token_count,
-    merge_consecutive_messages, transform_claude_request, transform_response,
-    ClaudeRequest, CountTokensRequest,
+    merge_consecutive_messages, transform_claude_request_with_options, transform_response,
+    ClaudeRequest, CountTokensRequest, TransformOptions,
     models::GeminiResponse,
 };
-use crate::proxy::session_manager::SessionManager;
+use crate::proxy::concurrency::hold_lease;
+use crate::proxy::middleware::auth::UserTokenIdentity;
+use crate::proxy::model_fallback::{get_token_with_fallback, FallbackToken};
+use crate::proxy::session_manager::{header_session_id, SessionManager};
+use crate::proxy::signature_cache::SignatureContext;
+use crate::proxy::token_manager::UpstreamOutcome;
+use crate::proxy::upstream::deadline::{
+    deadlines_for_request, peek_within_deadline, with_idle_timeout, with_total_deadline,
+    DeadlineClock,
+};
+use crate::proxy::upstream::hedging::{peek_with_hedge, HedgeContext};
+use crate::proxy::upstream::stream_peek::PeekOutcome;
+use crate::proxy::upstream::stream_recovery::{
+    stream_recovery_enabled, with_stream_recovery, RecoveryContext,
+};
+use crate::proxy::upstream::usage_scaling::{
+    scale_usage_metadata, scale_usage_stream, usage_scale_factor,
+};
 
 const MAX_RETRY_ATTEMPTS: usize = 3;
 
 /// Handle Claude Messages: POST /v1/messages [Req 2.2]
 pub async fn handle_messages(
     State(state): State<AppState>,
+    identity: Option<Extension<UserTokenIdentity>>,
+    headers: HeaderMap,
     Json(body): Json<Value>,
 ) -> Response {
+    let token_id = identity.map(|Extension(identity)| identity.token_id);
+    let header_sid = header_session_id(&headers);
     let trace_id = format!(
         "claude_{}",
         chrono::Utc::now().timestamp_subsec_millis()
@@ -74,7 +107,11 @@ pub async fn handle_messages(
     let mut last_error = String::new();
     let mut _last_email: Option<String> = None;
     let mut last_mapped_model: Option<String> = None;
+    let mut context_report: Option<ContextCompressionReport> = None;
+    let mut last_deadline: Option<DeadlineClock> = None;
 
+    // 总时长从客户端请求开始计时，换号重试不重新计时
+    let request_started = Instant::now();
     for attempt in 0..max_attempts {
         // Model route resolution
         let mapped_model = crate::proxy::common::model_mapping::map_model(
@@ -85,14 +122,24 @@ pub async fn handle_messages(
         last_mapped_model = Some(mapped_model.clone());
 
         // Extract session ID for sticky scheduling
-        let session_id_str = SessionManager::extract_session_id(
+        let session_id_str = SessionManager::resolve_claude_session_id(
             &serde_json::to_value(&request).unwrap(),
+            header_sid.as_deref(),
+            token_id.as_deref(),
         );
 
         // Get token
-        let token = match token_manager
-            .get_token(&mapped_model, Some(&session_id_str))
-            .await
+        let FallbackToken {
+            token,
+            model: mapped_model,
+            fallback_from,
+        } = match get_token_with_fallback(
+            &token_manager,
+            &mapped_model,
+            Some(&session_id_str),
+            token_id.as_deref(),
+        )
+        .await
         {
             Ok(t) => t,
             Err(e) => {
@@ -112,13 +159,47 @@ pub async fn handle_messages(
             }
         };
 
+        last_mapped_model = Some(mapped_model.clone());
         _last_email = Some(token.email.clone());
         let project_id = token.project_id.clone().unwrap_or_default();
         info!("✓ Using account: {}", token.email);
 
+        // 超出上下文窗口时逐级压缩历史 (软/激进思考块清理 → 裁剪旧工具轮次)
+        if let Some(report) = ContextManager::fit_to_context_window(&mut request, &mapped_model) {
+            info!("[{}] Context compressed: {}", trace_id, report.summary());
+            if report.tool_messages_removed > 0 {
+                merge_consecutive_messages(&mut request.messages);
+            }
+            context_report = Some(report);
+        }
+
+        // 跨模型族切换时清理历史中的思考块、签名与服务端工具块
+        let experimental = get_experimental_config();
+        if experimental.enable_cross_model_checks {
+            if let Some(report) =
+                apply_cross_model_checks(&session_id_str, &mut request.messages, &mapped_model)
+            {
+                info!(
+                    "[{}] Cross-model history cleaned: {}",
+                    trace_id,
+                    report.summary()
+                );
+            }
+        }
+
         // Transform request
-        let (gemini_body, _session_id, _message_count) =
-            match transform_claude_request(&request, &project_id, &mapped_model) {
+        let signature_cache_enabled = experimental.enable_signature_cache;
+        let transform_options = TransformOptions {
+            tool_result_limit: tool_result_limit_for_request(&mapped_model, &headers),
+            signature_session_id: signature_cache_enabled.then(|| session_id_str.clone()),
+        };
+        let (mut gemini_body, _session_id, _message_count) =
+            match transform_claude_request_with_options(
+                &request,
+                &project_id,
+                &mapped_model,
+                &transform_options,
+            ) {
                 Ok(result) => result,
                 Err(e) => {
                     return (
@@ -134,6 +215,25 @@ pub async fn handle_messages(
                         .into_response();
                 }
             };
+        apply_to_upstream_body(&mut gemini_body, &mapped_model, token_id.as_deref(), false);
+        apply_request_policy(
+            &mut gemini_body,
+            &mapped_model,
+            &PolicyInput::from_claude(&request),
+            &RequestPolicy::current(),
+        );
+        if experimental.enable_tool_loop_recovery {
+            if let Some(report) = apply_tool_loop_recovery(&mut gemini_body) {
+                tracing::warn!(
+                    "[{}] Tool loop detected: {:?} repeated {} times (broken: {})",
+                    trace_id,
+                    report.tool_names,
+                    report.repeats,
+                    report.broken
+                );
+            }
+        }
+        let usage_scale = usage_scale_factor(&request.model, &mapped_model);
 
         // Determine streaming
         let client_wants_stream = request.stream;
@@ -146,21 +246,44 @@ pub async fn handle_messages(
             "generateContent"
         };
         let query_string = if actual_stream { Some("alt=sse") } else { None };
+        let deadline =
+            DeadlineClock::new(request_started, deadlines_for_request(&mapped_model, &headers));
+        last_deadline = Some(deadline);
+        if deadline.expired() {
+            break;
+        }
+        let recovery_body = (actual_stream && stream_recovery_enabled()).then(|| gemini_body.clone());
+        let hedge = HedgeContext::for_model(
+            token_manager.clone(),
+            upstream.clone(),
+            &gemini_body,
+            &mapped_model,
+            &token.account_id,
+            &trace_id,
+            &deadline,
+        );
 
         // Send upstream request
         let call_result = match upstream
-            .call_v1_internal(
+            .call_v1_internal_with_deadline(
                 method,
                 &token.access_token,
                 gemini_body,
                 query_string,
                 Some(token.account_id.as_str()),
+                deadline,
             )
             .await
         {
             Ok(r) => r,
             Err(e) => {
                 last_error = e.clone();
+                // 客户端缩短的时限到期不计入账号状态
+                if !deadline.expired_by_client() {
+                    token_manager
+                        .report_outcome(&token.account_id, Some(mapped_model.as_str()), UpstreamOutcome::Transport)
+                        .await;
+                }
                 debug!(
                     "Claude Request failed on attempt {}/{}: {}",
                     attempt + 1,
@@ -175,19 +298,95 @@ pub async fn handle_messages(
         let status = response.status();
 
         if status.is_success() {
-            token_manager.mark_success(&token.account_id);
-
             if actual_stream {
+                let (peek_outcome, hedge_winner) = peek_within_deadline(
+                    &deadline,
+                    peek_with_hedge(Box::pin(response.bytes_stream()), hedge),
+                )
+                .await;
+                // 对冲请求胜出时后续以对冲账号为准 (成功已在对冲路径上报，被取消的主请求不计)
+                let hedge_won = hedge_winner.is_some();
+                let token = hedge_winner.unwrap_or(token);
+                let upstream_stream = match peek_outcome {
+                    PeekOutcome::Ready(stream) => {
+                        // 上游成功响应后才记录会话当前的模型族
+                        if experimental.enable_cross_model_checks {
+                            record_session_family(&session_id_str, &mapped_model);
+                        }
+                        // 200 仅代表响应头到达，收到首个有效内容后才计为成功
+                        if !hedge_won {
+                            token_manager
+                                .report_outcome(
+                                    &token.account_id,
+                                    Some(mapped_model.as_str()),
+                                    UpstreamOutcome::Success,
+                                )
+                                .await;
+                        }
+                        stream
+                    }
+                    PeekOutcome::Failed { error_type, message } => {
+                        // 尚未向客户端发送任何内容，透明换账号重试
+                        last_error = format!("Stream failed before first token ({}): {}", error_type, message);
+                        tracing::warn!(
+                            "Upstream stream on {} failed before first token, attempt {}/{}: {}",
+                            token.email,
+                            attempt + 1,
+                            max_attempts,
+                            last_error
+                        );
+                        if !deadline.expired_by_client() {
+                            token_manager
+                                .report_outcome(
+                                    &token.account_id,
+                                    Some(mapped_model.as_str()),
+                                    UpstreamOutcome::StreamInterrupted,
+                                )
+                                .await;
+                        }
+                        continue;
+                    }
+                };
+
+                // 响应流结束前持有账号并发租约
+                let upstream_stream = hold_lease(upstream_stream, token.lease.clone());
+                // 相邻数据块间隔超时视为断流
+                let upstream_stream = with_idle_timeout(upstream_stream, deadline.stream_idle());
+
+                // 可选：中途断流时换账号续写
+                let upstream_stream = match recovery_body {
+                    Some(body) => with_stream_recovery(
+                        upstream_stream,
+                        RecoveryContext::new(
+                            token_manager.clone(),
+                            upstream.clone(),
+                            body,
+                            &mapped_model,
+                            &token.account_id,
+                            &trace_id,
+                            &deadline,
+                        ),
+                    ),
+                    None => upstream_stream,
+                };
+                // 总时长套在断流续写之外，续写不重新计时
+                let upstream_stream = with_total_deadline(upstream_stream, &deadline);
+                let upstream_stream = scale_usage_stream(upstream_stream, usage_scale);
+
+                let signature_context = signature_cache_enabled.then(|| {
+                    SignatureContext::new(&session_id_str, &mapped_model, request.messages.len())
+                });
                 let claude_stream = create_claude_sse_stream(
-                    Box::pin(response.bytes_stream()),
+                    upstream_stream,
                     trace_id.clone(),
                     token.email.clone(),
+                    signature_context,
                 );
 
                 if client_wants_stream {
                     // Return SSE stream
                     let body = Body::from_stream(claude_stream);
-                    return Response::builder()
+                    let client_response = Response::builder()
                         .header("Content-Type", "text/event-stream")
                         .header("Cache-Control", "no-cache")
                         .header("Connection", "keep-alive")
@@ -197,43 +396,15 @@ pub async fn handle_messages(
                         .body(body)
                         .unwrap()
                         .into_response();
+                    return with_context_header(
+                        with_fallback_header(client_response, fallback_from.as_deref()),
+                        context_report.as_ref(),
+                    );
                 } else {
                     // Aggregate stream to non-streaming response
-                    let mut full_text = String::new();
-                    let mut stream = Box::pin(claude_stream);
-
-                    while let Some(chunk) = stream.next().await {
-                        if let Ok(bytes) = chunk {
-                            let text = String::from_utf8_lossy(&bytes);
-                            // Extract text content from SSE events
-                            for line in text.lines() {
-                                if let Some(data) = line.strip_prefix("data: ") {
-                                    if let Ok(event) = serde_json::from_str::<Value>(data) {
-                                        if let Some(delta) = event
-                                            .get("delta")
-                                            .and_then(|d| d.get("text"))
-                                            .and_then(|t| t.as_str())
-                                        {
-                                            full_text.push_str(delta);
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-
-                    let msg_id = uuid::Uuid::new_v4().to_string().replace('-', "");
-                    let resp = json!({
-                        "id": format!("msg_{}", &msg_id[..24.min(msg_id.len())]),
-                        "type": "message",
-                        "role": "assistant",
-                        "content": [{"type": "text", "text": full_text}],
-                        "model": request.model,
-                        "stop_reason": "end_turn",
-                        "usage": {"input_tokens": 0, "output_tokens": 0}
-                    });
+                    let resp = aggregate_claude_stream(claude_stream, &request.model).await;
 
-                    return (
+                    let client_response = (
                         StatusCode::OK,
                         [
                             ("X-Account-Email", token.email.as_str()),
@@ -242,11 +413,25 @@ pub async fn handle_messages(
                         Json(resp),
                     )
                         .into_response();
+                    return with_context_header(
+                        with_fallback_header(client_response, fallback_from.as_deref()),
+                        context_report.as_ref(),
+                    );
                 }
             }
 
             // Non-streaming response
-            let gemini_resp: GeminiResponse = match response.json().await {
+            let parsed = response
+                .json::<Value>()
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|mut json| {
+                    if let Some(factor) = usage_scale {
+                        scale_usage_metadata(&mut json, factor);
+                    }
+                    serde_json::from_value::<GeminiResponse>(json).map_err(|e| e.to_string())
+                });
+            let gemini_resp = match parsed {
                 Ok(json) => json,
                 Err(e) => {
                     return (
@@ -256,6 +441,12 @@ pub async fn handle_messages(
                         .into_response();
                 }
             };
+            token_manager
+                .report_outcome(&token.account_id, Some(mapped_model.as_str()), UpstreamOutcome::Success)
+                .await;
+            if experimental.enable_cross_model_checks {
+                record_session_family(&session_id_str, &mapped_model);
+            }
 
             let claude_response = match transform_response(&gemini_resp) {
                 Ok(resp) => serde_json::to_value(resp).unwrap_or(json!({"type": "error"})),
@@ -268,7 +459,7 @@ pub async fn handle_messages(
                 }
             };
 
-            return (
+            let client_response = (
                 StatusCode::OK,
                 [
                     ("X-Account-Email", token.email.as_str()),
@@ -277,15 +468,35 @@ pub async fn handle_messages(
                 Json(claude_response),
             )
                 .into_response();
+            return with_context_header(
+                with_fallback_header(client_response, fallback_from.as_deref()),
+                context_report.as_ref(),
+            );
         }
 
         // Handle errors
         let status_code = status.as_u16();
+        let retry_after = response
+            .headers()
+            .get("Retry-After")
+            .and_then(|h| h.to_str().ok())
+            .map(str::to_string);
         let error_text = response
             .text()
             .await
             .unwrap_or_else(|_| format!("HTTP {}", status_code));
         last_error = format!("HTTP {}: {}", status_code, error_text);
+        token_manager
+            .report_outcome(
+                &token.account_id,
+                Some(mapped_model.as_str()),
+                UpstreamOutcome::HttpError {
+                    status: status_code,
+                    retry_after: retry_after.as_deref(),
+                    body: &error_text,
+                },
+            )
+            .await;
 
         let strategy = determine_retry_strategy(status_code, &error_text, false);
 
@@ -316,8 +527,27 @@ pub async fn handle_messages(
             .into_response();
     }
 
-    // All attempts exhausted
     let mapped = last_mapped_model.unwrap_or_default();
+    if let Some(deadline) = last_deadline.filter(DeadlineClock::expired) {
+        return (
+            StatusCode::GATEWAY_TIMEOUT,
+            [("X-Mapped-Model", mapped.as_str())],
+            Json(json!({
+                "type": "error",
+                "error": {
+                    "type": "timeout_error",
+                    "message": format!(
+                        "Request deadline of {}s exceeded. Last error: {}",
+                        deadline.total().as_secs(),
+                        last_error
+                    )
+                }
+            })),
+        )
+            .into_response();
+    }
+
+    // All attempts exhausted
     (
         StatusCode::TOO_MANY_REQUESTS,
         [("X-Mapped-Model", mapped.as_str())],
@@ -332,6 +562,103 @@ pub async fn handle_messages(
         .into_response()
 }
 
+/// 将 Claude SSE 流聚合为非流式 Messages 响应
+///
+/// 内容块按 content_block_* 事件重建 (text / thinking / tool_use)，
+/// stop_reason 与 usage 取自 message_delta (缺失时回退到 message_start 的 usage)。
+async fn aggregate_claude_stream<S>(stream: S, model: &str) -> Value
+where
+    S: futures::Stream<Item = Result<bytes::Bytes, String>>,
+{
+    let mut stream = Box::pin(stream);
+    let mut pending = String::new();
+    let mut content: Vec<Value> = Vec::new();
+    let mut tool_inputs: Vec<String> = Vec::new();
+    let mut stop_reason = Value::from("end_turn");
+    let mut usage = json!({"input_tokens": 0, "output_tokens": 0});
+
+    while let Some(chunk) = stream.next().await {
+        let Ok(bytes) = chunk else { continue };
+        pending.push_str(&String::from_utf8_lossy(&bytes));
+        // 事件可能跨 chunk，只处理完整的行
+        while let Some(pos) = pending.find('\n') {
+            let line: String = pending.drain(..=pos).collect();
+            let Some(data) = line.trim_end().strip_prefix("data: ") else { continue };
+            let Ok(event) = serde_json::from_str::<Value>(data) else { continue };
+            let index = event.get("index").and_then(|i| i.as_u64()).unwrap_or(0) as usize;
+
+            match event.get("type").and_then(|t| t.as_str()) {
+                Some("message_start") => {
+                    if event["message"]["usage"].is_object() {
+                        usage = event["message"]["usage"].clone();
+                    }
+                }
+                Some("content_block_start") => {
+                    if content.len() <= index {
+                        content.resize(index + 1, Value::Null);
+                        tool_inputs.resize(index + 1, String::new());
+                    }
+                    content[index] = event["content_block"].clone();
+                }
+                Some("content_block_delta") => {
+                    let (Some(block), delta) = (content.get_mut(index), &event["delta"]) else {
+                        continue;
+                    };
+                    match delta["type"].as_str() {
+                        Some("text_delta") => append_str(block, "text", &delta["text"]),
+                        Some("thinking_delta") => append_str(block, "thinking", &delta["thinking"]),
+                        Some("signature_delta") => block["signature"] = delta["signature"].clone(),
+                        Some("input_json_delta") => {
+                            if let Some(partial) = delta["partial_json"].as_str() {
+                                tool_inputs[index].push_str(partial);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Some("content_block_stop") => {
+                    if let Some(block) = content.get_mut(index) {
+                        if block["type"] == "tool_use" && !tool_inputs[index].is_empty() {
+                            block["input"] = serde_json::from_str(&tool_inputs[index])
+                                .unwrap_or_else(|_| json!({}));
+                        }
+                    }
+                }
+                Some("message_delta") => {
+                    if let Some(reason) = event["delta"].get("stop_reason").filter(|r| !r.is_null()) {
+                        stop_reason = reason.clone();
+                    }
+                    if event["usage"].is_object() {
+                        usage = event["usage"].clone();
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let msg_id = uuid::Uuid::new_v4().to_string().replace('-', "");
+    content.retain(|block| !block.is_null());
+
+    json!({
+        "id": format!("msg_{}", &msg_id[..24.min(msg_id.len())]),
+        "type": "message",
+        "role": "assistant",
+        "content": content,
+        "model": model,
+        "stop_reason": stop_reason,
+        "stop_sequence": null,
+        "usage": usage
+    })
+}
+
+fn append_str(block: &mut Value, key: &str, text: &Value) {
+    if let Some(text) = text.as_str() {
+        let current = block[key].as_str().unwrap_or_default();
+        block[key] = Value::from(format!("{}{}", current, text));
+    }
+}
+
 /// Handle Claude Token Count: POST /v1/messages/count_tokens [Req 2.15]
 pub async fn handle_count_tokens(
     State(_state): State<AppState>,
@@ -400,4 +727,63 @@ mod tests {
         let count = estimate_token_count(&request);
         assert!(count > 0, "Token count should be positive");
     }
+
+    /// Gemini SSE 流经与 handler 相同的 create_claude_sse_stream → 非流式聚合路径
+    async fn aggregate_gemini_events(events: &[Value]) -> Value {
+        let body: String = events
+            .iter()
+            .map(|event| format!("data: {}\n\n", event))
+            .collect();
+        let upstream = futures::stream::iter(vec![Ok::<_, reqwest::Error>(bytes::Bytes::from(body))]);
+        let claude_stream = create_claude_sse_stream(
+            Box::pin(upstream),
+            "trace".to_string(),
+            "test@example.com".to_string(),
+            None,
+        );
+        aggregate_claude_stream(claude_stream, "claude-sonnet-4-5").await
+    }
+
+    #[tokio::test]
+    async fn test_non_stream_aggregation_keeps_safety_stop_reason_and_usage() {
+        let resp = aggregate_gemini_events(&[
+            json!({
+                "candidates": [{"content": {"role": "model", "parts": [{"text": "I can"}]}}],
+                "usageMetadata": {"promptTokenCount": 11}
+            }),
+            json!({
+                "candidates": [{
+                    "content": {"role": "model", "parts": [{"text": "not help"}]},
+                    "finishReason": "SAFETY"
+                }],
+                "usageMetadata": {"promptTokenCount": 11, "candidatesTokenCount": 4, "totalTokenCount": 15}
+            }),
+        ])
+        .await;
+
+        assert_eq!(resp["stop_reason"], "refusal");
+        assert_eq!(resp["content"], json!([{"type": "text", "text": "I cannot help"}]));
+        assert_eq!(resp["usage"]["input_tokens"], 11);
+        assert_eq!(resp["usage"]["output_tokens"], 4);
+        assert_eq!(resp["model"], "claude-sonnet-4-5");
+    }
+
+    #[tokio::test]
+    async fn test_non_stream_aggregation_rebuilds_tool_use() {
+        let resp = aggregate_gemini_events(&[json!({
+            "candidates": [{
+                "content": {"role": "model", "parts": [
+                    {"functionCall": {"name": "get_weather", "args": {"city": "Paris"}, "id": "call_1"}}
+                ]},
+                "finishReason": "STOP"
+            }]
+        })])
+        .await;
+
+        assert_eq!(resp["stop_reason"], "tool_use");
+        let block = &resp["content"][0];
+        assert_eq!(block["type"], "tool_use");
+        assert_eq!(block["name"], "get_weather");
+        assert_eq!(block["input"], json!({"city": "Paris"}));
+    }
 }
@@ -4,7 +4,7 @@
 
 use axum::{
     extract::State,
-    http::StatusCode,
+    http::{HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
@@ -138,6 +138,26 @@ pub fn should_rotate_account(status_code: u16) -> bool {
     matches!(status_code, 429 | 401 | 403 | 404 | 500)
 }
 
+/// Attach `X-Fallback-From` when the request was served by a fallback model
+pub fn with_fallback_header(mut response: Response, fallback_from: Option<&str>) -> Response {
+    if let Some(value) = fallback_from.and_then(|m| HeaderValue::from_str(m).ok()) {
+        response.headers_mut().insert("X-Fallback-From", value);
+    }
+    response
+}
+
+/// Attach `X-Context-Compressed` when history was compressed to fit the
+/// model's context window
+pub fn with_context_header(
+    mut response: Response,
+    report: Option<&crate::proxy::common::context_manager::ContextCompressionReport>,
+) -> Response {
+    if let Some(value) = report.and_then(|r| HeaderValue::from_str(&r.summary()).ok()) {
+        response.headers_mut().insert("X-Context-Compressed", value);
+    }
+    response
+}
+
 /// Detect model capabilities and configuration
 /// POST /v1/models/detect
 pub async fn handle_detect_model(
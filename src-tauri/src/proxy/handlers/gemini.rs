@@ -3,9 +3,11 @@
 // Requirements covered:
 // - 2.3: POST /v1beta/models/:model → Gemini native passthrough
 
+use std::time::Instant;
+
 use axum::{
-    extract::{Json, Path, State},
-    http::StatusCode,
+    extract::{Extension, Json, Path, State},
+    http::{HeaderMap, StatusCode},
     response::{IntoResponse, Response},
 };
 use bytes::{Bytes, BytesMut};
@@ -13,10 +15,30 @@ use futures::StreamExt;
 use serde_json::{json, Value};
 use tracing::{debug, error, info};
 
-use super::common::{apply_retry_strategy, determine_retry_strategy, should_rotate_account};
+use super::common::{
+    apply_retry_strategy, determine_retry_strategy, should_rotate_account, with_fallback_header,
+};
 use super::AppState;
+use crate::proxy::common::request_policy::{apply_request_policy, PolicyInput, RequestPolicy};
+use crate::proxy::common::safety::apply_to_upstream_body;
+use crate::proxy::common::tool_loop::apply_tool_loop_recovery;
+use crate::proxy::common::tool_result_compressor::{self, tool_result_limit_for_request};
 use crate::proxy::mappers::gemini::{unwrap_response, wrap_request};
-use crate::proxy::session_manager::SessionManager;
+use crate::proxy::concurrency::hold_lease;
+use crate::proxy::get_experimental_config;
+use crate::proxy::middleware::auth::UserTokenIdentity;
+use crate::proxy::model_fallback::{get_token_with_fallback, FallbackToken};
+use crate::proxy::session_manager::{header_session_id, SessionManager};
+use crate::proxy::token_manager::UpstreamOutcome;
+use crate::proxy::upstream::deadline::{
+    deadlines_for_request, peek_within_deadline, with_idle_timeout, with_total_deadline,
+    DeadlineClock,
+};
+use crate::proxy::upstream::hedging::{peek_with_hedge, HedgeContext};
+use crate::proxy::upstream::stream_peek::PeekOutcome;
+use crate::proxy::upstream::usage_scaling::{
+    scale_usage_metadata, scale_usage_stream, usage_scale_factor,
+};
 
 const MAX_RETRY_ATTEMPTS: usize = 3;
 
@@ -25,8 +47,13 @@ const MAX_RETRY_ATTEMPTS: usize = 3;
 pub async fn handle_generate(
     State(state): State<AppState>,
     Path(model_action): Path<String>,
+    identity: Option<Extension<UserTokenIdentity>>,
+    headers: HeaderMap,
     Json(body): Json<Value>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let token_id = identity.map(|Extension(identity)| identity.token_id);
+    let header_sid = header_session_id(&headers);
+
     // Parse model:method
     let (model_name, method) = if let Some((m, action)) = model_action.rsplit_once(':') {
         (m.to_string(), action.to_string())
@@ -59,7 +86,10 @@ pub async fn handle_generate(
 
     let mut last_error = String::new();
     let mut last_email: Option<String> = None;
+    let mut last_deadline: Option<DeadlineClock> = None;
 
+    // 总时长从客户端请求开始计时，换号重试不重新计时
+    let request_started = Instant::now();
     for attempt in 0..max_attempts {
         // Model route resolution
         let mapped_model = crate::proxy::common::model_mapping::map_model(
@@ -69,12 +99,25 @@ pub async fn handle_generate(
         );
 
         // Extract session ID
-        let session_id = SessionManager::extract_gemini_session_id(&body, &model_name);
+        let session_id = SessionManager::resolve_gemini_session_id(
+            &body,
+            &model_name,
+            header_sid.as_deref(),
+            token_id.as_deref(),
+        );
 
         // Get token
-        let token = match token_manager
-            .get_token(&mapped_model, Some(&session_id))
-            .await
+        let FallbackToken {
+            token,
+            model: mapped_model,
+            fallback_from,
+        } = match get_token_with_fallback(
+            &token_manager,
+            &mapped_model,
+            Some(&session_id),
+            token_id.as_deref(),
+        )
+        .await
         {
             Ok(t) => t,
             Err(e) => {
@@ -90,7 +133,31 @@ pub async fn handle_generate(
         info!("✓ Using account: {}", token.email);
 
         // Wrap request with project injection
-        let wrapped_body = wrap_request(&body, &project_id, &mapped_model, Some(&session_id));
+        let mut wrapped_body = wrap_request(&body, &project_id, &mapped_model, Some(&session_id));
+        // 原生协议: 策略允许时保留客户端自带的 safetySettings
+        apply_to_upstream_body(&mut wrapped_body, &mapped_model, token_id.as_deref(), true);
+        apply_request_policy(
+            &mut wrapped_body,
+            &mapped_model,
+            &PolicyInput::from_gemini(&body),
+            &RequestPolicy::current(),
+        );
+        tool_result_compressor::apply_to_upstream_body(
+            &mut wrapped_body,
+            tool_result_limit_for_request(&mapped_model, &headers),
+        );
+        if get_experimental_config().enable_tool_loop_recovery {
+            if let Some(report) = apply_tool_loop_recovery(&mut wrapped_body) {
+                tracing::warn!(
+                    "[{}] Tool loop detected: {:?} repeated {} times (broken: {})",
+                    trace_id,
+                    report.tool_names,
+                    report.repeats,
+                    report.broken
+                );
+            }
+        }
+        let usage_scale = usage_scale_factor(&model_name, &mapped_model);
 
         // Upstream call
         let query_string = if is_stream { Some("alt=sse") } else { None };
@@ -100,19 +167,42 @@ pub async fn handle_generate(
             "generateContent"
         };
 
+        let deadline =
+            DeadlineClock::new(request_started, deadlines_for_request(&mapped_model, &headers));
+        last_deadline = Some(deadline);
+        if deadline.expired() {
+            break;
+        }
+        let hedge = HedgeContext::for_model(
+            token_manager.clone(),
+            upstream.clone(),
+            &wrapped_body,
+            &mapped_model,
+            &token.account_id,
+            &trace_id,
+            &deadline,
+        );
+
         let call_result = match upstream
-            .call_v1_internal(
+            .call_v1_internal_with_deadline(
                 upstream_method,
                 &token.access_token,
                 wrapped_body,
                 query_string,
                 Some(token.account_id.as_str()),
+                deadline,
             )
             .await
         {
             Ok(r) => r,
             Err(e) => {
                 last_error = e.clone();
+                // 客户端缩短的时限到期不计入账号状态
+                if !deadline.expired_by_client() {
+                    token_manager
+                        .report_outcome(&token.account_id, Some(mapped_model.as_str()), UpstreamOutcome::Transport)
+                        .await;
+                }
                 debug!(
                     "Gemini Request failed on attempt {}/{}: {}",
                     attempt + 1,
@@ -127,12 +217,60 @@ pub async fn handle_generate(
         let status = response.status();
 
         if status.is_success() {
-            token_manager.mark_success(&token.account_id);
-
             if is_stream {
                 use axum::body::Body;
 
-                let mut response_stream = Box::pin(response.bytes_stream());
+                let (peek_outcome, hedge_winner) = peek_within_deadline(
+                    &deadline,
+                    peek_with_hedge(Box::pin(response.bytes_stream()), hedge),
+                )
+                .await;
+                // 对冲请求胜出时后续以对冲账号为准 (成功已在对冲路径上报，被取消的主请求不计)
+                let hedge_won = hedge_winner.is_some();
+                let token = hedge_winner.unwrap_or(token);
+                let upstream_stream = match peek_outcome {
+                    PeekOutcome::Ready(stream) => {
+                        // 200 仅代表响应头到达，收到首个有效内容后才计为成功
+                        if !hedge_won {
+                            token_manager
+                                .report_outcome(
+                                    &token.account_id,
+                                    Some(mapped_model.as_str()),
+                                    UpstreamOutcome::Success,
+                                )
+                                .await;
+                        }
+                        stream
+                    }
+                    PeekOutcome::Failed { error_type, message } => {
+                        // 尚未向客户端发送任何内容，透明换账号重试
+                        last_error = format!("Stream failed before first token ({}): {}", error_type, message);
+                        tracing::warn!(
+                            "Upstream stream on {} failed before first token, attempt {}/{}: {}",
+                            token.email,
+                            attempt + 1,
+                            max_attempts,
+                            last_error
+                        );
+                        if !deadline.expired_by_client() {
+                            token_manager
+                                .report_outcome(
+                                    &token.account_id,
+                                    Some(mapped_model.as_str()),
+                                    UpstreamOutcome::StreamInterrupted,
+                                )
+                                .await;
+                        }
+                        continue;
+                    }
+                };
+                // 响应流结束前持有账号并发租约
+                let upstream_stream = hold_lease(upstream_stream, token.lease.clone());
+                // 相邻数据块间隔超时视为断流
+                let upstream_stream = with_idle_timeout(upstream_stream, deadline.stream_idle());
+                // 总时长套在断流续写之外，续写不重新计时
+                let upstream_stream = with_total_deadline(upstream_stream, &deadline);
+                let mut response_stream = scale_usage_stream(upstream_stream, usage_scale);
                 let mut buffer = BytesMut::new();
 
                 let stream = async_stream::stream! {
@@ -186,7 +324,7 @@ pub async fn handle_generate(
 
                 if client_wants_stream {
                     let body = Body::from_stream(stream);
-                    return Ok(Response::builder()
+                    let client_response = Response::builder()
                         .header("Content-Type", "text/event-stream")
                         .header("Cache-Control", "no-cache")
                         .header("Connection", "keep-alive")
@@ -195,14 +333,15 @@ pub async fn handle_generate(
                         .header("X-Mapped-Model", &mapped_model)
                         .body(body)
                         .unwrap()
-                        .into_response());
+                        .into_response();
+                    return Ok(with_fallback_header(client_response, fallback_from.as_deref()));
                 } else {
                     // Collect stream to JSON
                     use crate::proxy::mappers::gemini::collector::collect_stream_to_json;
                     match collect_stream_to_json(Box::pin(stream)).await {
                         Ok(gemini_resp) => {
                             let unwrapped = unwrap_response(&gemini_resp);
-                            return Ok((
+                            let client_response = (
                                 StatusCode::OK,
                                 [
                                     ("X-Account-Email", token.email.as_str()),
@@ -210,7 +349,8 @@ pub async fn handle_generate(
                                 ],
                                 Json(unwrapped),
                             )
-                                .into_response());
+                                .into_response();
+                            return Ok(with_fallback_header(client_response, fallback_from.as_deref()));
                         }
                         Err(e) => {
                             return Ok((
@@ -224,13 +364,36 @@ pub async fn handle_generate(
             }
 
             // Non-streaming response
-            let gemini_resp: Value = response
-                .json()
-                .await
-                .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Parse error: {}", e)))?;
+            let mut gemini_resp: Value = match response.json().await {
+                Ok(json) => json,
+                Err(e) if e.is_timeout() => {
+                    // 响应体读取超时：尚未向客户端返回任何内容，换账号重试
+                    last_error = format!("Upstream response timed out: {}", e);
+                    tracing::warn!(
+                        "Upstream response on {} timed out after {}s, attempt {}/{}",
+                        token.email,
+                        deadline.total().as_secs(),
+                        attempt + 1,
+                        max_attempts
+                    );
+                    if !deadline.expired_by_client() {
+                        token_manager
+                            .report_outcome(&token.account_id, Some(mapped_model.as_str()), UpstreamOutcome::Transport)
+                            .await;
+                    }
+                    continue;
+                }
+                Err(e) => return Err((StatusCode::BAD_GATEWAY, format!("Parse error: {}", e))),
+            };
+            token_manager
+                .report_outcome(&token.account_id, Some(mapped_model.as_str()), UpstreamOutcome::Success)
+                .await;
+            if let Some(factor) = usage_scale {
+                scale_usage_metadata(&mut gemini_resp, factor);
+            }
 
             let unwrapped = unwrap_response(&gemini_resp);
-            return Ok((
+            let client_response = (
                 StatusCode::OK,
                 [
                     ("X-Account-Email", token.email.as_str()),
@@ -238,16 +401,33 @@ pub async fn handle_generate(
                 ],
                 Json(unwrapped),
             )
-                .into_response());
+                .into_response();
+            return Ok(with_fallback_header(client_response, fallback_from.as_deref()));
         }
 
         // Handle errors
         let status_code = status.as_u16();
+        let retry_after = response
+            .headers()
+            .get("Retry-After")
+            .and_then(|h| h.to_str().ok())
+            .map(str::to_string);
         let error_text = response
             .text()
             .await
             .unwrap_or_else(|_| format!("HTTP {}", status_code));
         last_error = format!("HTTP {}: {}", status_code, error_text);
+        token_manager
+            .report_outcome(
+                &token.account_id,
+                Some(mapped_model.as_str()),
+                UpstreamOutcome::HttpError {
+                    status: status_code,
+                    retry_after: retry_after.as_deref(),
+                    body: &error_text,
+                },
+            )
+            .await;
 
         let strategy = determine_retry_strategy(status_code, &error_text, false);
 
@@ -282,6 +462,24 @@ pub async fn handle_generate(
             .into_response());
     }
 
+    if let Some(deadline) = last_deadline.filter(DeadlineClock::expired) {
+        return Ok((
+            StatusCode::GATEWAY_TIMEOUT,
+            Json(json!({
+                "error": {
+                    "code": 504,
+                    "message": format!(
+                        "Request deadline of {}s exceeded. Last error: {}",
+                        deadline.total().as_secs(),
+                        last_error
+                    ),
+                    "status": "DEADLINE_EXCEEDED"
+                }
+            })),
+        )
+            .into_response());
+    }
+
     if let Some(email) = last_email {
         Ok((
             StatusCode::TOO_MANY_REQUESTS,
@@ -23,6 +23,8 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+use crate::models::config::AppConfig;
+use crate::proxy::security::ProxySecurityConfig;
 use crate::proxy::token_manager::TokenManager;
 use crate::proxy::upstream::client::UpstreamClient;
 
@@ -32,6 +34,7 @@ pub struct AppState {
     pub token_manager: Arc<TokenManager>,
     pub custom_mapping: Arc<RwLock<HashMap<String, String>>>,
     pub upstream: Arc<UpstreamClient>,
+    pub security_state: Arc<RwLock<ProxySecurityConfig>>,
 }
 
 impl AppState {
@@ -39,11 +42,60 @@ impl AppState {
         token_manager: Arc<TokenManager>,
         custom_mapping: Arc<RwLock<HashMap<String, String>>>,
         upstream: Arc<UpstreamClient>,
+        security_state: Arc<RwLock<ProxySecurityConfig>>,
     ) -> Self {
         Self {
             token_manager,
             custom_mapping,
             upstream,
+            security_state,
         }
     }
+
+    /// 将配置中所有可热更新的设置应用到运行中的服务
+    ///
+    /// 启动、管理端保存配置和安全配置更新共用此入口；新增的运行时配置只需在这里登记。
+    pub async fn apply_runtime_config(&self, config: &AppConfig) {
+        let proxy = &config.proxy;
+
+        // 服务实例状态：模型映射、鉴权 / JWT / mTLS 身份映射、UA、熔断与调度
+        *self.custom_mapping.write().await = proxy.custom_mapping.clone();
+        *self.security_state.write().await = ProxySecurityConfig::from_proxy_config(proxy);
+        self.upstream
+            .set_user_agent_override(proxy.user_agent_override.clone())
+            .await;
+        self.token_manager
+            .update_circuit_breaker_config(config.circuit_breaker.clone())
+            .await;
+        self.token_manager
+            .update_sticky_config(proxy.scheduling.clone())
+            .await;
+
+        // 全局配置
+        crate::proxy::update_thinking_budget_config(proxy.thinking_budget.clone());
+        crate::proxy::update_global_system_prompt_config(proxy.global_system_prompt.clone());
+        crate::proxy::update_image_thinking_mode(proxy.image_thinking_mode.clone());
+        crate::proxy::redaction::update_redaction_config(&proxy.redaction);
+        crate::proxy::update_safety_config(proxy.safety.clone());
+        crate::proxy::update_experimental_config(proxy.experimental.clone());
+        crate::proxy::model_fallback::update_fallback_config(&proxy.model_fallback);
+        crate::proxy::upstream::stream_recovery::update_stream_recovery_config(&proxy.stream_recovery);
+        crate::proxy::upstream::hedging::update_hedging_config(&proxy.hedging);
+        crate::proxy::session_manager::update_session_affinity_config(&proxy.session_affinity);
+        crate::proxy::common::tool_result_compressor::update_tool_result_compression_config(
+            &proxy.tool_result_compression,
+        );
+        crate::proxy::upstream::deadline::update_upstream_timeouts(
+            proxy.request_timeout,
+            &proxy.upstream_timeouts,
+        );
+        if let Some(monitor) = crate::proxy::monitor::get_global_monitor() {
+            monitor.set_enabled(proxy.enable_logging);
+        }
+        // 代理池 (代理列表 / 策略 / 自动故障转移)
+        if let Some(pool) = crate::proxy::proxy_pool::get_global_proxy_pool() {
+            pool.update_config(proxy.proxy_pool.clone()).await;
+        }
+        tracing::info!("[HotReload] Runtime config applied");
+    }
 }
@@ -13,29 +13,58 @@
 // - 7.4: Image attachment handling (base64/URL → inlineData)
 // - 7.5: Image thinking mode control (enabled/disabled)
 
+use std::time::Instant;
+
 use axum::{
-    extract::{Json, State},
-    http::StatusCode,
+    extract::{Extension, Json, State},
+    http::{HeaderMap, StatusCode},
     response::{IntoResponse, Response},
 };
 use base64::Engine;
 use serde_json::{json, Value};
 use tracing::{debug, error, info};
 
-use super::common::{apply_retry_strategy, determine_retry_strategy, should_rotate_account};
+use super::common::{
+    apply_retry_strategy, determine_retry_strategy, should_rotate_account, with_fallback_header,
+};
 use super::AppState;
+use crate::proxy::common::request_policy::{apply_request_policy, PolicyInput, RequestPolicy};
+use crate::proxy::common::safety::{apply_to_upstream_body, build_safety_settings};
+use crate::proxy::common::tool_loop::apply_tool_loop_recovery;
+use crate::proxy::common::tool_result_compressor::tool_result_limit_for_request;
+use crate::proxy::concurrency::hold_lease;
+use crate::proxy::get_experimental_config;
+use crate::proxy::middleware::auth::UserTokenIdentity;
 use crate::proxy::mappers::openai::{
-    transform_openai_request, transform_openai_response, OpenAIRequest,
+    transform_openai_request_with_tool_limit, transform_openai_response, OpenAIRequest,
+};
+use crate::proxy::model_fallback::{get_token_with_fallback, FallbackToken};
+use crate::proxy::session_manager::{header_session_id, SessionManager};
+use crate::proxy::token_manager::UpstreamOutcome;
+use crate::proxy::upstream::deadline::{
+    deadlines_for_request, peek_within_deadline, with_idle_timeout, with_total_deadline,
+    DeadlineClock,
+};
+use crate::proxy::upstream::hedging::{peek_with_hedge, HedgeContext};
+use crate::proxy::upstream::stream_peek::PeekOutcome;
+use crate::proxy::upstream::stream_recovery::{
+    stream_recovery_enabled, with_stream_recovery, RecoveryContext,
+};
+use crate::proxy::upstream::usage_scaling::{
+    scale_usage_metadata, scale_usage_stream, usage_scale_factor,
 };
-use crate::proxy::session_manager::SessionManager;
 
 const MAX_RETRY_ATTEMPTS: usize = 3;
 
 /// Handle OpenAI Chat Completions: POST /v1/chat/completions [Req 2.1]
 pub async fn handle_chat_completions(
     State(state): State<AppState>,
+    identity: Option<Extension<UserTokenIdentity>>,
+    headers: HeaderMap,
     Json(body): Json<Value>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let token_id = identity.map(|Extension(identity)| identity.token_id);
+    let header_sid = header_session_id(&headers);
     let openai_req: OpenAIRequest = serde_json::from_value(body)
         .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)))?;
 
@@ -55,6 +84,7 @@ pub async fn handle_chat_completions(
 
     let mut last_error = String::new();
     let mut last_email: Option<String> = None;
+    let mut last_deadline: Option<DeadlineClock> = None;
 
     // Model route resolution (outside loop for consistent header)
     let mapped_model = crate::proxy::common::model_mapping::map_model(
@@ -63,15 +93,28 @@ pub async fn handle_chat_completions(
         false,
     );
 
+    // 总时长从客户端请求开始计时，换号重试不重新计时
+    let request_started = Instant::now();
     for attempt in 0..max_attempts {
         // Extract session ID for sticky scheduling
-        let session_id =
-            SessionManager::extract_openai_session_id(&serde_json::to_value(&openai_req).unwrap());
+        let session_id = SessionManager::resolve_openai_session_id(
+            &serde_json::to_value(&openai_req).unwrap(),
+            header_sid.as_deref(),
+            token_id.as_deref(),
+        );
 
         // Get token via P2C selection
-        let token = match token_manager
-            .get_token(&mapped_model, Some(&session_id))
-            .await
+        let FallbackToken {
+            token,
+            model: mapped_model,
+            fallback_from,
+        } = match get_token_with_fallback(
+            &token_manager,
+            &mapped_model,
+            Some(&session_id),
+            token_id.as_deref(),
+        )
+        .await
         {
             Ok(t) => t,
             Err(e) => {
@@ -89,8 +132,32 @@ pub async fn handle_chat_completions(
         info!("✓ Using account: {}", token.email);
 
         // Transform request
-        let (gemini_body, session_id, message_count) =
-            transform_openai_request(&openai_req, &project_id, &mapped_model);
+        let tool_result_limit = tool_result_limit_for_request(&mapped_model, &headers);
+        let (mut gemini_body, session_id, message_count) = transform_openai_request_with_tool_limit(
+            &openai_req,
+            &project_id,
+            &mapped_model,
+            tool_result_limit,
+        );
+        apply_to_upstream_body(&mut gemini_body, &mapped_model, token_id.as_deref(), false);
+        apply_request_policy(
+            &mut gemini_body,
+            &mapped_model,
+            &PolicyInput::from_openai(&openai_req),
+            &RequestPolicy::current(),
+        );
+        if get_experimental_config().enable_tool_loop_recovery {
+            if let Some(report) = apply_tool_loop_recovery(&mut gemini_body) {
+                tracing::warn!(
+                    "[{}] Tool loop detected: {:?} repeated {} times (broken: {})",
+                    trace_id,
+                    report.tool_names,
+                    report.repeats,
+                    report.broken
+                );
+            }
+        }
+        let usage_scale = usage_scale_factor(&openai_req.model, &mapped_model);
 
         // Determine streaming mode
         let client_wants_stream = openai_req.stream;
@@ -103,21 +170,44 @@ pub async fn handle_chat_completions(
             "generateContent"
         };
         let query_string = if actual_stream { Some("alt=sse") } else { None };
+        let deadline =
+            DeadlineClock::new(request_started, deadlines_for_request(&mapped_model, &headers));
+        last_deadline = Some(deadline);
+        if deadline.expired() {
+            break;
+        }
+        let recovery_body = (actual_stream && stream_recovery_enabled()).then(|| gemini_body.clone());
+        let hedge = HedgeContext::for_model(
+            token_manager.clone(),
+            upstream.clone(),
+            &gemini_body,
+            &mapped_model,
+            &token.account_id,
+            &trace_id,
+            &deadline,
+        );
 
         // Send upstream request
         let call_result = match upstream
-            .call_v1_internal(
+            .call_v1_internal_with_deadline(
                 method,
                 &token.access_token,
                 gemini_body,
                 query_string,
                 Some(token.account_id.as_str()),
+                deadline,
             )
             .await
         {
             Ok(r) => r,
             Err(e) => {
                 last_error = e.clone();
+                // 客户端缩短的时限到期不计入账号状态
+                if !deadline.expired_by_client() {
+                    token_manager
+                        .report_outcome(&token.account_id, Some(mapped_model.as_str()), UpstreamOutcome::Transport)
+                        .await;
+                }
                 debug!(
                     "OpenAI Request failed on attempt {}/{}: {}",
                     attempt + 1,
@@ -132,15 +222,82 @@ pub async fn handle_chat_completions(
         let status = response.status();
 
         if status.is_success() {
-            // Mark success
-            token_manager.mark_success(&token.account_id);
-
             if actual_stream {
                 use axum::body::Body;
                 use crate::proxy::mappers::openai::streaming::create_openai_sse_stream;
 
+                let (peek_outcome, hedge_winner) = peek_within_deadline(
+                    &deadline,
+                    peek_with_hedge(Box::pin(response.bytes_stream()), hedge),
+                )
+                .await;
+                // 对冲请求胜出时后续以对冲账号为准 (成功已在对冲路径上报，被取消的主请求不计)
+                let hedge_won = hedge_winner.is_some();
+                let token = hedge_winner.unwrap_or(token);
+                let upstream_stream = match peek_outcome {
+                    PeekOutcome::Ready(stream) => {
+                        // 200 仅代表响应头到达，收到首个有效内容后才计为成功
+                        if !hedge_won {
+                            token_manager
+                                .report_outcome(
+                                    &token.account_id,
+                                    Some(mapped_model.as_str()),
+                                    UpstreamOutcome::Success,
+                                )
+                                .await;
+                        }
+                        stream
+                    }
+                    PeekOutcome::Failed { error_type, message } => {
+                        // 尚未向客户端发送任何内容，透明换账号重试
+                        last_error = format!("Stream failed before first token ({}): {}", error_type, message);
+                        tracing::warn!(
+                            "Upstream stream on {} failed before first token, attempt {}/{}: {}",
+                            token.email,
+                            attempt + 1,
+                            max_attempts,
+                            last_error
+                        );
+                        if !deadline.expired_by_client() {
+                            token_manager
+                                .report_outcome(
+                                    &token.account_id,
+                                    Some(mapped_model.as_str()),
+                                    UpstreamOutcome::StreamInterrupted,
+                                )
+                                .await;
+                        }
+                        continue;
+                    }
+                };
+
+                // 响应流结束前持有账号并发租约
+                let upstream_stream = hold_lease(upstream_stream, token.lease.clone());
+                // 相邻数据块间隔超时视为断流
+                let upstream_stream = with_idle_timeout(upstream_stream, deadline.stream_idle());
+
+                // 可选：中途断流时换账号续写
+                let upstream_stream = match recovery_body {
+                    Some(body) => with_stream_recovery(
+                        upstream_stream,
+                        RecoveryContext::new(
+                            token_manager.clone(),
+                            upstream.clone(),
+                            body,
+                            &mapped_model,
+                            &token.account_id,
+                            &trace_id,
+                            &deadline,
+                        ),
+                    ),
+                    None => upstream_stream,
+                };
+                // 总时长套在断流续写之外，续写不重新计时
+                let upstream_stream = with_total_deadline(upstream_stream, &deadline);
+                let upstream_stream = scale_usage_stream(upstream_stream, usage_scale);
+
                 let openai_stream = create_openai_sse_stream(
-                    Box::pin(response.bytes_stream()),
+                    upstream_stream,
                     openai_req.model.clone(),
                     session_id.clone(),
                     message_count,
@@ -149,7 +306,7 @@ pub async fn handle_chat_completions(
                 if client_wants_stream {
                     // Client wants SSE stream [Req 2.6]
                     let body = Body::from_stream(openai_stream);
-                    return Ok(Response::builder()
+                    let client_response = Response::builder()
                         .header("Content-Type", "text/event-stream")
                         .header("Cache-Control", "no-cache")
                         .header("Connection", "keep-alive")
@@ -158,14 +315,15 @@ pub async fn handle_chat_completions(
                         .header("X-Mapped-Model", &mapped_model)
                         .body(body)
                         .unwrap()
-                        .into_response());
+                        .into_response();
+                    return Ok(with_fallback_header(client_response, fallback_from.as_deref()));
                 } else {
                     // Aggregate stream to JSON [Req 2.7]
                     use crate::proxy::mappers::openai::collector::collect_stream_to_json;
                     match collect_stream_to_json(Box::pin(openai_stream)).await {
                         Ok(full_response) => {
                             info!("[{}] ✓ Stream collected to JSON", trace_id);
-                            return Ok((
+                            let client_response = (
                                 StatusCode::OK,
                                 [
                                     ("X-Account-Email", token.email.as_str()),
@@ -173,7 +331,8 @@ pub async fn handle_chat_completions(
                                 ],
                                 Json(serde_json::to_value(full_response).unwrap()),
                             )
-                                .into_response());
+                                .into_response();
+                            return Ok(with_fallback_header(client_response, fallback_from.as_deref()));
                         }
                         Err(e) => {
                             error!("[{}] Stream collection error: {}", trace_id, e);
@@ -188,14 +347,20 @@ pub async fn handle_chat_completions(
             }
 
             // Non-streaming response
-            let gemini_resp: Value = response
+            let mut gemini_resp: Value = response
                 .json()
                 .await
                 .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Parse error: {}", e)))?;
+            token_manager
+                .report_outcome(&token.account_id, Some(mapped_model.as_str()), UpstreamOutcome::Success)
+                .await;
+            if let Some(factor) = usage_scale {
+                scale_usage_metadata(&mut gemini_resp, factor);
+            }
 
             let openai_response =
                 transform_openai_response(&gemini_resp, Some(&session_id), message_count);
-            return Ok((
+            let client_response = (
                 StatusCode::OK,
                 [
                     ("X-Account-Email", token.email.as_str()),
@@ -203,16 +368,33 @@ pub async fn handle_chat_completions(
                 ],
                 Json(serde_json::to_value(openai_response).unwrap()),
             )
-                .into_response());
+                .into_response();
+            return Ok(with_fallback_header(client_response, fallback_from.as_deref()));
         }
 
         // Handle errors with retry
         let status_code = status.as_u16();
+        let retry_after = response
+            .headers()
+            .get("Retry-After")
+            .and_then(|h| h.to_str().ok())
+            .map(str::to_string);
         let error_text = response
             .text()
             .await
             .unwrap_or_else(|_| format!("HTTP {}", status_code));
         last_error = format!("HTTP {}: {}", status_code, error_text);
+        token_manager
+            .report_outcome(
+                &token.account_id,
+                Some(mapped_model.as_str()),
+                UpstreamOutcome::HttpError {
+                    status: status_code,
+                    retry_after: retry_after.as_deref(),
+                    body: &error_text,
+                },
+            )
+            .await;
 
         let strategy = determine_retry_strategy(status_code, &error_text, false);
 
@@ -247,6 +429,10 @@ pub async fn handle_chat_completions(
             .into_response());
     }
 
+    if let Some(deadline) = last_deadline.filter(DeadlineClock::expired) {
+        return Ok(deadline_exceeded_response(&deadline, &last_error));
+    }
+
     // All attempts exhausted
     if let Some(email) = last_email {
         Ok((
@@ -267,6 +453,25 @@ pub async fn handle_chat_completions(
     }
 }
 
+/// 总时长耗尽时返回 504 (而非账号耗尽的 429)
+fn deadline_exceeded_response(deadline: &DeadlineClock, last_error: &str) -> Response {
+    (
+        StatusCode::GATEWAY_TIMEOUT,
+        Json(json!({
+            "error": {
+                "message": format!(
+                    "Request deadline of {}s exceeded. Last error: {}",
+                    deadline.total().as_secs(),
+                    last_error
+                ),
+                "type": "timeout_error",
+                "code": 504
+            }
+        })),
+    )
+        .into_response()
+}
+
 /// Handle OpenAI Model List: GET /v1/models [Req 2.12]
 pub async fn handle_list_models(State(state): State<AppState>) -> impl IntoResponse {
     let custom_mapping = state.custom_mapping.read().await;
@@ -304,6 +509,7 @@ pub async fn handle_list_models(State(state): State<AppState>) -> impl IntoRespo
 /// Handle OpenAI Images Generations: POST /v1/images/generations [Req 2.10, 7.1, 7.2]
 pub async fn handle_images_generations(
     State(state): State<AppState>,
+    identity: Option<Extension<UserTokenIdentity>>,
     Json(body): Json<Value>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
     let prompt = body.get("prompt").and_then(|v| v.as_str()).ok_or((
@@ -373,6 +579,10 @@ pub async fn handle_images_generations(
     let max_attempts = MAX_RETRY_ATTEMPTS
         .min(max_pool_size.saturating_add(1))
         .max(2);
+    let safety_settings = build_safety_settings(
+        "gemini-3-pro-image",
+        identity.as_ref().map(|Extension(identity)| identity.token_id.as_str()),
+    );
 
     let mut tasks = Vec::new();
 
@@ -382,6 +592,7 @@ pub async fn handle_images_generations(
         let final_prompt = final_prompt.clone();
         let image_config = image_config.clone();
         let response_format = response_format.to_string();
+        let safety_settings = safety_settings.clone();
 
         tasks.push(tokio::spawn(async move {
             let mut last_error = String::new();
@@ -418,13 +629,7 @@ pub async fn handle_images_generations(
                             "candidateCount": 1,
                             "imageConfig": image_config
                         },
-                        "safetySettings": [
-                            { "category": "HARM_CATEGORY_HARASSMENT", "threshold": "OFF" },
-                            { "category": "HARM_CATEGORY_HATE_SPEECH", "threshold": "OFF" },
-                            { "category": "HARM_CATEGORY_SEXUALLY_EXPLICIT", "threshold": "OFF" },
-                            { "category": "HARM_CATEGORY_DANGEROUS_CONTENT", "threshold": "OFF" },
-                            { "category": "HARM_CATEGORY_CIVIC_INTEGRITY", "threshold": "OFF" },
-                        ]
+                        "safetySettings": safety_settings,
                     }
                 });
 
@@ -443,8 +648,24 @@ pub async fn handle_images_generations(
                         let status = response.status();
                         if !status.is_success() {
                             let status_code = status.as_u16();
+                            let retry_after = response
+                                .headers()
+                                .get("Retry-After")
+                                .and_then(|h| h.to_str().ok())
+                                .map(str::to_string);
                             let err_text = response.text().await.unwrap_or_default();
                             last_error = format!("Upstream error {}: {}", status_code, err_text);
+                            token_manager
+                                .report_outcome(
+                                    &token.account_id,
+                                    Some("gemini-3-pro-image"),
+                                    UpstreamOutcome::HttpError {
+                                        status: status_code,
+                                        retry_after: retry_after.as_deref(),
+                                        body: &err_text,
+                                    },
+                                )
+                                .await;
 
                             // 429/500/503 errors: mark rate limited and retry with rotation
                             if status_code == 429 || status_code == 503 || status_code == 500 {
@@ -460,7 +681,13 @@ pub async fn handle_images_generations(
                         }
                         match response.json::<Value>().await {
                             Ok(json) => {
-                                token_manager.mark_success(&token.account_id);
+                                token_manager
+                                    .report_outcome(
+                                        &token.account_id,
+                                        Some("gemini-3-pro-image"),
+                                        UpstreamOutcome::Success,
+                                    )
+                                    .await;
                                 return Ok((json, response_format.clone(), token.email));
                             }
                             Err(e) => return Err(format!("Parse error: {}", e)),
@@ -468,6 +695,13 @@ pub async fn handle_images_generations(
                     }
                     Err(e) => {
                         last_error = format!("Network error: {}", e);
+                        token_manager
+                            .report_outcome(
+                                &token.account_id,
+                                Some("gemini-3-pro-image"),
+                                UpstreamOutcome::Transport,
+                            )
+                            .await;
                         continue;
                     }
                 }
@@ -583,6 +817,7 @@ pub async fn handle_images_generations(
 /// - Mask-based editing (image + mask + prompt)
 pub async fn handle_images_edits(
     State(state): State<AppState>,
+    identity: Option<Extension<UserTokenIdentity>>,
     mut multipart: axum::extract::Multipart,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
     info!("[Images] Received edit request");
@@ -734,6 +969,10 @@ pub async fn handle_images_edits(
     let max_attempts = MAX_RETRY_ATTEMPTS
         .min(max_pool_size.saturating_add(1))
         .max(2);
+    let safety_settings = build_safety_settings(
+        &model,
+        identity.as_ref().map(|Extension(identity)| identity.token_id.as_str()),
+    );
 
     let mut tasks = Vec::new();
     for _ in 0..n {
@@ -743,6 +982,7 @@ pub async fn handle_images_edits(
         let image_config = image_config.clone();
         let response_format = response_format.clone();
         let model = model.clone();
+        let safety_settings = safety_settings.clone();
 
         tasks.push(tokio::spawn(async move {
             let mut last_error = String::new();
@@ -783,13 +1023,7 @@ pub async fn handle_images_edits(
                             "topP": 0.95,
                             "topK": 40
                         },
-                        "safetySettings": [
-                            { "category": "HARM_CATEGORY_HARASSMENT", "threshold": "OFF" },
-                            { "category": "HARM_CATEGORY_HATE_SPEECH", "threshold": "OFF" },
-                            { "category": "HARM_CATEGORY_SEXUALLY_EXPLICIT", "threshold": "OFF" },
-                            { "category": "HARM_CATEGORY_DANGEROUS_CONTENT", "threshold": "OFF" },
-                            { "category": "HARM_CATEGORY_CIVIC_INTEGRITY", "threshold": "OFF" },
-                        ]
+                        "safetySettings": safety_settings,
                     }
                 });
 
@@ -808,8 +1042,24 @@ pub async fn handle_images_edits(
                         let status = response.status();
                         if !status.is_success() {
                             let status_code = status.as_u16();
+                            let retry_after = response
+                                .headers()
+                                .get("Retry-After")
+                                .and_then(|h| h.to_str().ok())
+                                .map(str::to_string);
                             let err_text = response.text().await.unwrap_or_default();
                             last_error = format!("Upstream error {}: {}", status_code, err_text);
+                            token_manager
+                                .report_outcome(
+                                    &token.account_id,
+                                    Some(model.as_str()),
+                                    UpstreamOutcome::HttpError {
+                                        status: status_code,
+                                        retry_after: retry_after.as_deref(),
+                                        body: &err_text,
+                                    },
+                                )
+                                .await;
 
                             if status_code == 429 || status_code == 503 || status_code == 500 {
                                 tracing::warn!(
@@ -823,7 +1073,13 @@ pub async fn handle_images_edits(
                         }
                         match response.json::<Value>().await {
                             Ok(json) => {
-                                token_manager.mark_success(&token.account_id);
+                                token_manager
+                                    .report_outcome(
+                                        &token.account_id,
+                                        Some(model.as_str()),
+                                        UpstreamOutcome::Success,
+                                    )
+                                    .await;
                                 return Ok((json, response_format.clone(), token.email));
                             }
                             Err(e) => return Err(format!("Parse error: {}", e)),
@@ -831,6 +1087,13 @@ pub async fn handle_images_edits(
                     }
                     Err(e) => {
                         last_error = format!("Network error: {}", e);
+                        token_manager
+                            .report_outcome(
+                                &token.account_id,
+                                Some(model.as_str()),
+                                UpstreamOutcome::Transport,
+                            )
+                            .await;
                         continue;
                     }
                 }
@@ -934,8 +1197,13 @@ pub async fn handle_images_edits(
 /// Converts prompt-based requests to chat format and delegates to chat completions logic.
 pub async fn handle_completions(
     State(state): State<AppState>,
+    identity: Option<Extension<UserTokenIdentity>>,
+    headers: HeaderMap,
     Json(mut body): Json<Value>,
 ) -> Response {
+    let token_id = identity.map(|Extension(identity)| identity.token_id);
+    let header_sid = header_session_id(&headers);
+
     // Convert prompt to messages format
     if let Some(prompt_val) = body.get("prompt").cloned() {
         let prompt_str = match &prompt_val {
@@ -998,14 +1266,28 @@ pub async fn handle_completions(
     );
 
     let mut last_error = String::new();
+    let mut last_deadline: Option<DeadlineClock> = None;
 
+    // 总时长从客户端请求开始计时，换号重试不重新计时
+    let request_started = Instant::now();
     for attempt in 0..max_attempts {
-        let session_id =
-            SessionManager::extract_openai_session_id(&serde_json::to_value(&openai_req).unwrap());
+        let session_id = SessionManager::resolve_openai_session_id(
+            &serde_json::to_value(&openai_req).unwrap(),
+            header_sid.as_deref(),
+            token_id.as_deref(),
+        );
 
-        let token = match token_manager
-            .get_token(&mapped_model, Some(&session_id))
-            .await
+        let FallbackToken {
+            token,
+            model: mapped_model,
+            fallback_from,
+        } = match get_token_with_fallback(
+            &token_manager,
+            &mapped_model,
+            Some(&session_id),
+            token_id.as_deref(),
+        )
+        .await
         {
             Ok(t) => t,
             Err(e) => {
@@ -1018,23 +1300,70 @@ pub async fn handle_completions(
         };
 
         let project_id = token.project_id.clone().unwrap_or_default();
-        let (gemini_body, _session_id, message_count) =
-            transform_openai_request(&openai_req, &project_id, &mapped_model);
+        let tool_result_limit = tool_result_limit_for_request(&mapped_model, &headers);
+        let (mut gemini_body, _session_id, message_count) =
+            transform_openai_request_with_tool_limit(
+                &openai_req,
+                &project_id,
+                &mapped_model,
+                tool_result_limit,
+            );
+        apply_to_upstream_body(&mut gemini_body, &mapped_model, token_id.as_deref(), false);
+        apply_request_policy(
+            &mut gemini_body,
+            &mapped_model,
+            &PolicyInput::from_openai(&openai_req),
+            &RequestPolicy::current(),
+        );
+        if get_experimental_config().enable_tool_loop_recovery {
+            if let Some(report) = apply_tool_loop_recovery(&mut gemini_body) {
+                tracing::warn!(
+                    "[completions] Tool loop detected: {:?} repeated {} times (broken: {})",
+                    report.tool_names,
+                    report.repeats,
+                    report.broken
+                );
+            }
+        }
+        let usage_scale = usage_scale_factor(&openai_req.model, &mapped_model);
+
+        let deadline =
+            DeadlineClock::new(request_started, deadlines_for_request(&mapped_model, &headers));
+        last_deadline = Some(deadline);
+        if deadline.expired() {
+            break;
+        }
+        let recovery_body = stream_recovery_enabled().then(|| gemini_body.clone());
+        let hedge = HedgeContext::for_model(
+            token_manager.clone(),
+            upstream.clone(),
+            &gemini_body,
+            &mapped_model,
+            &token.account_id,
+            "completions",
+            &deadline,
+        );
 
         // Always use stream internally for better quota usage
         let call_result = match upstream
-            .call_v1_internal(
+            .call_v1_internal_with_deadline(
                 "streamGenerateContent",
                 &token.access_token,
                 gemini_body,
                 Some("alt=sse"),
                 Some(token.account_id.as_str()),
+                deadline,
             )
             .await
         {
             Ok(r) => r,
             Err(e) => {
                 last_error = e;
+                if !deadline.expired_by_client() {
+                    token_manager
+                        .report_outcome(&token.account_id, Some(mapped_model.as_str()), UpstreamOutcome::Transport)
+                        .await;
+                }
                 continue;
             }
         };
@@ -1043,14 +1372,82 @@ pub async fn handle_completions(
         let status = response.status();
 
         if status.is_success() {
-            token_manager.mark_success(&token.account_id);
-
             // Collect stream and convert to legacy format
             use crate::proxy::mappers::openai::collector::collect_stream_to_json;
             use crate::proxy::mappers::openai::streaming::create_openai_sse_stream;
 
+            let (peek_outcome, hedge_winner) = peek_within_deadline(
+                &deadline,
+                peek_with_hedge(Box::pin(response.bytes_stream()), hedge),
+            )
+            .await;
+            // 对冲请求胜出时后续以对冲账号为准 (成功已在对冲路径上报，被取消的主请求不计)
+            let hedge_won = hedge_winner.is_some();
+            let token = hedge_winner.unwrap_or(token);
+            let upstream_stream = match peek_outcome {
+                PeekOutcome::Ready(stream) => {
+                    // 200 仅代表响应头到达，收到首个有效内容后才计为成功
+                    if !hedge_won {
+                        token_manager
+                            .report_outcome(
+                                &token.account_id,
+                                Some(mapped_model.as_str()),
+                                UpstreamOutcome::Success,
+                            )
+                            .await;
+                    }
+                    stream
+                }
+                PeekOutcome::Failed { error_type, message } => {
+                    // 尚未向客户端发送任何内容，透明换账号重试
+                    last_error = format!("Stream failed before first token ({}): {}", error_type, message);
+                    tracing::warn!(
+                        "Upstream stream on {} failed before first token, attempt {}/{}: {}",
+                        token.email,
+                        attempt + 1,
+                        max_attempts,
+                        last_error
+                    );
+                    if !deadline.expired_by_client() {
+                        token_manager
+                            .report_outcome(
+                                &token.account_id,
+                                Some(mapped_model.as_str()),
+                                UpstreamOutcome::StreamInterrupted,
+                            )
+                            .await;
+                    }
+                    continue;
+                }
+            };
+
+            // 响应流结束前持有账号并发租约
+            let upstream_stream = hold_lease(upstream_stream, token.lease.clone());
+            // 相邻数据块间隔超时视为断流
+            let upstream_stream = with_idle_timeout(upstream_stream, deadline.stream_idle());
+
+            // 可选：中途断流时换账号续写
+            let upstream_stream = match recovery_body {
+                Some(body) => with_stream_recovery(
+                    upstream_stream,
+                    RecoveryContext::new(
+                        token_manager.clone(),
+                        upstream.clone(),
+                        body,
+                        &mapped_model,
+                        &token.account_id,
+                        "completions",
+                        &deadline,
+                    ),
+                ),
+                None => upstream_stream,
+            };
+            // 总时长套在断流续写之外，续写不重新计时
+            let upstream_stream = with_total_deadline(upstream_stream, &deadline);
+            let upstream_stream = scale_usage_stream(upstream_stream, usage_scale);
+
             let openai_stream = create_openai_sse_stream(
-                Box::pin(response.bytes_stream()),
+                upstream_stream,
                 openai_req.model.clone(),
                 _session_id,
                 message_count,
@@ -1083,7 +1480,7 @@ pub async fn handle_completions(
                         "usage": chat_resp.usage
                     });
 
-                    return (
+                    let client_response = (
                         StatusCode::OK,
                         [
                             ("X-Account-Email", token.email.as_str()),
@@ -1092,6 +1489,7 @@ pub async fn handle_completions(
                         Json(legacy_resp),
                     )
                         .into_response();
+                    return with_fallback_header(client_response, fallback_from.as_deref());
                 }
                 Err(e) => {
                     return (
@@ -1104,8 +1502,24 @@ pub async fn handle_completions(
         }
 
         let status_code = status.as_u16();
+        let retry_after = response
+            .headers()
+            .get("Retry-After")
+            .and_then(|h| h.to_str().ok())
+            .map(str::to_string);
         let error_text = response.text().await.unwrap_or_default();
         last_error = format!("HTTP {}: {}", status_code, error_text);
+        token_manager
+            .report_outcome(
+                &token.account_id,
+                Some(mapped_model.as_str()),
+                UpstreamOutcome::HttpError {
+                    status: status_code,
+                    retry_after: retry_after.as_deref(),
+                    body: &error_text,
+                },
+            )
+            .await;
 
         let strategy = determine_retry_strategy(status_code, &error_text, false);
         let trace_id = format!("completions_{}", attempt);
@@ -1114,6 +1528,10 @@ pub async fn handle_completions(
         }
     }
 
+    if let Some(deadline) = last_deadline.filter(DeadlineClock::expired) {
+        return deadline_exceeded_response(&deadline, &last_error);
+    }
+
     (
         StatusCode::TOO_MANY_REQUESTS,
         format!("All accounts exhausted. Last error: {}", last_error),
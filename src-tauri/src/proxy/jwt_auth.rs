@@ -0,0 +1,680 @@
+// JWT / OIDC 客户端鉴权
+//
+// - 校验内部 IdP 签发的短期 JWT (iss / aud / exp / nbf / 签名)
+// - JWKS 从 URL 或本地文件加载，按间隔缓存刷新；遇到未知 kid 时提前刷新
+// - 将 sub、用户组 claim 映射为调用方身份
+// - 可选的配额 claim 作为每日 token 上限，按 sub 统计当日用量
+
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::{Mutex, RwLock};
+use tracing::{debug, warn};
+
+use crate::models::config::JwtAuthConfig;
+
+/// 未知 kid 触发强制刷新的最小间隔，防止伪造 kid 打爆 IdP
+const MIN_FORCED_REFRESH: Duration = Duration::from_secs(30);
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// 从 JWT claims 映射出的调用方身份
+#[derive(Debug, Clone, PartialEq)]
+pub struct JwtIdentity {
+    pub subject: String,
+    pub username: String,
+    pub groups: Vec<String>,
+    /// quota_claim 对应的每日 token 上限
+    pub quota: Option<i64>,
+}
+
+/// JWT 校验失败
+#[derive(Debug, Clone, PartialEq)]
+pub enum JwtAuthError {
+    /// 令牌无效（签名、过期、iss/aud 不匹配、JWKS 不可用等）→ 401
+    Invalid(String),
+    /// 令牌有效但不允许访问（用户组不匹配）→ 403
+    Forbidden(String),
+}
+
+struct CachedJwks {
+    keys: Arc<JwkSet>,
+    fetched_at: Instant,
+}
+
+// ============================================================================
+// JwtVerifier
+// ============================================================================
+
+pub struct JwtVerifier {
+    config: JwtAuthConfig,
+    algorithms: Vec<Algorithm>,
+    jwks: RwLock<Option<CachedJwks>>,
+    /// 同一时间只有一个请求访问 IdP；持有期间不占用 jwks 锁
+    refresh: Mutex<()>,
+    http: reqwest::Client,
+}
+
+impl JwtVerifier {
+    pub fn new(config: &JwtAuthConfig) -> Self {
+        let algorithms = config
+            .algorithms
+            .iter()
+            .filter_map(|name| match Algorithm::from_str(name) {
+                // 只接受非对称算法，避免用公开的 JWKS 伪造 HMAC 签名
+                Ok(Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512) | Err(_) => {
+                    warn!("[JWT] Ignoring unsupported algorithm '{}'", name);
+                    None
+                }
+                Ok(alg) => Some(alg),
+            })
+            .collect();
+        if config.enabled && config.issuer.is_empty() {
+            warn!("[JWT] JWT auth is enabled without an issuer; all JWTs will be rejected");
+        }
+
+        Self {
+            config: config.clone(),
+            algorithms,
+            jwks: RwLock::new(None),
+            refresh: Mutex::new(()),
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    async fn load_jwks(&self) -> Result<JwkSet, String> {
+        if let Some(url) = self.config.jwks_url.as_deref().filter(|u| !u.is_empty()) {
+            let response = self
+                .http
+                .get(url)
+                .send()
+                .await
+                .map_err(|e| format!("Failed to fetch JWKS from {}: {}", url, e))?;
+            if !response.status().is_success() {
+                return Err(format!("Failed to fetch JWKS from {}: HTTP {}", url, response.status()));
+            }
+            return response
+                .json::<JwkSet>()
+                .await
+                .map_err(|e| format!("Invalid JWKS from {}: {}", url, e));
+        }
+
+        if let Some(path) = self.config.jwks_path.as_deref().filter(|p| !p.is_empty()) {
+            let content = tokio::fs::read_to_string(path)
+                .await
+                .map_err(|e| format!("Failed to read JWKS file {}: {}", path, e))?;
+            return serde_json::from_str(&content)
+                .map_err(|e| format!("Invalid JWKS file {}: {}", path, e));
+        }
+
+        Err("Neither jwks_url nor jwks_path is configured".to_string())
+    }
+
+    fn needs_refresh(&self, cached: Option<&CachedJwks>, force: bool) -> bool {
+        let refresh_after = Duration::from_secs(self.config.jwks_refresh_secs.max(1));
+        match cached {
+            None => true,
+            Some(cached) => {
+                let age = cached.fetched_at.elapsed();
+                age >= refresh_after || (force && age >= MIN_FORCED_REFRESH)
+            }
+        }
+    }
+
+    /// 获取 JWKS；缓存过期或 force 时重新加载，加载失败时沿用旧缓存
+    async fn key_set(&self, force: bool) -> Result<Arc<JwkSet>, String> {
+        {
+            let cached = self.jwks.read().await;
+            if !self.needs_refresh(cached.as_ref(), force) {
+                return Ok(cached.as_ref().unwrap().keys.clone());
+            }
+        }
+
+        let _refresh = self.refresh.lock().await;
+        // 其他请求可能已经刷新过
+        {
+            let cached = self.jwks.read().await;
+            if !self.needs_refresh(cached.as_ref(), force) {
+                return Ok(cached.as_ref().unwrap().keys.clone());
+            }
+        }
+
+        // 在 jwks 锁外加载，IdP 响应慢时不阻塞使用缓存的校验请求
+        let loaded = self.load_jwks().await;
+
+        let mut cached = self.jwks.write().await;
+        match loaded {
+            Ok(keys) => {
+                debug!("[JWT] Loaded JWKS with {} key(s)", keys.keys.len());
+                let keys = Arc::new(keys);
+                *cached = Some(CachedJwks {
+                    keys: keys.clone(),
+                    fetched_at: Instant::now(),
+                });
+                Ok(keys)
+            }
+            Err(e) => match cached.as_mut() {
+                Some(current) => {
+                    warn!("[JWT] {}; keeping previously loaded keys", e);
+                    // 推迟下一次重试，避免每个请求都访问 IdP
+                    current.fetched_at = Instant::now();
+                    Ok(current.keys.clone())
+                }
+                None => Err(e),
+            },
+        }
+    }
+
+    #[cfg(test)]
+    async fn set_key_set(&self, keys: JwkSet) {
+        *self.jwks.write().await = Some(CachedJwks {
+            keys: Arc::new(keys),
+            fetched_at: Instant::now(),
+        });
+    }
+
+    /// 校验 JWT 并映射身份
+    pub async fn verify(&self, token: &str) -> Result<JwtIdentity, JwtAuthError> {
+        // 未配置 issuer 时拒绝所有令牌，而不是跳过 iss 校验
+        if self.config.issuer.is_empty() {
+            return Err(JwtAuthError::Invalid(
+                "JWT auth is enabled but no issuer is configured".to_string(),
+            ));
+        }
+        let header = jsonwebtoken::decode_header(token)
+            .map_err(|e| JwtAuthError::Invalid(format!("Malformed JWT: {}", e)))?;
+        if !self.algorithms.contains(&header.alg) {
+            return Err(JwtAuthError::Invalid(format!(
+                "JWT algorithm {:?} is not allowed",
+                header.alg
+            )));
+        }
+
+        let mut keys = self.key_set(false).await.map_err(JwtAuthError::Invalid)?;
+        if let Some(kid) = header.kid.as_deref() {
+            if keys.find(kid).is_none() {
+                // 可能是 IdP 轮换了签名密钥
+                keys = self.key_set(true).await.map_err(JwtAuthError::Invalid)?;
+            }
+        }
+
+        let jwk = match header.kid.as_deref() {
+            Some(kid) => keys.find(kid),
+            None if keys.keys.len() == 1 => keys.keys.first(),
+            None => None,
+        }
+        .ok_or_else(|| JwtAuthError::Invalid("No matching JWKS key for JWT".to_string()))?;
+
+        let key = DecodingKey::from_jwk(jwk)
+            .map_err(|e| JwtAuthError::Invalid(format!("Unusable JWKS key: {}", e)))?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.algorithms = vec![header.alg];
+        validation.leeway = self.config.leeway_secs;
+        validation.validate_nbf = true;
+        validation.set_issuer(&[self.config.issuer.as_str()]);
+        if self.config.audiences.is_empty() {
+            validation.validate_aud = false;
+        } else {
+            validation.set_audience(&self.config.audiences);
+        }
+
+        let data = jsonwebtoken::decode::<Value>(token, &key, &validation)
+            .map_err(|e| JwtAuthError::Invalid(format!("JWT rejected: {}", e)))?;
+
+        identity_from_claims(&self.config, &data.claims)
+    }
+}
+
+// ============================================================================
+// Claim mapping
+// ============================================================================
+
+/// 将 claims 映射为身份，并检查 allowed_groups
+pub fn identity_from_claims(config: &JwtAuthConfig, claims: &Value) -> Result<JwtIdentity, JwtAuthError> {
+    let subject = claims
+        .get("sub")
+        .and_then(Value::as_str)
+        .filter(|s| !s.is_empty())
+        .ok_or_else(|| JwtAuthError::Invalid("JWT has no 'sub' claim".to_string()))?
+        .to_string();
+
+    let username = claims
+        .get(&config.username_claim)
+        .and_then(Value::as_str)
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| subject.clone());
+
+    let groups: Vec<String> = match claims.get(&config.groups_claim) {
+        Some(Value::Array(items)) => items
+            .iter()
+            .filter_map(Value::as_str)
+            .map(|s| s.to_string())
+            .collect(),
+        Some(Value::String(s)) => s
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|g| !g.is_empty())
+            .map(|g| g.to_string())
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    if !config.allowed_groups.is_empty() {
+        let allowed: HashSet<&str> = config.allowed_groups.iter().map(|g| g.as_str()).collect();
+        if !groups.iter().any(|g| allowed.contains(g.as_str())) {
+            return Err(JwtAuthError::Forbidden(format!(
+                "User '{}' is not in an allowed group",
+                username
+            )));
+        }
+    }
+
+    let quota = config
+        .quota_claim
+        .as_deref()
+        .and_then(|name| claims.get(name))
+        .and_then(|v| v.as_i64().or_else(|| v.as_str().and_then(|s| s.parse().ok())));
+
+    Ok(JwtIdentity {
+        subject,
+        username,
+        groups,
+        quota,
+    })
+}
+
+/// 粗略判断凭据是否为 JWT（三段式，header 以 base64 的 "{" 开头）
+pub fn looks_like_jwt(token: &str) -> bool {
+    token.starts_with("eyJ") && token.split('.').count() == 3
+}
+
+// ============================================================================
+// Daily quota (quota_claim)
+// ============================================================================
+
+/// 某个 JWT 调用方的配额与当日用量
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct JwtQuotaUsage {
+    pub subject: String,
+    pub username: String,
+    pub quota: i64,
+    pub used_today: u64,
+}
+
+struct DailyUsage {
+    day: i64,
+    username: String,
+    quota: i64,
+    used: u64,
+}
+
+static QUOTA_USAGE: OnceLock<std::sync::Mutex<HashMap<String, DailyUsage>>> = OnceLock::new();
+
+fn quota_usage() -> &'static std::sync::Mutex<HashMap<String, DailyUsage>> {
+    QUOTA_USAGE.get_or_init(|| std::sync::Mutex::new(HashMap::new()))
+}
+
+/// UTC 自然日序号，跨日后用量清零
+fn current_day() -> i64 {
+    chrono::Utc::now().timestamp().div_euclid(86_400)
+}
+
+/// 当日用量是否已达到令牌中的配额（无配额时总是 false）
+///
+/// 同时登记调用方的用户名与最新配额，供统计接口展示。
+pub fn quota_exhausted(identity: &JwtIdentity) -> bool {
+    let Some(quota) = identity.quota else {
+        return false;
+    };
+    let day = current_day();
+    let Ok(mut usage) = quota_usage().lock() else {
+        return false;
+    };
+    let entry = usage
+        .entry(identity.subject.clone())
+        .or_insert_with(|| DailyUsage {
+            day,
+            username: identity.username.clone(),
+            quota,
+            used: 0,
+        });
+    if entry.day != day {
+        entry.day = day;
+        entry.used = 0;
+    }
+    entry.username = identity.username.clone();
+    entry.quota = quota;
+    entry.used >= quota.max(0) as u64
+}
+
+/// 记录一次请求消耗的 token (仅对已登记配额的调用方计数)
+pub fn record_quota_usage(subject: &str, tokens: u64) {
+    let day = current_day();
+    let Ok(mut usage) = quota_usage().lock() else {
+        return;
+    };
+    if let Some(entry) = usage.get_mut(subject) {
+        if entry.day != day {
+            entry.day = day;
+            entry.used = 0;
+        }
+        entry.used = entry.used.saturating_add(tokens);
+    }
+}
+
+/// 各 JWT 调用方的配额与当日用量
+pub fn quota_usage_stats() -> Vec<JwtQuotaUsage> {
+    let day = current_day();
+    let Ok(usage) = quota_usage().lock() else {
+        return Vec::new();
+    };
+    let mut stats: Vec<JwtQuotaUsage> = usage
+        .iter()
+        .map(|(subject, entry)| JwtQuotaUsage {
+            subject: subject.clone(),
+            username: entry.username.clone(),
+            quota: entry.quota,
+            used_today: if entry.day == day { entry.used } else { 0 },
+        })
+        .collect();
+    stats.sort_by(|a, b| a.subject.cmp(&b.subject));
+    stats
+}
+
+// ============================================================================
+// Global verifier (JWKS cache survives security config hot updates)
+// ============================================================================
+
+static JWT_VERIFIER: OnceLock<std::sync::RwLock<Option<Arc<JwtVerifier>>>> = OnceLock::new();
+
+/// 获取与当前配置对应的校验器；配置变化时重建（并丢弃旧的 JWKS 缓存）
+pub fn verifier_for(config: &JwtAuthConfig) -> Option<Arc<JwtVerifier>> {
+    if !config.enabled {
+        return None;
+    }
+    let slot = JWT_VERIFIER.get_or_init(|| std::sync::RwLock::new(None));
+    if let Some(existing) = slot.read().unwrap().as_ref() {
+        if existing.config == *config {
+            return Some(existing.clone());
+        }
+    }
+    let mut guard = slot.write().unwrap();
+    match guard.as_ref() {
+        Some(existing) if existing.config == *config => Some(existing.clone()),
+        _ => {
+            let verifier = Arc::new(JwtVerifier::new(config));
+            *guard = Some(verifier.clone());
+            Some(verifier)
+        }
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::Engine;
+    use jsonwebtoken::{EncodingKey, Header};
+    use serde_json::json;
+
+    struct TestIdp {
+        key_pair: rcgen::KeyPair,
+        jwks: JwkSet,
+    }
+
+    impl TestIdp {
+        fn new(kid: &str) -> Self {
+            let key_pair = rcgen::KeyPair::generate().unwrap();
+            // Uncompressed P-256 point: 0x04 || X || Y
+            let raw = key_pair.public_key_raw();
+            let b64 = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+            let jwks: JwkSet = serde_json::from_value(json!({
+                "keys": [{
+                    "kty": "EC",
+                    "crv": "P-256",
+                    "kid": kid,
+                    "alg": "ES256",
+                    "x": b64.encode(&raw[1..33]),
+                    "y": b64.encode(&raw[33..65]),
+                }]
+            }))
+            .unwrap();
+            Self { key_pair, jwks }
+        }
+
+        fn sign(&self, kid: Option<&str>, claims: &Value) -> String {
+            let mut header = Header::new(Algorithm::ES256);
+            header.kid = kid.map(|k| k.to_string());
+            let key = EncodingKey::from_ec_pem(self.key_pair.serialize_pem().as_bytes()).unwrap();
+            jsonwebtoken::encode(&header, claims, &key).unwrap()
+        }
+    }
+
+    fn config() -> JwtAuthConfig {
+        JwtAuthConfig {
+            enabled: true,
+            issuer: "https://idp.example".to_string(),
+            audiences: vec!["kiro-gateway".to_string()],
+            quota_claim: Some("daily_tokens".to_string()),
+            ..JwtAuthConfig::default()
+        }
+    }
+
+    fn claims(exp_offset: i64) -> Value {
+        let now = chrono::Utc::now().timestamp();
+        json!({
+            "iss": "https://idp.example",
+            "aud": "kiro-gateway",
+            "sub": "u-123",
+            "preferred_username": "alice",
+            "groups": ["ml", "billing"],
+            "daily_tokens": 50000,
+            "iat": now,
+            "exp": now + exp_offset,
+        })
+    }
+
+    async fn verifier_with(config: &JwtAuthConfig, idp: &TestIdp) -> JwtVerifier {
+        let verifier = JwtVerifier::new(config);
+        verifier.set_key_set(idp.jwks.clone()).await;
+        verifier
+    }
+
+    #[tokio::test]
+    async fn test_valid_token_maps_identity() {
+        let idp = TestIdp::new("k1");
+        let mut config = config();
+        config.username_claim = "preferred_username".to_string();
+        let verifier = verifier_with(&config, &idp).await;
+
+        let identity = verifier.verify(&idp.sign(Some("k1"), &claims(300))).await.unwrap();
+        assert_eq!(identity.subject, "u-123");
+        assert_eq!(identity.username, "alice");
+        assert_eq!(identity.groups, vec!["ml", "billing"]);
+        assert_eq!(identity.quota, Some(50000));
+    }
+
+    #[tokio::test]
+    async fn test_expired_token_rejected() {
+        let idp = TestIdp::new("k1");
+        let verifier = verifier_with(&config(), &idp).await;
+        let result = verifier.verify(&idp.sign(Some("k1"), &claims(-3600))).await;
+        assert!(matches!(result, Err(JwtAuthError::Invalid(_))));
+    }
+
+    #[tokio::test]
+    async fn test_wrong_issuer_and_audience_rejected() {
+        let idp = TestIdp::new("k1");
+        let verifier = verifier_with(&config(), &idp).await;
+
+        let mut bad_iss = claims(300);
+        bad_iss["iss"] = json!("https://evil.example");
+        assert!(verifier.verify(&idp.sign(Some("k1"), &bad_iss)).await.is_err());
+
+        let mut bad_aud = claims(300);
+        bad_aud["aud"] = json!("other-service");
+        assert!(verifier.verify(&idp.sign(Some("k1"), &bad_aud)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_missing_issuer_rejects_all_tokens() {
+        let idp = TestIdp::new("k1");
+        let mut config = config();
+        config.issuer = String::new();
+        let verifier = verifier_with(&config, &idp).await;
+        let result = verifier.verify(&idp.sign(Some("k1"), &claims(300))).await;
+        assert!(matches!(result, Err(JwtAuthError::Invalid(_))));
+    }
+
+    #[tokio::test]
+    async fn test_token_signed_by_other_key_rejected() {
+        let idp = TestIdp::new("k1");
+        let attacker = TestIdp::new("k1");
+        let verifier = verifier_with(&config(), &idp).await;
+        let result = verifier.verify(&attacker.sign(Some("k1"), &claims(300))).await;
+        assert!(matches!(result, Err(JwtAuthError::Invalid(_))));
+    }
+
+    #[tokio::test]
+    async fn test_token_without_kid_uses_single_key() {
+        let idp = TestIdp::new("k1");
+        let verifier = verifier_with(&config(), &idp).await;
+        assert!(verifier.verify(&idp.sign(None, &claims(300))).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_disallowed_algorithm_rejected() {
+        let idp = TestIdp::new("k1");
+        let mut config = config();
+        config.algorithms = vec!["RS256".to_string()];
+        let verifier = verifier_with(&config, &idp).await;
+        let result = verifier.verify(&idp.sign(Some("k1"), &claims(300))).await;
+        assert!(matches!(result, Err(JwtAuthError::Invalid(_))));
+    }
+
+    #[tokio::test]
+    async fn test_hmac_algorithms_are_never_accepted() {
+        let mut config = config();
+        config.algorithms = vec!["HS256".to_string()];
+        let verifier = JwtVerifier::new(&config);
+        assert!(verifier.algorithms.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_jwks_loaded_from_file() {
+        let idp = TestIdp::new("k1");
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("jwks.json");
+        std::fs::write(&path, serde_json::to_string(&idp.jwks).unwrap()).unwrap();
+
+        let mut config = config();
+        config.jwks_path = Some(path.to_string_lossy().to_string());
+        let verifier = JwtVerifier::new(&config);
+        assert!(verifier.verify(&idp.sign(Some("k1"), &claims(300))).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_missing_jwks_source_is_invalid() {
+        let idp = TestIdp::new("k1");
+        let verifier = JwtVerifier::new(&config());
+        let result = verifier.verify(&idp.sign(Some("k1"), &claims(300))).await;
+        assert!(matches!(result, Err(JwtAuthError::Invalid(_))));
+    }
+
+    #[test]
+    fn test_allowed_groups_enforced() {
+        let mut config = config();
+        config.allowed_groups = vec!["admins".to_string()];
+        assert!(matches!(
+            identity_from_claims(&config, &claims(300)),
+            Err(JwtAuthError::Forbidden(_))
+        ));
+
+        config.allowed_groups = vec!["billing".to_string()];
+        assert!(identity_from_claims(&config, &claims(300)).is_ok());
+    }
+
+    #[test]
+    fn test_groups_claim_as_string_and_username_fallback() {
+        let config = config();
+        let identity = identity_from_claims(
+            &config,
+            &json!({ "sub": "svc-1", "groups": "ml, infra ops", "daily_tokens": "1200" }),
+        )
+        .unwrap();
+        assert_eq!(identity.username, "svc-1");
+        assert_eq!(identity.groups, vec!["ml", "infra", "ops"]);
+        assert_eq!(identity.quota, Some(1200));
+    }
+
+    #[test]
+    fn test_daily_quota_tracks_usage_per_subject() {
+        let identity = JwtIdentity {
+            subject: "quota-test-user".to_string(),
+            username: "quota".to_string(),
+            groups: Vec::new(),
+            quota: Some(100),
+        };
+        assert!(!quota_exhausted(&identity));
+
+        record_quota_usage("quota-test-user", 60);
+        assert!(!quota_exhausted(&identity));
+        record_quota_usage("quota-test-user", 40);
+        assert!(quota_exhausted(&identity));
+
+        // 配额提高后立即放行
+        let raised = JwtIdentity { quota: Some(500), ..identity.clone() };
+        assert!(!quota_exhausted(&raised));
+
+        let stats = quota_usage_stats();
+        let entry = stats.iter().find(|s| s.subject == "quota-test-user").unwrap();
+        assert_eq!(entry.quota, 500);
+        assert_eq!(entry.used_today, 100);
+
+        // 无配额的调用方不计数
+        record_quota_usage("quota-test-unknown", 10);
+        assert!(!quota_usage_stats().iter().any(|s| s.subject == "quota-test-unknown"));
+    }
+
+    #[test]
+    fn test_missing_subject_rejected() {
+        assert!(identity_from_claims(&config(), &json!({ "groups": [] })).is_err());
+    }
+
+    #[test]
+    fn test_looks_like_jwt() {
+        assert!(looks_like_jwt("eyJhbGciOiJFUzI1NiJ9.eyJzdWIiOiJ4In0.sig"));
+        assert!(!looks_like_jwt("sk-1234567890"));
+        assert!(!looks_like_jwt("eyJonly.two"));
+    }
+
+    #[test]
+    fn test_verifier_for_reuses_instance_until_config_changes() {
+        let config = config();
+        let a = verifier_for(&config).unwrap();
+        let b = verifier_for(&config).unwrap();
+        assert!(Arc::ptr_eq(&a, &b));
+
+        let mut changed = config.clone();
+        changed.issuer = "https://other.example".to_string();
+        let c = verifier_for(&changed).unwrap();
+        assert!(!Arc::ptr_eq(&a, &c));
+
+        assert!(verifier_for(&JwtAuthConfig::default()).is_none());
+    }
+}
@@ -13,7 +13,7 @@ pub mod streaming;
 pub use models::*;
 pub use request::{
     clean_cache_control_from_messages, merge_consecutive_messages, transform_claude_request,
-    estimate_token_count,
+    transform_claude_request_with_options, estimate_token_count, TransformOptions,
 };
 pub use response::transform_response;
 pub use streaming::{create_claude_sse_stream, PartProcessor, StreamingState};
@@ -288,6 +288,17 @@ pub struct GeminiResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(rename = "responseId")]
     pub response_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "promptFeedback")]
+    pub prompt_feedback: Option<PromptFeedback>,
+}
+
+/// Prompt-level feedback (set when the prompt itself was blocked)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptFeedback {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "blockReason")]
+    pub block_reason: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
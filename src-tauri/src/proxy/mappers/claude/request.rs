@@ -5,20 +5,16 @@
 // - 2.15: /v1/messages/count_tokens
 
 use super::models::*;
+use crate::proxy::common::request_policy::model_supports_thinking;
+use crate::proxy::common::safety::build_safety_settings;
+use crate::proxy::common::tool_result_compressor::{
+    cap_tool_result, compress_tool_result, default_tool_result_limit,
+    sanitize_tool_result_blocks_with_limit,
+};
+use crate::proxy::signature_cache::SignatureCache;
 use serde_json::{json, Value};
 use std::collections::HashMap;
 
-/// Build safety settings for Gemini API (all filters disabled for proxy compatibility)
-fn build_safety_settings() -> Value {
-    json!([
-        { "category": "HARM_CATEGORY_HARASSMENT", "threshold": "OFF" },
-        { "category": "HARM_CATEGORY_HATE_SPEECH", "threshold": "OFF" },
-        { "category": "HARM_CATEGORY_SEXUALLY_EXPLICIT", "threshold": "OFF" },
-        { "category": "HARM_CATEGORY_DANGEROUS_CONTENT", "threshold": "OFF" },
-        { "category": "HARM_CATEGORY_CIVIC_INTEGRITY", "threshold": "OFF" },
-    ])
-}
-
 /// Clean cache_control fields from messages (clients may send them back in history)
 pub fn clean_cache_control_from_messages(messages: &mut [Message]) {
     for msg in messages.iter_mut() {
@@ -80,6 +76,16 @@ pub fn merge_consecutive_messages(messages: &mut Vec<Message>) {
     *messages = merged;
 }
 
+/// Per-request options for [`transform_claude_request_with_options`]
+#[derive(Debug, Clone, Default)]
+pub struct TransformOptions {
+    /// Tool result character limit (`None` forwards tool results uncompressed)
+    pub tool_result_limit: Option<usize>,
+    /// Session whose cached signatures restore those missing from history
+    /// (`None` disables `SignatureCache` lookups)
+    pub signature_session_id: Option<String>,
+}
+
 /// Transform a Claude/Anthropic Messages request into Gemini generateContent format.
 ///
 /// Returns (gemini_body, session_id, message_count).
@@ -87,6 +93,20 @@ pub fn transform_claude_request(
     claude_req: &ClaudeRequest,
     project_id: &str,
     mapped_model: &str,
+) -> Result<(Value, String, usize), String> {
+    let options = TransformOptions {
+        tool_result_limit: default_tool_result_limit(mapped_model),
+        signature_session_id: None,
+    };
+    transform_claude_request_with_options(claude_req, project_id, mapped_model, &options)
+}
+
+/// Same as [`transform_claude_request`] with explicit per-request options.
+pub fn transform_claude_request_with_options(
+    claude_req: &ClaudeRequest,
+    project_id: &str,
+    mapped_model: &str,
+    options: &TransformOptions,
 ) -> Result<(Value, String, usize), String> {
     let mut cleaned_req = claude_req.clone();
 
@@ -103,17 +123,13 @@ pub fn transform_claude_request(
         &serde_json::to_value(claude_req).unwrap_or_default(),
     );
 
-    let mapped_model_lower = mapped_model.to_lowercase();
-
     // Determine if thinking is enabled
     let thinking_type = claude_req.thinking.as_ref().map(|t| t.type_.as_str());
     let is_thinking_enabled =
         thinking_type == Some("enabled") || thinking_type == Some("adaptive");
 
     // Check if target model supports thinking
-    let target_supports_thinking = mapped_model_lower.contains("-thinking")
-        || mapped_model_lower.contains("gemini-2.0-pro")
-        || mapped_model_lower.contains("gemini-3-pro");
+    let target_supports_thinking = model_supports_thinking(mapped_model);
 
     let actual_thinking = is_thinking_enabled && target_supports_thinking;
 
@@ -131,8 +147,13 @@ pub fn transform_claude_request(
     let system_instruction = build_system_instruction(&claude_req.system);
 
     // 2. Build contents (messages)
-    let contents =
-        build_contents(&claude_req.messages, &mut tool_id_to_name, actual_thinking)?;
+    let contents = build_contents(
+        &claude_req.messages,
+        &mut tool_id_to_name,
+        actual_thinking,
+        options,
+        mapped_model,
+    )?;
 
     // 3. Build tools
     let tools = build_tools(&claude_req.tools, has_web_search)?;
@@ -144,7 +165,7 @@ pub fn transform_claude_request(
     // 5. Assemble inner request
     let mut inner_request = json!({
         "contents": contents,
-        "safetySettings": build_safety_settings(),
+        "safetySettings": build_safety_settings(mapped_model, None),
     });
 
     if let Some(sys_inst) = system_instruction {
@@ -222,17 +243,29 @@ fn build_contents(
     messages: &[Message],
     tool_id_to_name: &mut HashMap<String, String>,
     is_thinking_enabled: bool,
+    options: &TransformOptions,
+    mapped_model: &str,
 ) -> Result<Vec<Value>, String> {
     let mut gemini_contents: Vec<Value> = Vec::new();
+    // L3 会话签名只属于最新一轮 assistant 回复
+    let latest_assistant = messages.iter().rposition(|m| m.role == "assistant");
 
-    for msg in messages {
+    for (index, msg) in messages.iter().enumerate() {
         let role = match msg.role.as_str() {
             "assistant" => "model",
             "user" => "user",
             _ => &msg.role,
         };
 
-        let parts = build_parts(&msg.content, role == "model", tool_id_to_name, is_thinking_enabled)?;
+        let parts = build_parts(
+            &msg.content,
+            role == "model",
+            latest_assistant == Some(index),
+            tool_id_to_name,
+            is_thinking_enabled,
+            options,
+            mapped_model,
+        )?;
 
         if parts.is_empty() {
             continue;
@@ -263,10 +296,27 @@ fn build_contents(
 fn build_parts(
     content: &MessageContent,
     is_assistant: bool,
+    is_latest_assistant: bool,
     tool_id_to_name: &mut HashMap<String, String>,
     is_thinking_enabled: bool,
+    options: &TransformOptions,
+    mapped_model: &str,
 ) -> Result<Vec<Value>, String> {
     let mut parts = Vec::new();
+    let tool_result_limit = options.tool_result_limit;
+
+    // 历史中缺失的签名从 SignatureCache 恢复，并丢弃与目标模型族不兼容的签名；
+    // 会话签名 (L3) 只用于最新一轮 assistant，更早的块只能用各自的工具签名
+    let resolve_signature =
+        |client: Option<&String>, tool_use_ids: &[&str]| match &options.signature_session_id {
+            Some(session_id) => SignatureCache::global().resolve_signature(
+                client.map(|s| s.as_str()),
+                tool_use_ids,
+                Some(session_id.as_str()).filter(|_| is_latest_assistant),
+                mapped_model,
+            ),
+            None => client.filter(|s| !s.is_empty()).cloned(),
+        };
 
     match content {
         MessageContent::String(text) => {
@@ -275,6 +325,14 @@ fn build_parts(
             }
         }
         MessageContent::Array(blocks) => {
+            let sibling_tool_ids: Vec<&str> = blocks
+                .iter()
+                .filter_map(|b| match b {
+                    ContentBlock::ToolUse { id, .. } => Some(id.as_str()),
+                    _ => None,
+                })
+                .collect();
+
             for block in blocks {
                 match block {
                     ContentBlock::Text { text } => {
@@ -297,10 +355,10 @@ fn build_parts(
                                 "text": thinking,
                                 "thought": true,
                             });
-                            if let Some(sig) = signature {
-                                if !sig.is_empty() {
-                                    part["thoughtSignature"] = json!(sig);
-                                }
+                            if let Some(sig) =
+                                resolve_signature(signature.as_ref(), &sibling_tool_ids)
+                            {
+                                part["thoughtSignature"] = json!(sig);
                             }
                             parts.push(part);
                         }
@@ -331,18 +389,26 @@ fn build_parts(
                         }
                     }
                     ContentBlock::ToolUse {
-                        id, name, input, ..
+                        id,
+                        name,
+                        input,
+                        signature,
+                        ..
                     } => {
                         if is_assistant {
                             tool_id_to_name.insert(id.clone(), name.clone());
                         }
-                        parts.push(json!({
+                        let mut part = json!({
                             "functionCall": {
                                 "name": name,
                                 "args": input,
                                 "id": id
                             }
-                        }));
+                        });
+                        if let Some(sig) = resolve_signature(signature.as_ref(), &[id.as_str()]) {
+                            part["thoughtSignature"] = json!(sig);
+                        }
+                        parts.push(part);
                     }
                     ContentBlock::ToolResult {
                         tool_use_id,
@@ -354,16 +420,27 @@ fn build_parts(
                             .cloned()
                             .unwrap_or_else(|| tool_use_id.clone());
 
+                        // 超大工具结果按模型上限压缩 (数组内容逐块压缩)
                         let merged_content = match content {
-                            serde_json::Value::String(s) => s.clone(),
-                            serde_json::Value::Array(arr) => arr
-                                .iter()
-                                .filter_map(|b| {
-                                    b.get("text").and_then(|v| v.as_str()).map(|s| s.to_string())
-                                })
-                                .collect::<Vec<_>>()
-                                .join("\n"),
-                            _ => content.to_string(),
+                            serde_json::Value::String(s) => {
+                                compress_tool_result(s.clone(), tool_result_limit)
+                            }
+                            serde_json::Value::Array(arr) => {
+                                let mut blocks = arr.clone();
+                                if let Some(max_chars) = tool_result_limit {
+                                    sanitize_tool_result_blocks_with_limit(&mut blocks, max_chars);
+                                }
+                                blocks
+                                    .iter()
+                                    .filter_map(|b| {
+                                        b.get("text")
+                                            .and_then(|v| v.as_str())
+                                            .map(|s| s.to_string())
+                                    })
+                                    .collect::<Vec<_>>()
+                                    .join("\n")
+                            }
+                            _ => compress_tool_result(content.to_string(), tool_result_limit),
                         };
 
                         let result_text = if merged_content.trim().is_empty() {
@@ -373,16 +450,8 @@ fn build_parts(
                                 "Command executed successfully.".to_string()
                             }
                         } else {
-                            // Truncate very large tool results
-                            const MAX_TOOL_RESULT_CHARS: usize = 200_000;
-                            if merged_content.len() > MAX_TOOL_RESULT_CHARS {
-                                let mut truncated: String =
-                                    merged_content.chars().take(MAX_TOOL_RESULT_CHARS).collect();
-                                truncated.push_str("\n...[truncated output]");
-                                truncated
-                            } else {
-                                merged_content
-                            }
+                            // 无论是否关闭压缩，都保留 200K 硬上限
+                            cap_tool_result(merged_content)
                         };
 
                         parts.push(json!({
@@ -828,6 +897,148 @@ mod tests {
         assert!(contents.len() >= 2);
     }
 
+    #[test]
+    fn test_signatures_restored_from_cache() {
+        use crate::proxy::signature_cache::SignatureCache;
+
+        let tool_sig = "r".repeat(64);
+        let cache = SignatureCache::global();
+        cache.cache_tool_signature("toolu_restore_1", tool_sig.clone());
+        cache.cache_thinking_family(tool_sig.clone(), "gemini-3-pro".to_string());
+
+        // 客户端剥离了历史中的签名
+        let content = MessageContent::Array(vec![
+            ContentBlock::Thinking {
+                thinking: "Need the file".to_string(),
+                signature: None,
+                cache_control: None,
+            },
+            ContentBlock::ToolUse {
+                id: "toolu_restore_1".to_string(),
+                name: "read_file".to_string(),
+                input: json!({}),
+                signature: None,
+                cache_control: None,
+            },
+        ]);
+        let build = |options: &TransformOptions, model: &str| {
+            build_parts(&content, true, true, &mut HashMap::new(), true, options, model).unwrap()
+        };
+
+        let restore = TransformOptions {
+            signature_session_id: Some("sid-restore".to_string()),
+            ..Default::default()
+        };
+        let parts = build(&restore, "gemini-3-pro-high");
+        assert_eq!(parts[0]["thoughtSignature"], json!(tool_sig));
+        assert_eq!(parts[1]["thoughtSignature"], json!(tool_sig));
+
+        // 目标模型族不同：不注入
+        let parts = build(&restore, "claude-sonnet-4-5");
+        assert!(parts[1].get("thoughtSignature").is_none());
+
+        // 未启用缓存：保持原样
+        let parts = build(&TransformOptions::default(), "gemini-3-pro-high");
+        assert!(parts[0].get("thoughtSignature").is_none());
+    }
+
+    #[test]
+    fn test_session_signature_only_for_latest_assistant_turn() {
+        let session_sig = "h".repeat(60);
+        let cache = SignatureCache::global();
+        cache.cache_session_signature("sid-history", session_sig.clone(), 4);
+        cache.cache_thinking_family(session_sig.clone(), "gemini-3-pro".to_string());
+
+        let content = MessageContent::Array(vec![ContentBlock::Thinking {
+            thinking: "Older thought".to_string(),
+            signature: None,
+            cache_control: None,
+        }]);
+        let options = TransformOptions {
+            signature_session_id: Some("sid-history".to_string()),
+            ..Default::default()
+        };
+        let build = |is_latest: bool| {
+            build_parts(
+                &content,
+                true,
+                is_latest,
+                &mut HashMap::new(),
+                true,
+                &options,
+                "gemini-3-pro-high",
+            )
+            .unwrap()
+        };
+
+        // 历史轮次不能借用最新一轮的会话签名
+        assert!(build(false)[0].get("thoughtSignature").is_none());
+
+        assert_eq!(build(true)[0]["thoughtSignature"], json!(session_sig));
+    }
+
+    #[test]
+    fn test_tool_result_compression() {
+        let content = MessageContent::Array(vec![ContentBlock::ToolResult {
+            tool_use_id: "call_1".to_string(),
+            content: json!([
+                {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "iVBORw0KGgo"}},
+                {"type": "text", "text": "z".repeat(50_000)}
+            ]),
+            is_error: None,
+        }]);
+        let result_of = |limit: Option<usize>| {
+            let options = TransformOptions {
+                tool_result_limit: limit,
+                ..Default::default()
+            };
+            let parts = build_parts(
+                &content,
+                false,
+                false,
+                &mut HashMap::new(),
+                false,
+                &options,
+                "gemini-2.5-flash",
+            )
+            .unwrap();
+            parts[0]["functionResponse"]["response"]["result"]
+                .as_str()
+                .unwrap()
+                .to_string()
+        };
+
+        let compressed = result_of(Some(10_000));
+        assert!(compressed.len() < 10_300);
+        assert!(compressed.contains("[image omitted"));
+
+        // 请求级关闭压缩时原样转发
+        let raw = result_of(None);
+        assert_eq!(raw.len(), 50_000);
+
+        // 关闭压缩也不能绕过 200K 硬上限
+        let huge = MessageContent::Array(vec![ContentBlock::ToolResult {
+            tool_use_id: "call_2".to_string(),
+            content: json!("y".repeat(500_000)),
+            is_error: None,
+        }]);
+        let parts = build_parts(
+            &huge,
+            false,
+            false,
+            &mut HashMap::new(),
+            false,
+            &TransformOptions::default(),
+            "gemini-2.5-flash",
+        )
+        .unwrap();
+        let capped = parts[0]["functionResponse"]["response"]["result"]
+            .as_str()
+            .unwrap();
+        assert!(capped.len() <= 200_000 + 30);
+        assert!(capped.ends_with("[truncated output]"));
+    }
+
     #[test]
     fn test_image_content_transform() {
         let req = ClaudeRequest {
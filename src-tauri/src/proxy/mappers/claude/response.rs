@@ -4,6 +4,7 @@
 // - 2.2: Gemini response → Anthropic Messages response
 
 use super::models::*;
+use crate::proxy::common::safety::is_safety_block;
 
 /// Convert Gemini UsageMetadata to Claude Usage format
 pub fn to_claude_usage(usage_metadata: &UsageMetadata) -> Usage {
@@ -148,9 +149,16 @@ impl NonStreamingProcessor {
             .as_ref()
             .and_then(|c| c.get(0))
             .and_then(|candidate| candidate.finish_reason.as_deref());
+        let prompt_blocked = gemini_response
+            .prompt_feedback
+            .as_ref()
+            .and_then(|f| f.block_reason.as_deref())
+            .is_some_and(|r| !r.is_empty() && r != "BLOCK_REASON_UNSPECIFIED");
 
         let stop_reason = if self.has_tool_call {
             "tool_use"
+        } else if prompt_blocked || finish_reason.is_some_and(is_safety_block) {
+            "refusal"
         } else if finish_reason == Some("MAX_TOKENS") {
             "max_tokens"
         } else {
@@ -221,6 +229,7 @@ mod tests {
             }),
             model_version: Some("gemini-2.5-flash".to_string()),
             response_id: Some("resp_123".to_string()),
+            prompt_feedback: None,
         };
 
         let result = transform_response(&gemini_resp);
@@ -271,6 +280,7 @@ mod tests {
             usage_metadata: None,
             model_version: Some("gemini-2.5-flash".to_string()),
             response_id: Some("resp_456".to_string()),
+            prompt_feedback: None,
         };
 
         let result = transform_response(&gemini_resp).unwrap();
@@ -317,6 +327,7 @@ mod tests {
             usage_metadata: None,
             model_version: Some("gemini-2.5-flash".to_string()),
             response_id: Some("resp_789".to_string()),
+            prompt_feedback: None,
         };
 
         let result = transform_response(&gemini_resp).unwrap();
@@ -359,6 +370,7 @@ mod tests {
             usage_metadata: None,
             model_version: Some("gemini-2.5-flash".to_string()),
             response_id: Some("resp_empty".to_string()),
+            prompt_feedback: None,
         };
 
         let result = transform_response(&gemini_resp).unwrap();
@@ -390,6 +402,7 @@ mod tests {
             usage_metadata: None,
             model_version: Some("gemini-3-pro-image".to_string()),
             response_id: Some("resp_img".to_string()),
+            prompt_feedback: None,
         };
 
         let result = transform_response(&gemini_resp).unwrap();
@@ -423,9 +436,37 @@ mod tests {
             usage_metadata: None,
             model_version: Some("gemini-2.5-flash".to_string()),
             response_id: Some("resp_max".to_string()),
+            prompt_feedback: None,
         };
 
         let result = transform_response(&gemini_resp).unwrap();
         assert_eq!(result.stop_reason, "max_tokens");
     }
+
+    #[test]
+    fn test_safety_block_maps_to_refusal() {
+        let gemini_resp = GeminiResponse {
+            candidates: Some(vec![Candidate {
+                content: None,
+                finish_reason: Some("SAFETY".to_string()),
+                index: Some(0),
+            }]),
+            usage_metadata: None,
+            model_version: None,
+            response_id: None,
+            prompt_feedback: None,
+        };
+        assert_eq!(transform_response(&gemini_resp).unwrap().stop_reason, "refusal");
+
+        let blocked_prompt = GeminiResponse {
+            candidates: None,
+            usage_metadata: None,
+            model_version: None,
+            response_id: None,
+            prompt_feedback: Some(PromptFeedback {
+                block_reason: Some("PROHIBITED_CONTENT".to_string()),
+            }),
+        };
+        assert_eq!(transform_response(&blocked_prompt).unwrap().stop_reason, "refusal");
+    }
 }
@@ -7,6 +7,8 @@
 
 use super::models::*;
 use super::response::to_claude_usage;
+use crate::proxy::common::safety::{is_safety_block, safety_block_reason};
+use crate::proxy::signature_cache::SignatureContext;
 use bytes::Bytes;
 use serde_json::{json, Value};
 
@@ -27,6 +29,8 @@ pub struct StreamingState {
     pub message_stop_sent: bool,
     used_tool: bool,
     pending_signature: Option<String>,
+    /// Records streamed signatures into SignatureCache when set
+    signature_context: Option<SignatureContext>,
 }
 
 impl StreamingState {
@@ -38,6 +42,19 @@ impl StreamingState {
             message_stop_sent: false,
             used_tool: false,
             pending_signature: None,
+            signature_context: None,
+        }
+    }
+
+    pub fn with_signature_context(mut self, context: Option<SignatureContext>) -> Self {
+        self.signature_context = context;
+        self
+    }
+
+    /// Record a signature for restoration on the next request
+    fn record_signature(&self, tool_use_id: Option<&str>, signature: &str) {
+        if let Some(context) = &self.signature_context {
+            context.record(tool_use_id, signature);
         }
     }
 
@@ -165,6 +182,8 @@ impl StreamingState {
 
         let stop_reason = if self.used_tool {
             "tool_use"
+        } else if finish_reason.is_some_and(is_safety_block) {
+            "refusal"
         } else if finish_reason == Some("MAX_TOKENS") {
             "max_tokens"
         } else {
@@ -209,7 +228,8 @@ impl StreamingState {
     }
 
     pub fn store_signature(&mut self, signature: Option<String>) {
-        if signature.is_some() {
+        if let Some(sig) = &signature {
+            self.record_signature(None, sig);
             self.pending_signature = signature;
         }
     }
@@ -322,6 +342,7 @@ impl<'a> PartProcessor<'a> {
         });
 
         if let Some(ref sig) = signature {
+            self.state.record_signature(Some(&tool_id), sig);
             tool_use["signature"] = json!(sig);
         }
 
@@ -348,12 +369,13 @@ pub fn create_claude_sse_stream(
     >,
     _trace_id: String,
     _email: String,
+    signature_context: Option<SignatureContext>,
 ) -> std::pin::Pin<Box<dyn futures::Stream<Item = Result<Bytes, String>> + Send>> {
     use bytes::BytesMut;
     use futures::StreamExt;
 
     Box::pin(async_stream::stream! {
-        let mut state = StreamingState::new();
+        let mut state = StreamingState::new().with_signature_context(signature_context);
         let mut buffer = BytesMut::new();
 
         loop {
@@ -466,13 +488,17 @@ fn process_sse_line(line: &str, state: &mut StreamingState) -> Option<Vec<Bytes>
         }
     }
 
-    // Check for finish
-    if let Some(finish_reason) = raw_json
-        .get("candidates")
-        .and_then(|c| c.get(0))
-        .and_then(|cand| cand.get("finishReason"))
-        .and_then(|f| f.as_str())
-    {
+    // Check for finish (a blocked prompt has no candidates, only promptFeedback.blockReason)
+    let finish_reason = if safety_block_reason(raw_json).is_some() {
+        Some("SAFETY")
+    } else {
+        raw_json
+            .get("candidates")
+            .and_then(|c| c.get(0))
+            .and_then(|cand| cand.get("finishReason"))
+            .and_then(|f| f.as_str())
+    };
+    if let Some(finish_reason) = finish_reason {
         let usage = raw_json
             .get("usageMetadata")
             .and_then(|u| serde_json::from_value::<UsageMetadata>(u.clone()).ok());
@@ -627,6 +653,53 @@ mod tests {
         assert!(output2.contains("The answer"));
     }
 
+    #[test]
+    fn test_signatures_recorded_in_cache() {
+        use crate::proxy::signature_cache::SignatureCache;
+
+        let context = SignatureContext::new("sid-stream-record", "gemini-3-pro", 4);
+        let mut state = StreamingState::new().with_signature_context(Some(context));
+        state.message_start_sent = true;
+        let thinking_sig = "t".repeat(64);
+        let tool_sig = "f".repeat(64);
+
+        let parts = [
+            GeminiPart {
+                text: Some("Planning".to_string()),
+                thought: Some(true),
+                thought_signature: Some(thinking_sig.clone()),
+                function_call: None,
+                function_response: None,
+                inline_data: None,
+            },
+            GeminiPart {
+                text: None,
+                thought: None,
+                thought_signature: Some(tool_sig.clone()),
+                function_call: Some(FunctionCall {
+                    name: "read_file".to_string(),
+                    args: Some(json!({})),
+                    id: Some("toolu_stream_record".to_string()),
+                }),
+                function_response: None,
+                inline_data: None,
+            },
+        ];
+        for part in &parts {
+            PartProcessor::new(&mut state).process(part);
+        }
+
+        let cache = SignatureCache::global();
+        let tool_cached = cache.get_tool_signature("toolu_stream_record");
+        assert_eq!(tool_cached, Some(tool_sig));
+        let session_sig = cache.get_session_signature("sid-stream-record");
+        assert_eq!(session_sig.as_ref(), Some(&thinking_sig));
+        assert_eq!(
+            cache.get_signature_family(&thinking_sig).as_deref(),
+            Some("gemini-3-pro")
+        );
+    }
+
     #[test]
     fn test_non_sse_line_ignored() {
         let mut state = StreamingState::new();
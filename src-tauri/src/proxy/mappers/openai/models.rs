@@ -40,8 +40,16 @@ pub struct OpenAIRequest {
     // Thinking/Extended Thinking support
     #[serde(default)]
     pub thinking: Option<ThinkingConfig>,
+    /// OpenAI reasoning models: "none" / "minimal" / "low" / "medium" / "high"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_effort: Option<String>,
     #[serde(default, rename = "imageSize")]
     pub image_size: Option<String>,
+    // Session affinity hints
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_cache_key: Option<String>,
 }
 
 /// Thinking 配置 (兼容 Anthropic 和 OpenAI 扩展协议)
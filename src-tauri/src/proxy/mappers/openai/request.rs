@@ -7,6 +7,10 @@
 
 use super::models::*;
 use serde_json::{json, Value};
+use crate::proxy::common::safety::build_safety_settings;
+use crate::proxy::common::tool_result_compressor::{
+    cap_tool_result, compress_tool_result, default_tool_result_limit,
+};
 
 /// Transform an OpenAI ChatCompletion request into Gemini generateContent format.
 ///
@@ -15,6 +19,22 @@ pub fn transform_openai_request(
     request: &OpenAIRequest,
     project_id: &str,
     mapped_model: &str,
+) -> (Value, String, usize) {
+    transform_openai_request_with_tool_limit(
+        request,
+        project_id,
+        mapped_model,
+        default_tool_result_limit(mapped_model),
+    )
+}
+
+/// Same as [`transform_openai_request`] with an explicit tool result limit
+/// (`None` forwards `role: tool` content uncompressed).
+pub fn transform_openai_request_with_tool_limit(
+    request: &OpenAIRequest,
+    project_id: &str,
+    mapped_model: &str,
+    tool_result_limit: Option<usize>,
 ) -> (Value, String, usize) {
     let session_id = crate::proxy::session_manager::SessionManager::extract_openai_session_id(
         &serde_json::to_value(request).unwrap_or_default(),
@@ -196,6 +216,7 @@ pub fn transform_openai_request(
                         .join("\n"),
                     None => String::new(),
                 };
+                let content_val = cap_tool_result(compress_tool_result(content_val, tool_result_limit));
 
                 parts.push(json!({
                     "functionResponse": {
@@ -312,13 +333,7 @@ pub fn transform_openai_request(
     let mut inner_request = json!({
         "contents": contents,
         "generationConfig": gen_config,
-        "safetySettings": [
-            { "category": "HARM_CATEGORY_HARASSMENT", "threshold": "OFF" },
-            { "category": "HARM_CATEGORY_HATE_SPEECH", "threshold": "OFF" },
-            { "category": "HARM_CATEGORY_SEXUALLY_EXPLICIT", "threshold": "OFF" },
-            { "category": "HARM_CATEGORY_DANGEROUS_CONTENT", "threshold": "OFF" },
-            { "category": "HARM_CATEGORY_CIVIC_INTEGRITY", "threshold": "OFF" },
-        ]
+        "safetySettings": build_safety_settings(mapped_model, None),
     });
 
     // 4. Handle Tools
@@ -438,13 +453,7 @@ pub fn transform_image_request(
     let inner_request = json!({
         "contents": contents,
         "generationConfig": gen_config,
-        "safetySettings": [
-            { "category": "HARM_CATEGORY_HARASSMENT", "threshold": "OFF" },
-            { "category": "HARM_CATEGORY_HATE_SPEECH", "threshold": "OFF" },
-            { "category": "HARM_CATEGORY_SEXUALLY_EXPLICIT", "threshold": "OFF" },
-            { "category": "HARM_CATEGORY_DANGEROUS_CONTENT", "threshold": "OFF" },
-            { "category": "HARM_CATEGORY_CIVIC_INTEGRITY", "threshold": "OFF" },
-        ]
+        "safetySettings": build_safety_settings(model, None),
     });
 
     json!({
@@ -558,6 +567,9 @@ mod tests {
             person_generation: None,
             thinking: None,
             image_size: None,
+            user: None,
+            prompt_cache_key: None,
+            reasoning_effort: None,
         }
     }
 
@@ -617,6 +629,9 @@ mod tests {
             person_generation: None,
             thinking: None,
             image_size: None,
+            user: None,
+            prompt_cache_key: None,
+            reasoning_effort: None,
         };
 
         let (result, _, _) = transform_openai_request(&req, "test-proj", "gemini-2.5-flash");
@@ -672,6 +687,9 @@ mod tests {
             person_generation: None,
             thinking: None,
             image_size: None,
+            user: None,
+            prompt_cache_key: None,
+            reasoning_effort: None,
         };
 
         let (result, _, _) = transform_openai_request(&req, "test-proj", "gemini-1.5-flash");
@@ -747,6 +765,9 @@ mod tests {
             person_generation: None,
             thinking: None,
             image_size: None,
+            user: None,
+            prompt_cache_key: None,
+            reasoning_effort: None,
         };
 
         let (result, _, _) = transform_openai_request(&req, "test-proj", "gemini-2.5-flash");
@@ -762,6 +783,49 @@ mod tests {
         assert!(contents.len() >= 2);
     }
 
+    #[test]
+    fn test_tool_message_compression() {
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4",
+            "messages": [
+                {"role": "user", "content": "Read the page"},
+                {"role": "assistant", "tool_calls": [{
+                    "id": "call_1",
+                    "type": "function",
+                    "function": {"name": "read_page", "arguments": "{}"}
+                }]},
+                {"role": "tool", "tool_call_id": "call_1", "content": "p".repeat(50_000)}
+            ]
+        }))
+        .unwrap();
+        let result_of = |limit: Option<usize>| {
+            let (body, _, _) = transform_openai_request_with_tool_limit(
+                &req,
+                "test-proj",
+                "gemini-2.5-flash",
+                limit,
+            );
+            body["request"]["contents"][2]["parts"][0]["functionResponse"]["response"]["result"]
+                .as_str()
+                .unwrap()
+                .len()
+        };
+
+        assert!(result_of(Some(8_000)) < 8_100);
+        assert_eq!(result_of(None), 50_000);
+
+        // 关闭压缩时仍保留 200K 硬上限
+        let mut huge = req.clone();
+        huge.messages[2].content = Some(OpenAIContent::String("p".repeat(500_000)));
+        let (body, _, _) =
+            transform_openai_request_with_tool_limit(&huge, "test-proj", "gemini-2.5-flash", None);
+        let capped = body["request"]["contents"][2]["parts"][0]["functionResponse"]["response"]
+            ["result"]
+            .as_str()
+            .unwrap();
+        assert!(capped.len() <= 200_000 + 30);
+    }
+
     #[test]
     fn test_thinking_model_budget_capping() {
         let req = make_simple_request("gemini-3-pro", "test");
@@ -855,6 +919,9 @@ mod tests {
             person_generation: None,
             thinking: None,
             image_size: None,
+            user: None,
+            prompt_cache_key: None,
+            reasoning_effort: None,
         };
 
         let (result, _, _) = transform_openai_request(&req, "test-proj", "gemini-2.5-flash");
@@ -991,6 +1058,9 @@ mod tests {
             person_generation: None,
             thinking: None,
             image_size: None,
+            user: None,
+            prompt_cache_key: None,
+            reasoning_effort: None,
         };
 
         let (result, _, _) = transform_openai_request(&req, "test-proj", "gemini-3-pro-image");
@@ -1104,6 +1174,9 @@ mod tests {
                     person_generation: None,
                     thinking: None,
                     image_size: None,
+                    user: None,
+                    prompt_cache_key: None,
+                    reasoning_effort: None,
                 };
 
                 (req, user_texts)
@@ -1240,6 +1313,9 @@ mod tests {
                     person_generation: None,
                     thinking: None,
                     image_size: None,
+                    user: None,
+                    prompt_cache_key: None,
+                    reasoning_effort: None,
                 };
 
                 // Step 2: Convert OpenAI request → Gemini format
@@ -5,6 +5,7 @@
 // - 2.9: Semantic equivalence of message content through conversion
 
 use super::models::*;
+use crate::proxy::common::safety::{is_safety_block, safety_block_reason};
 use serde_json::Value;
 
 /// Transform a Gemini response into OpenAI ChatCompletion format.
@@ -134,8 +135,7 @@ pub fn transform_openai_response(
                 .map(|f| match f {
                     "STOP" => "stop",
                     "MAX_TOKENS" => "length",
-                    "SAFETY" => "content_filter",
-                    "RECITATION" => "content_filter",
+                    f if is_safety_block(f) => "content_filter",
                     _ => "stop",
                 })
                 .unwrap_or("stop");
@@ -167,6 +167,22 @@ pub fn transform_openai_response(
         }
     }
 
+    // Prompt blocked by safety filter: no candidates, only promptFeedback.blockReason
+    if choices.is_empty() && safety_block_reason(raw).is_some() {
+        choices.push(Choice {
+            index: 0,
+            message: OpenAIMessage {
+                role: "assistant".to_string(),
+                content: None,
+                reasoning_content: None,
+                tool_calls: None,
+                tool_call_id: None,
+                name: None,
+            },
+            finish_reason: Some("content_filter".to_string()),
+        });
+    }
+
     // Extract usage metadata
     let usage = raw.get("usageMetadata").and_then(|u| {
         let prompt_tokens = u
@@ -389,6 +405,20 @@ mod tests {
         assert_eq!(tc.id, "call_abc");
     }
 
+    #[test]
+    fn test_prompt_blocked_maps_to_content_filter() {
+        let gemini_resp = json!({
+            "promptFeedback": { "blockReason": "SAFETY" },
+            "modelVersion": "test",
+            "responseId": "test"
+        });
+
+        let result = transform_openai_response(&gemini_resp, None, 1);
+        assert_eq!(result.choices.len(), 1);
+        assert_eq!(result.choices[0].finish_reason, Some("content_filter".to_string()));
+        assert!(result.choices[0].message.content.is_none());
+    }
+
     #[test]
     fn test_finish_reason_mapping() {
         let test_cases = vec![
@@ -396,6 +426,7 @@ mod tests {
             ("MAX_TOKENS", "length"),
             ("SAFETY", "content_filter"),
             ("RECITATION", "content_filter"),
+            ("PROHIBITED_CONTENT", "content_filter"),
             ("UNKNOWN", "stop"),
         ];
 
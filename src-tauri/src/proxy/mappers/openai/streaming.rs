@@ -11,6 +11,8 @@ use serde_json::{json, Value};
 use std::pin::Pin;
 use uuid::Uuid;
 
+use crate::proxy::common::safety::{is_safety_block, safety_block_reason};
+
 /// Create an OpenAI-compatible SSE stream from a Gemini stream.
 ///
 /// Converts Gemini streaming chunks into OpenAI chat.completion.chunk format.
@@ -58,6 +60,25 @@ pub fn create_openai_sse_stream(
                                                 final_usage = extract_usage_metadata(u);
                                             }
 
+                                            // Prompt blocked by safety filter: no candidates, only promptFeedback.blockReason
+                                            if actual_data.get("candidates").is_none() && safety_block_reason(&actual_data).is_some() {
+                                                let mut chunk = json!({
+                                                    "id": &stream_id,
+                                                    "object": "chat.completion.chunk",
+                                                    "created": created_ts,
+                                                    "model": &model,
+                                                    "choices": [{
+                                                        "index": 0,
+                                                        "delta": { "role": "assistant", "content": "" },
+                                                        "finish_reason": "content_filter"
+                                                    }]
+                                                });
+                                                if let Some(usage) = final_usage.take() {
+                                                    chunk["usage"] = serde_json::to_value(usage).unwrap();
+                                                }
+                                                yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&chunk).unwrap_or_default())));
+                                            }
+
                                             if let Some(candidates) = actual_data.get("candidates").and_then(|c| c.as_array()) {
                                                 for (idx, candidate) in candidates.iter().enumerate() {
                                                     let parts = candidate.get("content")
@@ -135,8 +156,7 @@ pub fn create_openai_sse_stream(
                                                         .map(|f| match f {
                                                             "STOP" => "stop",
                                                             "MAX_TOKENS" => "length",
-                                                            "SAFETY" => "content_filter",
-                                                            "RECITATION" => "content_filter",
+                                                            f if is_safety_block(f) => "content_filter",
                                                             _ => f,
                                                         });
 
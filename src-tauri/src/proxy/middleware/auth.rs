@@ -8,19 +8,66 @@ use axum::{
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+use crate::modules::admin_user_db::{self, AdminRole};
+use crate::modules::user_token_db;
 use crate::proxy::security::{ProxySecurityConfig, UserTokenValidation, validate_user_token, identify_user_token};
+use crate::proxy::jwt_auth::{self, JwtAuthError, JwtIdentity};
+use crate::proxy::tls::ClientCertIdentity;
 
 // ============================================================================
 // UserTokenIdentity - 用户令牌身份信息
 // ============================================================================
 
 /// 用户令牌身份信息 (传递给 Monitor 使用)
-#[derive(Clone, Debug)]
+///
+/// 来源可以是 User Token、mTLS 客户端证书或 JWT；JWT 身份的 token_id 为 "jwt:<sub>"，
+/// token 为空（不保留原始 JWT）。
+#[derive(Clone, Debug, Default)]
 pub struct UserTokenIdentity {
     pub token_id: String,
     #[allow(dead_code)]
     pub token: String,
     pub username: String,
+    /// JWT 中的用户组
+    pub groups: Vec<String>,
+    /// JWT 中的每日 token 配额 (quota_claim)，由 monitor 中间件记录用量
+    pub quota: Option<i64>,
+}
+
+impl UserTokenIdentity {
+    fn from_jwt(identity: JwtIdentity) -> Self {
+        Self {
+            token_id: format!("jwt:{}", identity.subject),
+            token: String::new(),
+            username: identity.username,
+            groups: identity.groups,
+            quota: identity.quota,
+        }
+    }
+}
+
+// ============================================================================
+// AdminIdentity - 管理员身份信息
+// ============================================================================
+
+/// 已认证的管理员身份 (由 admin_auth_middleware 注入，供角色检查和审计使用)
+#[derive(Clone, Debug)]
+pub struct AdminIdentity {
+    /// 管理员账号 ID；共享密码或本地免鉴权模式下为 None
+    pub user_id: Option<String>,
+    pub username: String,
+    pub role: AdminRole,
+}
+
+impl AdminIdentity {
+    /// 共享 admin_password / api_key 或免鉴权模式下的隐式 owner 身份
+    fn legacy_owner(username: &str) -> Self {
+        Self {
+            user_id: None,
+            username: username.to_string(),
+            role: AdminRole::Owner,
+        }
+    }
 }
 
 // ============================================================================
@@ -113,15 +160,52 @@ async fn auth_middleware_internal(
     let security = security.read().await.clone();
 
     if !force_strict {
-        // AI 代理接口
+        // AI 代理接口：mTLS 客户端证书映射的身份优先于 API Key
+        let cert_validation = match client_cert_token(&security, &request) {
+            Some(mapped) => Some(client_cert_validation(mapped).await),
+            None => None,
+        };
+        match cert_validation {
+            Some(UserTokenValidation::Valid {
+                token_id,
+                token,
+                username,
+            }) => {
+                let identity = UserTokenIdentity {
+                    token_id,
+                    token,
+                    username,
+                    ..Default::default()
+                };
+                let (mut parts, body) = request.into_parts();
+                parts.extensions.insert(identity);
+                let request = Request::from_parts(parts, body);
+                return Ok(next.run(request).await);
+            }
+            Some(UserTokenValidation::Rejected(reason)) => {
+                tracing::warn!("Client certificate rejected: {}", reason);
+                return Ok(token_rejected_response(&reason));
+            }
+            Some(UserTokenValidation::Error(e)) => {
+                tracing::error!("Client certificate validation error: {}", e);
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+            Some(UserTokenValidation::NotUserToken) | None => {}
+        }
+
         if !security.requires_auth(&path, false) {
             // auth_mode=Off 时，仍尝试识别 User Token 以记录使用情况
             if let Some(key) = extract_api_key(&request) {
-                if let Some((token_id, token, username)) = identify_user_token(&key) {
+                let identified = tokio::task::spawn_blocking(move || identify_user_token(&key))
+                    .await
+                    .ok()
+                    .flatten();
+                if let Some((token_id, token, username)) = identified {
                     let identity = UserTokenIdentity {
                         token_id,
                         token,
                         username,
+                        ..Default::default()
                     };
                     let (mut parts, body) = request.into_parts();
                     parts.extensions.insert(identity);
@@ -139,9 +223,7 @@ async fn auth_middleware_internal(
         }
     } else {
         // 管理接口
-        if !security.requires_auth(&path, true) {
-            return Ok(next.run(request).await);
-        }
+        return admin_authenticate(&security, &path, request, next).await;
     }
 
     let api_key = extract_api_key(&request);
@@ -149,33 +231,58 @@ async fn auth_middleware_internal(
     if security.api_key.is_empty()
         && (security.admin_password.is_none()
             || security.admin_password.as_ref().unwrap().is_empty())
+        && !security.jwt_auth.enabled
     {
         tracing::error!("Auth is required but both api_key and admin_password are empty");
         return Err(StatusCode::UNAUTHORIZED);
     }
 
-    let authorized = if force_strict {
-        // 管理接口：使用 validate_admin_key
-        api_key
-            .as_deref()
-            .map(|k| security.validate_admin_key(k))
-            .unwrap_or(false)
-    } else {
-        // AI 代理接口：使用 validate_api_key
-        api_key
-            .as_deref()
-            .map(|k| security.validate_api_key(k))
-            .unwrap_or(false)
-    };
+    // AI 代理接口：使用 validate_api_key
+    let authorized = api_key
+        .as_deref()
+        .map(|k| security.validate_api_key(k))
+        .unwrap_or(false);
 
     if authorized {
         Ok(next.run(request).await)
-    } else if !force_strict && api_key.is_some() {
-        // API Key 不匹配，尝试验证 User Token
+    } else if api_key.is_some() {
+        // API Key 不匹配，尝试验证 JWT / User Token
         let token_str = api_key.unwrap();
+
+        if jwt_auth::looks_like_jwt(&token_str) {
+            if let Some(verifier) = jwt_auth::verifier_for(&security.jwt_auth) {
+                return match verifier.verify(&token_str).await {
+                    Ok(claims) if jwt_auth::quota_exhausted(&claims) => {
+                        let reason = format!(
+                            "Daily token quota of {} exhausted for '{}'",
+                            claims.quota.unwrap_or_default(),
+                            claims.username
+                        );
+                        tracing::warn!("JWT rejected: {}", reason);
+                        Ok(quota_exceeded_response(&reason))
+                    }
+                    Ok(claims) => {
+                        let identity = UserTokenIdentity::from_jwt(claims);
+                        let (mut parts, body) = request.into_parts();
+                        parts.extensions.insert(identity);
+                        let request = Request::from_parts(parts, body);
+                        Ok(next.run(request).await)
+                    }
+                    Err(JwtAuthError::Forbidden(reason)) => {
+                        tracing::warn!("JWT rejected: {}", reason);
+                        Ok(token_rejected_response(&reason))
+                    }
+                    Err(JwtAuthError::Invalid(reason)) => {
+                        tracing::warn!("JWT rejected: {}", reason);
+                        Err(StatusCode::UNAUTHORIZED)
+                    }
+                };
+            }
+        }
+
         let client_ip = extract_client_ip(&request);
 
-        match validate_user_token(&token_str, &client_ip) {
+        match blocking_validate_user_token(token_str, client_ip).await {
             UserTokenValidation::Valid {
                 token_id,
                 token,
@@ -185,6 +292,7 @@ async fn auth_middleware_internal(
                     token_id,
                     token,
                     username,
+                    ..Default::default()
                 };
                 let (mut parts, body) = request.into_parts();
                 parts.extensions.insert(identity);
@@ -193,21 +301,7 @@ async fn auth_middleware_internal(
             }
             UserTokenValidation::Rejected(reason) => {
                 tracing::warn!("UserToken rejected: {}", reason);
-                let body = serde_json::json!({
-                    "error": {
-                        "message": reason,
-                        "type": "token_rejected",
-                        "code": "token_rejected"
-                    }
-                });
-                let response = axum::response::Response::builder()
-                    .status(StatusCode::FORBIDDEN)
-                    .header("Content-Type", "application/json")
-                    .body(axum::body::Body::from(
-                        serde_json::to_string(&body).unwrap(),
-                    ))
-                    .unwrap();
-                Ok(response)
+                Ok(token_rejected_response(&reason))
             }
             UserTokenValidation::Error(e) => {
                 tracing::error!("UserToken validation error: {}", e);
@@ -220,10 +314,187 @@ async fn auth_middleware_internal(
     }
 }
 
+/// User Token 被拒绝时的 403 响应
+fn token_rejected_response(reason: &str) -> Response {
+    let body = serde_json::json!({
+        "error": {
+            "message": reason,
+            "type": "token_rejected",
+            "code": "token_rejected"
+        }
+    });
+    axum::response::Response::builder()
+        .status(StatusCode::FORBIDDEN)
+        .header("Content-Type", "application/json")
+        .body(axum::body::Body::from(
+            serde_json::to_string(&body).unwrap(),
+        ))
+        .unwrap()
+}
+
+/// JWT 每日配额用尽时的 429 响应
+fn quota_exceeded_response(reason: &str) -> Response {
+    let body = serde_json::json!({
+        "error": {
+            "message": reason,
+            "type": "quota_exceeded",
+            "code": "quota_exceeded"
+        }
+    });
+    axum::response::Response::builder()
+        .status(StatusCode::TOO_MANY_REQUESTS)
+        .header("Content-Type", "application/json")
+        .body(axum::body::Body::from(
+            serde_json::to_string(&body).unwrap(),
+        ))
+        .unwrap()
+}
+
+/// mTLS 客户端证书映射到的 User Token
+struct ClientCertToken {
+    token_id: String,
+    subject: String,
+    client_ip: String,
+}
+
+/// 查找客户端证书映射的 Token；未携带证书或 Subject 未配置映射时返回 None，继续走 API Key 流程
+fn client_cert_token(security: &ProxySecurityConfig, request: &Request) -> Option<ClientCertToken> {
+    let cert = request.extensions().get::<ClientCertIdentity>()?;
+    let token_id = security.client_cert_token_id(cert)?;
+    Some(ClientCertToken {
+        token_id: token_id.to_string(),
+        subject: cert.subject.clone(),
+        client_ip: extract_client_ip(request),
+    })
+}
+
+/// 通过 mTLS 客户端证书解析 User Token 身份
+///
+/// 映射到的 Token 仍需通过过期、宵禁、IP 限制等常规校验；查询 SQLite，放到阻塞线程池执行。
+async fn client_cert_validation(mapped: ClientCertToken) -> UserTokenValidation {
+    tokio::task::spawn_blocking(move || match user_token_db::get_token_by_id(&mapped.token_id) {
+        Ok(Some(token)) => validate_user_token(&token.token, &mapped.client_ip),
+        Ok(None) => UserTokenValidation::Rejected(format!(
+            "Client certificate '{}' is mapped to an unknown token",
+            mapped.subject
+        )),
+        Err(e) => UserTokenValidation::Error(e),
+    })
+    .await
+    .unwrap_or_else(|e| UserTokenValidation::Error(format!("Client certificate task failed: {}", e)))
+}
+
+/// 在阻塞线程池中校验 User Token (SQLite 读写)
+async fn blocking_validate_user_token(token: String, client_ip: String) -> UserTokenValidation {
+    tokio::task::spawn_blocking(move || validate_user_token(&token, &client_ip))
+        .await
+        .unwrap_or_else(|e| UserTokenValidation::Error(format!("User token task failed: {}", e)))
+}
+
+/// 管理接口鉴权
+///
+/// 优先校验管理员会话令牌（/api/auth/login 签发）。尚未存在启用中的 owner 时，
+/// 兼容旧的共享 admin_password / api_key（视为 owner），便于创建首个 owner；
+/// 一旦存在启用中的 owner，共享密码不再具有管理权限。
+async fn admin_authenticate(
+    security: &ProxySecurityConfig,
+    path: &str,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    if ProxySecurityConfig::is_public_admin_path(path) {
+        return Ok(next.run(request).await);
+    }
+
+    // 免鉴权模式（仅本机访问）下视为 owner
+    if !security.requires_auth(path, true) {
+        return Ok(run_as_admin(request, next, AdminIdentity::legacy_owner("local")).await);
+    }
+
+    let api_key = match extract_api_key(&request) {
+        Some(k) if !k.is_empty() => k,
+        _ => return Err(StatusCode::UNAUTHORIZED),
+    };
+
+    // 会话校验与 owner 检查都会打开 SQLite 连接，放到阻塞线程池执行
+    let token = api_key.clone();
+    let lookup = tokio::task::spawn_blocking(move || -> Result<_, String> {
+        if let Some(user) = admin_user_db::validate_session(&token)? {
+            return Ok((Some(user), true));
+        }
+        Ok((None, admin_user_db::has_enabled_owner()?))
+    })
+    .await
+    .unwrap_or_else(|e| Err(format!("Admin auth task failed: {}", e)));
+    let (user, has_owner) = match lookup {
+        Ok(result) => result,
+        Err(e) => {
+            tracing::error!("Admin session validation error: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    if let Some(user) = user {
+        let identity = AdminIdentity {
+            user_id: Some(user.id),
+            username: user.username,
+            role: user.role,
+        };
+        return Ok(run_as_admin(request, next, identity).await);
+    }
+    if has_owner {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    if security.api_key.is_empty()
+        && security.admin_password.as_deref().unwrap_or_default().is_empty()
+    {
+        tracing::error!("Auth is required but both api_key and admin_password are empty");
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    if security.validate_admin_key(&api_key) {
+        Ok(run_as_admin(request, next, AdminIdentity::legacy_owner("admin")).await)
+    } else {
+        Err(StatusCode::UNAUTHORIZED)
+    }
+}
+
+async fn run_as_admin(request: Request, next: Next, identity: AdminIdentity) -> Response {
+    let (mut parts, body) = request.into_parts();
+    parts.extensions.insert(identity);
+    next.run(Request::from_parts(parts, body)).await
+}
+
+/// 管理接口角色检查中间件
+///
+/// 以路由所需的最低角色作为 State，挂在 admin_auth_middleware 之后。
+pub async fn require_admin_role(
+    State(required): State<AdminRole>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    match request.extensions().get::<AdminIdentity>() {
+        Some(identity) if identity.role.satisfies(required) => Ok(next.run(request).await),
+        Some(identity) => {
+            tracing::warn!(
+                "Admin '{}' ({}) denied: {} {} requires {}",
+                identity.username,
+                identity.role.as_str(),
+                request.method(),
+                request.uri().path(),
+                required.as_str()
+            );
+            Err(StatusCode::FORBIDDEN)
+        }
+        None => Err(StatusCode::UNAUTHORIZED),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::config::{ProxyAuthMode, SecurityMonitorConfig};
+    use crate::models::config::{JwtAuthConfig, ProxyAuthMode, SecurityMonitorConfig, TlsConfig};
     use proptest::prelude::*;
 
     #[test]
@@ -235,6 +506,8 @@ mod tests {
             allow_lan_access: false,
             port: 8080,
             security_monitor: SecurityMonitorConfig::default(),
+            tls: TlsConfig::default(),
+            jwt_auth: JwtAuthConfig::default(),
         };
         assert!(matches!(s.effective_auth_mode(), ProxyAuthMode::Off));
     }
@@ -248,6 +521,8 @@ mod tests {
             allow_lan_access: true,
             port: 8080,
             security_monitor: SecurityMonitorConfig::default(),
+            tls: TlsConfig::default(),
+            jwt_auth: JwtAuthConfig::default(),
         };
         assert!(matches!(
             s.effective_auth_mode(),
@@ -264,6 +539,8 @@ mod tests {
             allow_lan_access: false,
             port: 8080,
             security_monitor: SecurityMonitorConfig::default(),
+            tls: TlsConfig::default(),
+            jwt_auth: JwtAuthConfig::default(),
         };
         assert!(matches!(s.effective_auth_mode(), ProxyAuthMode::Strict));
     }
@@ -277,6 +554,8 @@ mod tests {
             allow_lan_access: true,
             port: 8080,
             security_monitor: SecurityMonitorConfig::default(),
+            tls: TlsConfig::default(),
+            jwt_auth: JwtAuthConfig::default(),
         };
         assert!(matches!(s.effective_auth_mode(), ProxyAuthMode::Off));
     }
@@ -289,6 +568,98 @@ mod tests {
         assert_eq!(sec.allow_lan_access, proxy_config.allow_lan_access);
     }
 
+    // ---- require_admin_role tests ----
+
+    fn admin(role: AdminRole) -> AdminIdentity {
+        AdminIdentity {
+            user_id: Some("u-1".to_string()),
+            username: "tester".to_string(),
+            role,
+        }
+    }
+
+    #[test]
+    fn test_client_cert_token_skips_unmapped_certificates() {
+        let security = make_security_config(ProxyAuthMode::Strict, true);
+
+        let request = Request::builder().uri("/v1/messages").body(axum::body::Body::empty()).unwrap();
+        assert!(client_cert_token(&security, &request).is_none());
+
+        let mut request = Request::builder().uri("/v1/messages").body(axum::body::Body::empty()).unwrap();
+        request.extensions_mut().insert(ClientCertIdentity {
+            subject: "CN=unmapped".to_string(),
+            common_name: Some("unmapped".to_string()),
+        });
+        assert!(client_cert_token(&security, &request).is_none());
+
+        // CN 映射到 Token 时返回映射结果，供阻塞线程池中查询
+        let mut security = security;
+        security
+            .tls
+            .client_cert_identities
+            .insert("unmapped".to_string(), "token-1".to_string());
+        let mapped = client_cert_token(&security, &request).unwrap();
+        assert_eq!(mapped.token_id, "token-1");
+        assert_eq!(mapped.subject, "CN=unmapped");
+    }
+
+    #[test]
+    fn test_identity_from_jwt_uses_prefixed_subject() {
+        let identity = UserTokenIdentity::from_jwt(JwtIdentity {
+            subject: "u-1".to_string(),
+            username: "alice".to_string(),
+            groups: vec!["ml".to_string()],
+            quota: Some(100),
+        });
+        assert_eq!(identity.token_id, "jwt:u-1");
+        assert!(identity.token.is_empty());
+        assert_eq!(identity.username, "alice");
+        assert_eq!(identity.groups, vec!["ml"]);
+        assert_eq!(identity.quota, Some(100));
+    }
+
+    async fn role_check(identity: Option<AdminIdentity>, required: AdminRole) -> StatusCode {
+        use tower::ServiceExt;
+
+        let app = axum::Router::new()
+            .route("/x", axum::routing::get(|| async { "ok" }))
+            .route_layer(axum::middleware::from_fn_with_state(
+                required,
+                require_admin_role,
+            ));
+        let mut request = Request::builder()
+            .uri("/x")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        if let Some(identity) = identity {
+            request.extensions_mut().insert(identity);
+        }
+        app.oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_require_admin_role_allows_sufficient_role() {
+        assert_eq!(role_check(Some(admin(AdminRole::Viewer)), AdminRole::Viewer).await, StatusCode::OK);
+        assert_eq!(role_check(Some(admin(AdminRole::Owner)), AdminRole::Operator).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_require_admin_role_rejects_insufficient_role() {
+        assert_eq!(
+            role_check(Some(admin(AdminRole::Viewer)), AdminRole::Operator).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            role_check(Some(admin(AdminRole::Operator)), AdminRole::Owner).await,
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[tokio::test]
+    async fn test_require_admin_role_without_identity_is_unauthorized() {
+        assert_eq!(role_check(None, AdminRole::Viewer).await, StatusCode::UNAUTHORIZED);
+    }
+
     // ---- Arbitrary strategies ----
 
     /// Generate a random auth_mode (only the 3 explicit modes; Auto is tested separately)
@@ -326,6 +697,8 @@ mod tests {
             allow_lan_access: allow_lan,
             port: 8080,
             security_monitor: SecurityMonitorConfig::default(),
+            tls: TlsConfig::default(),
+            jwt_auth: JwtAuthConfig::default(),
         }
     }
 
@@ -4,10 +4,12 @@ pub mod auth;
 pub mod cors;
 pub mod ip_filter;
 pub mod monitor;
+pub mod redaction;
 pub mod service_status;
 
-pub use auth::{admin_auth_middleware, auth_middleware};
+pub use auth::{admin_auth_middleware, auth_middleware, require_admin_role};
 pub use cors::cors_layer;
 pub use ip_filter::ip_filter_middleware;
 pub use monitor::monitor_middleware;
+pub use redaction::redaction_middleware;
 pub use service_status::service_status_middleware;
@@ -1,19 +1,40 @@
 // 请求监控中间件
 use axum::{
+    body::{Body, Bytes},
     extract::Request,
+    http::{header, HeaderMap, Method},
     middleware::Next,
     response::Response,
 };
+use futures::StreamExt;
+use serde_json::Value;
+use std::sync::Arc;
 use std::time::Instant;
 
+use crate::proxy::middleware::auth::UserTokenIdentity;
+use crate::proxy::monitor::{get_global_monitor, ProxyMonitor};
+
 // Re-export ProxyRequestLog from core monitor module
 pub use crate::proxy::monitor::ProxyRequestLog;
 
+/// 日志中保留的请求/响应体上限 (字节)
+const MAX_LOGGED_BODY: usize = 256 * 1024;
+
 /// 请求监控中间件
 ///
-/// 记录请求的基本信息（方法、URL、状态码、耗时）。
-/// 完整的日志持久化将在 Task 14.4 (ProxyMonitor) 中实现。
+/// 记录请求的基本信息（方法、URL、状态码、耗时）；监控开启时把请求/响应体
+/// 交给 ProxyMonitor 持久化 (持久化前按脱敏规则处理)。带配额的 JWT 调用方
+/// 在响应结束后累计 token 用量。
 pub async fn monitor_middleware(request: Request, next: Next) -> Response {
+    let monitor = get_global_monitor().filter(|m| m.is_enabled());
+    monitor_request(monitor, request, next).await
+}
+
+async fn monitor_request(
+    monitor: Option<Arc<ProxyMonitor>>,
+    request: Request,
+    next: Next,
+) -> Response {
     let method = request.method().to_string();
     let uri = request.uri().to_string();
 
@@ -39,7 +60,7 @@ pub async fn monitor_middleware(request: Request, next: Next) -> Response {
         });
 
     // 从 URL 提取模型名（Gemini 原生路径）
-    let model = if uri.contains("/v1beta/models/") {
+    let mut model = if uri.contains("/v1beta/models/") {
         uri.split("/v1beta/models/")
             .nth(1)
             .and_then(|s| s.split(':').next())
@@ -59,23 +80,270 @@ pub async fn monitor_middleware(request: Request, next: Next) -> Response {
         None
     };
 
+    // 鉴权中间件注入的调用方身份 (User Token / mTLS / JWT)
+    let identity = request.extensions().get::<UserTokenIdentity>();
+    let username = identity.map(|identity| identity.username.clone());
+    // 带配额的 JWT 调用方 (token_id 为 "jwt:<sub>")
+    let quota_subject = identity
+        .filter(|identity| identity.quota.is_some())
+        .and_then(|identity| identity.token_id.strip_prefix("jwt:"))
+        .map(str::to_string);
+
+    // 监控开启时缓存请求体用于持久化
+    let mut request = request;
+    let mut request_body = None;
+    if monitor.is_some() && request.method() == Method::POST {
+        let (parts, body) = request.into_parts();
+        let bytes = match axum::body::to_bytes(body, crate::proxy::server::max_body_size()).await {
+            Ok(bytes) => bytes,
+            Err(_) => {
+                return axum::response::IntoResponse::into_response(
+                    axum::http::StatusCode::PAYLOAD_TOO_LARGE,
+                )
+            }
+        };
+        if model.is_none() {
+            model = serde_json::from_slice::<Value>(&bytes)
+                .ok()
+                .and_then(|v| v.get("model").and_then(Value::as_str).map(str::to_string));
+        }
+        request_body = Some(body_text(&bytes));
+        request = Request::from_parts(parts, Body::from(bytes));
+    }
+
     let response = next.run(request).await;
 
     let duration = start.elapsed().as_millis() as u64;
     let status = response.status().as_u16();
 
+    // 处理器压缩了上下文时附带压缩摘要
+    let context = response
+        .headers()
+        .get("X-Context-Compressed")
+        .and_then(|v| v.to_str().ok())
+        .map(|s| format!(" context=[{}]", s))
+        .unwrap_or_default();
+
     tracing::info!(
-        "[Monitor] {} {} → {} ({}ms) client_ip={} model={} protocol={}",
+        "[Monitor] {} {} → {} ({}ms) client_ip={} user={} model={} protocol={}{}",
         method,
         uri,
         status,
         duration,
         client_ip.as_deref().unwrap_or("-"),
+        username.as_deref().unwrap_or("-"),
         model.as_deref().unwrap_or("-"),
         protocol.as_deref().unwrap_or("-"),
+        context,
     );
 
-    response
+    if monitor.is_none() && quota_subject.is_none() {
+        return response;
+    }
+
+    let log = ProxyRequestLog {
+        id: uuid::Uuid::new_v4().to_string(),
+        timestamp: chrono::Utc::now().timestamp(),
+        method,
+        url: uri,
+        status,
+        duration,
+        model,
+        mapped_model: header_value(response.headers(), "X-Mapped-Model"),
+        account_email: header_value(response.headers(), "X-Account-Email"),
+        client_ip,
+        error: None,
+        request_body,
+        response_body: None,
+        input_tokens: None,
+        output_tokens: None,
+        protocol,
+        username,
+    };
+
+    let is_stream = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.contains("event-stream"));
+    let (parts, body) = response.into_parts();
+
+    if is_stream {
+        // 流式响应：边转发边记录，流结束 (或客户端断开) 时写日志
+        let mut recorder = BodyRecorder::new(monitor, quota_subject, log);
+        let stream = body.into_data_stream().map(move |chunk| {
+            if let Ok(bytes) = &chunk {
+                recorder.push(bytes);
+            }
+            chunk
+        });
+        return Response::from_parts(parts, Body::from_stream(stream));
+    }
+
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            let mut log = log;
+            log.error = Some(format!("Failed to read response body: {}", e));
+            if let Some(monitor) = monitor {
+                monitor.log_request(log).await;
+            }
+            return Response::from_parts(parts, Body::empty());
+        }
+    };
+    let mut recorder = BodyRecorder::new(monitor, quota_subject, log);
+    recorder.push(&bytes);
+    drop(recorder);
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+fn header_value(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+}
+
+fn body_text(bytes: &[u8]) -> String {
+    let keep = &bytes[..bytes.len().min(MAX_LOGGED_BODY)];
+    let mut text = String::from_utf8_lossy(keep).into_owned();
+    if bytes.len() > MAX_LOGGED_BODY {
+        text.push_str("...[truncated]");
+    }
+    text
+}
+
+/// 收集响应体 (最多 MAX_LOGGED_BODY)，drop 时补全用量、计入配额并提交日志
+struct BodyRecorder {
+    monitor: Option<Arc<ProxyMonitor>>,
+    /// 需要累计配额用量的 JWT sub
+    quota_subject: Option<String>,
+    log: Option<ProxyRequestLog>,
+    buf: Vec<u8>,
+    total: usize,
+    usage: Usage,
+    /// 尚未按行解析的尾部 (SSE 分块可能截断在行中间)
+    pending_line: Vec<u8>,
+}
+
+impl BodyRecorder {
+    fn new(
+        monitor: Option<Arc<ProxyMonitor>>,
+        quota_subject: Option<String>,
+        log: ProxyRequestLog,
+    ) -> Self {
+        Self {
+            monitor,
+            quota_subject,
+            log: Some(log),
+            buf: Vec::new(),
+            total: 0,
+            usage: Usage::default(),
+            pending_line: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, bytes: &Bytes) {
+        self.total += bytes.len();
+        let room = MAX_LOGGED_BODY.saturating_sub(self.buf.len());
+        self.buf.extend_from_slice(&bytes[..bytes.len().min(room)]);
+
+        // 用量通常在流末尾，按行扫描整个流而不受截断影响
+        self.pending_line.extend_from_slice(bytes);
+        while let Some(pos) = self.pending_line.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.pending_line.drain(..=pos).collect();
+            self.usage.scan_line(&line);
+        }
+    }
+}
+
+impl Drop for BodyRecorder {
+    fn drop(&mut self) {
+        let Some(mut log) = self.log.take() else {
+            return;
+        };
+        let rest = std::mem::take(&mut self.pending_line);
+        self.usage.scan_line(&rest);
+        // 非流式 JSON 可能跨多行
+        if self.usage.input.is_none() && self.usage.output.is_none() {
+            if let Ok(json) = serde_json::from_slice::<Value>(&self.buf) {
+                self.usage.merge(&json);
+            }
+        }
+
+        let mut text = String::from_utf8_lossy(&self.buf).into_owned();
+        if self.total > self.buf.len() {
+            text.push_str("...[truncated]");
+        }
+        if log.status >= 400 {
+            log.error = Some(text.clone());
+        }
+        if !text.is_empty() {
+            log.response_body = Some(text);
+        }
+        log.input_tokens = self.usage.input;
+        log.output_tokens = self.usage.output;
+
+        if let Some(subject) = &self.quota_subject {
+            let tokens = self.usage.input.unwrap_or(0) as u64 + self.usage.output.unwrap_or(0) as u64;
+            crate::proxy::jwt_auth::record_quota_usage(subject, tokens);
+        }
+
+        let Some(monitor) = self.monitor.clone() else {
+            return;
+        };
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            handle.spawn(async move { monitor.log_request(log).await });
+        }
+    }
+}
+
+/// 从响应 (JSON 或 SSE 行) 中提取的 token 用量
+#[derive(Default)]
+struct Usage {
+    input: Option<u32>,
+    output: Option<u32>,
+}
+
+impl Usage {
+    fn scan_line(&mut self, line: &[u8]) {
+        let text = String::from_utf8_lossy(line);
+        let text = text.trim();
+        let text = text.strip_prefix("data:").map(str::trim).unwrap_or(text);
+        if !text.starts_with('{') {
+            return;
+        }
+        if let Ok(json) = serde_json::from_str::<Value>(text) {
+            self.merge(&json);
+        }
+    }
+
+    fn merge(&mut self, json: &Value) {
+        let token = |v: &Value, keys: &[&str]| {
+            keys.iter()
+                .find_map(|k| v.get(*k).and_then(Value::as_u64))
+                .map(|n| n as u32)
+        };
+        let candidates = [
+            json.get("usage"),
+            json.pointer("/message/usage"),
+            json.pointer("/response/usage"),
+            json.get("usageMetadata"),
+            json.pointer("/response/usageMetadata"),
+        ];
+        for usage in candidates.into_iter().flatten() {
+            if let Some(n) = token(usage, &["input_tokens", "prompt_tokens", "promptTokenCount"]) {
+                self.input = Some(n);
+            }
+            if let Some(n) = token(
+                usage,
+                &["output_tokens", "completion_tokens", "candidatesTokenCount"],
+            ) {
+                self.output = Some(n);
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -106,4 +374,162 @@ mod tests {
         assert_eq!(log.status, 200);
         assert_eq!(log.duration, 150);
     }
+
+    /// Routes `app` through the monitor with a mask-in-logs rule for `MON-\d{6}`
+    async fn logged_exchange(app: axum::Router, body: &str) -> (String, ProxyRequestLog) {
+        use crate::models::config::{RedactionAction, RedactionConfig, RedactionRule};
+        use tower::ServiceExt;
+
+        let _guard = crate::proxy::redaction::TEST_CONFIG_LOCK.lock().await;
+        crate::proxy::redaction::update_redaction_config(&RedactionConfig {
+            enabled: true,
+            rules: vec![RedactionRule {
+                name: "monitor_test_id".to_string(),
+                detector: None,
+                pattern: Some(r"MON-\d{6}".to_string()),
+                action: RedactionAction::MaskLogs,
+                enabled: true,
+            }],
+        });
+
+        let monitor = Arc::new(ProxyMonitor::in_memory(10));
+        monitor.set_enabled(true);
+        let recorder = monitor.clone();
+        let app = app.layer(axum::middleware::from_fn(move |req: Request, next: Next| {
+            monitor_request(Some(recorder.clone()), req, next)
+        }));
+
+        let response = app
+            .oneshot(
+                Request::post("/v1/messages")
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let client_body = String::from_utf8(bytes.to_vec()).unwrap();
+
+        // The log is submitted from a spawned task once the body is done
+        for _ in 0..50 {
+            if let Some(log) = monitor.get_recent_logs(1).await.pop() {
+                return (client_body, log);
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("request was not logged");
+    }
+
+    #[tokio::test]
+    async fn test_mask_logs_rule_redacts_persisted_bodies_only() {
+        let app = axum::Router::new().route(
+            "/v1/messages",
+            axum::routing::post(|body: String| async move {
+                (
+                    [("X-Mapped-Model", "claude-sonnet-4-5")],
+                    axum::Json(serde_json::json!({
+                        "echo": body,
+                        "usage": { "input_tokens": 12, "output_tokens": 34 }
+                    })),
+                )
+            }),
+        );
+        let (client_body, log) = logged_exchange(
+            app,
+            r#"{"model":"claude-sonnet-4-5","messages":[{"role":"user","content":"order MON-123456"}]}"#,
+        )
+        .await;
+
+        // The client (and upstream) see the original text
+        assert!(client_body.contains("MON-123456"));
+        let request_body = log.request_body.unwrap();
+        let response_body = log.response_body.unwrap();
+        assert!(!request_body.contains("MON-123456"));
+        assert!(request_body.contains("[REDACTED:monitor_test_id]"));
+        assert!(!response_body.contains("MON-123456"));
+        assert_eq!(log.model.as_deref(), Some("claude-sonnet-4-5"));
+        assert_eq!(log.mapped_model.as_deref(), Some("claude-sonnet-4-5"));
+        assert_eq!((log.input_tokens, log.output_tokens), (Some(12), Some(34)));
+    }
+
+    #[tokio::test]
+    async fn test_streamed_response_logged_when_finished() {
+        let app = axum::Router::new().route(
+            "/v1/messages",
+            axum::routing::post(|| async {
+                let events = [
+                    "event: message_start\ndata: {\"type\":\"message_start\",\"message\":{\"usage\":{\"input_tokens\":7,\"output_tokens\":1}}}\n\n",
+                    "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"delta\":{\"text\":\"id MON-654321\"}}\n\n",
+                    "event: message_delta\ndata: {\"type\":\"message_delta\",\"usage\":{\"output_tokens\":9}}\n\n",
+                ];
+                let stream = futures::stream::iter(
+                    events.map(|e| Ok::<_, std::io::Error>(Bytes::from(e))),
+                );
+                (
+                    [(header::CONTENT_TYPE, "text/event-stream")],
+                    Body::from_stream(stream),
+                )
+            }),
+        );
+        let (client_body, log) = logged_exchange(app, r#"{"model":"claude-sonnet-4-5"}"#).await;
+
+        assert!(client_body.contains("MON-654321"));
+        let response_body = log.response_body.unwrap();
+        assert!(response_body.contains("[REDACTED:monitor_test_id]"));
+        assert!(!response_body.contains("MON-654321"));
+        assert_eq!((log.input_tokens, log.output_tokens), (Some(7), Some(9)));
+    }
+
+    #[tokio::test]
+    async fn test_jwt_quota_usage_recorded_without_monitor() {
+        use crate::proxy::jwt_auth::{quota_exhausted, quota_usage_stats, JwtIdentity};
+        use tower::ServiceExt;
+
+        let jwt = JwtIdentity {
+            subject: "monitor-quota-user".to_string(),
+            username: "quota".to_string(),
+            groups: Vec::new(),
+            quota: Some(50),
+        };
+        assert!(!quota_exhausted(&jwt));
+        let identity = UserTokenIdentity {
+            token_id: "jwt:monitor-quota-user".to_string(),
+            username: "quota".to_string(),
+            quota: Some(50),
+            ..Default::default()
+        };
+
+        let app = axum::Router::new()
+            .route(
+                "/v1/messages",
+                axum::routing::post(|| async {
+                    axum::Json(serde_json::json!({
+                        "usage": { "input_tokens": 20, "output_tokens": 30 }
+                    }))
+                }),
+            )
+            .layer(axum::middleware::from_fn(|req: Request, next: Next| {
+                monitor_request(None, req, next)
+            }))
+            .layer(axum::middleware::from_fn(move |mut req: Request, next: Next| {
+                req.extensions_mut().insert(identity.clone());
+                next.run(req)
+            }));
+
+        let response = app
+            .oneshot(Request::post("/v1/messages").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+
+        let used = quota_usage_stats()
+            .into_iter()
+            .find(|s| s.subject == "monitor-quota-user")
+            .map(|s| s.used_today);
+        assert_eq!(used, Some(50));
+        assert!(quota_exhausted(&jwt));
+    }
 }
@@ -0,0 +1,75 @@
+// PII / 密钥脱敏中间件
+//
+// 在鉴权之后、进入协议处理器之前扫描 JSON 请求体：
+// - mask_upstream 规则命中时改写请求体
+// - block 规则命中时直接返回 400
+// 日志中的脱敏由 ProxyMonitor::log_request 在持久化前完成 (monitor_middleware 采集请求/响应体)。
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+
+use crate::proxy::redaction::{get_redaction_engine, ScanOutcome};
+
+fn is_json_request(request: &Request) -> bool {
+    request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|ct| ct.to_ascii_lowercase().contains("json"))
+        // 部分客户端不带 Content-Type，按 JSON 尝试解析
+        .unwrap_or(true)
+}
+
+/// 请求体脱敏中间件
+pub async fn redaction_middleware(request: Request, next: Next) -> Response {
+    let engine = get_redaction_engine();
+    if !engine.is_active() || request.method() != Method::POST || !is_json_request(&request) {
+        return next.run(request).await;
+    }
+
+    let (mut parts, body) = request.into_parts();
+    let bytes = match axum::body::to_bytes(body, crate::proxy::server::max_body_size()).await {
+        Ok(bytes) => bytes,
+        Err(_) => return StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+    };
+
+    let mut json: serde_json::Value = match serde_json::from_slice(&bytes) {
+        Ok(json) => json,
+        // 非 JSON 交给处理器报错
+        Err(_) => return next.run(Request::from_parts(parts, Body::from(bytes))).await,
+    };
+
+    match engine.scan_request(&mut json) {
+        ScanOutcome::Blocked { rule } => {
+            tracing::warn!(
+                "[Redaction] Blocked {} (matched rule '{}')",
+                parts.uri.path(),
+                rule
+            );
+            (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({
+                    "error": {
+                        "message": format!("Request blocked: content matched redaction rule '{}'", rule),
+                        "type": "invalid_request_error",
+                        "code": "content_blocked"
+                    }
+                })),
+            )
+                .into_response()
+        }
+        ScanOutcome::Pass { masked: true } => {
+            let rewritten = serde_json::to_vec(&json).unwrap_or_else(|_| bytes.to_vec());
+            parts.headers.remove(header::CONTENT_LENGTH);
+            next.run(Request::from_parts(parts, Body::from(rewritten))).await
+        }
+        ScanOutcome::Pass { masked: false } => {
+            next.run(Request::from_parts(parts, Body::from(bytes))).await
+        }
+    }
+}
@@ -1,27 +1,35 @@
 // Proxy service module
 
 pub mod audio;
+pub mod circuit_breaker;
 pub mod cli_sync;
 pub mod common;
+pub mod concurrency;
 pub mod config;
 pub mod droid_sync;
 pub mod handlers;
+pub mod jwt_auth;
 pub mod mappers;
 pub mod middleware;
+pub mod model_fallback;
 pub mod monitor;
 pub mod opencode_sync;
+pub mod proxy_import;
 pub mod proxy_pool;
 pub mod rate_limit;
+pub mod redaction;
 pub mod security;
 pub mod server;
 pub mod session_manager;
 pub mod signature_cache;
+pub mod tls;
 pub mod token_manager;
 pub mod upstream;
 
 pub use config::{
-    get_global_system_prompt, get_image_thinking_mode, get_thinking_budget_config,
-    update_global_system_prompt_config, update_image_thinking_mode,
+    get_experimental_config, get_global_system_prompt, get_image_thinking_mode,
+    get_safety_config, get_thinking_budget_config, update_experimental_config,
+    update_global_system_prompt_config, update_image_thinking_mode, update_safety_config,
     update_thinking_budget_config,
 };
 pub use security::ProxySecurityConfig;
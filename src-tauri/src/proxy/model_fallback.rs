@@ -0,0 +1,270 @@
+// 跨模型降级 (Model Fallback)
+//
+// 主模型在整个账号池中均不可用时 (全部限流 / 配额保护)，按配置的降级链
+// 依次尝试替代模型，例如 opus-thinking → sonnet-thinking → gemini-3-pro。
+//
+// - 仅在限流 / 配额耗尽时降级；账号繁忙 (排队超时) 等错误直接返回，
+//   避免每个候选模型再排队一次后静默降级
+// - 降级链按模型名精确匹配或通配符匹配 (见 model_mapping::lookup_model_pattern)
+// - User Token 可单独退出降级
+// - 降级次数计入全局统计
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock, RwLock};
+
+use serde::Serialize;
+
+use crate::models::config::ModelFallbackConfig;
+use crate::proxy::common::model_mapping::lookup_model_pattern;
+use crate::proxy::token_manager::{ProxyToken, TokenManager};
+
+// ============================================================================
+// Global Config
+// ============================================================================
+
+static GLOBAL_FALLBACK_CONFIG: OnceLock<RwLock<ModelFallbackConfig>> = OnceLock::new();
+
+/// 当前生效的降级配置
+pub fn get_fallback_config() -> ModelFallbackConfig {
+    GLOBAL_FALLBACK_CONFIG
+        .get()
+        .and_then(|lock| lock.read().ok())
+        .map(|cfg| cfg.clone())
+        .unwrap_or_default()
+}
+
+/// 更新降级配置 (启动及配置热更新时调用)
+pub fn update_fallback_config(config: &ModelFallbackConfig) {
+    let lock = GLOBAL_FALLBACK_CONFIG.get_or_init(|| RwLock::new(ModelFallbackConfig::default()));
+    if let Ok(mut cfg) = lock.write() {
+        *cfg = config.clone();
+    }
+    tracing::info!(
+        "[Fallback] Config updated: enabled={}, chains={}, opt_out_tokens={}",
+        config.enabled,
+        config.chains.len(),
+        config.opt_out_tokens.len()
+    );
+}
+
+// ============================================================================
+// Chain Resolution
+// ============================================================================
+
+/// 解析模型的降级链 (去除模型自身及重复项)
+pub fn fallback_chain(config: &ModelFallbackConfig, model: &str) -> Vec<String> {
+    let Some(chain) = lookup_model_pattern(model, &config.chains) else {
+        return Vec::new();
+    };
+    let mut result: Vec<String> = Vec::with_capacity(chain.len());
+    for candidate in chain {
+        if candidate != model && !candidate.is_empty() && !result.contains(candidate) {
+            result.push(candidate.clone());
+        }
+    }
+    result
+}
+
+/// 当前请求是否允许降级
+pub fn fallback_allowed(config: &ModelFallbackConfig, token_id: Option<&str>) -> bool {
+    config.enabled && !token_id.is_some_and(|id| config.opt_out_tokens.iter().any(|t| t == id))
+}
+
+/// 获取 Token 的结果 (可能来自降级模型)
+#[derive(Debug, Clone)]
+pub struct FallbackToken {
+    pub token: ProxyToken,
+    /// 实际使用的模型
+    pub model: String,
+    /// 发生降级时的原始模型
+    pub fallback_from: Option<String>,
+}
+
+/// 为模型获取 Token；主模型在账号池中不可用时按降级链尝试替代模型
+pub async fn get_token_with_fallback(
+    token_manager: &TokenManager,
+    model: &str,
+    session_id: Option<&str>,
+    token_id: Option<&str>,
+) -> Result<FallbackToken, String> {
+    let primary_err = match token_manager
+        .get_token_for_user(model, session_id, token_id)
+        .await
+    {
+        Ok(token) => {
+            return Ok(FallbackToken {
+                token,
+                model: model.to_string(),
+                fallback_from: None,
+            })
+        }
+        Err(e) => e,
+    };
+
+    let config = get_fallback_config();
+    if !fallback_allowed(&config, token_id) || !primary_err.is_pool_exhausted() {
+        return Err(primary_err.to_string());
+    }
+    let chain = fallback_chain(&config, model);
+    if chain.is_empty() {
+        return Err(primary_err.to_string());
+    }
+
+    for candidate in &chain {
+        match token_manager
+            .get_token_for_user(candidate, session_id, token_id)
+            .await
+        {
+            Ok(token) => {
+                tracing::warn!(
+                    "[Fallback] {} unavailable pool-wide ({}), falling back to {}",
+                    model,
+                    primary_err,
+                    candidate
+                );
+                record_fallback(model, Some(candidate));
+                return Ok(FallbackToken {
+                    token,
+                    model: candidate.clone(),
+                    fallback_from: Some(model.to_string()),
+                });
+            }
+            Err(e) if e.is_pool_exhausted() => {
+                tracing::debug!("[Fallback] Candidate {} also unavailable: {}", candidate, e);
+            }
+            // 候选模型因繁忙等原因失败时不再继续降级
+            Err(e) => {
+                tracing::debug!("[Fallback] Candidate {} failed: {}", candidate, e);
+                return Err(format!(
+                    "{} (fallback to {} failed: {})",
+                    primary_err, candidate, e
+                ));
+            }
+        }
+    }
+
+    record_fallback(model, None);
+    Err(format!(
+        "{} (fallback chain exhausted: {})",
+        primary_err,
+        chain.join(" → ")
+    ))
+}
+
+// ============================================================================
+// Stats
+// ============================================================================
+
+/// 降级统计
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FallbackStats {
+    /// 成功降级次数
+    pub total_fallbacks: u64,
+    /// 降级链全部不可用的次数
+    pub chain_exhausted: u64,
+    /// "原模型 -> 降级模型" -> 次数
+    pub routes: HashMap<String, u64>,
+}
+
+static FALLBACK_STATS: OnceLock<Mutex<FallbackStats>> = OnceLock::new();
+
+fn stats_lock() -> &'static Mutex<FallbackStats> {
+    FALLBACK_STATS.get_or_init(|| Mutex::new(FallbackStats::default()))
+}
+
+fn record_fallback(from: &str, to: Option<&str>) {
+    let Ok(mut stats) = stats_lock().lock() else {
+        return;
+    };
+    match to {
+        Some(to) => {
+            stats.total_fallbacks += 1;
+            *stats
+                .routes
+                .entry(format!("{} -> {}", from, to))
+                .or_insert(0) += 1;
+        }
+        None => stats.chain_exhausted += 1,
+    }
+}
+
+pub fn get_fallback_stats() -> FallbackStats {
+    stats_lock().lock().map(|s| s.clone()).unwrap_or_default()
+}
+
+pub fn reset_fallback_stats() {
+    if let Ok(mut stats) = stats_lock().lock() {
+        *stats = FallbackStats::default();
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ModelFallbackConfig {
+        ModelFallbackConfig {
+            enabled: true,
+            chains: HashMap::from([
+                (
+                    "claude-opus-4-6-thinking".to_string(),
+                    vec![
+                        "claude-sonnet-4-5-thinking".to_string(),
+                        "claude-opus-4-6-thinking".to_string(),
+                        "gemini-3-pro-high".to_string(),
+                        "gemini-3-pro-high".to_string(),
+                    ],
+                ),
+                ("claude-*".to_string(), vec!["gemini-3-flash".to_string()]),
+            ]),
+            opt_out_tokens: vec!["tok-strict".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_fallback_chain_resolution() {
+        let config = config();
+        assert_eq!(
+            fallback_chain(&config, "claude-opus-4-6-thinking"),
+            vec!["claude-sonnet-4-5-thinking", "gemini-3-pro-high"]
+        );
+        assert_eq!(
+            fallback_chain(&config, "claude-sonnet-4-5"),
+            vec!["gemini-3-flash"]
+        );
+        assert!(fallback_chain(&config, "gemini-3-flash").is_empty());
+    }
+
+    #[test]
+    fn test_fallback_allowed() {
+        let mut config = config();
+        assert!(fallback_allowed(&config, None));
+        assert!(fallback_allowed(&config, Some("tok-other")));
+        assert!(!fallback_allowed(&config, Some("tok-strict")));
+        config.enabled = false;
+        assert!(!fallback_allowed(&config, None));
+    }
+
+    #[test]
+    fn test_fallback_stats() {
+        reset_fallback_stats();
+        record_fallback("claude-opus-4-6-thinking", Some("gemini-3-pro-high"));
+        record_fallback("claude-opus-4-6-thinking", Some("gemini-3-pro-high"));
+        record_fallback("claude-opus-4-6-thinking", None);
+
+        let stats = get_fallback_stats();
+        assert_eq!(stats.total_fallbacks, 2);
+        assert_eq!(stats.chain_exhausted, 1);
+        assert_eq!(
+            stats
+                .routes
+                .get("claude-opus-4-6-thinking -> gemini-3-pro-high"),
+            Some(&2)
+        );
+        reset_fallback_stats();
+    }
+}
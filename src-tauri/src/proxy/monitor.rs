@@ -8,6 +8,7 @@ use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
 use tokio::sync::RwLock;
 
 // ============================================================================
@@ -298,6 +299,26 @@ pub struct ProxyMonitor {
     stats: RwLock<ProxyStats>,
     max_logs: usize,
     enabled: AtomicBool,
+    /// 是否写入 proxy.db (测试中关闭)
+    persist: bool,
+}
+
+static GLOBAL_MONITOR: OnceLock<std::sync::RwLock<Option<Arc<ProxyMonitor>>>> = OnceLock::new();
+
+/// 注册监控中间件使用的 ProxyMonitor
+pub fn set_global_monitor(monitor: Arc<ProxyMonitor>) {
+    let slot = GLOBAL_MONITOR.get_or_init(|| std::sync::RwLock::new(None));
+    if let Ok(mut guard) = slot.write() {
+        *guard = Some(monitor);
+    }
+}
+
+/// 当前注册的 ProxyMonitor
+pub fn get_global_monitor() -> Option<Arc<ProxyMonitor>> {
+    GLOBAL_MONITOR
+        .get()
+        .and_then(|slot| slot.read().ok())
+        .and_then(|guard| guard.clone())
 }
 
 impl ProxyMonitor {
@@ -328,6 +349,19 @@ impl ProxyMonitor {
             stats: RwLock::new(ProxyStats::default()),
             max_logs,
             enabled: AtomicBool::new(false),
+            persist: true,
+        }
+    }
+
+    /// 只保留内存日志、不写数据库的实例
+    #[cfg(test)]
+    pub fn in_memory(max_logs: usize) -> Self {
+        Self {
+            logs: RwLock::new(VecDeque::with_capacity(max_logs)),
+            stats: RwLock::new(ProxyStats::default()),
+            max_logs,
+            enabled: AtomicBool::new(false),
+            persist: false,
         }
     }
 
@@ -343,12 +377,20 @@ impl ProxyMonitor {
 
     /// 记录一条请求日志
     ///
-    /// 更新内存统计、内存日志缓存，并异步持久化到数据库。
-    pub async fn log_request(&self, log: ProxyRequestLog) {
+    /// 按脱敏规则处理请求/响应体后，更新内存统计、内存日志缓存，并异步持久化到数据库。
+    pub async fn log_request(&self, mut log: ProxyRequestLog) {
         if !self.is_enabled() {
             return;
         }
 
+        // 持久化前脱敏 (PII / 密钥)
+        let redaction = crate::proxy::redaction::get_redaction_engine();
+        for field in [&mut log.request_body, &mut log.response_body, &mut log.error] {
+            if let Some(text) = field.as_mut() {
+                *text = redaction.redact_for_log(text);
+            }
+        }
+
         // Update in-memory stats
         {
             let mut stats = self.stats.write().await;
@@ -370,9 +412,11 @@ impl ProxyMonitor {
         }
 
         // Persist to DB asynchronously
-        let log_to_save = log;
-        tokio::spawn(async move {
-            if let Err(e) = save_log(&log_to_save) {
+        if !self.persist {
+            return;
+        }
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = save_log(&log) {
                 tracing::error!("Failed to save proxy log to DB: {}", e);
             }
         });
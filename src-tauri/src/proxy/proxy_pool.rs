@@ -7,7 +7,7 @@
 
 use std::collections::{HashMap, HashSet};
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::{Arc, OnceLock};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::Duration;
 
 use dashmap::DashMap;
@@ -24,6 +24,12 @@ use crate::proxy::config::normalize_proxy_url;
 
 static GLOBAL_PROXY_POOL: OnceLock<Arc<ProxyPoolManager>> = OnceLock::new();
 
+/// Weight of a new sample in the per-proxy latency EWMA.
+const LATENCY_EWMA_ALPHA: f64 = 0.3;
+
+/// Consecutive connection failures before auto-failover marks a proxy unhealthy.
+const CONNECT_FAILURE_THRESHOLD: u64 = 3;
+
 /// Get the global proxy pool manager (if initialized).
 pub fn get_global_proxy_pool() -> Option<Arc<ProxyPoolManager>> {
     GLOBAL_PROXY_POOL.get().cloned()
@@ -31,11 +37,23 @@ pub fn get_global_proxy_pool() -> Option<Arc<ProxyPoolManager>> {
 
 /// Initialize the global proxy pool manager singleton.
 /// Returns the `Arc<ProxyPoolManager>` regardless of whether it was freshly
-/// created or already existed.
-pub fn init_global_proxy_pool(config: Arc<RwLock<ProxyPoolConfig>>) -> Arc<ProxyPoolManager> {
-    let manager = Arc::new(ProxyPoolManager::new(config));
-    let _ = GLOBAL_PROXY_POOL.set(manager.clone());
-    manager
+/// created or already existed. An existing manager takes over the new config
+/// (keeping runtime health state); a fresh one starts the health check loop.
+pub async fn init_global_proxy_pool(config: ProxyPoolConfig) -> Arc<ProxyPoolManager> {
+    if let Some(existing) = GLOBAL_PROXY_POOL.get() {
+        existing.update_config(config).await;
+        return existing.clone();
+    }
+
+    let manager = Arc::new(ProxyPoolManager::new(Arc::new(RwLock::new(config))));
+    match GLOBAL_PROXY_POOL.set(manager.clone()) {
+        Ok(()) => {
+            manager.clone().start_health_check_loop();
+            manager
+        }
+        // Lost an init race – use the winner.
+        Err(_) => GLOBAL_PROXY_POOL.get().cloned().unwrap_or(manager),
+    }
 }
 
 // ============================================================================
@@ -47,6 +65,48 @@ pub fn init_global_proxy_pool(config: Arc<RwLock<ProxyPoolConfig>>) -> Arc<Proxy
 pub struct PoolProxyConfig {
     pub proxy: reqwest::Proxy,
     pub entry_id: String,
+    /// Normalized proxy URL (lets callers invalidate cached clients on edits).
+    pub url: String,
+}
+
+// ============================================================================
+// ProxyLease – in-flight request marker
+// ============================================================================
+
+/// Marks one in-flight request through a proxy; released on drop.
+///
+/// Callers keep it alive until the upstream response body has been fully
+/// read (or dropped), so `LeastConnections` sees live connections.
+pub struct ProxyLease {
+    proxy_id: String,
+    in_flight: Arc<DashMap<String, usize>>,
+}
+
+impl ProxyLease {
+    pub fn proxy_id(&self) -> &str {
+        &self.proxy_id
+    }
+}
+
+impl Drop for ProxyLease {
+    fn drop(&mut self) {
+        if let Some(mut count) = self.in_flight.get_mut(&self.proxy_id) {
+            *count = count.saturating_sub(1);
+        }
+    }
+}
+
+/// Per-proxy traffic statistics (what actually served upstream requests).
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct ProxyUsageStats {
+    /// Upstream requests that got a response through this proxy.
+    pub served: u64,
+    /// Connection failures observed through this proxy.
+    pub connect_failures: u64,
+    /// Connection failures since the last served request or passed health check.
+    pub consecutive_connect_failures: u64,
+    pub last_served_at: Option<i64>,
+    pub last_account_id: Option<String>,
 }
 
 // ============================================================================
@@ -58,7 +118,7 @@ pub struct PoolProxyConfig {
 pub struct ProxyPoolManager {
     config: Arc<RwLock<ProxyPoolConfig>>,
 
-    /// Usage counter per proxy (proxy_id → request count).
+    /// Selection counter per proxy (proxy_id → times selected from the pool).
     usage_counter: Arc<DashMap<String, usize>>,
 
     /// Account-to-proxy bindings (account_id → proxy_id).
@@ -66,6 +126,18 @@ pub struct ProxyPoolManager {
 
     /// Round-robin index for the `RoundRobin` strategy.
     round_robin_index: Arc<AtomicUsize>,
+
+    /// Served / failed request statistics per proxy.
+    usage_stats: Arc<DashMap<String, ProxyUsageStats>>,
+
+    /// Live in-flight requests per proxy (see `ProxyLease`).
+    in_flight: Arc<DashMap<String, usize>>,
+
+    /// Latency EWMA in ms per proxy, fed by health checks and real traffic.
+    latency_ewma: Arc<DashMap<String, f64>>,
+
+    /// Current weights for smooth weighted round-robin.
+    swrr_weights: Arc<Mutex<HashMap<String, i64>>>,
 }
 
 impl ProxyPoolManager {
@@ -91,9 +163,51 @@ impl ProxyPoolManager {
             usage_counter: Arc::new(DashMap::new()),
             account_bindings,
             round_robin_index: Arc::new(AtomicUsize::new(0)),
+            usage_stats: Arc::new(DashMap::new()),
+            in_flight: Arc::new(DashMap::new()),
+            latency_ewma: Arc::new(DashMap::new()),
+            swrr_weights: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
+    /// Replace the pool config (hot reload).
+    ///
+    /// Health state of unchanged entries (same id + URL) is carried over so a
+    /// config save does not resurrect proxies the health check marked down.
+    /// In-memory account bindings stay authoritative.
+    pub async fn update_config(&self, mut new_config: ProxyPoolConfig) {
+        let mut config = self.config.write().await;
+        for entry in new_config.proxies.iter_mut() {
+            if let Some(old) = config
+                .proxies
+                .iter()
+                .find(|p| p.id == entry.id && p.url == entry.url)
+            {
+                entry.is_healthy = old.is_healthy;
+                entry.latency = old.latency;
+                entry.last_check_time = old.last_check_time;
+            }
+        }
+        new_config.account_bindings = self.get_all_bindings_snapshot();
+        *config = new_config;
+        tracing::info!(
+            "[ProxyPool] Config updated ({} proxies, enabled={})",
+            config.proxies.len(),
+            config.enabled
+        );
+    }
+
+    /// Current pool config, including runtime health state.
+    pub async fn get_config(&self) -> ProxyPoolConfig {
+        self.config.read().await.clone()
+    }
+
+    /// Whether failover to another proxy is allowed on connection errors.
+    pub async fn auto_failover_enabled(&self) -> bool {
+        let config = self.config.read().await;
+        config.enabled && config.auto_failover
+    }
+
     // ========================================================================
     // get_effective_client – main entry point
     // ========================================================================
@@ -204,11 +318,43 @@ impl ProxyPoolManager {
         Ok(None)
     }
 
+    /// Pick a replacement proxy for `account_id` after `exclude` failed.
+    ///
+    /// Only healthy shared-pool proxies are considered; proxies dedicated to
+    /// other accounts are never borrowed.
+    pub async fn select_failover_proxy(
+        &self,
+        account_id: &str,
+        exclude: &HashSet<String>,
+    ) -> Result<Option<PoolProxyConfig>, String> {
+        let config = self.config.read().await;
+        if !config.enabled || !config.auto_failover {
+            return Ok(None);
+        }
+        let res = self.select_proxy_excluding(&config, exclude).await?;
+        if let Some(ref p) = res {
+            tracing::warn!(
+                "[Proxy] Route: Account {} -> Proxy {} (Failover)",
+                account_id,
+                p.entry_id
+            );
+        }
+        Ok(res)
+    }
+
     /// Select a proxy from the pool using the configured strategy.
     /// Bound proxies are excluded from the shared pool to preserve IP isolation.
     async fn select_proxy_from_pool(
         &self,
         config: &ProxyPoolConfig,
+    ) -> Result<Option<PoolProxyConfig>, String> {
+        self.select_proxy_excluding(config, &HashSet::new()).await
+    }
+
+    async fn select_proxy_excluding(
+        &self,
+        config: &ProxyPoolConfig,
+        exclude: &HashSet<String>,
     ) -> Result<Option<PoolProxyConfig>, String> {
         let bound_ids: HashSet<String> = self
             .account_bindings
@@ -227,7 +373,7 @@ impl ProxyPoolManager {
                     return false;
                 }
                 // Exclude proxies already bound to specific accounts.
-                if bound_ids.contains(&p.id) {
+                if bound_ids.contains(&p.id) || exclude.contains(&p.id) {
                     return false;
                 }
                 true
@@ -246,6 +392,7 @@ impl ProxyPoolManager {
                 self.select_least_connections(&healthy_proxies)
             }
             ProxySelectionStrategy::WeightedRoundRobin => self.select_weighted(&healthy_proxies),
+            ProxySelectionStrategy::LatencyAware => self.select_lowest_latency(&healthy_proxies),
         };
 
         if let Some(entry) = selected {
@@ -281,16 +428,65 @@ impl ProxyPoolManager {
         proxies.iter().min_by_key(|p| p.priority).copied()
     }
 
+    /// Fewest in-flight requests; ties go to the better priority.
     fn select_least_connections<'a>(&self, proxies: &[&'a ProxyEntry]) -> Option<&'a ProxyEntry> {
         proxies
             .iter()
-            .min_by_key(|p| self.usage_counter.get(&p.id).map(|v| *v).unwrap_or(0))
+            .min_by_key(|p| (self.in_flight_count(&p.id), p.priority))
             .copied()
     }
 
+    /// Smooth weighted round-robin (nginx style): every candidate gains its
+    /// weight, the leader is picked and pays back the total. Spreads picks
+    /// evenly instead of bursting the heaviest proxy.
     fn select_weighted<'a>(&self, proxies: &[&'a ProxyEntry]) -> Option<&'a ProxyEntry> {
-        // Weighted round-robin: use priority as weight (lower priority = higher weight).
-        self.select_by_priority(proxies)
+        if proxies.is_empty() {
+            return None;
+        }
+        let mut current = self.swrr_weights.lock().unwrap_or_else(|e| e.into_inner());
+        // Forget proxies that left the candidate set (removed / unhealthy).
+        current.retain(|id, _| proxies.iter().any(|p| p.id == *id));
+
+        let total: i64 = proxies.iter().map(|p| p.weight.max(1) as i64).sum();
+        let mut best: Option<(&'a ProxyEntry, i64)> = None;
+        for p in proxies {
+            let weight = current.entry(p.id.clone()).or_insert(0);
+            *weight += p.weight.max(1) as i64;
+            let better = match best {
+                Some((_, best_weight)) => *weight > best_weight,
+                None => true,
+            };
+            if better {
+                best = Some((p, *weight));
+            }
+        }
+
+        let (selected, _) = best?;
+        if let Some(weight) = current.get_mut(&selected.id) {
+            *weight -= total;
+        }
+        Some(selected)
+    }
+
+    /// Lowest latency EWMA. Proxies without any sample sort first so they
+    /// get measured.
+    fn select_lowest_latency<'a>(&self, proxies: &[&'a ProxyEntry]) -> Option<&'a ProxyEntry> {
+        proxies
+            .iter()
+            .min_by(|a, b| {
+                self.effective_latency(a)
+                    .total_cmp(&self.effective_latency(b))
+                    .then(a.priority.cmp(&b.priority))
+            })
+            .copied()
+    }
+
+    fn effective_latency(&self, entry: &ProxyEntry) -> f64 {
+        self.latency_ewma
+            .get(&entry.id)
+            .map(|v| *v)
+            .or(entry.latency.map(|ms| ms as f64))
+            .unwrap_or(0.0)
     }
 
     // ========================================================================
@@ -309,9 +505,92 @@ impl ProxyPoolManager {
         Ok(PoolProxyConfig {
             proxy,
             entry_id: entry.id.clone(),
+            url,
         })
     }
 
+    // ========================================================================
+    // Traffic accounting
+    // ========================================================================
+
+    /// Record that `proxy_id` served an upstream request for `account_id`.
+    pub fn record_served(&self, proxy_id: &str, account_id: Option<&str>) {
+        let mut stats = self.usage_stats.entry(proxy_id.to_string()).or_default();
+        stats.served += 1;
+        stats.consecutive_connect_failures = 0;
+        stats.last_served_at = Some(chrono::Utc::now().timestamp());
+        stats.last_account_id = account_id.map(str::to_string);
+    }
+
+    /// Record a connection failure through `proxy_id`.
+    ///
+    /// With auto-failover the proxy is marked unhealthy once
+    /// `CONNECT_FAILURE_THRESHOLD` failures happen in a row, so a single
+    /// transient error doesn't take it out of rotation; the next health check
+    /// can bring it back.
+    pub async fn record_connect_failure(&self, proxy_id: &str) {
+        let consecutive = {
+            let mut stats = self.usage_stats.entry(proxy_id.to_string()).or_default();
+            stats.connect_failures += 1;
+            stats.consecutive_connect_failures += 1;
+            stats.consecutive_connect_failures
+        };
+        if consecutive < CONNECT_FAILURE_THRESHOLD {
+            return;
+        }
+
+        let mut config = self.config.write().await;
+        if !config.auto_failover {
+            return;
+        }
+        if let Some(entry) = config.proxies.iter_mut().find(|p| p.id == proxy_id) {
+            if entry.is_healthy {
+                entry.is_healthy = false;
+                tracing::warn!(
+                    "[ProxyPool] Proxy {} marked unhealthy after {} consecutive connection failures",
+                    proxy_id,
+                    consecutive
+                );
+            }
+        }
+    }
+
+    /// Mark a request as in flight through `proxy_id` until the lease drops.
+    pub fn acquire_lease(&self, proxy_id: &str) -> ProxyLease {
+        *self.in_flight.entry(proxy_id.to_string()).or_insert(0) += 1;
+        ProxyLease {
+            proxy_id: proxy_id.to_string(),
+            in_flight: self.in_flight.clone(),
+        }
+    }
+
+    /// Current in-flight requests through `proxy_id`.
+    pub fn in_flight_count(&self, proxy_id: &str) -> usize {
+        self.in_flight.get(proxy_id).map(|v| *v).unwrap_or(0)
+    }
+
+    /// Feed a latency sample (ms) into the proxy's EWMA.
+    pub fn record_latency(&self, proxy_id: &str, latency_ms: u64) {
+        let sample = latency_ms as f64;
+        self.latency_ewma
+            .entry(proxy_id.to_string())
+            .and_modify(|v| *v = LATENCY_EWMA_ALPHA * sample + (1.0 - LATENCY_EWMA_ALPHA) * *v)
+            .or_insert(sample);
+    }
+
+    /// Current latency EWMA (ms) for `proxy_id`, if any sample was recorded.
+    pub fn latency_ewma(&self, proxy_id: &str) -> Option<f64> {
+        self.latency_ewma.get(proxy_id).map(|v| *v)
+    }
+
+    /// Snapshot of per-proxy traffic statistics.
+    pub fn get_usage_stats_snapshot(&self) -> HashMap<String, ProxyUsageStats> {
+        self.usage_stats
+            .iter()
+            .map(|kv| (kv.key().clone(), kv.value().clone()))
+            .collect()
+    }
+
 
     // ========================================================================
     // Account binding management
@@ -417,8 +696,31 @@ impl ProxyPoolManager {
                 .collect()
         };
 
+        let results = self.check_entries(proxies_to_check).await;
+
+        // Batch-update proxy health state.
+        let mut config = self.config.write().await;
+        for (id, is_healthy, latency) in results {
+            if is_healthy {
+                if let Some(mut stats) = self.usage_stats.get_mut(&id) {
+                    stats.consecutive_connect_failures = 0;
+                }
+            }
+            if let Some(proxy) = config.proxies.iter_mut().find(|p| p.id == id) {
+                proxy.is_healthy = is_healthy;
+                proxy.latency = latency;
+                proxy.last_check_time = Some(chrono::Utc::now().timestamp());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check proxies concurrently (capped at 20) and feed the latency EWMA.
+    /// Returns `(proxy_id, is_healthy, latency_ms)` per entry.
+    async fn check_entries(&self, entries: Vec<ProxyEntry>) -> Vec<(String, bool, Option<u64>)> {
         let concurrency_limit = 20usize;
-        let results: Vec<(String, bool, Option<u64>)> = stream::iter(proxies_to_check)
+        let results: Vec<(String, bool, Option<u64>)> = stream::iter(entries)
             .map(|proxy| async move {
                 let (is_healthy, latency) = Self::check_proxy_health(&proxy).await;
                 tracing::info!(
@@ -434,17 +736,48 @@ impl ProxyPoolManager {
             .collect()
             .await;
 
-        // Batch-update proxy health state.
-        let mut config = self.config.write().await;
-        for (id, is_healthy, latency) in results {
-            if let Some(proxy) = config.proxies.iter_mut().find(|p| p.id == id) {
-                proxy.is_healthy = is_healthy;
-                proxy.latency = latency;
-                proxy.last_check_time = Some(chrono::Utc::now().timestamp());
+        for (id, _, latency) in &results {
+            if let Some(ms) = latency {
+                self.record_latency(id, *ms);
             }
         }
+        results
+    }
 
-        Ok(())
+    /// Health-check new entries, append them to the pool and persist.
+    /// Returns the entries with their health state filled in.
+    pub async fn add_proxies(
+        &self,
+        mut entries: Vec<ProxyEntry>,
+    ) -> Result<Vec<ProxyEntry>, String> {
+        if entries.is_empty() {
+            return Ok(entries);
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        let results = self.check_entries(entries.clone()).await;
+        for entry in entries.iter_mut() {
+            if let Some((_, is_healthy, latency)) =
+                results.iter().find(|(id, _, _)| *id == entry.id)
+            {
+                entry.is_healthy = *is_healthy;
+                entry.latency = *latency;
+                entry.last_check_time = Some(now);
+            }
+        }
+
+        let snapshot = {
+            let mut config = self.config.write().await;
+            config.proxies.extend(entries.iter().cloned());
+            config.clone()
+        };
+
+        let mut app_config = crate::modules::config::load_app_config()?;
+        app_config.proxy.proxy_pool = snapshot;
+        crate::modules::config::save_app_config(&app_config)?;
+
+        tracing::info!("[ProxyPool] Added {} proxies", entries.len());
+        Ok(entries)
     }
 
     /// Check a single proxy's health by issuing a lightweight HTTP request.
@@ -550,6 +883,7 @@ mod tests {
             last_check_time: None,
             is_healthy: healthy,
             latency: None,
+            weight: 1,
         }
     }
 
@@ -635,31 +969,43 @@ mod tests {
     }
 
     #[test]
-    fn test_least_connections_selects_least_used() {
+    fn test_least_connections_tracks_in_flight_leases() {
         let entries = vec![
             make_entry("a", 1, true, true),
-            make_entry("b", 1, true, true),
+            make_entry("b", 2, true, true),
         ];
         let mgr = pool_manager(make_config(
             entries.clone(),
             ProxySelectionStrategy::LeastConnections,
             false,
         ));
-        // Simulate usage: "a" has 5 requests, "b" has 2.
-        mgr.usage_counter.insert("a".to_string(), 5);
-        mgr.usage_counter.insert("b".to_string(), 2);
-
         let refs: Vec<&ProxyEntry> = entries.iter().collect();
-        let selected = mgr.select_least_connections(&refs).unwrap();
-        assert_eq!(selected.id, "b");
+
+        // Historic selections do not matter, only live requests.
+        mgr.usage_counter.insert("a".to_string(), 100);
+        let lease_a1 = mgr.acquire_lease("a");
+        let lease_a2 = mgr.acquire_lease("a");
+        let _lease_b = mgr.acquire_lease("b");
+        assert_eq!(mgr.select_least_connections(&refs).unwrap().id, "b");
+
+        drop(lease_a1);
+        // 1 vs 1 → tie broken by priority
+        assert_eq!(mgr.select_least_connections(&refs).unwrap().id, "a");
+        drop(lease_a2);
+        assert_eq!(mgr.in_flight_count("a"), 0);
+        assert_eq!(mgr.in_flight_count("b"), 1);
     }
 
     #[test]
-    fn test_weighted_delegates_to_priority() {
-        let entries = vec![
-            make_entry("a", 10, true, true),
+    fn test_smooth_weighted_round_robin() {
+        let mut entries = vec![
+            make_entry("a", 1, true, true),
             make_entry("b", 1, true, true),
+            make_entry("c", 1, true, true),
         ];
+        entries[0].weight = 5;
+        entries[1].weight = 1;
+        entries[2].weight = 1;
         let mgr = pool_manager(make_config(
             entries.clone(),
             ProxySelectionStrategy::WeightedRoundRobin,
@@ -667,8 +1013,39 @@ mod tests {
         ));
         let refs: Vec<&ProxyEntry> = entries.iter().collect();
 
-        let selected = mgr.select_weighted(&refs).unwrap();
-        assert_eq!(selected.id, "b");
+        let picks: Vec<String> = (0..7)
+            .map(|_| mgr.select_weighted(&refs).unwrap().id.clone())
+            .collect();
+        // Classic nginx sequence for weights {5, 1, 1}: no bursts of "a".
+        assert_eq!(picks, vec!["a", "a", "b", "a", "c", "a", "a"]);
+    }
+
+    #[test]
+    fn test_latency_aware_prefers_lowest_ewma() {
+        let mut entries = vec![
+            make_entry("a", 1, true, true),
+            make_entry("b", 2, true, true),
+        ];
+        entries[0].latency = Some(50);
+        entries[1].latency = Some(80);
+        let mgr = pool_manager(make_config(
+            entries.clone(),
+            ProxySelectionStrategy::LatencyAware,
+            false,
+        ));
+        let refs: Vec<&ProxyEntry> = entries.iter().collect();
+
+        // Falls back to the last health check latency without samples.
+        assert_eq!(mgr.select_lowest_latency(&refs).unwrap().id, "a");
+
+        // Real traffic on "a" turns slow; EWMA moves past "b".
+        mgr.record_latency("a", 50);
+        for _ in 0..5 {
+            mgr.record_latency("a", 400);
+        }
+        mgr.record_latency("b", 80);
+        assert!(mgr.latency_ewma("a").unwrap() > 80.0);
+        assert_eq!(mgr.select_lowest_latency(&refs).unwrap().id, "b");
     }
 
     #[test]
@@ -684,6 +1061,7 @@ mod tests {
         assert!(mgr.select_by_priority(&refs).is_none());
         assert!(mgr.select_least_connections(&refs).is_none());
         assert!(mgr.select_weighted(&refs).is_none());
+        assert!(mgr.select_lowest_latency(&refs).is_none());
     }
 
     // ── Pool selection tests ────────────────────────────────────────────
@@ -899,6 +1277,119 @@ mod tests {
         );
     }
 
+    // ── Failover / traffic accounting tests ─────────────────────────────
+
+    #[tokio::test]
+    async fn test_failover_skips_failed_and_bound_proxies() {
+        let config = make_config(
+            vec![
+                make_entry("a", 1, true, true),
+                make_entry("b", 2, true, true),
+                make_entry("c", 3, true, true),
+            ],
+            ProxySelectionStrategy::Priority,
+            true,
+        );
+        let mgr = pool_manager(config);
+        mgr.account_bindings
+            .insert("acc-2".to_string(), "b".to_string());
+
+        mgr.record_connect_failure("a").await;
+        let exclude: HashSet<String> = ["a".to_string()].into_iter().collect();
+        let picked = mgr
+            .select_failover_proxy("acc-1", &exclude)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(picked.entry_id, "c");
+
+        // A single failure only excludes "a" from this request's failover.
+        assert!(mgr.config.read().await.proxies[0].is_healthy);
+
+        // Repeated failures mark it unhealthy until the next health check.
+        for _ in 1..CONNECT_FAILURE_THRESHOLD {
+            mgr.record_connect_failure("a").await;
+        }
+        assert!(!mgr.config.read().await.proxies[0].is_healthy);
+
+        mgr.record_served("c", Some("acc-1"));
+        let stats = mgr.get_usage_stats_snapshot();
+        assert_eq!(stats["a"].connect_failures, CONNECT_FAILURE_THRESHOLD);
+        assert_eq!(stats["c"].served, 1);
+        assert_eq!(stats["c"].last_account_id.as_deref(), Some("acc-1"));
+    }
+
+    #[tokio::test]
+    async fn test_served_request_resets_consecutive_connect_failures() {
+        let config = make_config(
+            vec![make_entry("a", 1, true, true)],
+            ProxySelectionStrategy::Priority,
+            true,
+        );
+        let mgr = pool_manager(config);
+
+        for _ in 1..CONNECT_FAILURE_THRESHOLD {
+            mgr.record_connect_failure("a").await;
+        }
+        mgr.record_served("a", None);
+        mgr.record_connect_failure("a").await;
+        assert!(mgr.config.read().await.proxies[0].is_healthy);
+
+        let stats = mgr.get_usage_stats_snapshot();
+        assert_eq!(stats["a"].consecutive_connect_failures, 1);
+        assert_eq!(stats["a"].connect_failures, CONNECT_FAILURE_THRESHOLD);
+    }
+
+    #[tokio::test]
+    async fn test_failover_disabled_without_auto_failover() {
+        let config = make_config(
+            vec![
+                make_entry("a", 1, true, true),
+                make_entry("b", 2, true, true),
+            ],
+            ProxySelectionStrategy::Priority,
+            false,
+        );
+        let mgr = pool_manager(config);
+        mgr.record_connect_failure("a").await;
+        let exclude: HashSet<String> = ["a".to_string()].into_iter().collect();
+        assert!(mgr
+            .select_failover_proxy("acc-1", &exclude)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(mgr.config.read().await.proxies[0].is_healthy);
+    }
+
+    #[tokio::test]
+    async fn test_update_config_keeps_health_state_and_bindings() {
+        let config = make_config(
+            vec![
+                make_entry("a", 1, true, true),
+                make_entry("b", 2, true, true),
+            ],
+            ProxySelectionStrategy::Priority,
+            true,
+        );
+        let mgr = pool_manager(config.clone());
+        mgr.account_bindings
+            .insert("acc-1".to_string(), "a".to_string());
+        for _ in 0..CONNECT_FAILURE_THRESHOLD {
+            mgr.record_connect_failure("a").await;
+        }
+
+        let mut new_config = config;
+        new_config.proxies[1].url = "http://changed.example.com:8080".to_string();
+        new_config.proxies[1].is_healthy = false;
+        mgr.update_config(new_config).await;
+
+        let cfg = mgr.config.read().await.clone();
+        assert!(!cfg.proxies[0].is_healthy);
+        // URL changed → health state is not carried over.
+        assert!(!cfg.proxies[1].is_healthy);
+        assert_eq!(cfg.account_bindings.get("acc-1"), Some(&"a".to_string()));
+    }
+
     // ── Usage counter tests ─────────────────────────────────────────────
 
     #[tokio::test]
@@ -206,7 +206,10 @@ impl RateLimitTracker {
 
         // 1. Parse rate limit reason type
         let reason = if status == 429 {
-            tracing::warn!("Google 429 Error Body: {}", body);
+            tracing::warn!(
+                "Google 429 Error Body: {}",
+                crate::proxy::redaction::get_redaction_engine().redact_for_log(body)
+            );
             self.parse_rate_limit_reason(body)
         } else if status == 404 {
             tracing::warn!(
@@ -217,19 +220,8 @@ impl RateLimitTracker {
             RateLimitReason::ServerError
         };
 
-        let mut retry_after_sec = None;
-
-        // 2. Extract from Retry-After header
-        if let Some(retry_after) = retry_after_header {
-            if let Ok(seconds) = retry_after.parse::<u64>() {
-                retry_after_sec = Some(seconds);
-            }
-        }
-
-        // 3. Extract from error message body (try JSON first, then regex)
-        if retry_after_sec.is_none() {
-            retry_after_sec = self.parse_retry_time_from_body(body);
-        }
+        // 2-3. Retry-After header, then error body (RetryInfo / quotaResetDelay)
+        let retry_after_sec = self.server_reset_seconds(retry_after_header, body);
 
         // 4. Handle defaults and soft avoidance logic based on reason type
         let retry_sec = match retry_after_sec {
@@ -478,6 +470,18 @@ impl RateLimitTracker {
         }
     }
 
+    /// Server-provided reset time: Retry-After header first, then the error
+    /// body (JSON RetryInfo / quotaResetDelay, then regex).
+    pub fn server_reset_seconds(
+        &self,
+        retry_after_header: Option<&str>,
+        body: &str,
+    ) -> Option<u64> {
+        retry_after_header
+            .and_then(|v| v.parse::<u64>().ok())
+            .or_else(|| self.parse_retry_time_from_body(body))
+    }
+
     /// Parse retry time from error response body
     fn parse_retry_time_from_body(&self, body: &str) -> Option<u64> {
         // A. Try JSON parsing first
@@ -2,9 +2,12 @@
 //
 // Requirements covered:
 // - 6.1: API Key 验证 (strict mode)
-// - 6.4: Admin API 强制鉴权 (admin_password 或 api_key)
+// - 6.4: Admin API 强制鉴权 (管理员会话令牌；未创建管理员账号时回退 admin_password 或 api_key)
+// - mTLS: 客户端证书 Subject 映射为 User Token 身份
+// - JWT: 内部 IdP 签发的短期令牌 (见 proxy::jwt_auth)
 
-use crate::models::config::{ProxyAuthMode, ProxyConfig, SecurityMonitorConfig};
+use crate::models::config::{JwtAuthConfig, ProxyAuthMode, ProxyConfig, SecurityMonitorConfig, TlsConfig};
+use crate::proxy::tls::ClientCertIdentity;
 
 // ============================================================================
 // ProxySecurityConfig - 运行时安全配置
@@ -18,6 +21,8 @@ pub struct ProxySecurityConfig {
     pub allow_lan_access: bool,
     pub port: u16,
     pub security_monitor: SecurityMonitorConfig,
+    pub tls: TlsConfig,
+    pub jwt_auth: JwtAuthConfig,
 }
 
 impl ProxySecurityConfig {
@@ -29,6 +34,8 @@ impl ProxySecurityConfig {
             allow_lan_access: config.allow_lan_access,
             port: config.port,
             security_monitor: config.security_monitor.clone(),
+            tls: config.tls.clone(),
+            jwt_auth: config.jwt_auth.clone(),
         }
     }
 
@@ -59,6 +66,20 @@ impl ProxySecurityConfig {
         }
     }
 
+    /// 查找客户端证书映射的 User Token ID（完整 Subject 优先，其次 CN）
+    pub fn client_cert_token_id(&self, identity: &ClientCertIdentity) -> Option<&str> {
+        let identities = &self.tls.client_cert_identities;
+        identities
+            .get(&identity.subject)
+            .or_else(|| identity.common_name.as_ref().and_then(|cn| identities.get(cn)))
+            .map(|id| id.as_str())
+    }
+
+    /// 无需管理员身份即可访问的管理接口路径（健康检查、登录）
+    pub fn is_public_admin_path(path: &str) -> bool {
+        matches!(path, "/health" | "/healthz" | "/api/health" | "/auth/login")
+    }
+
     /// 检查是否需要鉴权（基于 effective_auth_mode 和路径）
     pub fn requires_auth(&self, path: &str, is_admin: bool) -> bool {
         let effective = self.effective_auth_mode();
@@ -153,6 +174,8 @@ mod tests {
             allow_lan_access: allow_lan,
             port: 8080,
             security_monitor: SecurityMonitorConfig::default(),
+            tls: TlsConfig::default(),
+            jwt_auth: JwtAuthConfig::default(),
         }
     }
 
@@ -240,6 +263,45 @@ mod tests {
         assert!(s.validate_admin_key("sk-test-key"));
     }
 
+    // ---- is_public_admin_path tests ----
+
+    #[test]
+    fn test_public_admin_paths() {
+        assert!(ProxySecurityConfig::is_public_admin_path("/health"));
+        assert!(ProxySecurityConfig::is_public_admin_path("/auth/login"));
+        assert!(!ProxySecurityConfig::is_public_admin_path("/auth/logout"));
+        assert!(!ProxySecurityConfig::is_public_admin_path("/accounts"));
+    }
+
+    #[test]
+    fn test_client_cert_token_id_prefers_full_subject() {
+        let mut s = make_config(ProxyAuthMode::Strict, true);
+        s.tls
+            .client_cert_identities
+            .insert("CN=billing, O=Acme".to_string(), "token-full".to_string());
+        s.tls
+            .client_cert_identities
+            .insert("billing".to_string(), "token-cn".to_string());
+
+        let exact = ClientCertIdentity {
+            subject: "CN=billing, O=Acme".to_string(),
+            common_name: Some("billing".to_string()),
+        };
+        assert_eq!(s.client_cert_token_id(&exact), Some("token-full"));
+
+        let other_org = ClientCertIdentity {
+            subject: "CN=billing, O=Other".to_string(),
+            common_name: Some("billing".to_string()),
+        };
+        assert_eq!(s.client_cert_token_id(&other_org), Some("token-cn"));
+
+        let unknown = ClientCertIdentity {
+            subject: "CN=unknown".to_string(),
+            common_name: Some("unknown".to_string()),
+        };
+        assert_eq!(s.client_cert_token_id(&unknown), None);
+    }
+
     // ---- requires_auth tests ----
 
     #[test]
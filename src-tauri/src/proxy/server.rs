@@ -10,8 +10,11 @@
 // - 14.6: Hot update global system prompt
 // - 14.7: Hot update proxy pool
 // - 14.8: Hot update experimental features
+// - HTTPS listener with optional mTLS (see proxy::tls)
+// - PII / secret redaction of request bodies (see proxy::redaction)
 
 use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
 use std::sync::{Arc, OnceLock};
 use tokio::sync::{oneshot, RwLock};
 use tracing::{debug, error, info};
@@ -24,13 +27,14 @@ use axum::{
     Router,
 };
 
-use crate::models::config::{ProxyConfig, UpstreamProxyConfig};
+use crate::models::config::{AppConfig, ProxyConfig, UpstreamProxyConfig};
 use crate::proxy::handlers::AppState;
 use crate::proxy::middleware::{
     admin_auth_middleware, auth_middleware, cors_layer, ip_filter_middleware, monitor_middleware,
-    service_status_middleware,
+    redaction_middleware, require_admin_role, service_status_middleware,
 };
 use crate::proxy::security::ProxySecurityConfig;
+use crate::proxy::tls::{client_identity_from_der, ClientCertIdentity, TlsReloader};
 use crate::proxy::token_manager::TokenManager;
 use crate::proxy::upstream::client::UpstreamClient;
 
@@ -145,8 +149,9 @@ fn proxy_routes(state: AppState, security: Arc<RwLock<ProxySecurityConfig>>) ->
         // Silent endpoints
         .route("/v1/api/event_logging/batch", post(silent_ok_handler))
         .route("/v1/api/event_logging", post(silent_ok_handler))
-        // Middleware stack (onion model): IP Filter → Auth → Monitor → Handler
+        // Middleware stack (onion model): IP Filter → Auth → Monitor → Redaction → Handler
         // Axum layers execute bottom-to-top for requests
+        .layer(axum::middleware::from_fn(redaction_middleware))
         .layer(axum::middleware::from_fn(monitor_middleware))
         .layer(axum::middleware::from_fn_with_state(
             security.clone(),
@@ -158,19 +163,110 @@ fn proxy_routes(state: AppState, security: Arc<RwLock<ProxySecurityConfig>>) ->
     routes
 }
 
+/// Max request body size (KIRO_MAX_BODY_SIZE, default 100MB)
+pub(crate) fn max_body_size() -> usize {
+    std::env::var("KIRO_MAX_BODY_SIZE")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(100 * 1024 * 1024)
+}
+
+/// TLS 握手超时：空闲或慢速 (slowloris) 连接不能无限占用任务
+const TLS_HANDSHAKE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Serve a single accepted connection (plain TCP or TLS)
+///
+/// Injects the peer address and, for mTLS connections, the verified client
+/// certificate identity as request extensions.
+async fn serve_connection<I>(
+    io: I,
+    app_service: axum::routing::RouterIntoService<hyper::body::Incoming>,
+    remote_addr: SocketAddr,
+    client_cert: Option<ClientCertIdentity>,
+) where
+    I: hyper::rt::Read + hyper::rt::Write + Unpin + Send + 'static,
+{
+    use hyper::body::Incoming;
+    use hyper::server::conn::http1;
+    use hyper_util::service::TowerToHyperService;
+    use tower::ServiceExt;
+
+    let svc = app_service.map_request(move |mut req: axum::http::Request<Incoming>| {
+        req.extensions_mut().insert(axum::extract::ConnectInfo(remote_addr));
+        if let Some(cert) = &client_cert {
+            req.extensions_mut().insert(cert.clone());
+        }
+        req
+    });
+
+    if let Err(err) = http1::Builder::new()
+        .serve_connection(io, TowerToHyperService::new(svc))
+        .with_upgrades()
+        .await
+    {
+        debug!("Connection ended: {:?}", err);
+    }
+}
+
 /// Build admin API routes with all management endpoints
+///
+/// Routes are grouped by the minimum admin role they require
+/// (viewer < operator < owner). `admin_auth_middleware` resolves the caller's
+/// identity, then each group's `require_admin_role` layer enforces its role.
 fn admin_routes(state: AppState, security: Arc<RwLock<ProxySecurityConfig>>) -> Router {
+    use crate::modules::admin_user_db::AdminRole;
     use crate::proxy::handlers::admin;
     use axum::routing::delete;
 
-    Router::new()
-        .route("/health", get(health_check_handler))
+    // Viewer: stats, logs, read-only status
+    let viewer = Router::new()
+        .route("/auth/me", get(admin::admin_get_current_admin))
+        .route("/auth/logout", post(admin::admin_logout))
+        .route("/proxy/status", get(admin::admin_get_proxy_status))
+        .route("/proxy/stats", get(admin::admin_get_proxy_stats))
+        .route("/proxy/preferred-account", get(admin::admin_get_preferred_account))
+        // Proxy Logs
+        .route("/logs", get(admin::admin_get_proxy_logs_filtered))
+        .route("/logs/count", get(admin::admin_get_proxy_logs_count_filtered))
+        .route("/logs/:logId", get(admin::admin_get_proxy_log_detail))
+        // Token Stats
+        .route("/stats/summary", get(admin::admin_get_token_stats_summary))
+        .route("/stats/hourly", get(admin::admin_get_token_stats_hourly))
+        .route("/stats/daily", get(admin::admin_get_token_stats_daily))
+        .route("/stats/weekly", get(admin::admin_get_token_stats_weekly))
+        .route("/stats/accounts", get(admin::admin_get_token_stats_by_account))
+        .route("/stats/models", get(admin::admin_get_token_stats_by_model))
+        .route("/stats/token/hourly", get(admin::admin_get_token_stats_hourly))
+        .route("/stats/token/daily", get(admin::admin_get_token_stats_daily))
+        .route("/stats/token/weekly", get(admin::admin_get_token_stats_weekly))
+        .route("/stats/token/by-account", get(admin::admin_get_token_stats_by_account))
+        .route("/stats/token/summary", get(admin::admin_get_token_stats_summary))
+        .route("/stats/token/by-model", get(admin::admin_get_token_stats_by_model))
+        .route("/stats/token/model-trend/hourly", get(admin::admin_get_token_stats_model_trend_hourly))
+        .route("/stats/token/model-trend/daily", get(admin::admin_get_token_stats_model_trend_daily))
+        .route("/stats/token/account-trend/hourly", get(admin::admin_get_token_stats_account_trend_hourly))
+        .route("/stats/token/account-trend/daily", get(admin::admin_get_token_stats_account_trend_daily))
+        .route("/stats/redaction", get(admin::admin_get_redaction_stats))
+        .route("/stats/fallback", get(admin::admin_get_fallback_stats))
+        .route("/stats/hedging", get(admin::admin_get_hedge_stats))
+        .route("/stats/concurrency", get(admin::admin_get_concurrency_stats))
+        .route("/stats/session-bindings", get(admin::admin_get_session_binding_stats))
+        // System (read-only)
+        .route("/system/data-dir", get(admin::admin_get_data_dir_path))
+        .route("/system/updates/check-status", get(admin::admin_should_check_updates))
+        .route("/system/autostart/status", get(admin::admin_is_auto_launch_enabled))
+        .route_layer(axum::middleware::from_fn_with_state(
+            AdminRole::Viewer,
+            require_admin_role,
+        ));
+
+    // Operator: accounts, rate limits, warmup, proxy service control
+    let operator = Router::new()
         // Account Management
         .route("/accounts", get(admin::admin_list_accounts).post(admin::admin_add_account))
         .route("/accounts/switch", post(admin::admin_switch_account))
         .route("/accounts/refresh", post(admin::admin_refresh_all_quotas))
         .route("/accounts/bulk-delete", post(admin::admin_delete_accounts))
-        .route("/accounts/export", post(admin::admin_export_accounts))
         .route("/accounts/reorder", post(admin::admin_reorder_accounts))
         .route("/accounts/warmup", post(admin::admin_warm_up_all_accounts))
         .route("/accounts/device-preview", post(admin::admin_preview_generate_profile))
@@ -192,27 +288,42 @@ fn admin_routes(state: AppState, security: Arc<RwLock<ProxySecurityConfig>>) ->
         .route("/accounts/oauth/cancel", post(admin::admin_cancel_oauth_login))
         .route("/accounts/oauth/submit-code", post(admin::admin_submit_oauth_code))
         .route("/auth/url", get(admin::admin_prepare_oauth_url_web))
-        // Config
-        .route("/config", get(admin::admin_get_config).post(admin::admin_save_config))
         // Proxy Control
-        .route("/proxy/status", get(admin::admin_get_proxy_status))
         .route("/proxy/start", post(admin::admin_start_proxy_service))
         .route("/proxy/stop", post(admin::admin_stop_proxy_service))
-        .route("/proxy/mapping", post(admin::admin_update_model_mapping))
-        .route("/proxy/api-key/generate", post(admin::admin_generate_api_key))
         .route("/proxy/session-bindings/clear", post(admin::admin_clear_proxy_session_bindings))
         .route("/proxy/rate-limits", delete(admin::admin_clear_all_rate_limits))
         .route("/proxy/rate-limits/:accountId", delete(admin::admin_clear_rate_limit))
-        .route("/proxy/preferred-account", get(admin::admin_get_preferred_account).post(admin::admin_set_preferred_account))
+        .route("/proxy/scheduling/explain", get(admin::admin_explain_scheduling))
+        .route("/proxy/preferred-account", post(admin::admin_set_preferred_account))
         .route("/proxy/monitor/toggle", post(admin::admin_set_proxy_monitor_enabled))
-        .route("/proxy/stats", get(admin::admin_get_proxy_stats))
-        // Proxy Pool
-        .route("/proxy/pool/config", get(admin::admin_get_proxy_pool_config))
+        .route("/proxy/signature-cache/stats", get(admin::admin_get_signature_cache_stats))
+        // Proxy Pool bindings
         .route("/proxy/pool/bindings", get(admin::admin_get_all_account_bindings))
         .route("/proxy/pool/bind", post(admin::admin_bind_account_proxy))
         .route("/proxy/pool/unbind", post(admin::admin_unbind_account_proxy))
         .route("/proxy/pool/binding/:accountId", get(admin::admin_get_account_proxy_binding))
+        .route("/proxy/pool/stats", get(admin::admin_get_proxy_pool_stats))
+        .route("/proxy/pool/import", post(admin::admin_import_proxies))
         .route("/proxy/health-check/trigger", post(admin::admin_trigger_proxy_health_check))
+        // Logs & caches
+        .route("/logs/clear", post(admin::admin_clear_proxy_logs))
+        .route("/system/updates/check", post(admin::admin_check_for_updates))
+        .route("/system/cache/clear", post(admin::admin_clear_cache))
+        .route("/system/logs/clear-cache", post(admin::admin_clear_log_cache))
+        .route_layer(axum::middleware::from_fn_with_state(
+            AdminRole::Operator,
+            require_admin_role,
+        ));
+
+    // Owner: config, security, tokens, credentials, admin users
+    let owner = Router::new()
+        // Config
+        .route("/config", get(admin::admin_get_config).post(admin::admin_save_config))
+        .route("/proxy/mapping", post(admin::admin_update_model_mapping))
+        .route("/proxy/api-key/generate", post(admin::admin_generate_api_key))
+        .route("/proxy/pool/config", get(admin::admin_get_proxy_pool_config))
+        .route("/accounts/export", post(admin::admin_export_accounts))
         // CLI Sync
         .route("/proxy/cli/status", post(admin::admin_get_cli_sync_status))
         .route("/proxy/cli/sync", post(admin::admin_execute_cli_sync))
@@ -229,11 +340,6 @@ fn admin_routes(state: AppState, security: Arc<RwLock<ProxySecurityConfig>>) ->
         .route("/proxy/droid/sync", post(admin::admin_execute_droid_sync))
         .route("/proxy/droid/restore", post(admin::admin_execute_droid_restore))
         .route("/proxy/droid/config", post(admin::admin_get_droid_config_content))
-        // Proxy Logs
-        .route("/logs", get(admin::admin_get_proxy_logs_filtered))
-        .route("/logs/count", get(admin::admin_get_proxy_logs_count_filtered))
-        .route("/logs/clear", post(admin::admin_clear_proxy_logs))
-        .route("/logs/:logId", get(admin::admin_get_proxy_log_detail))
         // Security / IP Monitoring
         .route("/security/logs", get(admin::admin_get_ip_access_logs))
         .route("/security/logs/clear", post(admin::admin_clear_ip_access_logs))
@@ -250,33 +356,28 @@ fn admin_routes(state: AppState, security: Arc<RwLock<ProxySecurityConfig>>) ->
         .route("/user-tokens/summary", get(admin::admin_get_user_token_summary))
         .route("/user-tokens/:id/renew", post(admin::admin_renew_user_token))
         .route("/user-tokens/:id", delete(admin::admin_delete_user_token).patch(admin::admin_update_user_token))
-        // Token Stats
-        .route("/stats/summary", get(admin::admin_get_token_stats_summary))
-        .route("/stats/hourly", get(admin::admin_get_token_stats_hourly))
-        .route("/stats/daily", get(admin::admin_get_token_stats_daily))
-        .route("/stats/weekly", get(admin::admin_get_token_stats_weekly))
-        .route("/stats/accounts", get(admin::admin_get_token_stats_by_account))
-        .route("/stats/models", get(admin::admin_get_token_stats_by_model))
+        // Admin Users
+        .route("/admin-users", get(admin::admin_list_admin_users).post(admin::admin_create_admin_user))
+        .route("/admin-users/:id", delete(admin::admin_delete_admin_user).patch(admin::admin_update_admin_user))
+        // Destructive stats / system
         .route("/stats/token/clear", post(admin::admin_clear_token_stats))
-        .route("/stats/token/hourly", get(admin::admin_get_token_stats_hourly))
-        .route("/stats/token/daily", get(admin::admin_get_token_stats_daily))
-        .route("/stats/token/weekly", get(admin::admin_get_token_stats_weekly))
-        .route("/stats/token/by-account", get(admin::admin_get_token_stats_by_account))
-        .route("/stats/token/summary", get(admin::admin_get_token_stats_summary))
-        .route("/stats/token/by-model", get(admin::admin_get_token_stats_by_model))
-        .route("/stats/token/model-trend/hourly", get(admin::admin_get_token_stats_model_trend_hourly))
-        .route("/stats/token/model-trend/daily", get(admin::admin_get_token_stats_model_trend_daily))
-        .route("/stats/token/account-trend/hourly", get(admin::admin_get_token_stats_account_trend_hourly))
-        .route("/stats/token/account-trend/daily", get(admin::admin_get_token_stats_account_trend_daily))
-        // System
-        .route("/system/data-dir", get(admin::admin_get_data_dir_path))
-        .route("/system/updates/check-status", get(admin::admin_should_check_updates))
-        .route("/system/updates/check", post(admin::admin_check_for_updates))
-        .route("/system/autostart/status", get(admin::admin_is_auto_launch_enabled))
+        .route("/stats/redaction/clear", post(admin::admin_clear_redaction_stats))
+        .route("/stats/fallback/clear", post(admin::admin_clear_fallback_stats))
+        .route("/stats/hedging/clear", post(admin::admin_clear_hedge_stats))
         .route("/system/autostart/toggle", post(admin::admin_toggle_auto_launch))
-        .route("/system/cache/clear", post(admin::admin_clear_cache))
-        .route("/system/logs/clear-cache", post(admin::admin_clear_log_cache))
-        // Admin auth middleware (forced authentication)
+        .route_layer(axum::middleware::from_fn_with_state(
+            AdminRole::Owner,
+            require_admin_role,
+        ));
+
+    Router::new()
+        // Public: health check and login
+        .route("/health", get(health_check_handler))
+        .route("/auth/login", post(admin::admin_login))
+        .merge(viewer)
+        .merge(operator)
+        .merge(owner)
+        // Admin auth middleware (forced authentication, resolves AdminIdentity)
         .layer(axum::middleware::from_fn_with_state(
             security,
             admin_auth_middleware,
@@ -295,6 +396,7 @@ pub struct AxumServer {
     custom_mapping: Arc<RwLock<HashMap<String, String>>>,
     upstream: Arc<UpstreamClient>,
     security_state: Arc<RwLock<ProxySecurityConfig>>,
+    app_state: AppState,
     pub is_running: Arc<RwLock<bool>>,
     pub token_manager: Arc<TokenManager>,
 }
@@ -310,6 +412,16 @@ impl AxumServer {
         user_agent_override: Option<String>,
         security_config: ProxySecurityConfig,
     ) -> Result<(Self, tokio::task::JoinHandle<()>), String> {
+        // TLS (HTTPS + optional mTLS); certificate files are hot-reloaded
+        let tls = if security_config.tls.enabled {
+            let data_dir = crate::modules::account::get_data_dir()?;
+            let reloader = TlsReloader::new(&security_config.tls, &data_dir)?;
+            reloader.spawn_watcher();
+            Some(reloader)
+        } else {
+            None
+        };
+
         let custom_mapping_state = Arc::new(RwLock::new(custom_mapping));
         let security_state = Arc::new(RwLock::new(security_config));
         let is_running_state = Arc::new(RwLock::new(true));
@@ -323,17 +435,14 @@ impl AxumServer {
             token_manager.clone(),
             custom_mapping_state.clone(),
             upstream.clone(),
+            security_state.clone(),
         );
 
         // Build routes
         let proxy = proxy_routes(app_state.clone(), security_state.clone());
-        let admin = admin_routes(app_state, security_state.clone());
+        let admin = admin_routes(app_state.clone(), security_state.clone());
 
-        // Max body size (default 100MB)
-        let max_body_size: usize = std::env::var("KIRO_MAX_BODY_SIZE")
-            .ok()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(100 * 1024 * 1024);
+        let max_body_size = max_body_size();
 
         let app = Router::new()
             .nest("/api", admin)
@@ -365,7 +474,11 @@ impl AxumServer {
             .await
             .map_err(|e| format!("Failed to bind {}: {}", addr, e))?;
 
-        info!("Proxy server started at http://{}", addr);
+        info!(
+            "Proxy server started at {}://{}",
+            if tls.is_some() { "https" } else { "http" },
+            addr
+        );
 
         // Shutdown channel
         let (shutdown_tx, mut shutdown_rx) = oneshot::channel::<()>();
@@ -375,16 +488,13 @@ impl AxumServer {
             custom_mapping: custom_mapping_state,
             upstream: upstream.clone(),
             security_state,
+            app_state,
             is_running: is_running_state,
             token_manager: token_manager.clone(),
         };
 
         // Spawn server task
         let handle = tokio::spawn(async move {
-            use hyper::server::conn::http1;
-            use hyper_util::rt::TokioIo;
-            use hyper_util::service::TowerToHyperService;
-
             let app_service = app.into_service();
 
             loop {
@@ -392,29 +502,35 @@ impl AxumServer {
                     res = listener.accept() => {
                         match res {
                             Ok((stream, remote_addr)) => {
-                                let io = TokioIo::new(stream);
-
-                                use tower::ServiceExt;
-                                use hyper::body::Incoming;
-                                let svc = app_service.clone().map_request(
-                                    move |mut req: axum::http::Request<Incoming>| {
-                                        req.extensions_mut().insert(
-                                            axum::extract::ConnectInfo(remote_addr),
-                                        );
-                                        req
-                                    },
-                                );
-
-                                let hyper_svc = TowerToHyperService::new(svc);
+                                let app_service = app_service.clone();
+                                let tls = tls.clone();
 
                                 tokio::task::spawn(async move {
-                                    if let Err(err) = http1::Builder::new()
-                                        .serve_connection(io, hyper_svc)
-                                        .with_upgrades()
-                                        .await
-                                    {
-                                        debug!("Connection ended: {:?}", err);
-                                    }
+                                    use hyper_util::rt::TokioIo;
+
+                                    let Some(tls) = tls else {
+                                        serve_connection(TokioIo::new(stream), app_service, remote_addr, None).await;
+                                        return;
+                                    };
+                                    let handshake = tls.acceptor_for(remote_addr.ip()).accept(stream);
+                                    let stream = match tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, handshake).await {
+                                        Ok(Ok(stream)) => stream,
+                                        Ok(Err(e)) => {
+                                            debug!("TLS handshake with {} failed: {}", remote_addr, e);
+                                            return;
+                                        }
+                                        Err(_) => {
+                                            debug!("TLS handshake with {} timed out", remote_addr);
+                                            return;
+                                        }
+                                    };
+                                    let client_cert = stream
+                                        .get_ref()
+                                        .1
+                                        .peer_certificates()
+                                        .and_then(|certs| certs.first())
+                                        .and_then(|cert| client_identity_from_der(cert.as_ref()));
+                                    serve_connection(TokioIo::new(stream), app_service, remote_addr, client_cert).await;
                                 });
                             }
                             Err(e) => {
@@ -473,10 +589,16 @@ impl AxumServer {
     }
 
     /// Hot update security config (IP blacklist/whitelist, auth) [Req 14.3]
-    pub async fn update_security(&self, config: &ProxyConfig) {
-        let new_security = ProxySecurityConfig::from_proxy_config(config);
-        *self.security_state.write().await = new_security;
-        info!("[HotReload] Security config updated");
+    ///
+    /// Security settings are part of the runtime config, so this reapplies
+    /// all of it (see [`AppState::apply_runtime_config`]).
+    pub async fn update_security(&self, config: &AppConfig) {
+        self.apply_runtime_config(config).await;
+    }
+
+    /// Apply every hot-reloadable setting from `config` to the running server
+    pub async fn apply_runtime_config(&self, config: &AppConfig) {
+        self.app_state.apply_runtime_config(config).await;
     }
 
     /// Hot update User-Agent override [Req 14.4]
@@ -562,10 +684,10 @@ mod tests {
         let tm = Arc::new(TokenManager::new(std::path::PathBuf::from("/tmp")));
         let mapping = Arc::new(RwLock::new(HashMap::new()));
         let upstream = Arc::new(UpstreamClient::new(None));
-        let state = AppState::new(tm, mapping, upstream);
         let security = Arc::new(RwLock::new(ProxySecurityConfig::from_proxy_config(
             &ProxyConfig::default(),
         )));
+        let state = AppState::new(tm, mapping, upstream, security.clone());
 
         // Should not panic
         let _router = proxy_routes(state, security);
@@ -576,10 +698,10 @@ mod tests {
         let tm = Arc::new(TokenManager::new(std::path::PathBuf::from("/tmp")));
         let mapping = Arc::new(RwLock::new(HashMap::new()));
         let upstream = Arc::new(UpstreamClient::new(None));
-        let state = AppState::new(tm, mapping, upstream);
         let security = Arc::new(RwLock::new(ProxySecurityConfig::from_proxy_config(
             &ProxyConfig::default(),
         )));
+        let state = AppState::new(tm, mapping, upstream, security.clone());
 
         let _router = admin_routes(state, security);
     }
@@ -671,8 +793,9 @@ mod tests {
         .unwrap();
 
         // Update security
-        let mut config = ProxyConfig::default();
-        config.api_key = "new-key-123".to_string();
+        let _guard = crate::proxy::redaction::TEST_CONFIG_LOCK.lock().await;
+        let mut config = AppConfig::new();
+        config.proxy.api_key = "new-key-123".to_string();
         server.update_security(&config).await;
 
         let sec = server.security_state.read().await;
@@ -681,6 +804,44 @@ mod tests {
         server.stop();
     }
 
+    #[tokio::test]
+    async fn test_apply_runtime_config_updates_server_state() {
+        let tm = Arc::new(TokenManager::new(std::path::PathBuf::from("/tmp")));
+        let security = ProxySecurityConfig::from_proxy_config(&ProxyConfig::default());
+
+        let (server, _handle) = AxumServer::start(
+            "127.0.0.1".to_string(),
+            0,
+            tm.clone(),
+            HashMap::new(),
+            UpstreamProxyConfig::default(),
+            None,
+            security,
+        )
+        .await
+        .unwrap();
+
+        let _guard = crate::proxy::redaction::TEST_CONFIG_LOCK.lock().await;
+        let mut config = AppConfig::new();
+        config.proxy.custom_mapping.insert("gpt-4".to_string(), "gemini-pro".to_string());
+        config.proxy.jwt_auth.enabled = true;
+        config.proxy.jwt_auth.issuer = "https://issuer.example".to_string();
+        config.proxy.scheduling.max_concurrent_per_account = 7;
+        server.apply_runtime_config(&config).await;
+
+        assert_eq!(
+            server.custom_mapping.read().await.get("gpt-4"),
+            Some(&"gemini-pro".to_string())
+        );
+        let sec = server.security_state.read().await;
+        assert!(sec.jwt_auth.enabled);
+        assert_eq!(sec.jwt_auth.issuer, "https://issuer.example");
+        drop(sec);
+        assert_eq!(tm.get_sticky_config().await.max_concurrent_per_account, 7);
+
+        server.stop();
+    }
+
     #[tokio::test]
     async fn test_set_running() {
         let tm = Arc::new(TokenManager::new(std::path::PathBuf::from("/tmp")));
@@ -1,5 +1,55 @@
 use sha2::{Digest, Sha256};
 use serde_json::Value;
+use std::sync::{OnceLock, RwLock};
+
+use axum::http::HeaderMap;
+
+use crate::models::config::SessionAffinityConfig;
+
+/// 显式会话 ID 的最大长度，超出视为无效
+const MAX_EXPLICIT_SESSION_ID_LEN: usize = 256;
+
+static GLOBAL_SESSION_AFFINITY_CONFIG: OnceLock<RwLock<SessionAffinityConfig>> = OnceLock::new();
+
+/// 当前生效的会话亲和配置
+pub fn get_session_affinity_config() -> SessionAffinityConfig {
+    GLOBAL_SESSION_AFFINITY_CONFIG
+        .get()
+        .and_then(|lock| lock.read().ok())
+        .map(|cfg| cfg.clone())
+        .unwrap_or_default()
+}
+
+/// 更新会话亲和配置 (启动及配置热更新时调用)
+pub fn update_session_affinity_config(config: &SessionAffinityConfig) {
+    let lock = GLOBAL_SESSION_AFFINITY_CONFIG
+        .get_or_init(|| RwLock::new(SessionAffinityConfig::default()));
+    if let Ok(mut cfg) = lock.write() {
+        *cfg = config.clone();
+    }
+    tracing::info!(
+        "[SessionManager] Affinity config updated: header={}, include_system={}, scope_by_user_token={}",
+        config.header_name,
+        config.fingerprint_include_system,
+        config.scope_by_user_token
+    );
+}
+
+/// 读取请求头中的显式会话 ID (头名称可配置)
+pub fn header_session_id(headers: &HeaderMap) -> Option<String> {
+    let config = get_session_affinity_config();
+    headers
+        .get(config.header_name.as_str())
+        .and_then(|v| v.to_str().ok())
+        .and_then(valid_explicit_id)
+}
+
+/// 显式会话 ID 校验：非空且不超长
+fn valid_explicit_id(value: &str) -> Option<String> {
+    let trimmed = value.trim();
+    (!trimmed.is_empty() && trimmed.len() <= MAX_EXPLICIT_SESSION_ID_LEN)
+        .then(|| trimmed.to_string())
+}
 
 /// 会话管理器 - 基于请求内容生成稳定的会话指纹
 ///
@@ -168,6 +218,151 @@ impl SessionManager {
     }
 }
 
+// ===== 调度用会话 ID (显式标识 + 指纹 + 租户隔离) =====
+impl SessionManager {
+    /// Claude 调度会话 ID：请求头 > metadata.user_id > 内容指纹
+    pub fn resolve_claude_session_id(
+        request: &Value,
+        header_sid: Option<&str>,
+        user_token_id: Option<&str>,
+    ) -> String {
+        let config = get_session_affinity_config();
+        let sid = match header_sid.and_then(valid_explicit_id) {
+            Some(explicit) => explicit,
+            None => {
+                let fingerprint = Self::extract_session_id(request);
+                let system = config
+                    .fingerprint_include_system
+                    .then(|| extract_claude_system_text(request));
+                mix_system_into_fingerprint(fingerprint, system.as_deref())
+            }
+        };
+        scope_to_user_token(sid, user_token_id, &config)
+    }
+
+    /// OpenAI 调度会话 ID：请求头 > prompt_cache_key > user > 内容指纹
+    pub fn resolve_openai_session_id(
+        request: &Value,
+        header_sid: Option<&str>,
+        user_token_id: Option<&str>,
+    ) -> String {
+        let config = get_session_affinity_config();
+        let body_sid = ["prompt_cache_key", "user"]
+            .iter()
+            .find_map(|key| request.get(*key).and_then(|v| v.as_str()).and_then(valid_explicit_id));
+        let sid = match header_sid.and_then(valid_explicit_id).or(body_sid) {
+            Some(explicit) => explicit,
+            None => {
+                let fingerprint = Self::extract_openai_session_id(request);
+                let system = config
+                    .fingerprint_include_system
+                    .then(|| extract_openai_system_text(request));
+                mix_system_into_fingerprint(fingerprint, system.as_deref())
+            }
+        };
+        scope_to_user_token(sid, user_token_id, &config)
+    }
+
+    /// Gemini 调度会话 ID：请求头 > 内容指纹
+    pub fn resolve_gemini_session_id(
+        request: &Value,
+        model: &str,
+        header_sid: Option<&str>,
+        user_token_id: Option<&str>,
+    ) -> String {
+        let config = get_session_affinity_config();
+        let sid = match header_sid.and_then(valid_explicit_id) {
+            Some(explicit) => explicit,
+            None => {
+                let fingerprint = Self::extract_gemini_session_id(request, model);
+                let system = config
+                    .fingerprint_include_system
+                    .then(|| extract_gemini_system_text(request));
+                mix_system_into_fingerprint(fingerprint, system.as_deref())
+            }
+        };
+        scope_to_user_token(sid, user_token_id, &config)
+    }
+}
+
+/// 将 system prompt 混入内容指纹 (system 为空时保持原指纹)
+fn mix_system_into_fingerprint(fingerprint: String, system: Option<&str>) -> String {
+    match system.map(str::trim).filter(|s| !s.is_empty()) {
+        Some(system) => {
+            let mut hasher = Sha256::new();
+            hasher.update(system.as_bytes());
+            hasher.update(b"\n");
+            hasher.update(fingerprint.as_bytes());
+            hash_to_session_id(hasher)
+        }
+        None => fingerprint,
+    }
+}
+
+/// 按 User Token 隔离会话 ID
+fn scope_to_user_token(
+    sid: String,
+    user_token_id: Option<&str>,
+    config: &SessionAffinityConfig,
+) -> String {
+    match user_token_id {
+        Some(token_id) if config.scope_by_user_token => format!("{}:{}", token_id, sid),
+        _ => sid,
+    }
+}
+
+/// Claude system 字段 (string 或 text blocks)
+fn extract_claude_system_text(request: &Value) -> String {
+    match request.get("system") {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Array(blocks)) => blocks
+            .iter()
+            .filter_map(|block| block.get("text").and_then(|v| v.as_str()))
+            .collect::<Vec<_>>()
+            .join(" "),
+        _ => String::new(),
+    }
+}
+
+/// OpenAI system / developer 消息及 Codex instructions
+fn extract_openai_system_text(request: &Value) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    if let Some(instructions) = request.get("instructions").and_then(|v| v.as_str()) {
+        parts.push(instructions.to_string());
+    }
+    if let Some(messages) = request.get("messages").and_then(|v| v.as_array()) {
+        parts.extend(
+            messages
+                .iter()
+                .filter(|msg| {
+                    matches!(
+                        msg.get("role").and_then(|v| v.as_str()),
+                        Some("system") | Some("developer")
+                    )
+                })
+                .map(extract_openai_message_text),
+        );
+    }
+    parts.join(" ")
+}
+
+/// Gemini systemInstruction.parts[].text
+fn extract_gemini_system_text(request: &Value) -> String {
+    request
+        .get("systemInstruction")
+        .or_else(|| request.get("system_instruction"))
+        .and_then(|si| si.get("parts"))
+        .and_then(|v| v.as_array())
+        .map(|parts| {
+            parts
+                .iter()
+                .filter_map(|part| part.get("text").and_then(|v| v.as_str()))
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .unwrap_or_default()
+}
+
 /// 从 Claude 消息中提取文本内容
 /// 支持 string 和 array（content blocks）两种格式
 fn extract_claude_message_text(msg: &Value) -> String {
@@ -754,4 +949,61 @@ mod tests {
         let sid2 = SessionManager::extract_session_id(&request2);
         assert_eq!(sid, sid2);
     }
+
+    #[test]
+    fn test_explicit_session_ids_take_priority() {
+        let request = json!({
+            "messages": [{ "role": "user", "content": "hi there" }],
+            "user": "end-user-1",
+            "prompt_cache_key": "conv-42"
+        });
+
+        // Header beats body fields; prompt_cache_key beats user
+        assert_eq!(
+            SessionManager::resolve_openai_session_id(&request, Some(" conv-header "), None),
+            "conv-header"
+        );
+        assert_eq!(
+            SessionManager::resolve_openai_session_id(&request, None, None),
+            "conv-42"
+        );
+
+        // Same opening prompt, different explicit IDs -> no collision
+        let claude = json!({ "messages": [{ "role": "user", "content": "hi there" }] });
+        assert_ne!(
+            SessionManager::resolve_claude_session_id(&claude, Some("a"), None),
+            SessionManager::resolve_claude_session_id(&claude, Some("b"), None)
+        );
+        // Blank header falls back to the content fingerprint
+        assert_eq!(
+            SessionManager::resolve_claude_session_id(&claude, Some("  "), None),
+            SessionManager::extract_session_id(&claude)
+        );
+    }
+
+    #[test]
+    fn test_session_id_scoped_by_user_token() {
+        let request = json!({ "contents": [{ "role": "user", "parts": [{ "text": "shared template" }] }] });
+        let a = SessionManager::resolve_gemini_session_id(&request, "gemini-3-flash", None, Some("tok-a"));
+        let b = SessionManager::resolve_gemini_session_id(&request, "gemini-3-flash", None, Some("tok-b"));
+        assert_ne!(a, b);
+        assert!(a.starts_with("tok-a:"));
+    }
+
+    #[test]
+    fn test_system_prompt_mixed_into_fingerprint() {
+        let fingerprint = "sid-0123456789abcdef".to_string();
+        assert_eq!(mix_system_into_fingerprint(fingerprint.clone(), None), fingerprint);
+        assert_eq!(mix_system_into_fingerprint(fingerprint.clone(), Some("  ")), fingerprint);
+
+        let with_a = mix_system_into_fingerprint(fingerprint.clone(), Some("You are A"));
+        let with_b = mix_system_into_fingerprint(fingerprint.clone(), Some("You are B"));
+        assert_ne!(with_a, with_b);
+        assert!(with_a.starts_with("sid-"));
+
+        let claude = json!({ "system": [{ "type": "text", "text": "You are A" }] });
+        assert_eq!(extract_claude_system_text(&claude), "You are A");
+        let openai = json!({ "messages": [{ "role": "developer", "content": "You are B" }] });
+        assert_eq!(extract_openai_system_text(&openai), "You are B");
+    }
 }
@@ -1,4 +1,6 @@
+use serde::Serialize;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Mutex, OnceLock};
 use std::time::{Duration, SystemTime};
 
@@ -53,6 +55,53 @@ pub struct SignatureCache {
     thinking_families: Mutex<HashMap<String, CacheEntry<String>>>,
     /// L3: Session ID → Latest Thinking Signature
     session_signatures: Mutex<HashMap<String, CacheEntry<SessionSignatureEntry>>>,
+    /// Missing signatures restored from the cache
+    hits: AtomicU64,
+    /// Missing signatures with no usable cache entry
+    misses: AtomicU64,
+    /// Signatures dropped because they belong to another model family
+    dropped_incompatible: AtomicU64,
+}
+
+/// Hit / miss counters and layer sizes (admin API)
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SignatureCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub dropped_incompatible: u64,
+    pub tool_entries: usize,
+    pub family_entries: usize,
+    pub session_entries: usize,
+}
+
+/// Per-request context for recording signatures as they stream out.
+///
+/// `model` is the mapped (upstream) model, recorded as the signature family.
+#[derive(Debug, Clone)]
+pub struct SignatureContext {
+    pub session_id: String,
+    pub model: String,
+    pub message_count: usize,
+}
+
+impl SignatureContext {
+    pub fn new(session_id: &str, model: &str, message_count: usize) -> Self {
+        Self {
+            session_id: session_id.to_string(),
+            model: model.to_string(),
+            message_count,
+        }
+    }
+
+    /// Record a streamed signature in all applicable layers.
+    pub fn record(&self, tool_use_id: Option<&str>, signature: &str) {
+        let cache = SignatureCache::global();
+        if let Some(id) = tool_use_id {
+            cache.cache_tool_signature(id, signature.to_string());
+        }
+        cache.cache_thinking_family(signature.to_string(), self.model.clone());
+        cache.cache_session_signature(&self.session_id, signature.to_string(), self.message_count);
+    }
 }
 
 impl SignatureCache {
@@ -61,6 +110,9 @@ impl SignatureCache {
             tool_signatures: Mutex::new(HashMap::new()),
             thinking_families: Mutex::new(HashMap::new()),
             session_signatures: Mutex::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            dropped_incompatible: AtomicU64::new(0),
         }
     }
 
@@ -200,7 +252,7 @@ impl SignatureCache {
     /// Normalize a model family string to its canonical prefix.
     /// e.g. "claude-3-5-sonnet-20241022" → "claude",
     ///      "gemini-2.0-flash-thinking" → "gemini"
-    fn normalize_family(family: &str) -> &str {
+    pub fn normalize_family(family: &str) -> &str {
         let lower = family.as_bytes();
         if lower.len() >= 6 && family[..6].eq_ignore_ascii_case("claude") {
             "claude"
@@ -308,6 +360,69 @@ impl SignatureCache {
         None
     }
 
+    // ===== Request-side Restoration =====
+
+    /// Resolve the signature to send for a history block.
+    ///
+    /// Priority: client-supplied > L1 tool signature (`tool_use_ids` in order) >
+    /// L3 session signature. Signatures whose recorded family is incompatible
+    /// with `target_model` are dropped and the next source is tried.
+    ///
+    /// Callers pass `session_id` only for the latest assistant turn: the session
+    /// signature belongs to the newest thought and doesn't match older blocks.
+    /// Hits / misses are counted only for blocks that had a cache source to try.
+    pub fn resolve_signature(
+        &self,
+        client_signature: Option<&str>,
+        tool_use_ids: &[&str],
+        session_id: Option<&str>,
+        target_model: &str,
+    ) -> Option<String> {
+        if let Some(sig) = client_signature.filter(|s| !s.is_empty()) {
+            if self.check_compatible(sig, target_model) {
+                return Some(sig.to_string());
+            }
+        }
+
+        if tool_use_ids.is_empty() && session_id.is_none() {
+            return None;
+        }
+
+        let cached = tool_use_ids
+            .iter()
+            .filter_map(|id| self.get_tool_signature(id))
+            .chain(session_id.and_then(|sid| self.get_session_signature(sid)))
+            .find(|sig| self.check_compatible(sig, target_model));
+
+        let counter = if cached.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        cached
+    }
+
+    fn check_compatible(&self, signature: &str, target_model: &str) -> bool {
+        let compatible = self.is_signature_compatible(signature, target_model);
+        if !compatible {
+            self.dropped_incompatible.fetch_add(1, Ordering::Relaxed);
+        }
+        compatible
+    }
+
+    /// Current counters and layer sizes.
+    pub fn stats(&self) -> SignatureCacheStats {
+        SignatureCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            dropped_incompatible: self.dropped_incompatible.load(Ordering::Relaxed),
+            tool_entries: self.tool_signatures.lock().map(|c| c.len()).unwrap_or(0),
+            family_entries: self.thinking_families.lock().map(|c| c.len()).unwrap_or(0),
+            session_entries: self.session_signatures.lock().map(|c| c.len()).unwrap_or(0),
+        }
+    }
+
     /// Delete a specific session's cached signature.
     #[allow(dead_code)]
     pub fn delete_session_signature(&self, session_id: &str) {
@@ -571,6 +686,52 @@ mod tests {
         cache.delete_session_signature("does-not-exist");
     }
 
+    // ===== Request-side Restoration =====
+
+    #[test]
+    fn test_resolve_signature_priority() {
+        let cache = SignatureCache::new();
+        let client = "c".repeat(60);
+        let tool = "t".repeat(60);
+        let session = "s".repeat(60);
+        cache.cache_tool_signature("toolu_1", tool.clone());
+        cache.cache_session_signature("sid-1", session.clone(), 3);
+
+        let resolve = |client: Option<&str>, ids: &[&str]| {
+            cache.resolve_signature(client, ids, Some("sid-1"), "gemini-3-pro")
+        };
+        assert_eq!(resolve(Some(&client), &["toolu_1"]), Some(client.clone()));
+        assert_eq!(resolve(None, &["toolu_1"]), Some(tool));
+        assert_eq!(resolve(None, &["toolu_unknown"]), Some(session));
+        let missing = cache.resolve_signature(None, &[], Some("sid-none"), "gemini-3-pro");
+        assert!(missing.is_none());
+        // 没有可查询的缓存来源 (历史块无工具 ID) 时不计入命中统计
+        assert!(cache.resolve_signature(None, &[], None, "gemini-3-pro").is_none());
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (2, 1));
+        assert_eq!((stats.tool_entries, stats.session_entries), (1, 1));
+    }
+
+    #[test]
+    fn test_resolve_signature_drops_incompatible() {
+        let cache = SignatureCache::new();
+        let claude_sig = "a".repeat(60);
+        let gemini_sig = "g".repeat(60);
+        cache.cache_thinking_family(claude_sig.clone(), "claude-sonnet-4-5".to_string());
+        cache.cache_thinking_family(gemini_sig.clone(), "gemini-3-pro".to_string());
+        cache.cache_tool_signature("toolu_1", gemini_sig.clone());
+
+        // 客户端带来的 Claude 签名不能发给 Gemini，回退到工具签名
+        assert_eq!(
+            cache.resolve_signature(Some(&claude_sig), &["toolu_1"], None, "gemini-3-flash"),
+            Some(gemini_sig)
+        );
+        let dropped = cache.resolve_signature(Some(&claude_sig), &[], None, "gemini-3-flash");
+        assert!(dropped.is_none());
+        assert_eq!(cache.stats().dropped_incompatible, 2);
+    }
+
     // ===== Clear =====
 
     #[test]
@@ -0,0 +1,523 @@
+// TLS 终止与可选的 mTLS 客户端证书认证
+//
+// - 从配置的证书/私钥加载 rustls ServerConfig，文件变化时热重载
+// - 首次运行可自动生成自签名证书 (data_dir/tls/)
+// - 可选 mTLS：校验客户端证书并提取 Subject，供 auth 中间件映射为 User Token 身份
+// - required 模式下本机回环连接可不带客户端证书 (预热、管理端回环调用)
+
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+use tracing::{error, info, warn};
+
+use crate::models::config::{ClientAuthMode, TlsConfig};
+
+// ============================================================================
+// ClientCertIdentity - 客户端证书身份
+// ============================================================================
+
+/// 通过 mTLS 校验的客户端证书身份（作为请求扩展注入）
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClientCertIdentity {
+    /// 完整 Subject DN，例如 "CN=billing-service, O=Acme"
+    pub subject: String,
+    /// Subject 中的 Common Name
+    pub common_name: Option<String>,
+}
+
+/// 从 DER 编码的证书中提取 Subject 信息
+pub fn client_identity_from_der(der: &[u8]) -> Option<ClientCertIdentity> {
+    let (_, cert) = x509_parser::parse_x509_certificate(der).ok()?;
+    let subject = cert.subject();
+    let identity = ClientCertIdentity {
+        subject: subject.to_string(),
+        common_name: subject
+            .iter_common_name()
+            .next()
+            .and_then(|cn| cn.as_str().ok())
+            .map(|s| s.to_string()),
+    };
+    Some(identity)
+}
+
+// ============================================================================
+// Certificate loading / generation
+// ============================================================================
+
+/// 解析证书和私钥路径；未配置时使用 data_dir/tls/ 下的默认位置
+pub fn resolve_cert_paths(config: &TlsConfig, data_dir: &Path) -> (PathBuf, PathBuf) {
+    let tls_dir = data_dir.join("tls");
+    let cert = config
+        .cert_path
+        .as_deref()
+        .filter(|p| !p.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| tls_dir.join("cert.pem"));
+    let key = config
+        .key_path
+        .as_deref()
+        .filter(|p| !p.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| tls_dir.join("key.pem"));
+    (cert, key)
+}
+
+/// 证书文件缺失且允许时，生成自签名证书 (localhost / 127.0.0.1 / 本机主机名)
+pub fn ensure_certificate(config: &TlsConfig, cert_path: &Path, key_path: &Path) -> Result<(), String> {
+    if cert_path.exists() && key_path.exists() {
+        return Ok(());
+    }
+    if !config.auto_generate_self_signed {
+        return Err(format!(
+            "TLS certificate not found: {} / {}",
+            cert_path.display(),
+            key_path.display()
+        ));
+    }
+
+    let mut names = vec!["localhost".to_string(), "127.0.0.1".to_string()];
+    if let Ok(host) = std::env::var("HOSTNAME") {
+        if !host.is_empty() && !names.contains(&host) {
+            names.push(host);
+        }
+    }
+    let generated = rcgen::generate_simple_self_signed(names)
+        .map_err(|e| format!("Failed to generate self-signed certificate: {}", e))?;
+
+    for path in [cert_path, key_path] {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+    }
+    std::fs::write(cert_path, generated.cert.pem())
+        .map_err(|e| format!("Failed to write {}: {}", cert_path.display(), e))?;
+    std::fs::write(key_path, generated.key_pair.serialize_pem())
+        .map_err(|e| format!("Failed to write {}: {}", key_path.display(), e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = std::fs::set_permissions(key_path, std::fs::Permissions::from_mode(0o600));
+    }
+
+    warn!(
+        "[TLS] Generated self-signed certificate at {} (clients must trust it explicitly)",
+        cert_path.display()
+    );
+    Ok(())
+}
+
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, String> {
+    let data = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let certs = rustls_pemfile::certs(&mut data.as_slice())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Invalid certificate PEM {}: {}", path.display(), e))?;
+    if certs.is_empty() {
+        return Err(format!("No certificates found in {}", path.display()));
+    }
+    Ok(certs)
+}
+
+fn load_private_key(path: &Path) -> Result<PrivateKeyDer<'static>, String> {
+    let data = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    rustls_pemfile::private_key(&mut data.as_slice())
+        .map_err(|e| format!("Invalid private key PEM {}: {}", path.display(), e))?
+        .ok_or_else(|| format!("No private key found in {}", path.display()))
+}
+
+/// 构建 rustls ServerConfig（含可选的客户端证书校验）
+pub fn build_server_config(
+    config: &TlsConfig,
+    cert_path: &Path,
+    key_path: &Path,
+) -> Result<Arc<ServerConfig>, String> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| format!("Invalid TLS protocol configuration: {}", e))?;
+
+    let builder = match config.client_auth {
+        ClientAuthMode::Off => builder.with_no_client_auth(),
+        ClientAuthMode::Optional | ClientAuthMode::Required => {
+            let ca_path = config
+                .client_ca_path
+                .as_deref()
+                .filter(|p| !p.is_empty())
+                .ok_or_else(|| "mTLS is enabled but client_ca_path is not set".to_string())?;
+            let mut roots = RootCertStore::empty();
+            for cert in load_certs(Path::new(ca_path))? {
+                roots
+                    .add(cert)
+                    .map_err(|e| format!("Invalid client CA certificate: {}", e))?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+            let verifier = if config.client_auth == ClientAuthMode::Optional {
+                verifier.allow_unauthenticated().build()
+            } else {
+                verifier.build()
+            }
+            .map_err(|e| format!("Failed to build client certificate verifier: {}", e))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+    };
+
+    let mut server_config = builder
+        .with_single_cert(load_certs(cert_path)?, load_private_key(key_path)?)
+        .map_err(|e| format!("Invalid TLS certificate/key pair: {}", e))?;
+    server_config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(Arc::new(server_config))
+}
+
+/// 远程连接与本机回环连接使用的 ServerConfig
+///
+/// 客户端证书为 required 时，回环连接改为 optional：本机调用方 (预热、管理端
+/// 回环请求) 没有客户端证书；若携带证书仍会按 CA 校验。
+#[derive(Clone)]
+struct ServerConfigs {
+    remote: Arc<ServerConfig>,
+    loopback: Arc<ServerConfig>,
+}
+
+fn build_server_configs(
+    config: &TlsConfig,
+    cert_path: &Path,
+    key_path: &Path,
+) -> Result<ServerConfigs, String> {
+    let remote = build_server_config(config, cert_path, key_path)?;
+    let loopback = if config.client_auth == ClientAuthMode::Required {
+        let loopback_config = TlsConfig {
+            client_auth: ClientAuthMode::Optional,
+            ..config.clone()
+        };
+        build_server_config(&loopback_config, cert_path, key_path)?
+    } else {
+        remote.clone()
+    };
+    Ok(ServerConfigs { remote, loopback })
+}
+
+// ============================================================================
+// Loopback access - 本机访问代理
+// ============================================================================
+
+/// 代理监听的 URL scheme
+pub fn scheme(config: &TlsConfig) -> &'static str {
+    if config.enabled {
+        "https"
+    } else {
+        "http"
+    }
+}
+
+/// 本机回环访问代理的 base URL，例如 `https://127.0.0.1:8045`
+pub fn loopback_url(config: &TlsConfig, port: u16) -> String {
+    format!("{}://127.0.0.1:{}", scheme(config), port)
+}
+
+/// 访问本机代理的 HTTP 客户端构建器 (不走系统代理)
+///
+/// 启用 TLS 时信任代理当前使用的证书，自签名证书无需加入系统信任库。
+/// 该客户端不出示客户端证书，依赖 required 模式对回环连接的豁免。
+pub fn loopback_client_builder(config: &TlsConfig) -> reqwest::ClientBuilder {
+    let builder = reqwest::Client::builder().no_proxy();
+    if !config.enabled {
+        return builder;
+    }
+
+    let certs = crate::modules::account::get_data_dir().and_then(|data_dir| {
+        let (cert_path, _) = resolve_cert_paths(config, &data_dir);
+        let pem = std::fs::read(&cert_path)
+            .map_err(|e| format!("Failed to read {}: {}", cert_path.display(), e))?;
+        reqwest::Certificate::from_pem_bundle(&pem)
+            .map_err(|e| format!("Invalid certificate PEM {}: {}", cert_path.display(), e))
+    });
+    match certs {
+        Ok(certs) => certs
+            .into_iter()
+            .fold(builder, |builder, cert| builder.add_root_certificate(cert)),
+        Err(e) => {
+            warn!("[TLS] Loopback client cannot trust the proxy certificate: {}", e);
+            builder
+        }
+    }
+}
+
+// ============================================================================
+// TlsReloader - 证书热重载
+// ============================================================================
+
+/// 持有当前 ServerConfig，定期检查证书/私钥/CA 文件的修改时间并热重载
+pub struct TlsReloader {
+    config: TlsConfig,
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    current: RwLock<ServerConfigs>,
+    mtimes: Mutex<Vec<Option<SystemTime>>>,
+}
+
+impl TlsReloader {
+    /// 加载证书（必要时生成自签名证书）并构建初始 ServerConfig
+    pub fn new(config: &TlsConfig, data_dir: &Path) -> Result<Arc<Self>, String> {
+        let (cert_path, key_path) = resolve_cert_paths(config, data_dir);
+        ensure_certificate(config, &cert_path, &key_path)?;
+        let server_configs = build_server_configs(config, &cert_path, &key_path)?;
+
+        let reloader = Self {
+            config: config.clone(),
+            cert_path,
+            key_path,
+            current: RwLock::new(server_configs),
+            mtimes: Mutex::new(Vec::new()),
+        };
+        *reloader.mtimes.lock().unwrap() = reloader.watched_mtimes();
+        info!(
+            "[TLS] Loaded certificate {} (client auth: {:?})",
+            reloader.cert_path.display(),
+            config.client_auth
+        );
+        Ok(Arc::new(reloader))
+    }
+
+    /// 当前证书对应的 TLS acceptor（按对端地址区分回环连接）
+    pub fn acceptor_for(&self, peer: IpAddr) -> TlsAcceptor {
+        let configs = self.current.read().unwrap();
+        let server_config = if peer.is_loopback() {
+            &configs.loopback
+        } else {
+            &configs.remote
+        };
+        TlsAcceptor::from(server_config.clone())
+    }
+
+    fn watched_mtimes(&self) -> Vec<Option<SystemTime>> {
+        let mut paths = vec![self.cert_path.clone(), self.key_path.clone()];
+        if let Some(ca) = self.config.client_ca_path.as_deref().filter(|p| !p.is_empty()) {
+            paths.push(PathBuf::from(ca));
+        }
+        paths
+            .iter()
+            .map(|p| std::fs::metadata(p).and_then(|m| m.modified()).ok())
+            .collect()
+    }
+
+    /// 文件变化时重建 ServerConfig；失败时保留旧配置。返回是否发生了重载
+    pub fn reload_if_changed(&self) -> bool {
+        let mtimes = self.watched_mtimes();
+        {
+            let mut last = self.mtimes.lock().unwrap();
+            if *last == mtimes {
+                return false;
+            }
+            *last = mtimes;
+        }
+
+        match build_server_configs(&self.config, &self.cert_path, &self.key_path) {
+            Ok(server_configs) => {
+                *self.current.write().unwrap() = server_configs;
+                info!("[TLS] Certificate reloaded from {}", self.cert_path.display());
+                true
+            }
+            Err(e) => {
+                error!("[TLS] Certificate reload failed, keeping previous certificate: {}", e);
+                false
+            }
+        }
+    }
+
+    /// 启动后台热重载任务（reloader 被释放后自动退出）
+    pub fn spawn_watcher(self: &Arc<Self>) {
+        let interval = Duration::from_secs(self.config.reload_interval_secs.max(1));
+        let weak = Arc::downgrade(self);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                match weak.upgrade() {
+                    Some(reloader) => {
+                        reloader.reload_if_changed();
+                    }
+                    None => break,
+                }
+            }
+        });
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tls_config() -> TlsConfig {
+        TlsConfig {
+            enabled: true,
+            ..TlsConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_resolve_cert_paths_defaults_to_data_dir() {
+        let (cert, key) = resolve_cert_paths(&tls_config(), Path::new("/data"));
+        assert_eq!(cert, PathBuf::from("/data/tls/cert.pem"));
+        assert_eq!(key, PathBuf::from("/data/tls/key.pem"));
+    }
+
+    #[test]
+    fn test_loopback_url_follows_tls_setting() {
+        assert_eq!(loopback_url(&tls_config(), 8045), "https://127.0.0.1:8045");
+        assert_eq!(loopback_url(&TlsConfig::default(), 8045), "http://127.0.0.1:8045");
+    }
+
+    #[test]
+    fn test_resolve_cert_paths_uses_configured_paths() {
+        let mut config = tls_config();
+        config.cert_path = Some("/etc/gw/cert.pem".to_string());
+        config.key_path = Some("/etc/gw/key.pem".to_string());
+        let (cert, key) = resolve_cert_paths(&config, Path::new("/data"));
+        assert_eq!(cert, PathBuf::from("/etc/gw/cert.pem"));
+        assert_eq!(key, PathBuf::from("/etc/gw/key.pem"));
+    }
+
+    #[test]
+    fn test_self_signed_generation_and_load() {
+        let tmp = tempfile::tempdir().unwrap();
+        let reloader = TlsReloader::new(&tls_config(), tmp.path()).unwrap();
+        assert!(tmp.path().join("tls/cert.pem").exists());
+        assert!(tmp.path().join("tls/key.pem").exists());
+        let _ = reloader.acceptor_for(IpAddr::from([127, 0, 0, 1]));
+        // Nothing changed since load
+        assert!(!reloader.reload_if_changed());
+    }
+
+    #[test]
+    fn test_missing_certificate_without_auto_generate_fails() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut config = tls_config();
+        config.auto_generate_self_signed = false;
+        assert!(TlsReloader::new(&config, tmp.path()).is_err());
+    }
+
+    #[test]
+    fn test_mtls_requires_client_ca() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut config = tls_config();
+        config.client_auth = ClientAuthMode::Required;
+        assert!(TlsReloader::new(&config, tmp.path()).is_err());
+    }
+
+    #[test]
+    fn test_mtls_with_client_ca_builds() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ca = rcgen::generate_simple_self_signed(vec!["internal-ca".to_string()]).unwrap();
+        let ca_path = tmp.path().join("ca.pem");
+        std::fs::write(&ca_path, ca.cert.pem()).unwrap();
+
+        let mut config = tls_config();
+        config.client_auth = ClientAuthMode::Optional;
+        config.client_ca_path = Some(ca_path.to_string_lossy().to_string());
+        assert!(TlsReloader::new(&config, tmp.path()).is_ok());
+    }
+
+    #[test]
+    fn test_reload_detects_replaced_certificate() {
+        let tmp = tempfile::tempdir().unwrap();
+        let reloader = TlsReloader::new(&tls_config(), tmp.path()).unwrap();
+
+        // Replace certificate and force a different mtime
+        let regenerated = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let cert_path = tmp.path().join("tls/cert.pem");
+        let key_path = tmp.path().join("tls/key.pem");
+        std::fs::write(&cert_path, regenerated.cert.pem()).unwrap();
+        std::fs::write(&key_path, regenerated.key_pair.serialize_pem()).unwrap();
+        reloader.mtimes.lock().unwrap().clear();
+
+        assert!(reloader.reload_if_changed());
+    }
+
+    #[test]
+    fn test_reload_keeps_previous_config_on_invalid_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let reloader = TlsReloader::new(&tls_config(), tmp.path()).unwrap();
+        let before = reloader.current.read().unwrap().remote.clone();
+
+        std::fs::write(tmp.path().join("tls/cert.pem"), "not a certificate").unwrap();
+        reloader.mtimes.lock().unwrap().clear();
+
+        assert!(!reloader.reload_if_changed());
+        assert!(Arc::ptr_eq(&before, &reloader.current.read().unwrap().remote));
+    }
+
+    /// 不带客户端证书完成一次握手，返回服务端握手是否成功
+    async fn handshake_without_client_cert(reloader: &TlsReloader, peer: IpAddr, ca_pem: &[u8]) -> bool {
+        let mut roots = RootCertStore::empty();
+        for cert in rustls_pemfile::certs(&mut &ca_pem[..]) {
+            roots.add(cert.unwrap()).unwrap();
+        }
+        let client_config = rustls::ClientConfig::builder_with_provider(Arc::new(
+            rustls::crypto::ring::default_provider(),
+        ))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+        let connector = tokio_rustls::TlsConnector::from(Arc::new(client_config));
+
+        let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+        let server_name = rustls::pki_types::ServerName::try_from("localhost").unwrap();
+        let (server, _client) = tokio::join!(
+            reloader.acceptor_for(peer).accept(server_io),
+            connector.connect(server_name, client_io)
+        );
+        server.is_ok()
+    }
+
+    #[tokio::test]
+    async fn test_required_client_auth_exempts_loopback_peers() {
+        let tmp = tempfile::tempdir().unwrap();
+        let ca = rcgen::generate_simple_self_signed(vec!["internal-ca".to_string()]).unwrap();
+        let ca_path = tmp.path().join("ca.pem");
+        std::fs::write(&ca_path, ca.cert.pem()).unwrap();
+
+        let mut config = tls_config();
+        config.client_auth = ClientAuthMode::Required;
+        config.client_ca_path = Some(ca_path.to_string_lossy().to_string());
+        let reloader = TlsReloader::new(&config, tmp.path()).unwrap();
+        let server_cert = std::fs::read(tmp.path().join("tls/cert.pem")).unwrap();
+
+        // 预热 / 管理端回环调用没有客户端证书
+        assert!(handshake_without_client_cert(&reloader, IpAddr::from([127, 0, 0, 1]), &server_cert).await);
+        // 远程连接仍必须出示客户端证书
+        assert!(!handshake_without_client_cert(&reloader, IpAddr::from([10, 0, 0, 8]), &server_cert).await);
+    }
+
+    #[test]
+    fn test_client_identity_from_der() {
+        let mut params = rcgen::CertificateParams::new(vec!["svc.internal".to_string()]).unwrap();
+        params
+            .distinguished_name
+            .push(rcgen::DnType::CommonName, "billing-service");
+        params
+            .distinguished_name
+            .push(rcgen::DnType::OrganizationName, "Acme");
+        let key = rcgen::KeyPair::generate().unwrap();
+        let cert = params.self_signed(&key).unwrap();
+
+        let identity = client_identity_from_der(cert.der()).unwrap();
+        assert_eq!(identity.common_name.as_deref(), Some("billing-service"));
+        assert!(identity.subject.contains("CN=billing-service"));
+        assert!(identity.subject.contains("O=Acme"));
+    }
+
+    #[test]
+    fn test_client_identity_from_invalid_der() {
+        assert!(client_identity_from_der(b"garbage").is_none());
+    }
+}
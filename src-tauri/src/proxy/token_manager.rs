@@ -16,10 +16,17 @@
 // - Single account reload
 // - Complete account removal with associated data cleanup
 // - Health score tracking and success recording
+// - Upstream outcome reporting (rate limit lockout + health) shared by all handlers
 // - P2C (Power of Two Choices) load balancing
 // - Session stickiness and scheduling modes
+// - Session binding persistence (SQLite, TTL + size cap)
+// - Per-account per-model circuit breakers (closed / open / half-open)
+// - Background access-token refresh (ahead of expiry, single-flight per account)
+// - Per-account concurrency leases with a weighted fair admission queue
+// - Read-only scheduling explanation (dry run of get_token)
 
 use dashmap::DashMap;
+use serde::Serialize;
 use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -27,7 +34,10 @@ use tokio::sync::RwLock;
 use tokio_util::sync::CancellationToken;
 
 use crate::models::config::{CircuitBreakerConfig, SchedulingMode, StickySessionConfig};
-use crate::proxy::rate_limit::RateLimitTracker;
+use crate::modules::session_binding_db;
+use crate::proxy::circuit_breaker::{BreakerSnapshot, CircuitBreakerRegistry};
+use crate::proxy::concurrency::{AccountLease, ConcurrencyLimiter, ConcurrencyStats, ANONYMOUS_QUEUE_KEY};
+use crate::proxy::rate_limit::{RateLimitInfo, RateLimitReason, RateLimitTracker};
 
 /// On-disk account state for safety checks
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -37,6 +47,112 @@ enum OnDiskAccountState {
     Unknown,
 }
 
+/// Timestamps of a session binding (unix seconds)
+#[derive(Debug, Clone, Copy)]
+struct BindingTimes {
+    bound_at: i64,
+    last_used: i64,
+    /// last_used value most recently written to disk (touches are throttled)
+    persisted_last_used: i64,
+}
+
+/// Background refresh kicks in this long before expiry (plus per-account jitter)
+const TOKEN_REFRESH_AHEAD_SECS: i64 = 600;
+/// Max per-account jitter added to the refresh lead time, spreads refreshes out
+const TOKEN_REFRESH_JITTER_SECS: u64 = 180;
+/// Tokens this close to expiry are refreshed inline before being handed out
+const TOKEN_REFRESH_INLINE_SECS: i64 = 60;
+/// Background refresh scan interval
+const TOKEN_REFRESH_SCAN_INTERVAL_SECS: u64 = 30;
+/// Concurrent refresh requests per scan
+const TOKEN_REFRESH_CONCURRENCY: usize = 4;
+
+/// Result of a single-flight token refresh
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefreshOutcome {
+    /// New access token stored
+    Refreshed,
+    /// Another caller refreshed it while we waited
+    AlreadyFresh,
+    /// Refresh token revoked (invalid_grant); account disabled and removed
+    Revoked,
+}
+
+/// Only write a binding's last_used to disk once per this many seconds
+const BINDING_TOUCH_PERSIST_INTERVAL_SECS: i64 = 60;
+
+/// Session binding overview for the admin API
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionBindingStats {
+    pub total: usize,
+    pub by_account: HashMap<String, usize>,
+    pub oldest_age_seconds: u64,
+    pub avg_age_seconds: u64,
+    pub max_idle_seconds: u64,
+    pub ttl_seconds: u64,
+    pub max_bindings: usize,
+}
+
+/// Outcome of a single upstream call, reported back by the protocol handlers
+#[derive(Debug, Clone, Copy)]
+pub enum UpstreamOutcome<'a> {
+    /// 2xx response
+    Success,
+    /// Non-2xx HTTP response
+    HttpError {
+        status: u16,
+        retry_after: Option<&'a str>,
+        body: &'a str,
+    },
+    /// Connection / transport failure (no HTTP status)
+    Transport,
+    /// 2xx stream that failed before any content reached the client
+    StreamInterrupted,
+}
+
+/// Soft avoidance after a stream failed before its first token (seconds)
+const STREAM_INTERRUPT_LOCKOUT_SECS: u64 = 5;
+
+/// Why no account could be selected for a request
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum TokenError {
+    #[error("Token pool is empty")]
+    PoolEmpty,
+    /// Every otherwise-eligible account is at its concurrency limit
+    #[error("All available accounts are at their concurrency limit")]
+    AllBusy,
+    /// Admission queue timed out waiting for a free lease
+    #[error("All available accounts are at their concurrency limit (queued {waited_ms}ms)")]
+    QueueTimeout { waited_ms: u64 },
+    #[error("All accounts limited. Wait {wait_secs}s.")]
+    RateLimited { wait_secs: u64 },
+    #[error("All accounts failed after optimistic reset.")]
+    RateLimitedAfterReset,
+    #[error("All accounts are quota-protected for {model}")]
+    QuotaProtected { model: String },
+    #[error("All accounts have an open circuit breaker for {model}")]
+    CircuitOpen { model: String },
+    #[error("Token acquisition timeout (5s) - system too busy or deadlock detected")]
+    Timeout,
+    #[error("Selected accounts were revoked during token refresh")]
+    Revoked,
+    #[error("All accounts failed or unhealthy.")]
+    Unhealthy,
+    #[error("All accounts failed")]
+    AllFailed,
+}
+
+impl TokenError {
+    /// Whether the model is exhausted pool-wide (rate limits or quota
+    /// protection), as opposed to busy / unhealthy accounts.
+    pub fn is_pool_exhausted(&self) -> bool {
+        matches!(
+            self,
+            Self::RateLimited { .. } | Self::RateLimitedAfterReset | Self::QuotaProtected { .. }
+        )
+    }
+}
+
 /// In-memory token cache for proxy request routing
 #[derive(Debug, Clone)]
 pub struct ProxyToken {
@@ -65,6 +181,8 @@ pub struct ProxyToken {
     pub validation_blocked_until: i64,
     /// In-memory cache for model-specific quotas
     pub model_quotas: HashMap<String, i32>,
+    /// Concurrency lease held by the request using this token (None for pool entries)
+    pub lease: Option<Arc<AccountLease>>,
 }
 
 /// Core token pool manager
@@ -79,14 +197,26 @@ pub struct TokenManager {
     sticky_config: Arc<RwLock<StickySessionConfig>>,
     /// Session-to-account bindings (session_id -> account_id)
     session_accounts: Arc<DashMap<String, String>>,
+    /// Binding timestamps (session_id -> times), persisted alongside the binding
+    session_times: Arc<DashMap<String, BindingTimes>>,
+    /// Background SQLite writer for session bindings (keeps disk I/O off the request path)
+    binding_writer: Arc<session_binding_db::BindingWriter>,
     /// Health scores per account
     health_scores: Arc<DashMap<String, f32>>,
     /// Preferred account ID for fixed-account mode
     preferred_account_id: Arc<RwLock<Option<String>>>,
     /// Circuit breaker configuration cache
     circuit_breaker_config: Arc<RwLock<CircuitBreakerConfig>>,
+    /// Per account+model breaker state machines
+    breakers: Arc<CircuitBreakerRegistry>,
+    /// Per-account in-flight leases and admission queue
+    concurrency: Arc<ConcurrencyLimiter>,
     /// Background auto-cleanup task handle
     auto_cleanup_handle: Arc<tokio::sync::Mutex<Option<tokio::task::JoinHandle<()>>>>,
+    /// Per-account refresh locks (single-flight)
+    refresh_locks: Arc<DashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+    /// Background token refresh task handle
+    token_refresh_handle: Arc<tokio::sync::Mutex<Option<tokio::task::JoinHandle<()>>>>,
     /// Cancellation token for graceful shutdown
     cancel_token: CancellationToken,
 }
@@ -96,14 +226,20 @@ impl TokenManager {
     pub fn new(data_dir: PathBuf) -> Self {
         Self {
             tokens: Arc::new(DashMap::new()),
+            binding_writer: Arc::new(session_binding_db::BindingWriter::new(data_dir.clone())),
             data_dir,
             rate_limit_tracker: Arc::new(RateLimitTracker::new()),
             sticky_config: Arc::new(RwLock::new(StickySessionConfig::default())),
             session_accounts: Arc::new(DashMap::new()),
+            session_times: Arc::new(DashMap::new()),
             health_scores: Arc::new(DashMap::new()),
             preferred_account_id: Arc::new(RwLock::new(None)),
             circuit_breaker_config: Arc::new(RwLock::new(CircuitBreakerConfig::default())),
+            breakers: Arc::new(CircuitBreakerRegistry::new()),
+            concurrency: Arc::new(ConcurrencyLimiter::new()),
             auto_cleanup_handle: Arc::new(tokio::sync::Mutex::new(None)),
+            refresh_locks: Arc::new(DashMap::new()),
+            token_refresh_handle: Arc::new(tokio::sync::Mutex::new(None)),
             cancel_token: CancellationToken::new(),
         }
     }
@@ -111,6 +247,10 @@ impl TokenManager {
     /// Start background auto-cleanup task (every 15s, cleans expired rate limit records)
     pub async fn start_auto_cleanup(&self) {
         let tracker = self.rate_limit_tracker.clone();
+        let sessions = self.session_accounts.clone();
+        let session_times = self.session_times.clone();
+        let sticky_config = self.sticky_config.clone();
+        let binding_writer = self.binding_writer.clone();
         let cancel = self.cancel_token.child_token();
 
         let handle = tokio::spawn(async move {
@@ -129,6 +269,19 @@ impl TokenManager {
                                 cleaned
                             );
                         }
+                        let config = sticky_config.read().await.clone();
+                        let pruned = Self::prune_session_bindings_in(
+                            &sessions,
+                            &session_times,
+                            &binding_writer,
+                            &config,
+                        );
+                        if pruned > 0 {
+                            tracing::info!(
+                                "Auto-cleanup: Removed {} expired session binding(s)",
+                                pruned
+                            );
+                        }
                     }
                 }
             }
@@ -191,6 +344,8 @@ impl TokenManager {
             }
         }
 
+        self.restore_session_bindings().await;
+
         Ok(count)
     }
 
@@ -248,11 +403,21 @@ impl TokenManager {
         // 2. Clean up health scores
         self.health_scores.remove(account_id);
 
-        // 3. Clean up rate limit records
+        // 3. Clean up rate limit records and breakers
         self.clear_rate_limit(account_id);
+        self.breakers.reset_account(account_id);
 
         // 4. Clean up session bindings referencing this account
-        self.session_accounts.retain(|_, v| v != account_id);
+        let sessions = &self.session_accounts;
+        let session_times = &self.session_times;
+        sessions.retain(|sid, v| {
+            let keep = v != account_id;
+            if !keep {
+                session_times.remove(sid);
+            }
+            keep
+        });
+        self.binding_writer.delete_for_account(account_id);
 
         // 5. Clear preferred account if it was this one
         if let Ok(mut preferred) = self.preferred_account_id.try_write() {
@@ -302,6 +467,102 @@ impl TokenManager {
         }
     }
 
+    /// Report the outcome of an upstream call for an account.
+    ///
+    /// - 2xx: marks success (health up, failure count reset)
+    /// - 429 / 5xx / 404: locks out the account (or account+model for quota
+    ///   exhaustion) using the server-provided reset time, falling back to
+    ///   `CircuitBreakerConfig.backoff_steps`; only the fallback is skipped
+    ///   when the circuit breaker is disabled
+    /// - 401 / 403 / transport errors: health score only
+    /// - stream interrupted before first token: health score + short soft
+    ///   avoidance so the retry lands on another account
+    /// - other 4xx: client errors, account is not penalized
+    ///
+    /// Penalized outcomes and successes also feed the account+model circuit
+    /// breaker when a model is given.
+    ///
+    /// Returns the rate limit info when a lockout was applied.
+    pub async fn report_outcome(
+        &self,
+        account_id: &str,
+        model: Option<&str>,
+        outcome: UpstreamOutcome<'_>,
+    ) -> Option<RateLimitInfo> {
+        let penalized = match outcome {
+            UpstreamOutcome::Success => Some(false),
+            UpstreamOutcome::HttpError { status, .. }
+                if !matches!(status, 401 | 403 | 404 | 429) && status < 500 =>
+            {
+                None
+            }
+            _ => Some(true),
+        };
+        if let (Some(failed), Some(model)) = (penalized, model) {
+            let cb = self.circuit_breaker_config.read().await.clone();
+            if cb.enabled {
+                let normalized = crate::proxy::common::model_mapping::normalize_to_standard_id(model)
+                    .unwrap_or_else(|| model.to_string());
+                self.breakers.record(account_id, &normalized, !failed, &cb);
+            }
+        }
+
+        match outcome {
+            UpstreamOutcome::Success => {
+                self.mark_success(account_id);
+                None
+            }
+            UpstreamOutcome::Transport => {
+                self.record_failure(account_id);
+                None
+            }
+            UpstreamOutcome::StreamInterrupted => {
+                self.record_failure(account_id);
+                if !self.circuit_breaker_config.read().await.enabled {
+                    return None;
+                }
+                self.rate_limit_tracker.set_lockout_until(
+                    account_id,
+                    std::time::SystemTime::now()
+                        + std::time::Duration::from_secs(STREAM_INTERRUPT_LOCKOUT_SECS),
+                    RateLimitReason::ServerError,
+                    None,
+                );
+                self.rate_limit_tracker.get(account_id)
+            }
+            UpstreamOutcome::HttpError {
+                status,
+                retry_after,
+                body,
+            } => {
+                if !matches!(status, 401 | 403 | 404 | 429) && status < 500 {
+                    return None;
+                }
+                self.record_failure(account_id);
+
+                // 服务端给出的重置时间 (Retry-After / RetryInfo / quotaResetDelay)
+                // 始终生效；熔断器关闭时只跳过 backoff_steps 兜底
+                let cb = self.circuit_breaker_config.read().await.clone();
+                if !cb.enabled
+                    && self
+                        .rate_limit_tracker
+                        .server_reset_seconds(retry_after, body)
+                        .is_none()
+                {
+                    return None;
+                }
+                self.rate_limit_tracker.parse_from_error(
+                    account_id,
+                    status,
+                    retry_after,
+                    body,
+                    model.map(str::to_string),
+                    &cb.backoff_steps,
+                )
+            }
+        }
+    }
+
     /// Get the number of tokens in the pool
     pub fn len(&self) -> usize {
         self.tokens.len()
@@ -349,6 +610,12 @@ impl TokenManager {
         self.circuit_breaker_config.read().await.clone()
     }
 
+    /// Breaker state per model for one account (admin listing)
+    pub async fn circuit_breaker_states(&self, account_id: &str) -> Vec<BreakerSnapshot> {
+        let cb = self.circuit_breaker_config.read().await.clone();
+        self.breakers.snapshot_for_account(account_id, &cb)
+    }
+
     /// Set preferred account ID (fixed account mode)
     pub async fn set_preferred_account(&self, account_id: Option<String>) {
         let mut preferred = self.preferred_account_id.write().await;
@@ -362,12 +629,14 @@ impl TokenManager {
 
     /// Clear session binding for a specific session
     pub fn clear_session_binding(&self, session_id: &str) {
-        self.session_accounts.remove(session_id);
+        self.unbind_session(session_id);
     }
 
-    /// Clear all session bindings
+    /// Clear all session bindings (memory and disk)
     pub fn clear_all_sessions(&self) {
         self.session_accounts.clear();
+        self.session_times.clear();
+        self.binding_writer.clear();
     }
 
     /// Clear rate limit for a specific account
@@ -401,6 +670,11 @@ impl TokenManager {
         if let Some(handle) = guard.take() {
             let _ = tokio::time::timeout(timeout, handle).await;
         }
+
+        let mut guard = self.token_refresh_handle.lock().await;
+        if let Some(handle) = guard.take() {
+            let _ = tokio::time::timeout(timeout, handle).await;
+        }
     }
 
     /// P2C pool size: pick 2 random candidates from the top N
@@ -414,37 +688,133 @@ impl TokenManager {
     /// - P2C load balancing [Req 4.1]
     /// - PerformanceFirst pure rotation [Req 4.4]
     /// - Sorting: subscription tier > model quota > health score [Req 4.12]
+    /// - Per-account concurrency limit (the returned token carries the lease)
     pub async fn get_token(
         &self,
         model: &str,
         session_id: Option<&str>,
-    ) -> Result<ProxyToken, String> {
-        // 5-second timeout to prevent deadlocks
+    ) -> Result<ProxyToken, TokenError> {
+        self.get_token_for_user(model, session_id, None).await
+    }
+
+    /// Same as [`get_token`](Self::get_token), queueing fairly per user token
+    /// when every eligible account is at its concurrency limit.
+    pub async fn get_token_for_user(
+        &self,
+        model: &str,
+        session_id: Option<&str>,
+        user_token_id: Option<&str>,
+    ) -> Result<ProxyToken, TokenError> {
+        let scheduling = self.sticky_config.read().await.clone();
+        let max = scheduling.max_concurrent_per_account;
+        if max == 0 {
+            return self.select_token(model, session_id, &HashSet::new()).await;
+        }
+
+        let user = user_token_id.unwrap_or(ANONYMOUS_QUEUE_KEY);
+        let weight = scheduling
+            .fair_queue_weights
+            .get(user)
+            .copied()
+            .unwrap_or(1);
+        let deadline = tokio::time::Instant::now()
+            + std::time::Duration::from_secs(scheduling.queue_timeout_seconds);
+
+        // Join the queue behind existing waiters so freed leases are handed out fairly
+        let mut ticket = None;
+        if self.concurrency.has_waiters() {
+            let queued = self.concurrency.enqueue(user, weight);
+            self.concurrency.wake_head();
+            if !queued.wait(deadline).await {
+                let waited_ms = queued.timed_out();
+                return Err(TokenError::QueueTimeout { waited_ms });
+            }
+            ticket = Some(queued);
+        }
+
+        loop {
+            let saturated = self.concurrency.saturated_accounts(max);
+            match self.select_token(model, session_id, &saturated).await {
+                Ok(mut token) => {
+                    // Another request may have taken the last slot since the snapshot
+                    let Some(lease) = self.concurrency.try_acquire(&token.account_id, max) else {
+                        continue;
+                    };
+                    if let Some(queued) = ticket.take() {
+                        queued.admit();
+                    }
+                    token.lease = Some(Arc::new(lease));
+                    return Ok(token);
+                }
+                Err(TokenError::AllBusy) => match ticket.as_ref() {
+                    // Retry once after enqueueing so a lease released meanwhile isn't missed
+                    None => ticket = Some(self.concurrency.enqueue(user, weight)),
+                    Some(queued) => {
+                        queued.pass_on();
+                        if !queued.wait(deadline).await {
+                            let waited_ms = ticket.take().map(|t| t.timed_out()).unwrap_or(0);
+                            tracing::warn!(
+                                "Admission queue timeout for {} after {}ms (user={})",
+                                model,
+                                waited_ms,
+                                user
+                            );
+                            return Err(TokenError::QueueTimeout { waited_ms });
+                        }
+                    }
+                },
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Current in-flight leases and admission queue state
+    pub async fn concurrency_stats(&self) -> ConcurrencyStats {
+        let max = self.sticky_config.read().await.max_concurrent_per_account;
+        self.concurrency.stats(max)
+    }
+
+    /// One selection pass, bounded by a 5-second timeout to prevent deadlocks
+    async fn select_token(
+        &self,
+        model: &str,
+        session_id: Option<&str>,
+        saturated: &HashSet<String>,
+    ) -> Result<ProxyToken, TokenError> {
         let timeout_duration = std::time::Duration::from_secs(5);
-        match tokio::time::timeout(
-            timeout_duration,
-            self.get_token_internal(model, session_id),
-        )
-        .await
-        {
-            Ok(result) => result,
-            Err(_) => Err(
-                "Token acquisition timeout (5s) - system too busy or deadlock detected".to_string(),
-            ),
+        // A revoked account is removed from the pool on refresh, so the second pass skips it
+        for _ in 0..2 {
+            let token = match tokio::time::timeout(
+                timeout_duration,
+                self.get_token_internal(model, session_id, saturated),
+            )
+            .await
+            {
+                Ok(result) => result?,
+                Err(_) => return Err(TokenError::Timeout),
+            };
+            if let Some(token) = self.ensure_fresh_token(token).await {
+                return Ok(token);
+            }
         }
+        Err(TokenError::Revoked)
     }
 
     /// Internal implementation of the token selection logic.
+    ///
+    /// `saturated` accounts are at their concurrency limit: they are skipped
+    /// without dropping their session bindings.
     async fn get_token_internal(
         &self,
         target_model: &str,
         session_id: Option<&str>,
-    ) -> Result<ProxyToken, String> {
+        saturated: &HashSet<String>,
+    ) -> Result<ProxyToken, TokenError> {
         let mut tokens_snapshot: Vec<ProxyToken> =
             self.tokens.iter().map(|e| e.value().clone()).collect();
 
         if tokens_snapshot.is_empty() {
-            return Err("Token pool is empty".to_string());
+            return Err(TokenError::PoolEmpty);
         }
 
         let total = tokens_snapshot.len();
@@ -459,6 +829,7 @@ impl TokenManager {
 
         // Read scheduling config
         let scheduling = self.sticky_config.read().await.clone();
+        let cb = self.circuit_breaker_config.read().await.clone();
 
         // ===== Fixed account mode [Req 4.15] =====
         let preferred_id = self.preferred_account_id.read().await.clone();
@@ -484,7 +855,7 @@ impl TokenManager {
                             *preferred = None;
                         }
                         if tokens_snapshot.is_empty() {
-                            return Err("Token pool is empty".to_string());
+                            return Err(TokenError::PoolEmpty);
                         }
                     }
                     OnDiskAccountState::Unknown => {
@@ -495,7 +866,7 @@ impl TokenManager {
                         tokens_snapshot
                             .retain(|t| t.account_id != preferred_token.account_id);
                         if tokens_snapshot.is_empty() {
-                            return Err("Token pool is empty".to_string());
+                            return Err(TokenError::PoolEmpty);
                         }
                     }
                     OnDiskAccountState::Enabled => {
@@ -505,8 +876,20 @@ impl TokenManager {
                         );
                         let is_quota_protected =
                             preferred_token.protected_models.contains(&normalized_target);
+                        let is_saturated = saturated.contains(&preferred_token.account_id);
+                        let is_circuit_open = cb.enabled
+                            && !(self.breakers.is_available(
+                                &preferred_token.account_id,
+                                &normalized_target,
+                                &cb,
+                            ) && self.breakers.try_acquire(
+                                &preferred_token.account_id,
+                                &normalized_target,
+                                &cb,
+                            ));
 
-                        if !is_rate_limited && !is_quota_protected {
+                        if !is_rate_limited && !is_quota_protected && !is_saturated && !is_circuit_open
+                        {
                             tracing::info!(
                                 "Using preferred account: {} (fixed mode)",
                                 preferred_token.email
@@ -518,8 +901,12 @@ impl TokenManager {
                                 preferred_token.email,
                                 if is_rate_limited {
                                     "rate-limited"
-                                } else {
+                                } else if is_quota_protected {
                                     "quota-protected"
+                                } else if is_saturated {
+                                    "at its concurrency limit"
+                                } else {
+                                    "circuit-open"
                                 },
                                 target_model
                             );
@@ -534,13 +921,35 @@ impl TokenManager {
             }
         }
 
+        // Accounts whose breaker is open for this model are skipped
+        if cb.enabled {
+            tokens_snapshot.retain(|t| {
+                self.breakers
+                    .is_available(&t.account_id, &normalized_target, &cb)
+            });
+            if tokens_snapshot.is_empty() {
+                return Err(TokenError::CircuitOpen {
+                    model: normalized_target,
+                });
+            }
+        }
+
+        // Accounts at their concurrency limit don't take part in this pass
+        if !saturated.is_empty() {
+            tokens_snapshot.retain(|t| !saturated.contains(&t.account_id));
+            if tokens_snapshot.is_empty() {
+                return Err(TokenError::AllBusy);
+            }
+        }
+
         // ===== Main scheduling loop =====
         let mut attempted: HashSet<String> = HashSet::new();
-        let last_error: Option<String> = None;
 
         for attempt in 0..total {
             let rotate = attempt > 0;
             let mut target_token: Option<ProxyToken> = None;
+            // Bound account is only busy: overflow to another account but keep the binding
+            let mut bound_busy = false;
 
             // === Mode A: Sticky session (CacheFirst or Balance with session_id) [Req 4.2, 4.3] ===
             if !rotate
@@ -549,8 +958,19 @@ impl TokenManager {
             {
                 let sid = session_id.unwrap();
 
+                if self.session_binding_expired(sid, scheduling.session_binding_ttl_seconds) {
+                    tracing::debug!("Sticky Session: Binding for session {} expired", sid);
+                    self.unbind_session(sid);
+                }
+
                 if let Some(bound_id) = self.session_accounts.get(sid).map(|v| v.clone()) {
-                    if let Some(bound_token) =
+                    if saturated.contains(&bound_id) {
+                        tracing::debug!(
+                            "Sticky Session: Bound account {} is at its concurrency limit, overflowing",
+                            bound_id
+                        );
+                        bound_busy = true;
+                    } else if let Some(bound_token) =
                         tokens_snapshot.iter().find(|t| t.account_id == bound_id)
                     {
                         let reset_sec = self
@@ -579,13 +999,14 @@ impl TokenManager {
                                             .protected_models
                                             .contains(&normalized_target)
                                         {
+                                            self.touch_session(sid);
                                             target_token = Some(bound_token.clone());
                                         } else {
-                                            self.session_accounts.remove(sid);
+                                            self.unbind_session(sid);
                                         }
                                     } else {
                                         // Wait too long, unbind and switch
-                                        self.session_accounts.remove(sid);
+                                        self.unbind_session(sid);
                                     }
                                 }
                                 SchedulingMode::Balance => {
@@ -595,7 +1016,7 @@ impl TokenManager {
                                         bound_token.email,
                                         reset_sec
                                     );
-                                    self.session_accounts.remove(sid);
+                                    self.unbind_session(sid);
                                 }
                                 SchedulingMode::PerformanceFirst => {
                                     // Should not reach here due to outer check
@@ -611,6 +1032,7 @@ impl TokenManager {
                                 bound_token.email,
                                 sid
                             );
+                            self.touch_session(sid);
                             target_token = Some(bound_token.clone());
                         } else if bound_token.protected_models.contains(&normalized_target) {
                             tracing::debug!(
@@ -618,11 +1040,11 @@ impl TokenManager {
                                 bound_token.email,
                                 normalized_target
                             );
-                            self.session_accounts.remove(sid);
+                            self.unbind_session(sid);
                         }
                     } else {
                         // Bound account no longer exists
-                        self.session_accounts.remove(sid);
+                        self.unbind_session(sid);
                     }
                 }
             }
@@ -648,9 +1070,8 @@ impl TokenManager {
 
                     // Bind session if sticky mode [Req 4.2, 4.3]
                     if let Some(sid) = session_id {
-                        if scheduling.mode != SchedulingMode::PerformanceFirst {
-                            self.session_accounts
-                                .insert(sid.to_string(), selected.account_id.clone());
+                        if scheduling.mode != SchedulingMode::PerformanceFirst && !bound_busy {
+                            self.bind_session(sid, &selected.account_id);
                             tracing::debug!(
                                 "Sticky Session: Bound new account {} to session {}",
                                 selected.email,
@@ -663,6 +1084,10 @@ impl TokenManager {
 
             let token = match target_token {
                 Some(t) => t,
+                None if !saturated.is_empty() => {
+                    // Remaining accounts are unavailable; wait for a busy one to free up
+                    return Err(TokenError::AllBusy);
+                }
                 None => {
                     // Optimistic reset: if shortest wait <= 2s, buffer and retry [Req 4.11]
                     let min_wait = tokens_snapshot
@@ -710,19 +1135,23 @@ impl TokenManager {
                                 if let Some(t) = final_token {
                                     t.clone()
                                 } else {
-                                    return Err(
-                                        "All accounts failed after optimistic reset.".to_string(),
-                                    );
+                                    return Err(TokenError::RateLimitedAfterReset);
                                 }
                             }
                         } else {
-                            return Err(format!(
-                                "All accounts limited. Wait {}s.",
-                                wait_sec
-                            ));
+                            return Err(TokenError::RateLimited {
+                                wait_secs: wait_sec,
+                            });
                         }
+                    } else if tokens_snapshot
+                        .iter()
+                        .all(|t| t.protected_models.contains(&normalized_target))
+                    {
+                        return Err(TokenError::QuotaProtected {
+                            model: normalized_target,
+                        });
                     } else {
-                        return Err("All accounts failed or unhealthy.".to_string());
+                        return Err(TokenError::Unhealthy);
                     }
                 }
             };
@@ -749,10 +1178,20 @@ impl TokenManager {
                 OnDiskAccountState::Enabled => {}
             }
 
+            // Half-open breakers admit a single probe; lost the race -> pick another
+            if cb.enabled
+                && !self
+                    .breakers
+                    .try_acquire(&token.account_id, &normalized_target, &cb)
+            {
+                attempted.insert(token.account_id.clone());
+                continue;
+            }
+
             return Ok(token);
         }
 
-        Err(last_error.unwrap_or_else(|| "All accounts failed".to_string()))
+        Err(TokenError::AllFailed)
     }
 
     /// P2C (Power of Two Choices) selection algorithm [Req 4.1].
@@ -870,6 +1309,720 @@ impl TokenManager {
     }
 }
 
+// Background token refresh
+impl TokenManager {
+    /// Start the background refresh worker (refreshes tokens ahead of expiry)
+    pub async fn start_token_refresh(self: &Arc<Self>) {
+        let manager = Arc::downgrade(self);
+        let cancel = self.cancel_token.child_token();
+
+        let handle = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(
+                TOKEN_REFRESH_SCAN_INTERVAL_SECS,
+            ));
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => {
+                        tracing::info!("Token refresh task received cancel signal");
+                        break;
+                    }
+                    _ = interval.tick() => {
+                        let Some(manager) = manager.upgrade() else { break };
+                        manager.refresh_expiring_tokens().await;
+                    }
+                }
+            }
+        });
+
+        let mut guard = self.token_refresh_handle.lock().await;
+        if let Some(old) = guard.take() {
+            old.abort();
+            tracing::warn!("Aborted previous token refresh task");
+        }
+        *guard = Some(handle);
+
+        tracing::info!(
+            "Token refresh task started (interval: {}s, lead: {}s + jitter)",
+            TOKEN_REFRESH_SCAN_INTERVAL_SECS,
+            TOKEN_REFRESH_AHEAD_SECS
+        );
+    }
+
+    /// Refresh lead time for an account: fixed lead + stable per-account jitter
+    fn refresh_lead_secs(account_id: &str) -> i64 {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        account_id.hash(&mut hasher);
+        TOKEN_REFRESH_AHEAD_SECS + (hasher.finish() % (TOKEN_REFRESH_JITTER_SECS + 1)) as i64
+    }
+
+    /// One scan: refresh every pooled token inside its lead window
+    async fn refresh_expiring_tokens(&self) {
+        use futures::StreamExt;
+
+        let now = chrono::Utc::now().timestamp();
+        let due: Vec<(String, i64)> = self
+            .tokens
+            .iter()
+            .filter_map(|e| {
+                let lead = Self::refresh_lead_secs(e.key());
+                (e.value().timestamp - now <= lead).then(|| (e.key().clone(), lead))
+            })
+            .collect();
+        if due.is_empty() {
+            return;
+        }
+
+        futures::stream::iter(due)
+            .for_each_concurrent(TOKEN_REFRESH_CONCURRENCY, |(account_id, lead)| async move {
+                match self.refresh_account_token(&account_id, lead).await {
+                    Ok(RefreshOutcome::Refreshed) => {
+                        tracing::debug!("Background refresh: renewed token for {}", account_id)
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        tracing::warn!("Background refresh failed for {}: {}", account_id, e)
+                    }
+                }
+            })
+            .await;
+    }
+
+    /// Refresh inline if the token is about to expire.
+    ///
+    /// Returns None when the account turned out to be revoked; transient refresh
+    /// errors hand back the current token (the 401 path still covers it).
+    async fn ensure_fresh_token(&self, mut token: ProxyToken) -> Option<ProxyToken> {
+        let now = chrono::Utc::now().timestamp();
+        if token.timestamp - now > TOKEN_REFRESH_INLINE_SECS {
+            return Some(token);
+        }
+
+        match self
+            .refresh_account_token(&token.account_id, TOKEN_REFRESH_INLINE_SECS)
+            .await
+        {
+            Ok(RefreshOutcome::Revoked) => None,
+            Ok(_) => {
+                if let Some(fresh) = self.tokens.get(&token.account_id) {
+                    token.access_token = fresh.access_token.clone();
+                    token.refresh_token = fresh.refresh_token.clone();
+                    token.expires_in = fresh.expires_in;
+                    token.timestamp = fresh.timestamp;
+                }
+                Some(token)
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "Inline token refresh failed for {}: {}, using current token",
+                    token.email,
+                    e
+                );
+                Some(token)
+            }
+        }
+    }
+
+    /// Single-flight refresh of one account's access token.
+    ///
+    /// Concurrent callers wait on the same per-account lock; whoever gets it second
+    /// sees the new expiry and returns `AlreadyFresh`.
+    pub async fn refresh_account_token(
+        &self,
+        account_id: &str,
+        lead_secs: i64,
+    ) -> Result<RefreshOutcome, String> {
+        let lock = self
+            .refresh_locks
+            .entry(account_id.to_string())
+            .or_insert_with(|| Arc::new(tokio::sync::Mutex::new(())))
+            .clone();
+        let _guard = lock.lock().await;
+
+        let token = self
+            .tokens
+            .get(account_id)
+            .map(|t| t.clone())
+            .ok_or_else(|| format!("Account {} is not in the pool", account_id))?;
+        if token.timestamp - chrono::Utc::now().timestamp() > lead_secs {
+            return Ok(RefreshOutcome::AlreadyFresh);
+        }
+
+        match crate::modules::oauth::refresh_access_token(&token.refresh_token).await {
+            Ok(response) => {
+                self.apply_refreshed_token(
+                    account_id,
+                    &response.access_token,
+                    response.expires_in,
+                    response.refresh_token.as_deref(),
+                )?;
+                Ok(RefreshOutcome::Refreshed)
+            }
+            Err(e) if e.contains("invalid_grant") => {
+                self.disable_revoked_account(account_id, &e)?;
+                Ok(RefreshOutcome::Revoked)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Persist a refreshed token to the account file, then swap it into the pool
+    fn apply_refreshed_token(
+        &self,
+        account_id: &str,
+        access_token: &str,
+        expires_in: i64,
+        new_refresh_token: Option<&str>,
+    ) -> Result<(), String> {
+        let path = self
+            .tokens
+            .get(account_id)
+            .map(|t| t.account_path.clone())
+            .ok_or_else(|| format!("Account {} is not in the pool", account_id))?;
+        let expiry_timestamp = chrono::Utc::now().timestamp() + expires_in;
+
+        let mut account_json = Self::read_account_json(&path)?;
+        let token_obj = account_json
+            .get_mut("token")
+            .and_then(|t| t.as_object_mut())
+            .ok_or("Missing token field")?;
+        token_obj.insert("access_token".to_string(), serde_json::json!(access_token));
+        token_obj.insert("expires_in".to_string(), serde_json::json!(expires_in));
+        token_obj.insert(
+            "expiry_timestamp".to_string(),
+            serde_json::json!(expiry_timestamp),
+        );
+        if let Some(refresh_token) = new_refresh_token {
+            token_obj.insert("refresh_token".to_string(), serde_json::json!(refresh_token));
+        }
+        Self::write_account_json_atomic(&path, &account_json)?;
+
+        // Swap all token fields under a single entry lock
+        if let Some(mut token) = self.tokens.get_mut(account_id) {
+            token.access_token = access_token.to_string();
+            token.expires_in = expires_in;
+            token.timestamp = expiry_timestamp;
+            if let Some(refresh_token) = new_refresh_token {
+                token.refresh_token = refresh_token.to_string();
+            }
+        }
+        Ok(())
+    }
+
+    /// Mark an account whose refresh token was revoked as disabled and drop it from the pool
+    fn disable_revoked_account(&self, account_id: &str, error: &str) -> Result<(), String> {
+        let path = self
+            .tokens
+            .get(account_id)
+            .map(|t| t.account_path.clone())
+            .unwrap_or_else(|| {
+                self.data_dir
+                    .join("accounts")
+                    .join(format!("{}.json", account_id))
+            });
+
+        let mut account_json = Self::read_account_json(&path)?;
+        account_json["disabled"] = serde_json::Value::Bool(true);
+        account_json["disabled_reason"] =
+            serde_json::json!(format!("invalid_grant: refresh token revoked ({})", error));
+        account_json["disabled_at"] = serde_json::json!(chrono::Utc::now().timestamp());
+        Self::write_account_json_atomic(&path, &account_json)?;
+
+        tracing::warn!(
+            "Account {} refresh token revoked (invalid_grant), disabled and removed from pool",
+            account_id
+        );
+        self.remove_account(account_id);
+        Ok(())
+    }
+
+    fn read_account_json(path: &std::path::Path) -> Result<serde_json::Value, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read account file: {}", e))?;
+        serde_json::from_str(&content).map_err(|e| format!("Failed to parse account file: {}", e))
+    }
+
+    /// Write via temp file + rename so readers never see a half-written account
+    fn write_account_json_atomic(
+        path: &std::path::Path,
+        account_json: &serde_json::Value,
+    ) -> Result<(), String> {
+        let tmp_path = path.with_extension("json.tmp");
+        let content = serde_json::to_string_pretty(account_json)
+            .map_err(|e| format!("Failed to serialize account: {}", e))?;
+        std::fs::write(&tmp_path, content)
+            .map_err(|e| format!("Failed to write file: {}", e))?;
+        std::fs::rename(&tmp_path, path).map_err(|e| format!("Failed to replace file: {}", e))
+    }
+}
+
+// Session binding persistence
+impl TokenManager {
+    /// Bind a session to an account in memory and on disk
+    fn bind_session(&self, session_id: &str, account_id: &str) {
+        let now = chrono::Utc::now().timestamp();
+        let previous = self
+            .session_accounts
+            .insert(session_id.to_string(), account_id.to_string());
+        let bound_at = match (previous, self.session_times.get(session_id)) {
+            (Some(prev), Some(times)) if prev == account_id => times.bound_at,
+            _ => now,
+        };
+        self.session_times.insert(
+            session_id.to_string(),
+            BindingTimes {
+                bound_at,
+                last_used: now,
+                persisted_last_used: now,
+            },
+        );
+        self.binding_writer.upsert(session_id, account_id, now);
+    }
+
+    /// Record a reuse of the binding; disk writes are throttled
+    fn touch_session(&self, session_id: &str) {
+        let now = chrono::Utc::now().timestamp();
+        let persist = match self.session_times.get_mut(session_id) {
+            Some(mut times) => {
+                times.last_used = now;
+                if now - times.persisted_last_used >= BINDING_TOUCH_PERSIST_INTERVAL_SECS {
+                    times.persisted_last_used = now;
+                    true
+                } else {
+                    false
+                }
+            }
+            None => {
+                // Binding created without timestamps (e.g. inserted directly); start tracking now
+                self.session_times.insert(
+                    session_id.to_string(),
+                    BindingTimes {
+                        bound_at: now,
+                        last_used: now,
+                        persisted_last_used: 0,
+                    },
+                );
+                false
+            }
+        };
+        if persist {
+            self.binding_writer.touch(session_id, now);
+        }
+    }
+
+    /// Remove a binding from memory and disk
+    fn unbind_session(&self, session_id: &str) {
+        self.session_accounts.remove(session_id);
+        self.session_times.remove(session_id);
+        self.binding_writer.delete(session_id);
+    }
+
+    /// Whether the binding has been idle longer than the TTL
+    fn session_binding_expired(&self, session_id: &str, ttl_seconds: u64) -> bool {
+        let now = chrono::Utc::now().timestamp();
+        self.session_times
+            .get(session_id)
+            .is_some_and(|t| now - t.last_used > ttl_seconds as i64)
+    }
+
+    /// Reload persisted bindings, dropping expired ones and those whose account left the pool
+    async fn restore_session_bindings(&self) {
+        let config = self.sticky_config.read().await.clone();
+        let cutoff = chrono::Utc::now().timestamp() - config.session_binding_ttl_seconds as i64;
+
+        // Pending writes from this manager must land before reading back.
+        // Both the flush and the SQLite read block, keep them off the executor.
+        let writer = self.binding_writer.clone();
+        let data_dir = self.data_dir.clone();
+        let max_entries = config.max_session_bindings;
+        let loaded = tokio::task::spawn_blocking(move || {
+            writer.flush();
+            session_binding_db::load_bindings(&data_dir, cutoff, max_entries)
+        })
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|result| result);
+        let persisted = match loaded {
+            Ok(rows) => rows,
+            Err(e) => {
+                tracing::warn!("Failed to load persisted session bindings: {}", e);
+                return;
+            }
+        };
+
+        let mut restored = 0;
+        let mut dropped = 0;
+        for binding in persisted {
+            if !self.tokens.contains_key(&binding.account_id) {
+                dropped += 1;
+                self.unbind_session(&binding.session_id);
+                continue;
+            }
+            if self.session_accounts.contains_key(&binding.session_id) {
+                continue;
+            }
+            self.session_accounts
+                .insert(binding.session_id.clone(), binding.account_id);
+            self.session_times.insert(
+                binding.session_id,
+                BindingTimes {
+                    bound_at: binding.bound_at,
+                    last_used: binding.last_used,
+                    persisted_last_used: binding.last_used,
+                },
+            );
+            restored += 1;
+        }
+
+        // In-memory bindings to accounts that are no longer loaded
+        let stale: Vec<String> = self
+            .session_accounts
+            .iter()
+            .filter(|e| !self.tokens.contains_key(e.value()))
+            .map(|e| e.key().clone())
+            .collect();
+        for sid in &stale {
+            self.unbind_session(sid);
+        }
+        dropped += stale.len();
+
+        if restored > 0 || dropped > 0 {
+            tracing::info!(
+                "Session bindings: restored {}, dropped {} pointing at missing accounts",
+                restored,
+                dropped
+            );
+        }
+    }
+
+    /// Drop expired bindings and enforce the size cap (LRU by last use)
+    pub async fn prune_session_bindings(&self) -> usize {
+        let config = self.sticky_config.read().await.clone();
+        Self::prune_session_bindings_in(
+            &self.session_accounts,
+            &self.session_times,
+            &self.binding_writer,
+            &config,
+        )
+    }
+
+    fn prune_session_bindings_in(
+        sessions: &DashMap<String, String>,
+        session_times: &DashMap<String, BindingTimes>,
+        binding_writer: &session_binding_db::BindingWriter,
+        config: &StickySessionConfig,
+    ) -> usize {
+        let cutoff = chrono::Utc::now().timestamp() - config.session_binding_ttl_seconds as i64;
+
+        let mut by_last_used: Vec<(String, i64)> = session_times
+            .iter()
+            .map(|e| (e.key().clone(), e.value().last_used))
+            .collect();
+        by_last_used.sort_by_key(|(_, last_used)| std::cmp::Reverse(*last_used));
+
+        let mut removed = 0;
+        for (index, (sid, last_used)) in by_last_used.iter().enumerate() {
+            if *last_used < cutoff || index >= config.max_session_bindings {
+                sessions.remove(sid);
+                session_times.remove(sid);
+                removed += 1;
+            }
+        }
+
+        if removed > 0 {
+            binding_writer.prune(cutoff, config.max_session_bindings);
+        }
+        removed
+    }
+
+    /// Binding counts and ages for the admin API
+    pub async fn session_binding_stats(&self) -> SessionBindingStats {
+        let config = self.sticky_config.read().await.clone();
+        let now = chrono::Utc::now().timestamp();
+
+        let mut by_account: HashMap<String, usize> = HashMap::new();
+        let mut total_age: u64 = 0;
+        let mut oldest_age: u64 = 0;
+        let mut max_idle: u64 = 0;
+        for entry in self.session_accounts.iter() {
+            *by_account.entry(entry.value().clone()).or_default() += 1;
+            if let Some(times) = self.session_times.get(entry.key()) {
+                let age = (now - times.bound_at).max(0) as u64;
+                total_age += age;
+                oldest_age = oldest_age.max(age);
+                max_idle = max_idle.max((now - times.last_used).max(0) as u64);
+            }
+        }
+
+        let total = self.session_accounts.len();
+        SessionBindingStats {
+            total,
+            by_account,
+            oldest_age_seconds: oldest_age,
+            avg_age_seconds: if total > 0 { total_age / total as u64 } else { 0 },
+            max_idle_seconds: max_idle,
+            ttl_seconds: config.session_binding_ttl_seconds,
+            max_bindings: config.max_session_bindings,
+        }
+    }
+}
+
+/// Why an account is skipped when scheduling a model
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExclusionReason {
+    DisabledOnDisk,
+    DiskStateUnknown,
+    RateLimited,
+    QuotaProtected,
+    AtConcurrencyLimit,
+    CircuitOpen,
+}
+
+/// One account in the ranked candidate list
+#[derive(Debug, Clone, Serialize)]
+pub struct CandidateExplanation {
+    /// Position after sorting by tier > model quota > health score (1-based)
+    pub rank: usize,
+    pub account_id: String,
+    pub email: String,
+    pub tier: Option<String>,
+    pub model_quota: Option<i32>,
+    pub health_score: f32,
+    pub rate_limit_wait_seconds: u64,
+    pub in_flight: u32,
+    pub excluded: Option<ExclusionReason>,
+}
+
+/// Result of a scheduling dry run
+#[derive(Debug, Clone, Serialize)]
+pub struct SchedulingExplanation {
+    pub model: String,
+    pub normalized_model: String,
+    pub mode: SchedulingMode,
+    pub preferred_account_id: Option<String>,
+    pub session_id: Option<String>,
+    pub session_bound_account: Option<String>,
+    pub candidates: Vec<CandidateExplanation>,
+    /// Top eligible accounts P2C draws its two random picks from
+    pub p2c_pool: Vec<String>,
+    /// preferred_account | session_binding | session_binding_after_wait | p2c | none
+    pub selection_path: String,
+    pub selected_account_id: Option<String>,
+    pub selected_email: Option<String>,
+    pub notes: Vec<String>,
+}
+
+// Scheduling explanation (read-only)
+impl TokenManager {
+    /// Explain how `get_token` would schedule `target_model` right now.
+    ///
+    /// Mirrors the fixed-account, sticky-session and P2C paths without
+    /// creating/removing session bindings, sleeping, or purging accounts.
+    /// P2C is randomized, so `selected_account_id` is one draw from `p2c_pool`.
+    pub async fn explain_selection(
+        &self,
+        target_model: &str,
+        session_id: Option<&str>,
+    ) -> SchedulingExplanation {
+        let normalized_target =
+            crate::proxy::common::model_mapping::normalize_to_standard_id(target_model)
+                .unwrap_or_else(|| target_model.to_string());
+
+        let mut tokens_snapshot: Vec<ProxyToken> =
+            self.tokens.iter().map(|e| e.value().clone()).collect();
+        tokens_snapshot.sort_by(|a, b| Self::compare_tokens(a, b, &normalized_target));
+
+        let scheduling = self.sticky_config.read().await.clone();
+        let preferred_id = self.preferred_account_id.read().await.clone();
+        let cb = self.circuit_breaker_config.read().await.clone();
+        let saturated = if scheduling.max_concurrent_per_account > 0 {
+            self.concurrency
+                .saturated_accounts(scheduling.max_concurrent_per_account)
+        } else {
+            HashSet::new()
+        };
+
+        let mut candidates = Vec::with_capacity(tokens_snapshot.len());
+        for (index, token) in tokens_snapshot.iter().enumerate() {
+            let excluded = match Self::get_account_state_on_disk(&token.account_path).await {
+                OnDiskAccountState::Disabled => Some(ExclusionReason::DisabledOnDisk),
+                OnDiskAccountState::Unknown => Some(ExclusionReason::DiskStateUnknown),
+                OnDiskAccountState::Enabled
+                    if self
+                        .rate_limit_tracker
+                        .is_rate_limited(&token.account_id, Some(&normalized_target)) =>
+                {
+                    Some(ExclusionReason::RateLimited)
+                }
+                OnDiskAccountState::Enabled
+                    if token.protected_models.contains(&normalized_target) =>
+                {
+                    Some(ExclusionReason::QuotaProtected)
+                }
+                OnDiskAccountState::Enabled
+                    if cb.enabled
+                        && !self.breakers.is_available(
+                            &token.account_id,
+                            &normalized_target,
+                            &cb,
+                        ) =>
+                {
+                    Some(ExclusionReason::CircuitOpen)
+                }
+                OnDiskAccountState::Enabled if saturated.contains(&token.account_id) => {
+                    Some(ExclusionReason::AtConcurrencyLimit)
+                }
+                OnDiskAccountState::Enabled => None,
+            };
+            candidates.push(CandidateExplanation {
+                rank: index + 1,
+                account_id: token.account_id.clone(),
+                email: token.email.clone(),
+                tier: token.subscription_tier.clone(),
+                model_quota: token
+                    .model_quotas
+                    .get(&normalized_target)
+                    .copied()
+                    .or(token.remaining_quota),
+                health_score: token.health_score,
+                rate_limit_wait_seconds: self
+                    .rate_limit_tracker
+                    .get_remaining_wait(&token.account_id, Some(&normalized_target)),
+                in_flight: self.concurrency.in_flight(&token.account_id),
+                excluded,
+            });
+        }
+
+        let exclusion_of = |account_id: &str| {
+            candidates
+                .iter()
+                .find(|c| c.account_id == account_id)
+                .map(|c| c.excluded)
+        };
+
+        let mut explanation = SchedulingExplanation {
+            model: target_model.to_string(),
+            normalized_model: normalized_target.clone(),
+            mode: scheduling.mode,
+            preferred_account_id: preferred_id.clone(),
+            session_id: session_id.map(|s| s.to_string()),
+            session_bound_account: session_id
+                .and_then(|sid| self.session_accounts.get(sid).map(|v| v.clone())),
+            candidates: Vec::new(),
+            p2c_pool: Vec::new(),
+            selection_path: "none".to_string(),
+            selected_account_id: None,
+            selected_email: None,
+            notes: Vec::new(),
+        };
+
+        // ===== Fixed account mode =====
+        if let Some(pref_id) = preferred_id.as_deref() {
+            match exclusion_of(pref_id) {
+                Some(None) => {
+                    explanation.selection_path = "preferred_account".to_string();
+                    explanation.selected_account_id = Some(pref_id.to_string());
+                }
+                Some(Some(reason)) => explanation.notes.push(format!(
+                    "Preferred account {} skipped: {:?}",
+                    pref_id, reason
+                )),
+                None => explanation
+                    .notes
+                    .push(format!("Preferred account {} not found in pool", pref_id)),
+            }
+        }
+
+        // ===== Sticky session =====
+        if explanation.selected_account_id.is_none()
+            && scheduling.mode != SchedulingMode::PerformanceFirst
+        {
+            if let Some(bound_id) = explanation.session_bound_account.clone() {
+                let reset_sec = self.rate_limit_tracker.get_remaining_wait(&bound_id, None);
+                match exclusion_of(&bound_id) {
+                    None => explanation.notes.push(format!(
+                        "Bound account {} no longer in pool; binding would be dropped",
+                        bound_id
+                    )),
+                    Some(Some(ExclusionReason::AtConcurrencyLimit)) => explanation.notes.push(
+                        format!(
+                            "Bound account {} is at its concurrency limit; request overflows, binding kept",
+                            bound_id
+                        ),
+                    ),
+                    Some(_)
+                        if reset_sec > 0
+                            && scheduling.mode == SchedulingMode::CacheFirst
+                            && reset_sec <= scheduling.max_wait_seconds =>
+                    {
+                        explanation.selection_path = "session_binding_after_wait".to_string();
+                        explanation.selected_account_id = Some(bound_id.clone());
+                        explanation.notes.push(format!(
+                            "CacheFirst would wait {}s for bound account {}",
+                            reset_sec, bound_id
+                        ));
+                    }
+                    Some(None) if reset_sec == 0 => {
+                        explanation.selection_path = "session_binding".to_string();
+                        explanation.selected_account_id = Some(bound_id.clone());
+                    }
+                    Some(reason) => explanation.notes.push(format!(
+                        "Bound account {} unusable ({}); binding would be dropped",
+                        bound_id,
+                        reason
+                            .map(|r| format!("{:?}", r))
+                            .unwrap_or_else(|| format!("rate-limited for {}s", reset_sec))
+                    )),
+                }
+            }
+        }
+
+        // ===== P2C =====
+        let eligible: Vec<ProxyToken> = tokens_snapshot
+            .iter()
+            .filter(|t| exclusion_of(&t.account_id) == Some(None))
+            .cloned()
+            .collect();
+        explanation.p2c_pool = eligible
+            .iter()
+            .take(Self::P2C_POOL_SIZE)
+            .map(|t| t.account_id.clone())
+            .collect();
+
+        if explanation.selected_account_id.is_none() {
+            if let Some(selected) =
+                self.select_with_p2c(&eligible, &HashSet::new(), &normalized_target)
+            {
+                explanation.selection_path = "p2c".to_string();
+                explanation.selected_account_id = Some(selected.account_id.clone());
+            } else if let Some(wait) = tokens_snapshot
+                .iter()
+                .filter_map(|t| self.rate_limit_tracker.get_reset_seconds(&t.account_id))
+                .min()
+            {
+                explanation.notes.push(format!(
+                    "No eligible account; shortest rate-limit wait is {}s",
+                    wait
+                ));
+            } else {
+                explanation
+                    .notes
+                    .push("No eligible account for this model".to_string());
+            }
+        }
+
+        explanation.selected_email = explanation.selected_account_id.as_deref().and_then(|id| {
+            tokens_snapshot
+                .iter()
+                .find(|t| t.account_id == id)
+                .map(|t| t.email.clone())
+        });
+        explanation.candidates = candidates;
+        explanation
+    }
+}
+
 // Quota protection methods
 impl TokenManager {
     /// Check and apply quota protection for an account during loading.
@@ -1496,6 +2649,7 @@ impl TokenManager {
                 .and_then(|v| v.as_i64())
                 .unwrap_or(0),
             model_quotas,
+            lease: None,
         }))
     }
 
@@ -1883,6 +3037,179 @@ mod tests {
         assert!((token.health_score - 0.6).abs() < f32::EPSILON);
     }
 
+    #[tokio::test]
+    async fn test_report_outcome_locks_out_and_recovers() {
+        let dir = TestDataDir::new();
+        create_account_file(&dir.path, "acc1", "user1@test.com");
+
+        let tm = TokenManager::new(dir.path.clone());
+        tm.load_accounts().await.unwrap();
+        tm.update_circuit_breaker_config(CircuitBreakerConfig {
+            enabled: true,
+            backoff_steps: vec![120, 600],
+            ..Default::default()
+        })
+        .await;
+
+        // Quota exhausted without a reset hint -> model-level lockout from backoff_steps
+        let body = r#"{"error":{"details":[{"reason":"QUOTA_EXHAUSTED"}]}}"#;
+        let info = tm
+            .report_outcome(
+                "acc1",
+                Some("gemini-3-flash"),
+                UpstreamOutcome::HttpError { status: 429, retry_after: None, body },
+            )
+            .await
+            .unwrap();
+        assert_eq!(info.retry_after_sec, 120);
+        assert!(tm.is_rate_limited("acc1", Some("gemini-3-flash")));
+        assert!(!tm.is_rate_limited("acc1", Some("gemini-3-pro")));
+        assert!((*tm.health_scores.get("acc1").unwrap() - 0.8).abs() < f32::EPSILON);
+
+        // Retry-After header wins over backoff
+        let info = tm
+            .report_outcome(
+                "acc1",
+                None,
+                UpstreamOutcome::HttpError { status: 503, retry_after: Some("42"), body: "" },
+            )
+            .await
+            .unwrap();
+        assert_eq!(info.retry_after_sec, 42);
+        assert!(tm.is_rate_limited("acc1", None));
+
+        // Success clears the account-level lockout
+        tm.report_outcome("acc1", None, UpstreamOutcome::Success).await;
+        assert!(!tm.is_rate_limited("acc1", None));
+    }
+
+    #[tokio::test]
+    async fn test_refreshed_token_persisted_and_swapped() {
+        let dir = TestDataDir::new();
+        let path = create_account_file(&dir.path, "acc1", "user1@test.com");
+
+        let tm = TokenManager::new(dir.path.clone());
+        tm.load_accounts().await.unwrap();
+
+        // Still fresh -> single-flight recheck short-circuits without a network call
+        assert_eq!(
+            tm.refresh_account_token("acc1", TOKEN_REFRESH_INLINE_SECS).await,
+            Ok(RefreshOutcome::AlreadyFresh)
+        );
+
+        tm.apply_refreshed_token("acc1", "at_new", 3599, None).unwrap();
+        let token = tm.tokens.get("acc1").unwrap().clone();
+        assert_eq!(token.access_token, "at_new");
+        assert_eq!(token.refresh_token, "rt_acc1");
+        assert!(token.timestamp > chrono::Utc::now().timestamp() + 3500);
+
+        let on_disk: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(on_disk["token"]["access_token"], "at_new");
+        assert_eq!(on_disk["token"]["expiry_timestamp"], token.timestamp);
+        assert!(!path.with_extension("json.tmp").exists());
+
+        let lead = TokenManager::refresh_lead_secs("acc1");
+        assert!((TOKEN_REFRESH_AHEAD_SECS
+            ..=TOKEN_REFRESH_AHEAD_SECS + TOKEN_REFRESH_JITTER_SECS as i64)
+            .contains(&lead));
+        assert_eq!(lead, TokenManager::refresh_lead_secs("acc1"));
+    }
+
+    #[tokio::test]
+    async fn test_revoked_refresh_token_disables_account() {
+        let dir = TestDataDir::new();
+        let path = create_account_file(&dir.path, "acc1", "user1@test.com");
+        create_account_file(&dir.path, "acc2", "user2@test.com");
+
+        let tm = TokenManager::new(dir.path.clone());
+        tm.load_accounts().await.unwrap();
+
+        tm.disable_revoked_account("acc1", "Refresh failed: {\"error\": \"invalid_grant\"}")
+            .unwrap();
+        assert!(!tm.tokens.contains_key("acc1"));
+
+        let on_disk: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(on_disk["disabled"], true);
+        assert!(on_disk["disabled_reason"]
+            .as_str()
+            .unwrap()
+            .starts_with("invalid_grant"));
+
+        // Reload keeps it out of the pool
+        assert_eq!(tm.load_accounts().await.unwrap(), 1);
+        assert_eq!(tm.get_token("gemini-3-flash", None).await.unwrap().account_id, "acc2");
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_skips_account_for_model() {
+        let dir = TestDataDir::new();
+        create_account_file(&dir.path, "acc1", "user1@test.com");
+        create_account_file(&dir.path, "acc2", "user2@test.com");
+
+        let tm = TokenManager::new(dir.path.clone());
+        tm.load_accounts().await.unwrap();
+        tm.update_circuit_breaker_config(CircuitBreakerConfig {
+            failure_threshold: 2,
+            open_seconds: 3600,
+            ..Default::default()
+        })
+        .await;
+
+        for _ in 0..2 {
+            tm.report_outcome("acc1", Some("gemini-3-flash"), UpstreamOutcome::Transport)
+                .await;
+        }
+        let states = tm.circuit_breaker_states("acc1").await;
+        assert_eq!(states.len(), 1);
+        assert_eq!(states[0].state, crate::proxy::circuit_breaker::BreakerState::Open);
+
+        for _ in 0..5 {
+            let token = tm.get_token("gemini-3-flash", None).await.unwrap();
+            assert_eq!(token.account_id, "acc2");
+        }
+        // Breaker is per model
+        let explanation = tm.explain_selection("gemini-3-pro-high", None).await;
+        assert!(explanation.candidates.iter().all(|c| c.excluded.is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_report_outcome_ignores_client_errors_and_disabled_breaker() {
+        let dir = TestDataDir::new();
+        create_account_file(&dir.path, "acc1", "user1@test.com");
+
+        let tm = TokenManager::new(dir.path.clone());
+        tm.load_accounts().await.unwrap();
+
+        let bad_request = UpstreamOutcome::HttpError { status: 400, retry_after: None, body: "bad" };
+        assert!(tm.report_outcome("acc1", None, bad_request).await.is_none());
+        assert!(!tm.health_scores.contains_key("acc1"));
+
+        tm.update_circuit_breaker_config(CircuitBreakerConfig {
+            enabled: false,
+            backoff_steps: vec![60],
+            ..Default::default()
+        })
+        .await;
+        // No server reset time: the backoff_steps fallback is skipped
+        let exhausted = UpstreamOutcome::HttpError { status: 429, retry_after: None, body: "" };
+        assert!(tm.report_outcome("acc1", None, exhausted).await.is_none());
+        assert!(!tm.is_rate_limited("acc1", None));
+        // Health still drops so P2C prefers other accounts
+        assert!(tm.health_scores.contains_key("acc1"));
+
+        // Server-provided reset times still apply
+        let rate_limited = UpstreamOutcome::HttpError { status: 429, retry_after: Some("30"), body: "" };
+        assert!(tm.report_outcome("acc1", None, rate_limited).await.is_some());
+        assert!(tm.is_rate_limited("acc1", None));
+
+        let quota_body = r#"{"error":{"details":[{"metadata":{"quotaResetDelay":"90s"}}]}}"#;
+        let quota = UpstreamOutcome::HttpError { status: 429, retry_after: None, body: quota_body };
+        let info = tm.report_outcome("acc1", Some("gemini-3-flash"), quota).await;
+        assert!(info.is_some());
+    }
+
     #[tokio::test]
     async fn test_record_failure_decreases_health() {
         let dir = TestDataDir::new();
@@ -2074,6 +3401,7 @@ mod tests {
             validation_blocked: false,
             validation_blocked_until: 0,
             model_quotas,
+            lease: None,
         }
     }
 
@@ -2303,7 +3631,7 @@ mod tests {
 
         let result = tm.get_token("gemini-flash", None).await;
         assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Token pool is empty"));
+        assert_eq!(result.unwrap_err(), TokenError::PoolEmpty);
     }
 
     #[tokio::test]
@@ -2416,6 +3744,7 @@ mod tests {
         tm.update_sticky_config(StickySessionConfig {
             mode: SchedulingMode::PerformanceFirst,
             max_wait_seconds: 60,
+            ..Default::default()
         })
         .await;
 
@@ -2428,6 +3757,157 @@ mod tests {
         assert!(!tm.session_accounts.contains_key(session_id));
     }
 
+    #[tokio::test]
+    async fn test_session_bindings_survive_restart() {
+        let dir = TestDataDir::new();
+        create_account_file(&dir.path, "acc1", "user1@test.com");
+        create_account_file(&dir.path, "acc2", "user2@test.com");
+
+        let tm = TokenManager::new(dir.path.clone());
+        tm.load_accounts().await.unwrap();
+        let token = tm.get_token("gemini-3-flash", Some("sess-keep")).await.unwrap();
+        tm.bind_session("sess-gone", "acc2");
+        tm.binding_writer.flush();
+
+        // Simulate a restart after acc2 was deleted
+        fs::remove_file(dir.path.join("accounts").join("acc2.json")).unwrap();
+        let restarted = TokenManager::new(dir.path.clone());
+        restarted.load_accounts().await.unwrap();
+
+        if token.account_id == "acc1" {
+            assert_eq!(
+                restarted.session_accounts.get("sess-keep").map(|v| v.clone()),
+                Some("acc1".to_string())
+            );
+        } else {
+            assert!(!restarted.session_accounts.contains_key("sess-keep"));
+        }
+        assert!(!restarted.session_accounts.contains_key("sess-gone"));
+
+        let stats = restarted.session_binding_stats().await;
+        assert_eq!(stats.total, restarted.session_accounts.len());
+    }
+
+    #[tokio::test]
+    async fn test_session_binding_ttl_and_cap() {
+        let dir = TestDataDir::new();
+        create_account_file(&dir.path, "acc1", "user1@test.com");
+
+        let tm = TokenManager::new(dir.path.clone());
+        tm.load_accounts().await.unwrap();
+        tm.update_sticky_config(StickySessionConfig {
+            max_session_bindings: 1,
+            ..Default::default()
+        })
+        .await;
+
+        for sid in ["s1", "s2", "s3"] {
+            tm.bind_session(sid, "acc1");
+        }
+        // s2 is long expired; s1 is older than s3 and falls outside the cap
+        tm.session_times.get_mut("s1").unwrap().last_used -= 10;
+        tm.session_times.get_mut("s2").unwrap().last_used -= 200_000;
+
+        assert_eq!(tm.prune_session_bindings().await, 2);
+        assert!(tm.session_accounts.contains_key("s3"));
+        assert_eq!(tm.session_accounts.len(), 1);
+
+        // Expired bindings are ignored (and dropped) by the scheduler
+        tm.session_times.get_mut("s3").unwrap().last_used -= 200_000;
+        assert!(tm.session_binding_expired("s3", 86400));
+    }
+
+    #[tokio::test]
+    async fn test_get_token_respects_concurrency_limit() {
+        let dir = TestDataDir::new();
+        create_account_file(&dir.path, "acc1", "user1@test.com");
+        create_account_file(&dir.path, "acc2", "user2@test.com");
+
+        let tm = Arc::new(TokenManager::new(dir.path.clone()));
+        tm.load_accounts().await.unwrap();
+        tm.update_sticky_config(StickySessionConfig {
+            max_concurrent_per_account: 1,
+            queue_timeout_seconds: 1,
+            ..Default::default()
+        })
+        .await;
+
+        let first = tm.get_token("gemini-flash", None).await.unwrap();
+        let second = tm.get_token("gemini-flash", None).await.unwrap();
+        assert_ne!(first.account_id, second.account_id);
+        assert!(first.lease.is_some());
+
+        // Pool saturated: the next request queues until a lease is released
+        let waiter = {
+            let tm = tm.clone();
+            tokio::spawn(async move { tm.get_token_for_user("gemini-flash", None, Some("tok-a")).await })
+        };
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert_eq!(tm.concurrency_stats().await.queue_depth, 1);
+
+        let released = first.account_id.clone();
+        drop(first);
+        let third = waiter.await.unwrap().unwrap();
+        assert_eq!(third.account_id, released);
+
+        let stats = tm.concurrency_stats().await;
+        assert_eq!(stats.queue_depth, 0);
+        assert_eq!(stats.admitted_after_wait, 1);
+
+        // Nothing released before the deadline: queue timeout
+        let err = tm.get_token("gemini-flash", None).await.unwrap_err();
+        assert!(matches!(err, TokenError::QueueTimeout { .. }), "unexpected error: {}", err);
+        assert!(err.to_string().contains("concurrency limit"));
+        drop(second);
+        drop(third);
+    }
+
+    #[tokio::test]
+    async fn test_explain_selection_is_read_only() {
+        let dir = TestDataDir::new();
+        create_account_file(&dir.path, "acc1", "user1@test.com");
+        create_account_file(&dir.path, "acc2", "user2@test.com");
+        create_account_file(&dir.path, "acc3", "user3@test.com");
+
+        let tm = TokenManager::new(dir.path.clone());
+        tm.load_accounts().await.unwrap();
+        tm.rate_limit_tracker.set_lockout_until(
+            "acc1",
+            std::time::SystemTime::now() + std::time::Duration::from_secs(120),
+            RateLimitReason::RateLimitExceeded,
+            None,
+        );
+        tm.tokens
+            .get_mut("acc2")
+            .unwrap()
+            .protected_models
+            .insert("gemini-3-flash".to_string());
+
+        let explanation = tm.explain_selection("gemini-3-flash", Some("sess-x")).await;
+        let reason_of = |id: &str| {
+            explanation
+                .candidates
+                .iter()
+                .find(|c| c.account_id == id)
+                .unwrap()
+                .excluded
+        };
+        assert_eq!(explanation.candidates.len(), 3);
+        assert_eq!(reason_of("acc1"), Some(ExclusionReason::RateLimited));
+        assert_eq!(reason_of("acc2"), Some(ExclusionReason::QuotaProtected));
+        assert_eq!(reason_of("acc3"), None);
+        assert_eq!(explanation.selection_path, "p2c");
+        assert_eq!(explanation.selected_account_id.as_deref(), Some("acc3"));
+        assert!(explanation
+            .candidates
+            .iter()
+            .find(|c| c.account_id == "acc1")
+            .is_some_and(|c| c.rate_limit_wait_seconds > 0));
+
+        // Dry run must not bind the session
+        assert!(!tm.session_accounts.contains_key("sess-x"));
+    }
+
     #[tokio::test]
     async fn test_get_token_skips_quota_protected() {
         let dir = TestDataDir::new();
@@ -2465,6 +3945,26 @@ mod tests {
         assert_eq!(result.unwrap().account_id, "acc2");
     }
 
+    #[tokio::test]
+    async fn test_all_quota_protected_is_pool_exhausted() {
+        let dir = TestDataDir::new();
+        create_account_with_quota(
+            &dir.path,
+            "acc1",
+            "protected@test.com",
+            vec![("gemini-3-flash", 5)],
+            vec!["gemini-3-flash"],
+        );
+
+        let tm = TokenManager::new(dir.path.clone());
+        tm.load_accounts().await.unwrap();
+
+        let err = tm.get_token("gemini-flash", None).await.unwrap_err();
+        assert!(err.is_pool_exhausted(), "unexpected error: {}", err);
+        assert!(!TokenError::QueueTimeout { waited_ms: 5000 }.is_pool_exhausted());
+        assert!(!TokenError::PoolEmpty.is_pool_exhausted());
+    }
+
     /// Helper: create an account file with quota data for quota protection tests
     fn create_account_with_quota(
         dir: &PathBuf,
@@ -2809,6 +4309,7 @@ mod prop_tests {
             validation_blocked: false,
             validation_blocked_until: 0,
             model_quotas: HashMap::new(),
+            lease: None,
         }
     }
 
@@ -1,17 +1,26 @@
 // 上游客户端实现
 // 基于 reqwest 封装，支持上游代理和代理池
 
+use std::collections::HashSet;
+
 use dashmap::DashMap;
+use futures::StreamExt;
 use reqwest::{header, Client, Response, StatusCode};
 use serde_json::Value;
 use tokio::sync::RwLock;
 use tokio::time::Duration;
 
+use super::deadline::DeadlineClock;
 use crate::models::config::UpstreamProxyConfig;
+pub use crate::proxy::proxy_pool::PoolProxyConfig;
+use crate::proxy::proxy_pool::{get_global_proxy_pool, ProxyLease, ProxyPoolManager};
 
 /// 默认 User-Agent
 const DEFAULT_USER_AGENT: &str = "kiro-ai-gateway/1.0";
 
+/// 单次调用内因连接错误切换代理的最大次数
+const MAX_PROXY_FAILOVERS: usize = 3;
+
 /// 端点降级尝试的记录信息
 #[derive(Debug, Clone)]
 pub struct FallbackAttemptLog {
@@ -24,6 +33,28 @@ pub struct FallbackAttemptLog {
 pub struct UpstreamCallResult {
     pub response: Response,
     pub fallback_attempts: Vec<FallbackAttemptLog>,
+    /// 实际承载本次请求的代理池条目 (None = 直连或全局上游代理)
+    pub proxy_id: Option<String>,
+}
+
+/// 让响应体持有代理租约，直到读完或被丢弃 (LeastConnections 统计在途连接)
+fn hold_proxy_lease(resp: Response, lease: Option<ProxyLease>) -> Response {
+    let Some(lease) = lease else {
+        return resp;
+    };
+    let status = resp.status();
+    let version = resp.version();
+    let headers = resp.headers().clone();
+    let body = resp.bytes_stream().map(move |item| {
+        let _ = &lease;
+        item
+    });
+
+    let mut held = axum::http::Response::new(reqwest::Body::wrap_stream(body));
+    *held.status_mut() = status;
+    *held.version_mut() = version;
+    *held.headers_mut() = headers;
+    Response::from(held)
 }
 
 /// 邮箱脱敏
@@ -52,13 +83,6 @@ const V1_INTERNAL_BASE_URL_FALLBACKS: [&str; 3] = [
     V1_INTERNAL_BASE_URL_PROD,
 ];
 
-/// 代理池代理配置（简化版，供 client 使用）
-#[derive(Clone)]
-pub struct PoolProxyConfig {
-    pub entry_id: String,
-    pub proxy: reqwest::Proxy,
-}
-
 /// 标准化代理 URL（确保有协议前缀）
 pub fn normalize_proxy_url(url: &str) -> String {
     let trimmed = url.trim();
@@ -100,7 +124,6 @@ impl UpstreamClient {
             .pool_max_idle_per_host(16)
             .pool_idle_timeout(Duration::from_secs(90))
             .tcp_keepalive(Duration::from_secs(60))
-            .timeout(Duration::from_secs(600))
             .user_agent(DEFAULT_USER_AGENT);
 
         if let Some(config) = proxy_config {
@@ -125,7 +148,6 @@ impl UpstreamClient {
             .pool_max_idle_per_host(16)
             .pool_idle_timeout(Duration::from_secs(90))
             .tcp_keepalive(Duration::from_secs(60))
-            .timeout(Duration::from_secs(600))
             .user_agent(DEFAULT_USER_AGENT)
             .proxy(proxy_config.proxy)
             .build()
@@ -148,21 +170,78 @@ impl UpstreamClient {
     }
 
     /// Get client for a specific account (uses default if no proxy pool binding)
-    pub async fn get_client(&self, _account_id: Option<&str>) -> Client {
-        // Proxy pool integration will be added when proxy_pool module is wired up.
-        // For now, always return the default client.
-        self.default_client.clone()
+    pub async fn get_client(&self, account_id: Option<&str>) -> Client {
+        self.resolve_client(account_id).await.0
+    }
+
+    /// 解析账号出口：账号绑定代理 → 代理池策略选择 → 默认客户端 (全局上游代理/直连)
+    async fn resolve_client(&self, account_id: Option<&str>) -> (Client, Option<String>) {
+        let (Some(pool), Some(acc_id)) = (get_global_proxy_pool(), account_id) else {
+            return (self.default_client.clone(), None);
+        };
+        match pool.get_proxy_for_account(acc_id).await {
+            Ok(Some(proxy_cfg)) => {
+                let proxy_id = proxy_cfg.entry_id.clone();
+                (
+                    self.get_client_with_pool_proxy(proxy_cfg).await,
+                    Some(proxy_id),
+                )
+            }
+            Ok(None) => (self.default_client.clone(), None),
+            Err(e) => {
+                tracing::warn!(
+                    "[Proxy] Failed to resolve proxy for account {}: {}, using default client",
+                    acc_id,
+                    e
+                );
+                (self.default_client.clone(), None)
+            }
+        }
+    }
+
+    /// 连接失败后切换到另一个健康代理 (需开启 auto_failover)
+    async fn failover_client(
+        &self,
+        pool: &ProxyPoolManager,
+        account_id: Option<&str>,
+        tried: &HashSet<String>,
+    ) -> Option<(Client, String)> {
+        if !pool.auto_failover_enabled().await {
+            return None;
+        }
+        match pool
+            .select_failover_proxy(account_id.unwrap_or("-"), tried)
+            .await
+        {
+            Ok(Some(proxy_cfg)) => {
+                let proxy_id = proxy_cfg.entry_id.clone();
+                Some((self.get_client_with_pool_proxy(proxy_cfg).await, proxy_id))
+            }
+            Ok(None) => None,
+            Err(e) => {
+                tracing::warn!("[Proxy] Failover proxy selection failed: {}", e);
+                None
+            }
+        }
+    }
+
+    fn client_cache_key(proxy_cfg: &PoolProxyConfig) -> String {
+        format!("{}|{}", proxy_cfg.entry_id, proxy_cfg.url)
     }
 
-    /// Get client with a specific pool proxy config, caching by entry_id
+    /// Get client with a specific pool proxy config, caching by entry_id + URL
     pub async fn get_client_with_pool_proxy(&self, proxy_cfg: PoolProxyConfig) -> Client {
-        if let Some(client) = self.client_cache.get(&proxy_cfg.entry_id) {
+        let cache_key = Self::client_cache_key(&proxy_cfg);
+        if let Some(client) = self.client_cache.get(&cache_key) {
             return client.clone();
         }
         match Self::build_client_with_proxy(proxy_cfg.clone()) {
             Ok(client) => {
+                // 代理 URL 变更后旧条目作废
+                let stale_prefix = format!("{}|", proxy_cfg.entry_id);
                 self.client_cache
-                    .insert(proxy_cfg.entry_id.clone(), client.clone());
+                    .retain(|k, _| !k.starts_with(&stale_prefix));
+                self.client_cache.insert(cache_key, client.clone());
                 client
             }
             Err(e) => {
@@ -213,7 +292,31 @@ impl UpstreamClient {
         .await
     }
 
+    /// Call v1internal API under an explicit deadline (e.g. resolved from `X-Request-Timeout`)
+    pub async fn call_v1_internal_with_deadline(
+        &self,
+        method: &str,
+        access_token: &str,
+        body: Value,
+        query_string: Option<&str>,
+        account_id: Option<&str>,
+        deadline: DeadlineClock,
+    ) -> Result<UpstreamCallResult, String> {
+        self.send_v1_internal(
+            method,
+            access_token,
+            body,
+            query_string,
+            std::collections::HashMap::new(),
+            account_id,
+            deadline,
+        )
+        .await
+    }
+
     /// Call v1internal API with extra headers and multi-endpoint fallback
+    ///
+    /// The deadline is resolved from the body's model and the configured timeouts.
     pub async fn call_v1_internal_with_headers(
         &self,
         method: &str,
@@ -223,7 +326,38 @@ impl UpstreamClient {
         extra_headers: std::collections::HashMap<String, String>,
         account_id: Option<&str>,
     ) -> Result<UpstreamCallResult, String> {
-        let client = self.get_client(account_id).await;
+        let deadline = DeadlineClock::for_body(&body);
+        self.send_v1_internal(
+            method,
+            access_token,
+            body,
+            query_string,
+            extra_headers,
+            account_id,
+            deadline,
+        )
+        .await
+    }
+
+    /// Deadlines are measured from `deadline`'s fixed start, so endpoint fallback
+    /// and proxy failover share one budget. Streaming calls bound the header wait
+    /// by the first-byte deadline and leave the body to the caller's stream
+    /// deadlines; other calls bound the whole exchange by the remaining total.
+    #[allow(clippy::too_many_arguments)]
+    async fn send_v1_internal(
+        &self,
+        method: &str,
+        access_token: &str,
+        body: Value,
+        query_string: Option<&str>,
+        extra_headers: std::collections::HashMap<String, String>,
+        account_id: Option<&str>,
+        deadline: DeadlineClock,
+    ) -> Result<UpstreamCallResult, String> {
+        let is_stream = method.starts_with("stream");
+        let (mut client, mut proxy_id) = self.resolve_client(account_id).await;
+        let pool = get_global_proxy_pool();
+        let mut tried_proxies: HashSet<String> = proxy_id.iter().cloned().collect();
 
         let mut headers = header::HeaderMap::new();
         headers.insert(
@@ -254,19 +388,66 @@ impl UpstreamClient {
         let mut last_err: Option<String> = None;
         let mut fallback_attempts: Vec<FallbackAttemptLog> = Vec::new();
 
-        for (idx, base_url) in V1_INTERNAL_BASE_URL_FALLBACKS.iter().enumerate() {
+        let mut idx = 0;
+        while idx < V1_INTERNAL_BASE_URL_FALLBACKS.len() {
+            let base_url = V1_INTERNAL_BASE_URL_FALLBACKS[idx];
             let url = Self::build_url(base_url, method, query_string);
             let has_next = idx + 1 < V1_INTERNAL_BASE_URL_FALLBACKS.len();
 
-            let response = client
-                .post(&url)
-                .headers(headers.clone())
-                .json(&body)
-                .send()
-                .await;
+            let remaining = deadline.remaining();
+            if remaining.is_zero() {
+                last_err = Some(format!(
+                    "Request deadline of {}s exceeded before calling {}",
+                    deadline.total().as_secs(),
+                    base_url
+                ));
+                break;
+            }
+
+            let lease = match (&pool, &proxy_id) {
+                (Some(pool), Some(pid)) => Some(pool.acquire_lease(pid)),
+                _ => None,
+            };
+            let started = std::time::Instant::now();
+            let mut request = client.post(&url).headers(headers.clone()).json(&body);
+            if !is_stream {
+                request = request.timeout(remaining);
+            }
+            let headers_at = if is_stream {
+                deadline.first_byte_at()
+            } else {
+                deadline.total_at()
+            };
+            let response = match tokio::time::timeout_at(headers_at.into(), request.send()).await {
+                Ok(response) => response,
+                Err(_) => {
+                    // 首字节 / 总时长已耗尽，换端点也无剩余时间，交由调用方换账号重试
+                    let msg = format!(
+                        "Upstream {} sent no response headers within {}ms (method={})",
+                        base_url,
+                        started.elapsed().as_millis(),
+                        method
+                    );
+                    tracing::warn!("{}", msg);
+                    fallback_attempts.push(FallbackAttemptLog {
+                        endpoint_url: url.clone(),
+                        status: None,
+                        error: msg.clone(),
+                    });
+                    last_err = Some(msg);
+                    break;
+                }
+            };
 
             match response {
                 Ok(resp) => {
+                    if let (Some(pool), Some(pid)) = (&pool, &proxy_id) {
+                        pool.record_served(pid, account_id);
+                        // 流式响应头到达耗时计入代理延迟 EWMA；非流式要等整段生成完，不具参考性
+                        if method.starts_with("stream") {
+                            pool.record_latency(pid, started.elapsed().as_millis() as u64);
+                        }
+                    }
                     let status = resp.status();
                     if status.is_success() {
                         if idx > 0 {
@@ -277,8 +458,9 @@ impl UpstreamClient {
                             );
                         }
                         return Ok(UpstreamCallResult {
-                            response: resp,
+                            response: hold_proxy_lease(resp, lease),
                             fallback_attempts,
+                            proxy_id,
                         });
                     }
 
@@ -296,16 +478,26 @@ impl UpstreamClient {
                             error: err_msg.clone(),
                         });
                         last_err = Some(err_msg);
+                        idx += 1;
                         continue;
                     }
 
                     return Ok(UpstreamCallResult {
-                        response: resp,
+                        response: hold_proxy_lease(resp, lease),
                         fallback_attempts,
+                        proxy_id,
                     });
                 }
                 Err(e) => {
-                    let msg = format!("HTTP request failed at {}: {}", base_url, e);
+                    let msg = match &proxy_id {
+                        Some(pid) => {
+                            format!(
+                                "HTTP request failed at {} via proxy {}: {}",
+                                base_url, pid, e
+                            )
+                        }
+                        None => format!("HTTP request failed at {}: {}", base_url, e),
+                    };
                     tracing::debug!("{}", msg);
                     fallback_attempts.push(FallbackAttemptLog {
                         endpoint_url: url.clone(),
@@ -313,9 +505,34 @@ impl UpstreamClient {
                         error: msg.clone(),
                     });
                     last_err = Some(msg);
+
+                    // 代理连接失败：换一个健康代理重试同一端点
+                    if let (Some(pool), Some(pid)) = (&pool, &proxy_id) {
+                        if e.is_connect() {
+                            pool.record_connect_failure(pid).await;
+                            if tried_proxies.len() <= MAX_PROXY_FAILOVERS {
+                                if let Some((next_client, next_id)) =
+                                    self.failover_client(pool, account_id, &tried_proxies).await
+                                {
+                                    tracing::warn!(
+                                        "Proxy {} connection failed (method={}), failing over to proxy {}",
+                                        pid,
+                                        method,
+                                        next_id
+                                    );
+                                    tried_proxies.insert(next_id.clone());
+                                    client = next_client;
+                                    proxy_id = Some(next_id);
+                                    continue;
+                                }
+                            }
+                        }
+                    }
+
                     if !has_next {
                         break;
                     }
+                    idx += 1;
                     continue;
                 }
             }
@@ -0,0 +1,504 @@
+// 上游请求时限 - 总时长 / 首字节 / 流式空闲
+//
+// - 总时长: 默认取 `ProxyConfig.request_timeout`，从客户端请求开始计时，
+//   端点回退、代理切换与换号重试共用同一起点
+// - 首字节: 本次尝试发出请求到收到首个有效内容 (含等待响应头)；超时按首包失败处理，
+//   调用方透明换账号重试
+// - 流式空闲: 相邻两个数据块的最长间隔；与总时长一起由流包装器执行 (不使用 reqwest 超时)，
+//   超时后结束上游流，交由断流续写 (若开启) 处理
+//
+// 优先级: 请求头 `X-Request-Timeout` (限制在 min / max_request_timeout 之间) > 模型 (精确 / 通配符) > 全局
+//
+// 总时长被请求头缩短后到期属于客户端原因，不计入账号健康度 / 熔断 / 限流状态。
+
+use std::future::Future;
+use std::sync::{OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+use axum::http::HeaderMap;
+use futures::StreamExt;
+use serde_json::Value;
+
+use super::stream_peek::{PeekOutcome, UpstreamByteStream};
+use crate::models::config::UpstreamTimeoutConfig;
+use crate::proxy::common::model_mapping::lookup_model_pattern;
+use crate::proxy::token_manager::ProxyToken;
+
+/// 客户端指定单次请求总时长 (秒) 的请求头
+pub const REQUEST_TIMEOUT_HEADER: &str = "x-request-timeout";
+
+/// 未初始化配置时使用的总时长 (与 ProxyConfig 默认值一致)
+const FALLBACK_REQUEST_TIMEOUT: u64 = 120;
+
+// ============================================================================
+// Global Config
+// ============================================================================
+
+#[derive(Debug, Clone)]
+struct TimeoutSettings {
+    request_timeout: u64,
+    config: UpstreamTimeoutConfig,
+}
+
+static GLOBAL_TIMEOUT_SETTINGS: OnceLock<RwLock<TimeoutSettings>> = OnceLock::new();
+
+fn current_settings() -> TimeoutSettings {
+    GLOBAL_TIMEOUT_SETTINGS
+        .get()
+        .and_then(|lock| lock.read().ok())
+        .map(|s| s.clone())
+        .unwrap_or_else(|| TimeoutSettings {
+            request_timeout: FALLBACK_REQUEST_TIMEOUT,
+            config: UpstreamTimeoutConfig::default(),
+        })
+}
+
+/// 更新上游时限配置 (启动及配置热更新时调用)
+pub fn update_upstream_timeouts(request_timeout: u64, config: &UpstreamTimeoutConfig) {
+    let settings = TimeoutSettings {
+        request_timeout,
+        config: config.clone(),
+    };
+    let lock = GLOBAL_TIMEOUT_SETTINGS.get_or_init(|| RwLock::new(settings.clone()));
+    if let Ok(mut current) = lock.write() {
+        *current = settings;
+    }
+    tracing::info!(
+        "[Deadline] Config updated: request={}s, first_byte={}s, stream_idle={}s, header_range={}-{}s, model_rules={}",
+        request_timeout,
+        config.first_byte_timeout,
+        config.stream_idle_timeout,
+        config.min_request_timeout,
+        config.max_request_timeout,
+        config.model_overrides.len()
+    );
+}
+
+// ============================================================================
+// Resolution
+// ============================================================================
+
+/// 单次上游请求的各项时限
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deadlines {
+    pub total: Duration,
+    pub first_byte: Duration,
+    pub stream_idle: Duration,
+    /// 总时长被 `X-Request-Timeout` 缩短到模型 / 全局配置以下
+    pub client_limited: bool,
+}
+
+/// 解析 `X-Request-Timeout` 请求头 (秒)
+pub fn requested_timeout(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get(REQUEST_TIMEOUT_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok())
+}
+
+/// 按 请求头 > 模型 > 全局 计算时限；首字节与空闲时限不超过总时长
+pub fn resolve_deadlines(
+    request_timeout: u64,
+    config: &UpstreamTimeoutConfig,
+    mapped_model: &str,
+    requested: Option<u64>,
+) -> Deadlines {
+    let model = lookup_model_pattern(mapped_model, &config.model_overrides);
+
+    let configured = model
+        .and_then(|m| m.request_timeout)
+        .unwrap_or(request_timeout)
+        .max(1);
+    let total = match requested {
+        Some(secs) => secs
+            .max(config.min_request_timeout)
+            .min(config.max_request_timeout)
+            .max(1),
+        None => configured,
+    };
+    let first_byte = model
+        .and_then(|m| m.first_byte_timeout)
+        .unwrap_or(config.first_byte_timeout)
+        .clamp(1, total);
+    let stream_idle = model
+        .and_then(|m| m.stream_idle_timeout)
+        .unwrap_or(config.stream_idle_timeout)
+        .clamp(1, total);
+
+    Deadlines {
+        total: Duration::from_secs(total),
+        first_byte: Duration::from_secs(first_byte),
+        stream_idle: Duration::from_secs(stream_idle),
+        client_limited: total < configured,
+    }
+}
+
+/// 以固定起点计算的截止时刻
+///
+/// 总时长从 `request_started` 起算，首字节时限从本次尝试 (创建时刻) 起算且不晚于总截止时刻。
+#[derive(Debug, Clone, Copy)]
+pub struct DeadlineClock {
+    request_started: Instant,
+    attempt_started: Instant,
+    deadlines: Deadlines,
+}
+
+impl DeadlineClock {
+    pub fn new(request_started: Instant, deadlines: Deadlines) -> Self {
+        Self {
+            request_started,
+            attempt_started: Instant::now(),
+            deadlines,
+        }
+    }
+
+    /// 同一请求的下一次上游尝试 (对冲 / 续写)：总时长起点不变，首字节时限重新计时
+    pub fn next_attempt(&self) -> Self {
+        Self::new(self.request_started, self.deadlines)
+    }
+
+    /// 无请求头时按 v1internal 请求体中的模型计时 (非对话请求)
+    pub fn for_body(body: &Value) -> Self {
+        Self::new(Instant::now(), default_deadlines(body))
+    }
+
+    pub fn total(&self) -> Duration {
+        self.deadlines.total
+    }
+
+    pub fn stream_idle(&self) -> Duration {
+        self.deadlines.stream_idle
+    }
+
+    pub fn total_at(&self) -> Instant {
+        self.request_started + self.deadlines.total
+    }
+
+    pub fn first_byte_at(&self) -> Instant {
+        (self.attempt_started + self.deadlines.first_byte).min(self.total_at())
+    }
+
+    /// 距总截止时刻的剩余时间
+    pub fn remaining(&self) -> Duration {
+        self.total_at().saturating_duration_since(Instant::now())
+    }
+
+    pub fn expired(&self) -> bool {
+        self.remaining().is_zero()
+    }
+
+    /// 已到达被客户端缩短的总截止时刻；此时的失败不应计入账号状态
+    pub fn expired_by_client(&self) -> bool {
+        self.deadlines.client_limited && self.expired()
+    }
+}
+
+/// 按当前配置与请求头计算某次请求的时限
+pub fn deadlines_for_request(mapped_model: &str, headers: &HeaderMap) -> Deadlines {
+    let settings = current_settings();
+    resolve_deadlines(
+        settings.request_timeout,
+        &settings.config,
+        mapped_model,
+        requested_timeout(headers),
+    )
+}
+
+/// 无请求头时的默认时限 (按 v1internal 请求体中的模型)
+pub fn default_deadlines(body: &Value) -> Deadlines {
+    let model = body.get("model").and_then(|m| m.as_str()).unwrap_or("");
+    deadlines_for_request(model, &HeaderMap::new())
+}
+
+// ============================================================================
+// Enforcement
+// ============================================================================
+
+/// 在首字节截止时刻前等待首包检测结果；超时视为首包失败
+pub async fn peek_within_deadline<F>(
+    deadline: &DeadlineClock,
+    peek: F,
+) -> (PeekOutcome, Option<ProxyToken>)
+where
+    F: Future<Output = (PeekOutcome, Option<ProxyToken>)>,
+{
+    match tokio::time::timeout_at(deadline.first_byte_at().into(), peek).await {
+        Ok(result) => result,
+        Err(_) => (
+            PeekOutcome::Failed {
+                error_type: "first_byte_timeout",
+                message: format!(
+                    "No content within {}s",
+                    deadline.deadlines.first_byte.as_secs()
+                ),
+            },
+            None,
+        ),
+    }
+}
+
+/// 包裹上游字节流：相邻数据块间隔超过 `idle` 时结束流
+///
+/// 结束时尚未收到 finishReason，开启断流续写时会换账号继续生成。
+pub fn with_idle_timeout(stream: UpstreamByteStream, idle: Duration) -> UpstreamByteStream {
+    limit_stream(stream, Some(idle), None)
+}
+
+/// 包裹上游字节流：到达总截止时刻时结束流
+///
+/// 应套在断流续写之外，避免续写绕过总时长。
+pub fn with_total_deadline(
+    stream: UpstreamByteStream,
+    deadline: &DeadlineClock,
+) -> UpstreamByteStream {
+    limit_stream(stream, None, Some((deadline.total_at(), deadline.total())))
+}
+
+/// 同时执行空闲时限与总截止时刻 (单段上游流，如对冲 / 续写打开的流)
+pub fn with_stream_deadlines(
+    stream: UpstreamByteStream,
+    deadline: &DeadlineClock,
+) -> UpstreamByteStream {
+    limit_stream(
+        stream,
+        Some(deadline.stream_idle()),
+        Some((deadline.total_at(), deadline.total())),
+    )
+}
+
+fn limit_stream(
+    stream: UpstreamByteStream,
+    idle: Option<Duration>,
+    total: Option<(Instant, Duration)>,
+) -> UpstreamByteStream {
+    let mut stream = stream;
+
+    Box::pin(async_stream::stream! {
+        loop {
+            let idle_at = idle.map(|idle| Instant::now() + idle);
+            let until = match (idle_at, total) {
+                (Some(idle_at), Some((total_at, _))) => Some(idle_at.min(total_at)),
+                (Some(idle_at), None) => Some(idle_at),
+                (None, Some((total_at, _))) => Some(total_at),
+                (None, None) => None,
+            };
+            let next = match until {
+                Some(until) => tokio::time::timeout_at(until.into(), stream.next()).await,
+                None => Ok(stream.next().await),
+            };
+            match next {
+                Ok(Some(item)) => yield item,
+                Ok(None) => break,
+                Err(_) => {
+                    match total {
+                        Some((total_at, total)) if Instant::now() >= total_at => tracing::warn!(
+                            "[Deadline] Request deadline of {}s reached, closing upstream stream",
+                            total.as_secs()
+                        ),
+                        _ => tracing::warn!(
+                            "[Deadline] Upstream stream idle for {}s, closing",
+                            idle.unwrap_or_default().as_secs()
+                        ),
+                    }
+                    break;
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use std::collections::HashMap;
+
+    use crate::models::config::ModelTimeoutOverride;
+
+    fn config() -> UpstreamTimeoutConfig {
+        UpstreamTimeoutConfig {
+            first_byte_timeout: 90,
+            stream_idle_timeout: 60,
+            min_request_timeout: 30,
+            max_request_timeout: 600,
+            model_overrides: HashMap::from([
+                (
+                    "*-image*".to_string(),
+                    ModelTimeoutOverride {
+                        request_timeout: Some(300),
+                        first_byte_timeout: Some(240),
+                        stream_idle_timeout: None,
+                    },
+                ),
+                (
+                    "*flash*".to_string(),
+                    ModelTimeoutOverride {
+                        request_timeout: None,
+                        first_byte_timeout: Some(45),
+                        stream_idle_timeout: Some(30),
+                    },
+                ),
+            ]),
+        }
+    }
+
+    fn secs(total: u64, first_byte: u64, stream_idle: u64) -> Deadlines {
+        Deadlines {
+            total: Duration::from_secs(total),
+            first_byte: Duration::from_secs(first_byte),
+            stream_idle: Duration::from_secs(stream_idle),
+            client_limited: false,
+        }
+    }
+
+    fn client_limited(total: u64, first_byte: u64, stream_idle: u64) -> Deadlines {
+        Deadlines {
+            client_limited: true,
+            ..secs(total, first_byte, stream_idle)
+        }
+    }
+
+    #[test]
+    fn test_resolve_deadlines_priority() {
+        let cfg = config();
+        // 全局
+        assert_eq!(
+            resolve_deadlines(120, &cfg, "gemini-3-pro", None),
+            secs(120, 90, 60)
+        );
+        // 模型覆盖：flash 更快，图像更慢 (flash-image 命中更具体的 *-image*)
+        assert_eq!(
+            resolve_deadlines(120, &cfg, "gemini-3-flash", None),
+            secs(120, 45, 30)
+        );
+        assert_eq!(
+            resolve_deadlines(120, &cfg, "gemini-2.5-flash-image", None),
+            secs(300, 240, 60)
+        );
+        // 请求头覆盖总时长，且受上限约束
+        assert_eq!(
+            resolve_deadlines(120, &cfg, "gemini-3-pro", Some(400)),
+            secs(400, 90, 60)
+        );
+        assert_eq!(
+            resolve_deadlines(120, &cfg, "gemini-3-pro", Some(5000)),
+            secs(600, 90, 60)
+        );
+        // 首字节 / 空闲时限不超过总时长
+        assert_eq!(
+            resolve_deadlines(120, &cfg, "gemini-3-pro", Some(40)),
+            client_limited(40, 40, 40)
+        );
+        // 请求头不能低于下限
+        assert_eq!(
+            resolve_deadlines(120, &cfg, "gemini-3-pro", Some(1)),
+            client_limited(30, 30, 30)
+        );
+        assert_eq!(
+            resolve_deadlines(20, &cfg, "gemini-3-pro", Some(0)),
+            secs(30, 30, 30)
+        );
+    }
+
+    #[test]
+    fn test_expired_by_client_only_when_header_shortened_total() {
+        let started = Instant::now() - Duration::from_secs(60);
+        let shortened = DeadlineClock::new(started, client_limited(30, 30, 30));
+        assert!(shortened.expired_by_client());
+        let configured = DeadlineClock::new(started, secs(30, 30, 30));
+        assert!(configured.expired());
+        assert!(!configured.expired_by_client());
+
+        // 下一次尝试沿用同一起点
+        let next = shortened.next_attempt();
+        assert_eq!(next.total_at(), shortened.total_at());
+        assert!(next.expired_by_client());
+    }
+
+    #[test]
+    fn test_requested_timeout_header() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(requested_timeout(&headers), None);
+        headers.insert(REQUEST_TIMEOUT_HEADER, " 300 ".parse().unwrap());
+        assert_eq!(requested_timeout(&headers), Some(300));
+        headers.insert(REQUEST_TIMEOUT_HEADER, "soon".parse().unwrap());
+        assert_eq!(requested_timeout(&headers), None);
+    }
+
+    fn clock(total_ms: u64, first_byte_ms: u64, idle_ms: u64) -> DeadlineClock {
+        DeadlineClock::new(
+            Instant::now(),
+            Deadlines {
+                total: Duration::from_millis(total_ms),
+                first_byte: Duration::from_millis(first_byte_ms),
+                stream_idle: Duration::from_millis(idle_ms),
+                client_limited: false,
+            },
+        )
+    }
+
+    #[test]
+    fn test_deadline_clock_shares_request_start() {
+        let request_started = Instant::now() - Duration::from_secs(100);
+        let deadline = DeadlineClock::new(request_started, secs(120, 90, 60));
+        // 总时长从客户端请求开始计时，首字节截止不晚于总截止
+        assert_eq!(
+            deadline.total_at(),
+            request_started + Duration::from_secs(120)
+        );
+        assert_eq!(deadline.first_byte_at(), deadline.total_at());
+        assert!(deadline.remaining() <= Duration::from_secs(20));
+        assert!(!deadline.expired());
+
+        let late = DeadlineClock::new(Instant::now() - Duration::from_secs(200), secs(120, 90, 60));
+        assert!(late.expired());
+    }
+
+    #[tokio::test]
+    async fn test_peek_within_deadline_times_out() {
+        let deadline = clock(60_000, 50, 60_000);
+        let (outcome, winner) = peek_within_deadline(&deadline, async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            (
+                PeekOutcome::Failed {
+                    error_type: "unreachable",
+                    message: String::new(),
+                },
+                None,
+            )
+        })
+        .await;
+        assert!(winner.is_none());
+        assert!(matches!(
+            outcome,
+            PeekOutcome::Failed {
+                error_type: "first_byte_timeout",
+                ..
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_idle_timeout_closes_stalled_stream() {
+        let stalled =
+            futures::stream::iter(vec![Ok::<_, reqwest::Error>(Bytes::from("data: a\n\n"))])
+                .chain(futures::stream::pending());
+        let stream = with_idle_timeout(Box::pin(stalled), Duration::from_millis(50));
+        let items: Vec<Bytes> = stream.map(|r| r.unwrap()).collect().await;
+        assert_eq!(items, vec![Bytes::from("data: a\n\n")]);
+    }
+
+    #[tokio::test]
+    async fn test_total_deadline_closes_active_stream() {
+        // 持续有数据 (不触发空闲超时)，到达总截止时刻后结束
+        let ticking = futures::stream::unfold((), |_| async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            Some((Ok::<_, reqwest::Error>(Bytes::from("data: a\n\n")), ()))
+        });
+        let stream = with_total_deadline(Box::pin(ticking), &clock(100, 100, 50));
+        let started = Instant::now();
+        let count = stream.count().await;
+        assert!(count > 0);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
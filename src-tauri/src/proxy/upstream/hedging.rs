@@ -0,0 +1,517 @@
+// 对冲请求 (Hedged Requests) - 降低首 token 尾延迟
+//
+// 对配置了对冲策略的模型，若选中账号在首 token 延迟的指定百分位内仍未返回内容，
+// 则在另一账号上发出相同请求，取先返回首个有效内容的一路，丢弃 (取消) 另一路。
+//
+// - 对冲延迟取该模型近期首 token 延迟的百分位，样本不足时使用初始延迟
+// - 每个 attempt 的样本从其自身发出时刻计时；被取消的主请求记为删失样本
+// - 对冲请求数受预算限制 (默认不超过启用对冲请求数的 5%)
+// - 日志与统计中以 attempt 类型 "hedge" 与主请求 "primary" 区分
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::client::UpstreamClient;
+use super::deadline::DeadlineClock;
+use super::stream_peek::{
+    open_alternate_stream, peek_first_content, PeekOutcome, UpstreamByteStream,
+};
+use crate::models::config::HedgingConfig;
+use crate::proxy::common::model_mapping::lookup_model_pattern;
+use crate::proxy::token_manager::{ProxyToken, TokenManager};
+
+/// 每个模型保留的首 token 延迟样本数
+const MAX_LATENCY_SAMPLES: usize = 200;
+/// 样本数达到该值后才按百分位计算延迟
+const MIN_LATENCY_SAMPLES: usize = 20;
+
+// ============================================================================
+// Global Config
+// ============================================================================
+
+static GLOBAL_HEDGING_CONFIG: OnceLock<RwLock<HedgingConfig>> = OnceLock::new();
+
+/// 当前生效的对冲配置
+pub fn get_hedging_config() -> HedgingConfig {
+    GLOBAL_HEDGING_CONFIG
+        .get()
+        .and_then(|lock| lock.read().ok())
+        .map(|cfg| cfg.clone())
+        .unwrap_or_default()
+}
+
+/// 更新对冲配置 (启动及配置热更新时调用)
+pub fn update_hedging_config(config: &HedgingConfig) {
+    let lock = GLOBAL_HEDGING_CONFIG.get_or_init(|| RwLock::new(HedgingConfig::default()));
+    if let Ok(mut cfg) = lock.write() {
+        *cfg = config.clone();
+    }
+    tracing::info!(
+        "[Hedge] Config updated: enabled={}, models={}, max_extra_percent={}",
+        config.enabled,
+        config.model_percentiles.len(),
+        config.max_extra_percent
+    );
+}
+
+// ============================================================================
+// Attempt Type
+// ============================================================================
+
+/// 上游请求的 attempt 类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttemptType {
+    /// 选中账号上的原始请求
+    Primary,
+    /// 首 token 超时后在另一账号上发出的对冲请求
+    Hedge,
+}
+
+impl AttemptType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AttemptType::Primary => "primary",
+            AttemptType::Hedge => "hedge",
+        }
+    }
+}
+
+// ============================================================================
+// Latency Tracking
+// ============================================================================
+
+static FIRST_TOKEN_LATENCIES: OnceLock<Mutex<HashMap<String, VecDeque<u64>>>> = OnceLock::new();
+
+fn latencies_lock() -> &'static Mutex<HashMap<String, VecDeque<u64>>> {
+    FIRST_TOKEN_LATENCIES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn record_first_token_latency(model: &str, latency: Duration) {
+    let Ok(mut latencies) = latencies_lock().lock() else {
+        return;
+    };
+    let samples = latencies.entry(model.to_string()).or_default();
+    if samples.len() >= MAX_LATENCY_SAMPLES {
+        samples.pop_front();
+    }
+    samples.push_back(latency.as_millis() as u64);
+}
+
+/// 按最近邻法计算百分位
+fn percentile(samples: &[u64], percentile: u8) -> Option<u64> {
+    if samples.is_empty() {
+        return None;
+    }
+    let mut sorted = samples.to_vec();
+    sorted.sort_unstable();
+    let rank = (f64::from(percentile.clamp(1, 100)) / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted.get(rank.saturating_sub(1)).copied()
+}
+
+/// 根据延迟样本计算对冲延迟
+fn compute_hedge_delay(config: &HedgingConfig, target_percentile: u8, samples: &[u64]) -> Duration {
+    let delay_ms = if samples.len() >= MIN_LATENCY_SAMPLES {
+        percentile(samples, target_percentile).unwrap_or(config.initial_delay_ms)
+    } else {
+        config.initial_delay_ms
+    };
+    Duration::from_millis(delay_ms.max(config.min_delay_ms))
+}
+
+/// 模型的对冲延迟；未配置对冲策略时返回 None
+pub fn hedge_delay_for(config: &HedgingConfig, model: &str) -> Option<Duration> {
+    if !config.enabled {
+        return None;
+    }
+    let target = *lookup_model_pattern(model, &config.model_percentiles)?;
+    let samples: Vec<u64> = latencies_lock()
+        .lock()
+        .ok()
+        .and_then(|latencies| latencies.get(model).map(|s| s.iter().copied().collect()))
+        .unwrap_or_default();
+    Some(compute_hedge_delay(config, target, &samples))
+}
+
+// ============================================================================
+// Stats & Budget
+// ============================================================================
+
+/// 对冲统计
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HedgeStats {
+    /// 启用对冲策略的请求数
+    pub eligible_requests: u64,
+    /// 已发出的对冲请求数
+    pub hedges_fired: u64,
+    /// 因预算耗尽未发出的对冲
+    pub budget_denied: u64,
+    /// attempt 类型 ("primary" / "hedge") -> 胜出次数
+    pub wins: HashMap<String, u64>,
+}
+
+static HEDGE_STATS: OnceLock<Mutex<HedgeStats>> = OnceLock::new();
+
+fn stats_lock() -> &'static Mutex<HedgeStats> {
+    HEDGE_STATS.get_or_init(|| Mutex::new(HedgeStats::default()))
+}
+
+fn record_eligible() {
+    if let Ok(mut stats) = stats_lock().lock() {
+        stats.eligible_requests += 1;
+    }
+}
+
+fn record_win(attempt: AttemptType) {
+    if let Ok(mut stats) = stats_lock().lock() {
+        *stats.wins.entry(attempt.as_str().to_string()).or_insert(0) += 1;
+    }
+}
+
+/// 预算内则占用一次对冲额度
+fn try_acquire_budget(stats: &mut HedgeStats, max_extra_percent: u32) -> bool {
+    let allowed = stats.eligible_requests * u64::from(max_extra_percent) / 100;
+    if stats.hedges_fired < allowed {
+        stats.hedges_fired += 1;
+        true
+    } else {
+        stats.budget_denied += 1;
+        false
+    }
+}
+
+pub fn get_hedge_stats() -> HedgeStats {
+    stats_lock().lock().map(|s| s.clone()).unwrap_or_default()
+}
+
+pub fn reset_hedge_stats() {
+    if let Ok(mut stats) = stats_lock().lock() {
+        *stats = HedgeStats::default();
+    }
+}
+
+// ============================================================================
+// Hedged Peek
+// ============================================================================
+
+/// 对冲所需的请求上下文
+pub struct HedgeContext {
+    pub token_manager: Arc<TokenManager>,
+    pub upstream: Arc<UpstreamClient>,
+    /// 主请求的请求体 (v1internal 包装格式)
+    pub body: Value,
+    pub model: String,
+    /// 主请求使用的账号
+    pub account_id: String,
+    pub trace_id: String,
+    /// 主请求发出时间
+    pub started: Instant,
+    pub delay: Duration,
+    pub max_extra_percent: u32,
+    /// 主请求的计时，对冲请求沿用其总截止时刻
+    pub deadline: DeadlineClock,
+}
+
+impl HedgeContext {
+    /// 模型启用了对冲策略时构造上下文 (需在主请求发出前调用)
+    pub fn for_model(
+        token_manager: Arc<TokenManager>,
+        upstream: Arc<UpstreamClient>,
+        body: &Value,
+        model: &str,
+        account_id: &str,
+        trace_id: &str,
+        deadline: &DeadlineClock,
+    ) -> Option<Self> {
+        let config = get_hedging_config();
+        let delay = hedge_delay_for(&config, model)?;
+        Some(Self {
+            token_manager,
+            upstream,
+            body: body.clone(),
+            model: model.to_string(),
+            account_id: account_id.to_string(),
+            trace_id: trace_id.to_string(),
+            started: Instant::now(),
+            delay,
+            max_extra_percent: config.max_extra_percent,
+            deadline: *deadline,
+        })
+    }
+
+    /// 记录胜出 attempt 的首 token 延迟 (从该 attempt 自身发出时刻计时)
+    fn finish(&self, attempt: AttemptType, started: Instant) {
+        record_first_token_latency(&self.model, started.elapsed());
+        record_win(attempt);
+    }
+
+    /// 对冲胜出、主请求被取消时记录主请求的删失样本
+    ///
+    /// 主请求的真实首 token 延迟不小于取消时已等待的时长；只记录胜者会让
+    /// 样本偏向快速请求，对冲延迟随之不断缩短。
+    fn record_cancelled_primary(&self) {
+        record_first_token_latency(&self.model, self.started.elapsed());
+    }
+}
+
+/// 等待首个有效内容；启用对冲时在延迟后于另一账号发出对冲请求
+///
+/// 对冲请求胜出时返回其账号，调用方应以该账号继续处理 (日志 / 状态上报)。
+pub async fn peek_with_hedge(
+    stream: UpstreamByteStream,
+    hedge: Option<HedgeContext>,
+) -> (PeekOutcome, Option<ProxyToken>) {
+    let Some(ctx) = hedge else {
+        return (peek_first_content(stream).await, None);
+    };
+    record_eligible();
+
+    let primary = peek_first_content(stream);
+    tokio::pin!(primary);
+
+    let remaining = ctx.delay.saturating_sub(ctx.started.elapsed());
+    tokio::select! {
+        outcome = &mut primary => {
+            if matches!(outcome, PeekOutcome::Ready(_)) {
+                ctx.finish(AttemptType::Primary, ctx.started);
+            }
+            return (outcome, None);
+        }
+        _ = tokio::time::sleep(remaining) => {}
+    }
+
+    let acquired = stats_lock()
+        .lock()
+        .map(|mut stats| try_acquire_budget(&mut stats, ctx.max_extra_percent))
+        .unwrap_or(false);
+    if !acquired {
+        tracing::debug!(
+            "[{}] [Hedge] Budget exhausted, waiting on primary attempt for {}",
+            ctx.trace_id,
+            ctx.model
+        );
+        let outcome = primary.await;
+        if matches!(outcome, PeekOutcome::Ready(_)) {
+            ctx.finish(AttemptType::Primary, ctx.started);
+        }
+        return (outcome, None);
+    }
+
+    tracing::info!(
+        "[{}] [Hedge] No first token from primary attempt after {}ms, firing attempt=hedge for {}",
+        ctx.trace_id,
+        ctx.started.elapsed().as_millis(),
+        ctx.model
+    );
+    let hedge_started = Instant::now();
+    let hedge = open_alternate_stream(
+        &ctx.token_manager,
+        &ctx.upstream,
+        &ctx.body,
+        &ctx.model,
+        &ctx.account_id,
+        false,
+        &ctx.deadline,
+    );
+    tokio::pin!(hedge);
+
+    tokio::select! {
+        outcome = &mut primary => match outcome {
+            PeekOutcome::Ready(stream) => {
+                tracing::info!("[{}] [Hedge] attempt=primary won, cancelling hedge", ctx.trace_id);
+                ctx.finish(AttemptType::Primary, ctx.started);
+                (PeekOutcome::Ready(stream), None)
+            }
+            failed => match hedge.await {
+                Ok((stream, token)) => {
+                    tracing::info!(
+                        "[{}] [Hedge] attempt=hedge won on {} after primary failure",
+                        ctx.trace_id,
+                        token.email
+                    );
+                    ctx.finish(AttemptType::Hedge, hedge_started);
+                    (PeekOutcome::Ready(stream), Some(token))
+                }
+                Err(e) => {
+                    tracing::warn!("[{}] [Hedge] attempt=hedge failed: {}", ctx.trace_id, e);
+                    (failed, None)
+                }
+            },
+        },
+        result = &mut hedge => match result {
+            Ok((stream, token)) => {
+                tracing::info!(
+                    "[{}] [Hedge] attempt=hedge won on {}, cancelling primary",
+                    ctx.trace_id,
+                    token.email
+                );
+                ctx.finish(AttemptType::Hedge, hedge_started);
+                ctx.record_cancelled_primary();
+                (PeekOutcome::Ready(stream), Some(token))
+            }
+            Err(e) => {
+                tracing::warn!("[{}] [Hedge] attempt=hedge failed: {}", ctx.trace_id, e);
+                let outcome = primary.await;
+                if matches!(outcome, PeekOutcome::Ready(_)) {
+                    ctx.finish(AttemptType::Primary, ctx.started);
+                }
+                (outcome, None)
+            }
+        },
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> HedgingConfig {
+        HedgingConfig {
+            enabled: true,
+            model_percentiles: HashMap::from([
+                ("gemini-3-flash".to_string(), 90),
+                ("claude-*".to_string(), 95),
+            ]),
+            max_extra_percent: 5,
+            initial_delay_ms: 3000,
+            min_delay_ms: 500,
+        }
+    }
+
+    #[test]
+    fn test_percentile() {
+        let samples: Vec<u64> = (1..=100).collect();
+        assert_eq!(percentile(&samples, 95), Some(95));
+        assert_eq!(percentile(&samples, 100), Some(100));
+        assert_eq!(percentile(&[7], 50), Some(7));
+        assert_eq!(percentile(&[], 50), None);
+    }
+
+    #[test]
+    fn test_compute_hedge_delay() {
+        let config = config();
+        // 样本不足时使用初始延迟
+        assert_eq!(
+            compute_hedge_delay(&config, 90, &[100, 200]),
+            Duration::from_millis(3000)
+        );
+
+        let samples: Vec<u64> = (1..=40).map(|i| i * 100).collect();
+        assert_eq!(
+            compute_hedge_delay(&config, 90, &samples),
+            Duration::from_millis(3600)
+        );
+
+        // 不低于下限
+        let fast = vec![50u64; 40];
+        assert_eq!(
+            compute_hedge_delay(&config, 90, &fast),
+            Duration::from_millis(500)
+        );
+    }
+
+    #[test]
+    fn test_hedge_delay_requires_policy() {
+        let mut config = config();
+        assert!(hedge_delay_for(&config, "claude-sonnet-4-5").is_some());
+        assert!(hedge_delay_for(&config, "gemini-3-pro-high").is_none());
+        config.enabled = false;
+        assert!(hedge_delay_for(&config, "gemini-3-flash").is_none());
+    }
+
+    #[test]
+    fn test_budget_limits_extra_requests() {
+        let mut stats = HedgeStats {
+            eligible_requests: 19,
+            ..Default::default()
+        };
+        assert!(!try_acquire_budget(&mut stats, 5));
+        assert_eq!(stats.budget_denied, 1);
+
+        stats.eligible_requests = 40;
+        assert!(try_acquire_budget(&mut stats, 5));
+        assert!(try_acquire_budget(&mut stats, 5));
+        assert!(!try_acquire_budget(&mut stats, 5));
+        assert_eq!(stats.hedges_fired, 2);
+        assert_eq!(stats.budget_denied, 2);
+    }
+
+    #[tokio::test]
+    async fn test_fast_primary_skips_hedge() {
+        let ctx = test_context("hedge-test-model", Instant::now(), Duration::from_secs(30));
+        let stream: UpstreamByteStream =
+            Box::pin(futures::stream::iter(vec![Ok(bytes::Bytes::from(
+                "data: {\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"Hi\"}]}}]}\n",
+            ))]));
+
+        let (outcome, winner) = peek_with_hedge(stream, Some(ctx)).await;
+        assert!(matches!(outcome, PeekOutcome::Ready(_)));
+        assert!(winner.is_none());
+    }
+
+    fn latency_samples(model: &str) -> Vec<u64> {
+        latencies_lock()
+            .lock()
+            .unwrap()
+            .get(model)
+            .map(|s| s.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    fn test_context(model: &str, started: Instant, delay: Duration) -> HedgeContext {
+        let dir = std::env::temp_dir().join(format!("hedging_{}", uuid::Uuid::new_v4()));
+        HedgeContext {
+            token_manager: Arc::new(TokenManager::new(dir)),
+            upstream: Arc::new(UpstreamClient::new(None)),
+            body: serde_json::json!({}),
+            model: model.to_string(),
+            account_id: "acc-1".to_string(),
+            trace_id: "test".to_string(),
+            started,
+            delay,
+            max_extra_percent: 100,
+            deadline: DeadlineClock::for_body(&serde_json::json!({})),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slow_primary_timed_from_its_own_start() {
+        // 无可用账号：对冲请求立即失败，回到主请求
+        let ctx = test_context("hedge-slow-primary-model", Instant::now(), Duration::from_millis(10));
+        let stream: UpstreamByteStream = Box::pin(async_stream::stream! {
+            tokio::time::sleep(Duration::from_millis(60)).await;
+            yield Ok(bytes::Bytes::from(
+                "data: {\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"Hi\"}]}}]}\n",
+            ));
+        });
+
+        let (outcome, winner) = peek_with_hedge(stream, Some(ctx)).await;
+        assert!(matches!(outcome, PeekOutcome::Ready(_)));
+        assert!(winner.is_none());
+        let samples = latency_samples("hedge-slow-primary-model");
+        assert_eq!(samples.len(), 1);
+        assert!(samples[0] >= 60, "primary sample {}ms excludes its pre-hedge wait", samples[0]);
+    }
+
+    #[test]
+    fn test_cancelled_primary_recorded_as_censored_sample() {
+        let started = Instant::now() - Duration::from_millis(800);
+        let ctx = test_context("hedge-censored-model", started, Duration::from_millis(300));
+
+        // 对冲请求 200ms 内胜出：胜者样本按对冲自身计时，主请求记录已等待的 800ms
+        ctx.finish(AttemptType::Hedge, Instant::now() - Duration::from_millis(200));
+        ctx.record_cancelled_primary();
+
+        let samples = latency_samples("hedge-censored-model");
+        assert_eq!(samples.len(), 2);
+        assert!((200..800).contains(&samples[0]));
+        assert!(samples[1] >= 800);
+    }
+}
@@ -1,4 +1,9 @@
 // Upstream 模块 - 上游客户端
 
 pub mod client;
+pub mod deadline;
+pub mod hedging;
 pub mod retry;
+pub mod stream_peek;
+pub mod stream_recovery;
+pub mod usage_scaling;
@@ -0,0 +1,367 @@
+// 首包缓冲 - 在向客户端发送第一个有效内容前检测上游流失败
+//
+// 上游返回 200 后仍可能出现空流、立即返回 error 事件或连接重置。
+// 在收到第一个有效内容 (文本 / 工具调用 / 图片 / 结束原因) 之前缓冲原始 SSE 字节，
+// 若此窗口内失败，调用方可透明地换账号重试；之后的行为与直接转发一致。
+
+use std::pin::Pin;
+
+use bytes::{Bytes, BytesMut};
+use futures::{Stream, StreamExt};
+use serde_json::Value;
+
+use super::client::UpstreamClient;
+use super::deadline::{peek_within_deadline, with_stream_deadlines, DeadlineClock};
+use crate::proxy::common::error_classifier::classify_stream_error;
+use crate::proxy::concurrency::hold_lease;
+use crate::proxy::token_manager::{ProxyToken, TokenManager, UpstreamOutcome};
+
+/// 上游原始字节流
+pub type UpstreamByteStream = Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>;
+
+/// 首包检测结果
+pub enum PeekOutcome {
+    /// 已收到有效内容；返回的流会先重放已缓冲的字节
+    Ready(UpstreamByteStream),
+    /// 在任何有效内容之前失败
+    Failed {
+        error_type: &'static str,
+        message: String,
+    },
+}
+
+/// 单个 SSE data 事件的判定
+#[derive(Debug, PartialEq)]
+enum EventKind {
+    /// 包含可转发给客户端的内容
+    Content,
+    /// 上游 error 事件
+    Error(String),
+    /// 元数据 / 空 parts，继续等待
+    Pending,
+}
+
+fn classify_event(json: &Value) -> EventKind {
+    let raw = json.get("response").unwrap_or(json);
+
+    if let Some(err) = raw.get("error").or_else(|| json.get("error")) {
+        let message = err
+            .get("message")
+            .and_then(|m| m.as_str())
+            .map(|m| m.to_string())
+            .unwrap_or_else(|| err.to_string());
+        return EventKind::Error(message);
+    }
+
+    // 整个 prompt 被拦截也是有效的终止响应
+    if raw
+        .get("promptFeedback")
+        .and_then(|f| f.get("blockReason"))
+        .is_some()
+    {
+        return EventKind::Content;
+    }
+
+    let Some(candidate) = raw.get("candidates").and_then(|c| c.get(0)) else {
+        return EventKind::Pending;
+    };
+
+    let has_content = candidate
+        .get("content")
+        .and_then(|c| c.get("parts"))
+        .and_then(|p| p.as_array())
+        .is_some_and(|parts| {
+            parts.iter().any(|part| {
+                part.get("text")
+                    .and_then(|t| t.as_str())
+                    .is_some_and(|t| !t.is_empty())
+                    || part.get("functionCall").is_some()
+                    || part.get("inlineData").is_some()
+            })
+        });
+
+    if has_content || candidate.get("finishReason").is_some() {
+        EventKind::Content
+    } else {
+        EventKind::Pending
+    }
+}
+
+/// 扫描缓冲区中已完整的行，返回第一个非 Pending 的判定
+fn scan_lines(buffer: &[u8], scanned: &mut usize) -> EventKind {
+    while let Some(rel) = buffer[*scanned..].iter().position(|&b| b == b'\n') {
+        let line = &buffer[*scanned..*scanned + rel];
+        *scanned += rel + 1;
+
+        let Ok(line) = std::str::from_utf8(line) else {
+            continue;
+        };
+        let Some(data) = line.trim().strip_prefix("data:") else {
+            continue;
+        };
+        let data = data.trim();
+        if data.is_empty() || data == "[DONE]" {
+            continue;
+        }
+        if let Ok(json) = serde_json::from_str::<Value>(data) {
+            match classify_event(&json) {
+                EventKind::Pending => continue,
+                other => return other,
+            }
+        }
+    }
+    EventKind::Pending
+}
+
+/// 缓冲上游流直到第一个有效内容
+pub async fn peek_first_content(mut stream: UpstreamByteStream) -> PeekOutcome {
+    let mut buffer = BytesMut::new();
+    let mut scanned = 0usize;
+
+    loop {
+        match stream.next().await {
+            Some(Ok(chunk)) => {
+                buffer.extend_from_slice(&chunk);
+                match scan_lines(&buffer, &mut scanned) {
+                    EventKind::Pending => continue,
+                    EventKind::Content => {
+                        let replay = futures::stream::once(async move { Ok(buffer.freeze()) });
+                        return PeekOutcome::Ready(Box::pin(replay.chain(stream)));
+                    }
+                    EventKind::Error(message) => {
+                        return PeekOutcome::Failed {
+                            error_type: "upstream_error",
+                            message,
+                        };
+                    }
+                }
+            }
+            Some(Err(e)) => {
+                let (error_type, _, _) = classify_stream_error(&e);
+                return PeekOutcome::Failed {
+                    error_type,
+                    message: e.to_string(),
+                };
+            }
+            None => {
+                // 末尾可能有未以换行结束的事件
+                buffer.extend_from_slice(b"\n");
+                return match scan_lines(&buffer, &mut scanned) {
+                    EventKind::Content => {
+                        let replay = futures::stream::once(async move { Ok(buffer.freeze()) });
+                        PeekOutcome::Ready(Box::pin(replay))
+                    }
+                    EventKind::Error(message) => PeekOutcome::Failed {
+                        error_type: "upstream_error",
+                        message,
+                    },
+                    EventKind::Pending => PeekOutcome::Failed {
+                        error_type: "empty_stream",
+                        message: "Upstream stream ended before any content".to_string(),
+                    },
+                };
+            }
+        }
+    }
+}
+
+/// 在另一个账号上重新发起流式请求并等待首个有效内容 (续写 / 对冲共用)
+///
+/// `body` 为 v1internal 包装格式，project 会替换为新账号的 project。
+/// `allow_same_account` 为 false 时，池中没有其他可用账号即返回错误。
+/// `deadline` 为原请求的计时，新请求沿用其总截止时刻。
+pub async fn open_alternate_stream(
+    token_manager: &TokenManager,
+    upstream: &UpstreamClient,
+    body: &Value,
+    model: &str,
+    exclude_account: &str,
+    allow_same_account: bool,
+    deadline: &DeadlineClock,
+) -> Result<(UpstreamByteStream, ProxyToken), String> {
+    let mut same_account = None;
+    for _ in 0..3 {
+        let token = token_manager
+            .get_token(model, None)
+            .await
+            .map_err(|e| e.to_string())?;
+        if token.account_id != exclude_account {
+            return open_on_account(token_manager, upstream, body, model, token, deadline).await;
+        }
+        same_account = Some(token);
+    }
+    match same_account {
+        Some(token) if allow_same_account => {
+            open_on_account(token_manager, upstream, body, model, token, deadline).await
+        }
+        _ => Err("No alternate account available".to_string()),
+    }
+}
+
+async fn open_on_account(
+    token_manager: &TokenManager,
+    upstream: &UpstreamClient,
+    body: &Value,
+    model: &str,
+    token: ProxyToken,
+    deadline: &DeadlineClock,
+) -> Result<(UpstreamByteStream, ProxyToken), String> {
+    let mut body = body.clone();
+    body["project"] = Value::String(token.project_id.clone().unwrap_or_default());
+
+    let deadline = deadline.next_attempt();
+    let call_result = match upstream
+        .call_v1_internal_with_deadline(
+            "streamGenerateContent",
+            &token.access_token,
+            body,
+            Some("alt=sse"),
+            Some(token.account_id.as_str()),
+            deadline,
+        )
+        .await
+    {
+        Ok(r) => r,
+        Err(e) => {
+            if !deadline.expired_by_client() {
+                token_manager
+                    .report_outcome(&token.account_id, Some(model), UpstreamOutcome::Transport)
+                    .await;
+            }
+            return Err(e);
+        }
+    };
+
+    let response = call_result.response;
+    let status = response.status();
+    if !status.is_success() {
+        let retry_after = response
+            .headers()
+            .get("Retry-After")
+            .and_then(|h| h.to_str().ok())
+            .map(|s| s.to_string());
+        let error_text = response
+            .text()
+            .await
+            .unwrap_or_else(|_| format!("HTTP {}", status));
+        token_manager
+            .report_outcome(
+                &token.account_id,
+                Some(model),
+                UpstreamOutcome::HttpError {
+                    status: status.as_u16(),
+                    retry_after: retry_after.as_deref(),
+                    body: &error_text,
+                },
+            )
+            .await;
+        return Err(format!("HTTP {}: {}", status, error_text));
+    }
+
+    // 200 仅代表响应头到达，收到首个有效内容后才计为成功
+    let peek = async {
+        (
+            peek_first_content(Box::pin(response.bytes_stream())).await,
+            None,
+        )
+    };
+    match peek_within_deadline(&deadline, peek).await.0 {
+        PeekOutcome::Ready(stream) => {
+            token_manager
+                .report_outcome(&token.account_id, Some(model), UpstreamOutcome::Success)
+                .await;
+            let stream = with_stream_deadlines(stream, &deadline);
+            Ok((hold_lease(stream, token.lease.clone()), token))
+        }
+        PeekOutcome::Failed {
+            error_type,
+            message,
+        } => {
+            if !deadline.expired_by_client() {
+                token_manager
+                    .report_outcome(
+                        &token.account_id,
+                        Some(model),
+                        UpstreamOutcome::StreamInterrupted,
+                    )
+                    .await;
+            }
+            Err(format!("{}: {}", error_type, message))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn byte_stream(chunks: Vec<&'static str>) -> UpstreamByteStream {
+        Box::pin(futures::stream::iter(
+            chunks.into_iter().map(|c| Ok(Bytes::from(c))),
+        ))
+    }
+
+    async fn collect(stream: UpstreamByteStream) -> String {
+        let chunks: Vec<_> = stream.collect().await;
+        chunks
+            .into_iter()
+            .map(|c| String::from_utf8(c.unwrap().to_vec()).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_ready_replays_buffered_bytes() {
+        let stream = byte_stream(vec![
+            "data: {\"response\":{\"candidates\":[{\"content\":{\"parts\":[]}}]}}\n",
+            "data: {\"response\":{\"candidates\":[{\"content\":{\"parts\":[{\"te",
+            "xt\":\"Hi\"}]}}]}}\n",
+            "data: {\"response\":{\"candidates\":[{\"finishReason\":\"STOP\"}]}}\n",
+        ]);
+        match peek_first_content(stream).await {
+            PeekOutcome::Ready(s) => {
+                let all = collect(s).await;
+                assert!(all.contains("\"parts\":[]"));
+                assert!(all.contains("\"text\":\"Hi\""));
+                assert!(all.contains("STOP"));
+            }
+            PeekOutcome::Failed { .. } => panic!("expected ready"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_empty_stream_fails() {
+        let stream = byte_stream(vec!["data: {\"response\":{\"usageMetadata\":{}}}\n", "\n"]);
+        match peek_first_content(stream).await {
+            PeekOutcome::Failed { error_type, .. } => assert_eq!(error_type, "empty_stream"),
+            PeekOutcome::Ready(_) => panic!("expected failure"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_error_event_fails() {
+        let stream = byte_stream(vec![
+            "data: {\"error\":{\"code\":503,\"message\":\"overloaded\"}}\n",
+        ]);
+        match peek_first_content(stream).await {
+            PeekOutcome::Failed {
+                error_type,
+                message,
+            } => {
+                assert_eq!(error_type, "upstream_error");
+                assert_eq!(message, "overloaded");
+            }
+            PeekOutcome::Ready(_) => panic!("expected failure"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unterminated_final_event_counts() {
+        let stream = byte_stream(vec![
+            "data: {\"candidates\":[{\"content\":{\"parts\":[{\"functionCall\":{\"name\":\"f\"}}]}}]}",
+        ]);
+        assert!(matches!(
+            peek_first_content(stream).await,
+            PeekOutcome::Ready(_)
+        ));
+    }
+}
@@ -0,0 +1,496 @@
+// 流中断续写 - 上游流在输出中途断开时换账号继续生成
+//
+// 长时间的思考 / 代码生成若在中途被上游重置，客户端会丢失已等待数分钟的输出。
+// 开启后在原始 SSE 字节层包裹上游流：
+// - 只向下游转发完整的 SSE 行，并累计已转发的可见文本
+// - 流出错或在 finishReason 之前结束时，换账号重新请求，已输出文本作为 model 角色的 prefill
+// - 新流的字节继续送入同一个协议转换器，因此 Claude 的 block index、OpenAI 的 choice delta 保持连续
+// - 已输出工具调用或图片时不续写 (无法安全拼接)
+
+use std::sync::{Arc, OnceLock, RwLock};
+
+use bytes::{Bytes, BytesMut};
+use futures::StreamExt;
+use serde_json::{json, Value};
+
+use super::client::UpstreamClient;
+use super::deadline::DeadlineClock;
+use super::stream_peek::{open_alternate_stream, UpstreamByteStream};
+use crate::models::config::StreamRecoveryConfig;
+use crate::proxy::token_manager::{ProxyToken, TokenManager, UpstreamOutcome};
+
+// ============================================================================
+// Global Config
+// ============================================================================
+
+static GLOBAL_STREAM_RECOVERY_CONFIG: OnceLock<RwLock<StreamRecoveryConfig>> = OnceLock::new();
+
+/// 当前生效的续写配置
+pub fn get_stream_recovery_config() -> StreamRecoveryConfig {
+    GLOBAL_STREAM_RECOVERY_CONFIG
+        .get()
+        .and_then(|lock| lock.read().ok())
+        .map(|cfg| cfg.clone())
+        .unwrap_or_default()
+}
+
+/// 更新续写配置 (启动及配置热更新时调用)
+pub fn update_stream_recovery_config(config: &StreamRecoveryConfig) {
+    let lock =
+        GLOBAL_STREAM_RECOVERY_CONFIG.get_or_init(|| RwLock::new(StreamRecoveryConfig::default()));
+    if let Ok(mut cfg) = lock.write() {
+        *cfg = config.clone();
+    }
+    tracing::info!(
+        "[StreamRecovery] Config updated: enabled={}, max_recoveries={}",
+        config.enabled,
+        config.max_recoveries
+    );
+}
+
+/// 是否启用续写 (决定是否需要保留请求体副本)
+pub fn stream_recovery_enabled() -> bool {
+    let config = get_stream_recovery_config();
+    config.enabled && config.max_recoveries > 0
+}
+
+// ============================================================================
+// Forwarded Content Tracking
+// ============================================================================
+
+/// 已转发给客户端的内容
+#[derive(Debug, Default, Clone)]
+struct ForwardedContent {
+    /// 可见文本 (不含思考内容)，用作 prefill
+    text: String,
+    /// 已转发的思考内容字符数
+    thought_chars: usize,
+    /// 已转发工具调用 / 图片，此时不再续写
+    has_opaque_part: bool,
+    /// 已收到 finishReason 或 prompt 拦截
+    finished: bool,
+}
+
+impl ForwardedContent {
+    /// 记录一段完整的 SSE 行
+    fn observe(&mut self, bytes: &[u8]) {
+        let Ok(text) = std::str::from_utf8(bytes) else {
+            return;
+        };
+        for line in text.lines() {
+            let Some(data) = line.trim().strip_prefix("data:") else {
+                continue;
+            };
+            let data = data.trim();
+            if data.is_empty() || data == "[DONE]" {
+                continue;
+            }
+            if let Ok(json) = serde_json::from_str::<Value>(data) {
+                self.observe_event(&json);
+            }
+        }
+    }
+
+    fn observe_event(&mut self, json: &Value) {
+        let raw = json.get("response").unwrap_or(json);
+
+        if raw
+            .get("promptFeedback")
+            .and_then(|f| f.get("blockReason"))
+            .is_some()
+        {
+            self.finished = true;
+        }
+
+        let Some(candidate) = raw.get("candidates").and_then(|c| c.get(0)) else {
+            return;
+        };
+
+        if let Some(parts) = candidate
+            .get("content")
+            .and_then(|c| c.get("parts"))
+            .and_then(|p| p.as_array())
+        {
+            for part in parts {
+                if part.get("functionCall").is_some() || part.get("inlineData").is_some() {
+                    self.has_opaque_part = true;
+                }
+                let Some(text) = part.get("text").and_then(|t| t.as_str()) else {
+                    continue;
+                };
+                if part
+                    .get("thought")
+                    .and_then(|t| t.as_bool())
+                    .unwrap_or(false)
+                {
+                    self.thought_chars += text.chars().count();
+                } else {
+                    self.text.push_str(text);
+                }
+            }
+        }
+
+        if candidate.get("finishReason").is_some() {
+            self.finished = true;
+        }
+    }
+}
+
+/// 构造续写请求：把已输出文本追加为 model 角色的 prefill
+fn build_recovery_body(body: &Value, partial: &str) -> Value {
+    let mut body = body.clone();
+
+    if partial.is_empty() {
+        // 仅输出了思考内容，直接重新生成
+        return body;
+    }
+
+    let Some(request) = body.get_mut("request") else {
+        return body;
+    };
+
+    if let Some(contents) = request.get_mut("contents").and_then(|c| c.as_array_mut()) {
+        // 客户端自带 prefill 时合并到同一条 model 消息，避免连续的 model 角色
+        let appended = contents
+            .last_mut()
+            .filter(|last| last.get("role").and_then(|r| r.as_str()) == Some("model"))
+            .and_then(|last| last.get_mut("parts"))
+            .and_then(|parts| parts.as_array_mut())
+            .map(|parts| parts.push(json!({ "text": partial })))
+            .is_some();
+        if !appended {
+            contents.push(json!({
+                "role": "model",
+                "parts": [{ "text": partial }]
+            }));
+        }
+    }
+
+    // 可见文本已开始输出说明思考阶段已结束，且 prefill 与 thinking 不兼容
+    if let Some(generation_config) = request
+        .get_mut("generationConfig")
+        .and_then(|g| g.as_object_mut())
+    {
+        generation_config.remove("thinkingConfig");
+    }
+
+    body
+}
+
+// ============================================================================
+// Recovery Stream
+// ============================================================================
+
+/// 续写所需的请求上下文
+pub struct RecoveryContext {
+    pub token_manager: Arc<TokenManager>,
+    pub upstream: Arc<UpstreamClient>,
+    /// 首次发往上游的请求体 (v1internal 包装格式)
+    pub body: Value,
+    pub model: String,
+    /// 首次请求使用的账号
+    pub account_id: String,
+    pub trace_id: String,
+    pub max_recoveries: u32,
+    /// 原请求的计时，续写请求沿用其总截止时刻
+    pub deadline: DeadlineClock,
+}
+
+impl RecoveryContext {
+    pub fn new(
+        token_manager: Arc<TokenManager>,
+        upstream: Arc<UpstreamClient>,
+        body: Value,
+        model: &str,
+        account_id: &str,
+        trace_id: &str,
+        deadline: &DeadlineClock,
+    ) -> Self {
+        Self {
+            token_manager,
+            upstream,
+            body,
+            model: model.to_string(),
+            account_id: account_id.to_string(),
+            trace_id: trace_id.to_string(),
+            max_recoveries: get_stream_recovery_config().max_recoveries,
+            deadline: *deadline,
+        }
+    }
+
+    /// 发起续写请求，返回首包已就绪的新流及其账号
+    async fn reopen(
+        &self,
+        partial: &str,
+        exclude: &str,
+    ) -> Result<(UpstreamByteStream, ProxyToken), String> {
+        let body = build_recovery_body(&self.body, partial);
+        // 池中只剩中断账号时仍在其上续写
+        open_alternate_stream(
+            &self.token_manager,
+            &self.upstream,
+            &body,
+            &self.model,
+            exclude,
+            true,
+            &self.deadline,
+        )
+        .await
+    }
+}
+
+/// 上游流的中断方式
+enum Interruption {
+    Error(reqwest::Error),
+    /// 在 finishReason 之前结束；携带未以换行结束的尾部字节
+    Eof(Bytes),
+}
+
+/// 包裹已通过首包检测的上游流，使其在中途断开时自动续写
+pub fn with_stream_recovery(
+    stream: UpstreamByteStream,
+    ctx: RecoveryContext,
+) -> UpstreamByteStream {
+    Box::pin(async_stream::stream! {
+        let mut current = stream;
+        let mut account_id = ctx.account_id.clone();
+        let mut forwarded = ForwardedContent::default();
+        let mut pending = BytesMut::new();
+        let mut recoveries = 0u32;
+
+        loop {
+            let interruption = loop {
+                match current.next().await {
+                    Some(Ok(chunk)) => {
+                        pending.extend_from_slice(&chunk);
+                        // 只转发完整的行，半行在续写时丢弃
+                        if let Some(pos) = pending.iter().rposition(|&b| b == b'\n') {
+                            let complete = pending.split_to(pos + 1).freeze();
+                            forwarded.observe(&complete);
+                            yield Ok(complete);
+                        }
+                    }
+                    Some(Err(e)) => break Interruption::Error(e),
+                    None => {
+                        let tail = pending.split().freeze();
+                        let mut with_tail = forwarded.clone();
+                        with_tail.observe(&tail);
+                        if with_tail.finished {
+                            if !tail.is_empty() {
+                                yield Ok(tail);
+                            }
+                            return;
+                        }
+                        break Interruption::Eof(tail);
+                    }
+                }
+            };
+
+            let reason = match &interruption {
+                Interruption::Error(e) => e.to_string(),
+                Interruption::Eof(_) => "stream ended before finishReason".to_string(),
+            };
+
+            // 续写条件：仍有次数且已输出内容可安全拼接
+            let mut reopened = None;
+            if !forwarded.has_opaque_part {
+                // 先释放中断流 (及其账号租约)，再在其他账号上续写
+                drop(std::mem::replace(&mut current, Box::pin(futures::stream::empty())));
+                ctx.token_manager
+                    .report_outcome(&account_id, Some(&ctx.model), UpstreamOutcome::StreamInterrupted)
+                    .await;
+                while recoveries < ctx.max_recoveries {
+                    recoveries += 1;
+                    match ctx.reopen(&forwarded.text, &account_id).await {
+                        Ok(result) => {
+                            reopened = Some(result);
+                            break;
+                        }
+                        Err(e) => {
+                            tracing::warn!(
+                                "[{}] Stream recovery attempt {}/{} failed: {}",
+                                ctx.trace_id,
+                                recoveries,
+                                ctx.max_recoveries,
+                                e
+                            );
+                        }
+                    }
+                }
+            }
+
+            match reopened {
+                Some((stream, token)) => {
+                    tracing::warn!(
+                        "[{}] Stream recovered ({}/{}): {} -> {} | Model: {} | Prefill: {} chars (+{} thinking) | Reason: {}",
+                        ctx.trace_id,
+                        recoveries,
+                        ctx.max_recoveries,
+                        account_id,
+                        token.email,
+                        ctx.model,
+                        forwarded.text.chars().count(),
+                        forwarded.thought_chars,
+                        reason
+                    );
+                    current = stream;
+                    account_id = token.account_id;
+                }
+                None => {
+                    tracing::warn!(
+                        "[{}] Upstream stream interrupted without recovery after {} chars: {}",
+                        ctx.trace_id,
+                        forwarded.text.chars().count(),
+                        reason
+                    );
+                    match interruption {
+                        Interruption::Error(e) => yield Err(e),
+                        Interruption::Eof(tail) => {
+                            if !tail.is_empty() {
+                                yield Ok(tail);
+                            }
+                        }
+                    }
+                    return;
+                }
+            }
+        }
+    })
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn byte_stream(chunks: Vec<&'static str>) -> UpstreamByteStream {
+        Box::pin(futures::stream::iter(
+            chunks.into_iter().map(|c| Ok(Bytes::from(c))),
+        ))
+    }
+
+    fn context(max_recoveries: u32) -> RecoveryContext {
+        let dir = std::env::temp_dir().join(format!("stream_recovery_{}", uuid::Uuid::new_v4()));
+        RecoveryContext {
+            token_manager: Arc::new(TokenManager::new(dir)),
+            upstream: Arc::new(UpstreamClient::new(None)),
+            body: json!({ "project": "p1", "request": { "contents": [] } }),
+            model: "gemini-3-flash".to_string(),
+            account_id: "acc-1".to_string(),
+            trace_id: "test".to_string(),
+            max_recoveries,
+            deadline: DeadlineClock::for_body(&json!({})),
+        }
+    }
+
+    async fn collect(stream: UpstreamByteStream) -> String {
+        let chunks: Vec<_> = stream.collect().await;
+        chunks
+            .into_iter()
+            .map(|c| String::from_utf8(c.unwrap().to_vec()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_observe_tracks_text_and_state() {
+        let mut content = ForwardedContent::default();
+        content.observe(
+            concat!(
+                "data: {\"response\":{\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"plan\",\"thought\":true}]}}]}}\n",
+                "data: {\"response\":{\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"Hello \"}]}}]}}\n",
+                "data: {\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"world\"}]}}]}\n",
+            )
+            .as_bytes(),
+        );
+        assert_eq!(content.text, "Hello world");
+        assert_eq!(content.thought_chars, 4);
+        assert!(!content.finished);
+        assert!(!content.has_opaque_part);
+
+        content.observe(
+            b"data: {\"candidates\":[{\"content\":{\"parts\":[{\"functionCall\":{\"name\":\"f\"}}]},\"finishReason\":\"STOP\"}]}\n",
+        );
+        assert!(content.has_opaque_part);
+        assert!(content.finished);
+    }
+
+    #[test]
+    fn test_build_recovery_body_appends_prefill() {
+        let body = json!({
+            "project": "old",
+            "request": {
+                "contents": [{ "role": "user", "parts": [{ "text": "write code" }] }],
+                "generationConfig": { "maxOutputTokens": 100, "thinkingConfig": { "thinkingBudget": 1024 } }
+            }
+        });
+        let result = build_recovery_body(&body, "fn main() {");
+        let contents = result["request"]["contents"].as_array().unwrap();
+        assert_eq!(contents.len(), 2);
+        assert_eq!(contents[1]["role"], "model");
+        assert_eq!(contents[1]["parts"][0]["text"], "fn main() {");
+        assert!(result["request"]["generationConfig"]
+            .get("thinkingConfig")
+            .is_none());
+        assert_eq!(
+            result["request"]["generationConfig"]["maxOutputTokens"],
+            100
+        );
+
+        // 仅有思考内容时不追加 prefill，保留 thinking
+        let result = build_recovery_body(&body, "");
+        assert_eq!(result["request"]["contents"].as_array().unwrap().len(), 1);
+        assert!(result["request"]["generationConfig"]
+            .get("thinkingConfig")
+            .is_some());
+    }
+
+    #[test]
+    fn test_build_recovery_body_merges_client_prefill() {
+        let body = json!({
+            "request": {
+                "contents": [
+                    { "role": "user", "parts": [{ "text": "json please" }] },
+                    { "role": "model", "parts": [{ "text": "{" }] }
+                ]
+            }
+        });
+        let result = build_recovery_body(&body, "\"a\": 1");
+        let contents = result["request"]["contents"].as_array().unwrap();
+        assert_eq!(contents.len(), 2);
+        assert_eq!(contents[1]["parts"][1]["text"], "\"a\": 1");
+    }
+
+    #[tokio::test]
+    async fn test_completed_stream_passes_through() {
+        let stream = byte_stream(vec![
+            "data: {\"response\":{\"candidates\":[{\"content\":{\"parts\":[{\"te",
+            "xt\":\"Hi\"}]}}]}}\n",
+            "data: {\"response\":{\"candidates\":[{\"finishReason\":\"STOP\"}]}}",
+        ]);
+        let all = collect(with_stream_recovery(stream, context(2))).await;
+        assert!(all.contains("\"text\":\"Hi\""));
+        assert!(all.ends_with("\"STOP\"}]}}"));
+    }
+
+    #[tokio::test]
+    async fn test_unrecoverable_interruption_forwards_tail() {
+        // 已输出工具调用，不续写
+        let stream = byte_stream(vec![
+            "data: {\"candidates\":[{\"content\":{\"parts\":[{\"functionCall\":{\"name\":\"f\"}}]}}]}\n",
+            "data: {\"candidates\"",
+        ]);
+        let all = collect(with_stream_recovery(stream, context(2))).await;
+        assert!(all.contains("functionCall"));
+        assert!(all.ends_with("data: {\"candidates\""));
+
+        // 次数为 0 时同样直接透传
+        let stream = byte_stream(vec![
+            "data: {\"candidates\":[{\"content\":{\"parts\":[{\"text\":\"partial\"}]}}]}\n",
+        ]);
+        let all = collect(with_stream_recovery(stream, context(0))).await;
+        assert!(all.contains("partial"));
+    }
+}
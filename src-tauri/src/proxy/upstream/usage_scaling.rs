@@ -0,0 +1,166 @@
+// 用量缩放 - 让客户端的上下文占用百分比与真实上游窗口一致
+//
+// 客户端按自己请求的模型估算上下文窗口 (如 Claude Code 按 200K 计算)，
+// 而实际上游可能是 1M 窗口的 Gemini。开启 `enable_usage_scaling` 后，
+// 按 客户端窗口 / 上游窗口 的比例缩放 usageMetadata 中的输入 token 数：
+// - 只缩放 prompt / cached token，输出 token 保持原值
+// - 流式响应在原始 SSE 字节层逐行改写，非流式响应直接改写 JSON
+
+use bytes::{Bytes, BytesMut};
+use futures::StreamExt;
+use serde_json::{json, Value};
+
+use super::stream_peek::UpstreamByteStream;
+use crate::proxy::common::context_manager::context_window_for_model;
+
+/// 计算缩放系数；未开启或两侧窗口相同时返回 None
+pub fn usage_scale_factor(client_model: &str, mapped_model: &str) -> Option<f64> {
+    if !crate::proxy::get_experimental_config().enable_usage_scaling {
+        return None;
+    }
+    scale_factor_between(client_model, mapped_model)
+}
+
+fn scale_factor_between(client_model: &str, mapped_model: &str) -> Option<f64> {
+    let client_window = context_window_for_model(client_model);
+    let upstream_window = context_window_for_model(mapped_model);
+    (client_window != upstream_window).then(|| client_window as f64 / upstream_window as f64)
+}
+
+fn scale_count(value: &mut Value, factor: f64) -> Option<u64> {
+    let count = value.as_u64()?;
+    let scaled = (count as f64 * factor).round() as u64;
+    *value = json!(scaled);
+    Some(scaled)
+}
+
+/// 缩放 Gemini 响应 (或 v1internal `{ "response": ... }` 包装) 中的 usageMetadata
+pub fn scale_usage_metadata(response: &mut Value, factor: f64) {
+    let target = if response.get("response").is_some() {
+        response.get_mut("response")
+    } else {
+        Some(response)
+    };
+    let Some(usage) = target
+        .and_then(|r| r.get_mut("usageMetadata"))
+        .and_then(|u| u.as_object_mut())
+    else {
+        return;
+    };
+
+    let prompt = usage
+        .get_mut("promptTokenCount")
+        .and_then(|v| scale_count(v, factor));
+    if let Some(cached) = usage.get_mut("cachedContentTokenCount") {
+        scale_count(cached, factor);
+    }
+    if let Some(prompt) = prompt {
+        let output = ["candidatesTokenCount", "thoughtsTokenCount"]
+            .iter()
+            .filter_map(|k| usage.get(*k).and_then(|v| v.as_u64()))
+            .sum::<u64>();
+        if usage.contains_key("totalTokenCount") {
+            usage.insert("totalTokenCount".to_string(), json!(prompt + output));
+        }
+    }
+}
+
+fn scale_sse_line(line: &[u8], factor: f64) -> Option<Bytes> {
+    let text = std::str::from_utf8(line).ok()?;
+    let data = text.trim_end().strip_prefix("data:")?.trim();
+    if !data.contains("usageMetadata") {
+        return None;
+    }
+    let mut value: Value = serde_json::from_str(data).ok()?;
+    scale_usage_metadata(&mut value, factor);
+    Some(Bytes::from(format!("data: {}\n", value)))
+}
+
+/// 包裹上游 SSE 字节流，逐行缩放 usageMetadata
+pub fn scale_usage_stream(stream: UpstreamByteStream, factor: Option<f64>) -> UpstreamByteStream {
+    let Some(factor) = factor else {
+        return stream;
+    };
+    let mut stream = stream;
+
+    Box::pin(async_stream::stream! {
+        let mut buffer = BytesMut::new();
+        while let Some(item) = stream.next().await {
+            let chunk = match item {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    yield Err(e);
+                    break;
+                }
+            };
+            buffer.extend_from_slice(&chunk);
+
+            let mut out = BytesMut::new();
+            while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+                let line = buffer.split_to(pos + 1);
+                match scale_sse_line(&line, factor) {
+                    Some(scaled) => out.extend_from_slice(&scaled),
+                    None => out.extend_from_slice(&line),
+                }
+            }
+            if !out.is_empty() {
+                yield Ok(out.freeze());
+            }
+        }
+        if !buffer.is_empty() {
+            let line = buffer.split();
+            yield Ok(scale_sse_line(&line, factor).unwrap_or_else(|| line.freeze()));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scale_factor_between_windows() {
+        let factor = scale_factor_between("claude-sonnet-4-5", "gemini-3-pro").unwrap();
+        assert!((factor - 200_000.0 / 1_048_576.0).abs() < 1e-9);
+        assert!(scale_factor_between("gemini-2.5-flash", "gemini-3-pro").is_none());
+    }
+
+    #[test]
+    fn test_scale_usage_metadata() {
+        let mut resp = json!({
+            "response": {
+                "usageMetadata": {
+                    "promptTokenCount": 500_000,
+                    "cachedContentTokenCount": 100_000,
+                    "candidatesTokenCount": 300,
+                    "totalTokenCount": 500_300
+                }
+            }
+        });
+        scale_usage_metadata(&mut resp, 0.2);
+        let usage = &resp["response"]["usageMetadata"];
+        assert_eq!(usage["promptTokenCount"], 100_000);
+        assert_eq!(usage["cachedContentTokenCount"], 20_000);
+        assert_eq!(usage["candidatesTokenCount"], 300);
+        assert_eq!(usage["totalTokenCount"], 100_300);
+    }
+
+    #[tokio::test]
+    async fn test_scale_usage_stream_split_lines() {
+        let chunks: Vec<Result<Bytes, reqwest::Error>> = vec![
+            Ok(Bytes::from(
+                "data: {\"response\":{\"candidates\":[]}}\n\ndata: {\"response\":{\"usage",
+            )),
+            Ok(Bytes::from("Metadata\":{\"promptTokenCount\":1000}}}\n\n")),
+        ];
+        let stream: UpstreamByteStream = Box::pin(futures::stream::iter(chunks));
+        let out: Vec<Bytes> = scale_usage_stream(stream, Some(0.5))
+            .map(|r| r.unwrap())
+            .collect()
+            .await;
+        let text: String = out.iter().map(|b| String::from_utf8_lossy(b)).collect();
+
+        assert!(text.contains("data: {\"response\":{\"candidates\":[]}}\n\n"));
+        assert!(text.contains("\"promptTokenCount\":500"));
+    }
+}
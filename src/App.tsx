@@ -18,6 +18,7 @@ import Settings from './pages/Settings';
 // Layout & common components
 import Layout from './components/layout/Layout';
 import ThemeManager from './components/common/ThemeManager';
+import { AdminAuthGuard } from './components/common/AdminAuthGuard';
 
 const router = createBrowserRouter([
     {
@@ -83,7 +84,9 @@ function App() {
     return (
         <>
             <ThemeManager />
-            <RouterProvider router={router} />
+            <AdminAuthGuard>
+                <RouterProvider router={router} />
+            </AdminAuthGuard>
         </>
     );
 }
@@ -1,5 +1,5 @@
 import React, { useState, useEffect } from 'react';
-import { Lock, Key, Globe, AlertCircle, Loader2 } from 'lucide-react';
+import { Lock, Key, User, Globe, AlertCircle, Loader2 } from 'lucide-react';
 import { useTranslation } from 'react-i18next';
 import { isTauri } from '../../utils/env';
 
@@ -15,6 +15,7 @@ const LANGUAGES = [
 export const AdminAuthGuard: React.FC<{ children: React.ReactNode }> = ({ children }) => {
     const { t, i18n } = useTranslation();
     const [isAuthenticated, setIsAuthenticated] = useState(isTauri());
+    const [username, setUsername] = useState('');
     const [apiKey, setApiKey] = useState('');
     const [showLangMenu, setShowLangMenu] = useState(false);
     const [isLoading, setIsLoading] = useState(false);
@@ -22,28 +23,50 @@ export const AdminAuthGuard: React.FC<{ children: React.ReactNode }> = ({ childr
 
     useEffect(() => {
         if (isTauri()) return;
-        const sessionKey = sessionStorage.getItem('kiro_admin_api_key');
-        if (sessionKey) { setIsAuthenticated(true); setApiKey(sessionKey); return; }
-        const savedKey = localStorage.getItem('kiro_admin_api_key');
-        if (savedKey) {
-            sessionStorage.setItem('kiro_admin_api_key', savedKey);
-            localStorage.removeItem('kiro_admin_api_key');
-            setIsAuthenticated(true); setApiKey(savedKey);
-        }
+        // Expired sessions and revoked keys surface as 401s from request()
         const handleUnauthorized = () => {
             sessionStorage.removeItem('kiro_admin_api_key');
             setIsAuthenticated(false);
         };
         window.addEventListener('kiro-unauthorized', handleUnauthorized);
+        const sessionKey = sessionStorage.getItem('kiro_admin_api_key');
+        const savedKey = localStorage.getItem('kiro_admin_api_key');
+        if (sessionKey) {
+            setIsAuthenticated(true);
+        } else if (savedKey) {
+            sessionStorage.setItem('kiro_admin_api_key', savedKey);
+            localStorage.removeItem('kiro_admin_api_key');
+            setIsAuthenticated(true);
+        }
         return () => window.removeEventListener('kiro-unauthorized', handleUnauthorized);
     }, []);
 
+    // Admin account login: exchange username/password for a session token
+    const loginWithAccount = async (name: string, password: string) => {
+        const response = await fetch('/api/auth/login', {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ username: name, password })
+        });
+        if (response.ok) {
+            const session = await response.json();
+            sessionStorage.setItem('kiro_admin_api_key', session.token);
+            setIsAuthenticated(true); window.location.reload();
+        } else if (response.status === 429) {
+            setError(t('login.error_throttled', 'Too many failed attempts, please try again later'));
+        } else {
+            setError(t('login.error_invalid_account', 'Invalid username or password'));
+        }
+    };
+
     const handleLogin = async (e: React.FormEvent) => {
         e.preventDefault();
         const trimmedKey = apiKey.trim();
+        const trimmedUser = username.trim();
         if (!trimmedKey) return;
         setIsLoading(true); setError('');
         try {
+            if (trimmedUser) { await loginWithAccount(trimmedUser, apiKey); return; }
             sessionStorage.setItem('kiro_admin_api_key', trimmedKey);
             const response = await fetch('/api/accounts', {
                 method: 'GET',
@@ -94,11 +117,17 @@ export const AdminAuthGuard: React.FC<{ children: React.ReactNode }> = ({ childr
                     <h2 className="text-2xl font-bold text-center text-slate-900 dark:text-slate-100 mb-2">{t('login.title', 'Admin Login')}</h2>
                     <p className="text-center text-slate-500 dark:text-slate-400 mb-8 text-sm">{t('login.desc', 'Enter your admin password or API key')}</p>
                     <form onSubmit={handleLogin} className="space-y-6">
+                        <div className="relative">
+                            <User className="absolute left-4 top-1/2 -translate-y-1/2 w-5 h-5 text-slate-400" />
+                            <input type="text" autoComplete="username" placeholder={t('login.username_placeholder', 'Admin username (leave empty to use API Key)')}
+                                className="w-full pl-12 pr-4 py-4 bg-slate-50 dark:bg-base-200 border-2 border-transparent rounded-2xl focus:ring-2 focus:ring-blue-500 transition-all outline-none text-slate-900 dark:text-white"
+                                value={username} onChange={(e) => { setUsername(e.target.value); setError(''); }} autoFocus disabled={isLoading} />
+                        </div>
                         <div className="relative">
                             <Key className="absolute left-4 top-1/2 -translate-y-1/2 w-5 h-5 text-slate-400" />
                             <input type="password" placeholder={t('login.placeholder', 'API Key / Admin Password')}
                                 className={`w-full pl-12 pr-4 py-4 bg-slate-50 dark:bg-base-200 border-2 rounded-2xl focus:ring-2 focus:ring-blue-500 transition-all outline-none text-slate-900 dark:text-white ${error ? 'border-red-400' : 'border-transparent'}`}
-                                value={apiKey} onChange={(e) => { setApiKey(e.target.value); setError(''); }} autoFocus disabled={isLoading} />
+                                value={apiKey} onChange={(e) => { setApiKey(e.target.value); setError(''); }} disabled={isLoading} />
                         </div>
                         {error && <div className="flex items-center gap-2 text-red-500 text-sm"><AlertCircle className="w-4 h-4" /><span>{error}</span></div>}
                         <button type="submit" disabled={isLoading || !apiKey.trim()}
@@ -38,9 +38,12 @@ export interface ThinkingBudgetConfig {
     effort?: ThinkingEffort;
 }
 
+export type SystemPromptMode = 'prepend' | 'append' | 'replace';
+
 export interface GlobalSystemPromptConfig {
     enabled: boolean;
     content: string;
+    mode?: SystemPromptMode;
 }
 
 export interface DebugLoggingConfig {
@@ -170,6 +173,7 @@ export interface ProxyEntry {
     last_check_time?: number;
     is_healthy: boolean;
     latency?: number;
+    weight?: number;
 }
 
 export type ProxySelectionStrategy =
@@ -177,7 +181,8 @@ export type ProxySelectionStrategy =
     | 'random'
     | 'priority'
     | 'least_connections'
-    | 'weighted_round_robin';
+    | 'weighted_round_robin'
+    | 'latency_aware';
 
 export interface ProxyPoolConfig {
     enabled: boolean;
@@ -186,7 +186,7 @@ export async function request<T>(cmd: string, args?: Record<string, unknown>): P
     }
 
     const apiKey = typeof window !== 'undefined'
-        ? sessionStorage.getItem('abv_admin_api_key')
+        ? sessionStorage.getItem('kiro_admin_api_key')
         : null;
 
     const options: RequestInit = {
@@ -222,7 +222,7 @@ export async function request<T>(cmd: string, args?: Record<string, unknown>): P
                 if (now - lastAuthError > 2000) {
                     // eslint-disable-next-line @typescript-eslint/no-explicit-any
                     (window as any)._lastAuthErrorTime = now;
-                    window.dispatchEvent(new CustomEvent('abv-unauthorized'));
+                    window.dispatchEvent(new CustomEvent('kiro-unauthorized'));
                 }
             }
             const errorData = await response.json().catch(() => ({}));